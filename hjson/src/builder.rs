@@ -33,6 +33,9 @@
 //!     .unwrap();
 //! ```
 
+use alloc::string::String;
+use alloc::vec::Vec;
+
 use serde::ser;
 
 use value::{self, Map, Value};
@@ -60,6 +63,23 @@ impl ArrayBuilder {
         self
     }
 
+    /// Insert a value into the array if `cond` is true.
+    pub fn push_if<T: ser::Serialize>(self, cond: bool, v: T) -> ArrayBuilder {
+        if cond {
+            self.push(v)
+        } else {
+            self
+        }
+    }
+
+    /// Insert the value of an `Option` into the array if it is `Some`.
+    pub fn push_some<T: ser::Serialize>(self, v: Option<T>) -> ArrayBuilder {
+        match v {
+            Some(v) => self.push(v),
+            None => self,
+        }
+    }
+
     /// Creates and passes an `ArrayBuilder` into a closure, then inserts the resulting array into
     /// this array.
     pub fn push_array<F>(mut self, f: F) -> ArrayBuilder
@@ -112,6 +132,31 @@ impl ObjectBuilder {
         self
     }
 
+    /// Insert a key-value pair into the object if `cond` is true.
+    pub fn insert_if<S, V>(self, cond: bool, key: S, value: V) -> ObjectBuilder
+    where
+        S: Into<String>,
+        V: ser::Serialize,
+    {
+        if cond {
+            self.insert(key, value)
+        } else {
+            self
+        }
+    }
+
+    /// Insert a key-value pair into the object if the value is `Some`.
+    pub fn insert_some<S, V>(self, key: S, value: Option<V>) -> ObjectBuilder
+    where
+        S: Into<String>,
+        V: ser::Serialize,
+    {
+        match value {
+            Some(value) => self.insert(key, value),
+            None => self,
+        }
+    }
+
     /// Creates and passes an `ObjectBuilder` into a closure, then inserts the resulting array into
     /// this object.
     pub fn insert_array<S, F>(mut self, key: S, f: F) -> ObjectBuilder
@@ -136,3 +181,34 @@ impl ObjectBuilder {
         self
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{ArrayBuilder, ObjectBuilder};
+    use de::from_str;
+    use Value;
+
+    #[test]
+    fn conditional_inserts() {
+        let port: Option<u16> = Some(80);
+        let host: Option<&str> = None;
+        let value = ObjectBuilder::new()
+            .insert_some("port", port)
+            .insert_some("host", host)
+            .insert_if(true, "debug", false)
+            .insert_if(false, "verbose", true)
+            .unwrap();
+        assert_eq!(
+            value,
+            from_str::<Value>("{port: 80, debug: false}").unwrap()
+        );
+
+        let value = ArrayBuilder::new()
+            .push_some(Some("a"))
+            .push_some(None::<&str>)
+            .push_if(false, "b")
+            .push_if(true, "c")
+            .unwrap();
+        assert_eq!(value, from_str::<Value>("[\"a\", \"c\"]").unwrap());
+    }
+}
@@ -0,0 +1,84 @@
+//! Hjson Comments
+//!
+//! This module provides the type `Comments`, which holds the comments of a document so they can
+//! be written back out after the parsed value has been edited. Comments are collected by
+//! `from_str_with_comments` and written by `to_string_with_comments`.
+
+use alloc::collections::{btree_map, BTreeMap};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+/// The comments attached to a single object member or array element.
+///
+/// Each comment is kept verbatim, including its `#`, `//` or `/* */` markers.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MemberComments {
+    /// Comments on their own lines directly before the member or element.
+    pub before: Vec<String>,
+    /// A comment following the value on the same line.
+    pub after: Option<String>,
+}
+
+/// The comments of a document, keyed by the JSON Pointer (see `Value::pointer`) of the object
+/// member or array element they are attached to.
+///
+/// Only comments next to object members and array elements are kept. Comments before the closing
+/// bracket of an array or object, or outside of the root value, are dropped.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Comments {
+    members: BTreeMap<String, MemberComments>,
+}
+
+impl Comments {
+    /// Creates an empty set of comments.
+    pub fn new() -> Self {
+        Comments::default()
+    }
+
+    /// Returns true if no member has any comments.
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// Returns the comments of the member at `pointer`.
+    pub fn get(&self, pointer: &str) -> Option<&MemberComments> {
+        self.members.get(pointer)
+    }
+
+    /// Returns the text of the comment on the line directly before the member at `pointer`,
+    /// without its `#`, `//` or `/* */` markers and the whitespace around it. Returns `None` when
+    /// the member has no leading comment.
+    pub fn comments_for(&self, pointer: &str) -> Option<&str> {
+        let comment = self.members.get(pointer)?.before.last()?;
+        let text = if let Some(block) = comment.strip_prefix("/*") {
+            block.strip_suffix("*/").unwrap_or(block)
+        } else {
+            comment
+                .strip_prefix("//")
+                .or_else(|| comment.strip_prefix('#'))
+                .unwrap_or(comment)
+        };
+        Some(text.trim())
+    }
+
+    /// Returns the comments of the member at `pointer`, adding an empty entry if there is none
+    /// yet.
+    pub fn entry(&mut self, pointer: &str) -> &mut MemberComments {
+        self.members.entry(pointer.to_string()).or_default()
+    }
+
+    /// Removes and returns the comments of the member at `pointer`.
+    pub fn remove(&mut self, pointer: &str) -> Option<MemberComments> {
+        self.members.remove(pointer)
+    }
+
+    /// Iterates over the members that have comments, ordered by pointer.
+    pub fn iter(&self) -> btree_map::Iter<'_, String, MemberComments> {
+        self.members.iter()
+    }
+}
+
+/// Appends `token` to the JSON Pointer `parent`, escaping `~` and `/`.
+pub(crate) fn pointer_push(parent: &str, token: &str) -> String {
+    format!("{}/{}", parent, token.replace('~', "~0").replace('/', "~1"))
+}
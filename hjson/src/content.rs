@@ -0,0 +1,551 @@
+//! A record of the calls a `Serialize` implementation makes, so that the serializer can hold
+//! values back, for example to sort the members of an object, and write them later exactly as
+//! if they had been serialized directly. Unlike a `Value` it keeps floats as `f32`, integers
+//! wider than 64 bits, chars and the newtypes `RawValue` is passed through.
+//!
+//! Only what the Hjson serializer tells apart is kept: tuples and bytes become sequences,
+//! structs maps with their keys as text, and variants the newtype variant of what they hold.
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::result;
+
+use serde::ser::{self, Serialize};
+
+use super::error::{Error, Result};
+use super::ser::key_text;
+
+#[derive(Clone, Debug)]
+pub(crate) enum Content {
+    Bool(bool),
+    I64(i64),
+    I128(i128),
+    U64(u64),
+    U128(u128),
+    F32(f32),
+    F64(f64),
+    Char(char),
+    Str(String),
+    None,
+    Some(Box<Content>),
+    Unit,
+    UnitVariant(&'static str),
+    NewtypeStruct(&'static str, Box<Content>),
+    NewtypeVariant(&'static str, Box<Content>),
+    Seq(Option<usize>, Vec<Content>),
+    Map(Option<usize>, Vec<(String, Content)>),
+}
+
+/// Records how `value` serializes itself.
+pub(crate) fn to_content<T>(value: &T) -> Result<Content>
+where
+    T: ?Sized + Serialize,
+{
+    value.serialize(ContentSerializer)
+}
+
+impl Serialize for Content {
+    fn serialize<S>(&self, serializer: S) -> result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        use serde::ser::{SerializeMap, SerializeSeq};
+
+        match *self {
+            Content::Bool(v) => serializer.serialize_bool(v),
+            Content::I64(v) => serializer.serialize_i64(v),
+            Content::I128(v) => serializer.serialize_i128(v),
+            Content::U64(v) => serializer.serialize_u64(v),
+            Content::U128(v) => serializer.serialize_u128(v),
+            Content::F32(v) => serializer.serialize_f32(v),
+            Content::F64(v) => serializer.serialize_f64(v),
+            Content::Char(v) => serializer.serialize_char(v),
+            Content::Str(ref v) => serializer.serialize_str(v),
+            Content::None => serializer.serialize_none(),
+            Content::Some(ref v) => serializer.serialize_some(&**v),
+            Content::Unit => serializer.serialize_unit(),
+            Content::UnitVariant(variant) => serializer.serialize_unit_variant("", 0, variant),
+            Content::NewtypeStruct(name, ref v) => serializer.serialize_newtype_struct(name, &**v),
+            Content::NewtypeVariant(variant, ref v) => {
+                serializer.serialize_newtype_variant("", 0, variant, &**v)
+            }
+            Content::Seq(len, ref items) => {
+                let mut seq = serializer.serialize_seq(len)?;
+                for item in items {
+                    seq.serialize_element(item)?;
+                }
+                seq.end()
+            }
+            Content::Map(len, ref entries) => {
+                let mut map = serializer.serialize_map(len)?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+struct ContentSerializer;
+
+impl ser::Serializer for ContentSerializer {
+    type Ok = Content;
+    type Error = Error;
+
+    type SerializeSeq = SeqContent;
+    type SerializeTuple = SeqContent;
+    type SerializeTupleStruct = SeqContent;
+    type SerializeTupleVariant = SeqContent;
+    type SerializeMap = MapContent;
+    type SerializeStruct = MapContent;
+    type SerializeStructVariant = MapContent;
+
+    fn serialize_bool(self, v: bool) -> Result<Content> {
+        Ok(Content::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Content> {
+        Ok(Content::I64(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Content> {
+        Ok(Content::I64(v.into()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Content> {
+        Ok(Content::I64(v.into()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Content> {
+        Ok(Content::I64(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Content> {
+        Ok(Content::I128(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Content> {
+        Ok(Content::U64(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Content> {
+        Ok(Content::U64(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Content> {
+        Ok(Content::U64(v.into()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Content> {
+        Ok(Content::U64(v))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Content> {
+        Ok(Content::U128(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Content> {
+        Ok(Content::F32(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Content> {
+        Ok(Content::F64(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Content> {
+        Ok(Content::Char(v))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Content> {
+        Ok(Content::Str(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Content> {
+        let items = v.iter().map(|&b| Content::U64(b.into())).collect();
+        Ok(Content::Seq(Some(v.len()), items))
+    }
+
+    fn serialize_none(self) -> Result<Content> {
+        Ok(Content::None)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Content>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(Content::Some(Box::new(to_content(value)?)))
+    }
+
+    fn serialize_unit(self) -> Result<Content> {
+        Ok(Content::Unit)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Content> {
+        Ok(Content::Unit)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Content> {
+        Ok(Content::UnitVariant(variant))
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Content>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(Content::NewtypeStruct(name, Box::new(to_content(value)?)))
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Content>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(Content::NewtypeVariant(
+            variant,
+            Box::new(to_content(value)?),
+        ))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqContent> {
+        Ok(SeqContent::new(None, len))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqContent> {
+        Ok(SeqContent::new(None, Some(len)))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SeqContent> {
+        Ok(SeqContent::new(None, Some(len)))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SeqContent> {
+        Ok(SeqContent::new(Some(variant), Some(len)))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<MapContent> {
+        Ok(MapContent::new(None, len))
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<MapContent> {
+        Ok(MapContent::new(None, Some(len)))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<MapContent> {
+        Ok(MapContent::new(Some(variant), Some(len)))
+    }
+}
+
+// Wraps the content of a tuple or struct variant into the newtype variant it is written as.
+fn in_variant(variant: Option<&'static str>, content: Content) -> Content {
+    match variant {
+        Some(variant) => Content::NewtypeVariant(variant, Box::new(content)),
+        None => content,
+    }
+}
+
+struct SeqContent {
+    variant: Option<&'static str>,
+    len: Option<usize>,
+    items: Vec<Content>,
+}
+
+impl SeqContent {
+    fn new(variant: Option<&'static str>, len: Option<usize>) -> Self {
+        SeqContent {
+            variant,
+            len,
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        }
+    }
+
+    fn push<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.items.push(to_content(value)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Content> {
+        Ok(in_variant(self.variant, Content::Seq(self.len, self.items)))
+    }
+}
+
+impl ser::SerializeSeq for SeqContent {
+    type Ok = Content;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Content> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SeqContent {
+    type Ok = Content;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Content> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqContent {
+    type Ok = Content;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Content> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqContent {
+    type Ok = Content;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Content> {
+        self.finish()
+    }
+}
+
+struct MapContent {
+    variant: Option<&'static str>,
+    len: Option<usize>,
+    entries: Vec<(String, Content)>,
+}
+
+impl MapContent {
+    fn new(variant: Option<&'static str>, len: Option<usize>) -> Self {
+        MapContent {
+            variant,
+            len,
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+        }
+    }
+
+    fn finish(self) -> Result<Content> {
+        Ok(in_variant(
+            self.variant,
+            Content::Map(self.len, self.entries),
+        ))
+    }
+}
+
+impl ser::SerializeMap for MapContent {
+    type Ok = Content;
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.entries.push((key_text(key)?, Content::Unit));
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let entry = self
+            .entries
+            .last_mut()
+            .expect("serialize_value called before serialize_key");
+        entry.1 = to_content(value)?;
+        Ok(())
+    }
+
+    fn end(self) -> Result<Content> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for MapContent {
+    type Ok = Content;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.entries.push((key.to_string(), to_content(value)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Content> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for MapContent {
+    type Ok = Content;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<Content> {
+        self.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::collections::BTreeMap;
+    use raw::RawValue;
+
+    // Serializes itself through the serializer method it is named after, with the shapes that
+    // `Content` folds into others.
+    struct Shape(&'static str);
+
+    impl Serialize for Shape {
+        fn serialize<S>(&self, s: S) -> result::Result<S::Ok, S::Error>
+        where
+            S: ser::Serializer,
+        {
+            use serde::ser::{
+                SerializeStruct, SerializeStructVariant, SerializeTupleStruct,
+                SerializeTupleVariant,
+            };
+
+            match self.0 {
+                "bytes" => s.serialize_bytes(b"ab"),
+                "unit_struct" => s.serialize_unit_struct("Unit"),
+                "unit_variant" => s.serialize_unit_variant("Mode", 0, "Off"),
+                "newtype_variant" => s.serialize_newtype_variant("Mode", 1, "Scale", &0.1f32),
+                "tuple_struct" => {
+                    let mut tuple = s.serialize_tuple_struct("Pair", 2)?;
+                    tuple.serialize_field(&1u8)?;
+                    tuple.serialize_field(&'x')?;
+                    tuple.end()
+                }
+                "tuple_variant" => {
+                    let mut tuple = s.serialize_tuple_variant("Mode", 2, "Range", 2)?;
+                    tuple.serialize_field(&i128::MIN)?;
+                    tuple.serialize_field(&u128::MAX)?;
+                    tuple.end()
+                }
+                "struct" => {
+                    let mut st = s.serialize_struct("Limits", 2)?;
+                    st.serialize_field("min", &None::<u8>)?;
+                    st.serialize_field("max", &Some(()))?;
+                    st.end()
+                }
+                _ => {
+                    let mut st = s.serialize_struct_variant("Mode", 3, "Auto", 2)?;
+                    st.serialize_field("step", &Some(-5))?;
+                    st.serialize_field("name", "low")?;
+                    st.end()
+                }
+            }
+        }
+    }
+
+    fn write<T>(value: &T, sort_keys: bool, compact_width: usize, skip_none: bool) -> String
+    where
+        T: Serialize,
+    {
+        let mut out = Vec::new();
+        let mut ser = ::ser::Serializer::new(&mut out)
+            .sort_keys(sort_keys)
+            .compact_width(compact_width)
+            .skip_none(skip_none);
+        value.serialize(&mut ser).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn replays_like_the_value() {
+        let names = [
+            "bytes",
+            "unit_struct",
+            "unit_variant",
+            "newtype_variant",
+            "tuple_struct",
+            "tuple_variant",
+            "struct",
+            "struct_variant",
+        ];
+        let mut shapes = BTreeMap::new();
+        for &name in names.iter() {
+            shapes.insert(name, Shape(name));
+        }
+        let raw = RawValue::from_string("[1, 2] # kept".to_string()).unwrap();
+        let value = (shapes, raw);
+        let content = to_content(&value).unwrap();
+
+        for &(sort_keys, compact_width, skip_none) in [
+            (false, 0, false),
+            (true, 0, true),
+            (false, 80, false),
+            (true, 80, true),
+        ]
+        .iter()
+        {
+            assert_eq!(
+                write(&content, sort_keys, compact_width, skip_none),
+                write(&value, sort_keys, compact_width, skip_none)
+            );
+        }
+        assert_eq!(
+            write(&content, false, 80, false),
+            "[\n  {\n    bytes: [97, 98]\n    newtype_variant:\n    {\n      Scale: 0.1\n    }\n    struct: {min: null, max: null}\n    struct_variant:\n    {\n      Auto: {step: -5, name: \"low\"}\n    }\n    tuple_struct: [1, \"x\"]\n    tuple_variant:\n    {\n      Range:\n      [\n        -170141183460469231731687303715884105728\n        340282366920938463463374607431768211455\n      ]\n    }\n    unit_struct: null\n    unit_variant: Off\n  }\n  [1, 2] # kept\n]"
+        );
+    }
+}
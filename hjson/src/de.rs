@@ -2,16 +2,29 @@
 //!
 //! This module provides for Hjson deserialization with the type `Deserializer`.
 
-use std::char;
+use alloc::collections::BTreeSet;
+use alloc::rc::Rc;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::cell::Cell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::Range;
+use core::result;
+use core::str;
+#[cfg(feature = "std")]
 use std::io;
-use std::marker::PhantomData;
-use std::str;
 
 use serde::de;
 
+use super::comments::{self, Comments};
 use super::error::{Error, ErrorCode, Result};
-use super::util::StringReader;
-use super::util::{Number, ParseNumber};
+use super::raw;
+use super::styles::{StringStyle, StringStyles};
+use super::tokenizer::{as_str, Lexer, Scalar};
+use super::util::Number;
+use super::value::{Map, Value};
 
 enum State {
     Normal,
@@ -19,11 +32,88 @@ enum State {
     Keyname,
 }
 
+/// What the deserializer does with an unknown escape like `\q` in a quoted string.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EscapePolicy {
+    /// Fail with `ErrorCode::InvalidEscape` (the default).
+    Strict,
+    /// Keep the backslash and the character, so `\q` is read as `\q`.
+    PassThrough,
+    /// Drop the backslash, so `\q` is read as `q`.
+    DropBackslash,
+}
+
+/// The input of a `Deserializer` as a whole, so strings can be borrowed from it instead of
+/// being copied. This is implemented by `()` for input that is only available as an iterator,
+/// and by `&[u8]` for input held in memory.
+pub trait Source<'de> {
+    /// Returns the bytes of the input in `start..end`, if they are available.
+    fn slice(&self, start: usize, end: usize) -> Option<&'de [u8]>;
+}
+
+impl<'de> Source<'de> for () {
+    #[inline]
+    fn slice(&self, _start: usize, _end: usize) -> Option<&'de [u8]> {
+        None
+    }
+}
+
+impl<'de> Source<'de> for &'de [u8] {
+    #[inline]
+    fn slice(&self, start: usize, end: usize) -> Option<&'de [u8]> {
+        let input: &'de [u8] = self;
+        input.get(start..end)
+    }
+}
+
 /// A structure that deserializes Hjson into Rust values.
-pub struct Deserializer<Iter: Iterator<Item = u8>> {
-    rdr: StringReader<Iter>,
-    str_buf: Vec<u8>,
+///
+/// A `Deserializer` created with `Deserializer::from_str` borrows the strings it hands out from
+/// the input where possible (see `Source`).
+pub struct Deserializer<Iter: Iterator<Item = u8>, Src = ()> {
+    lexer: Lexer<Iter>,
+    src: Src,
     state: State,
+    deny_duplicate_keys: bool,
+    trailing_commas: bool,
+    max_depth: usize,
+    capacity_hint: Option<usize>,
+    // number of arrays and objects that are currently being parsed
+    depth: usize,
+    // set when the next value is deserialized into a type that takes number literals, see
+    // `value::deserialize_any_with_literals`
+    #[cfg(feature = "arbitrary_precision")]
+    literal_numbers: bool,
+    // set while deserializing an i128 or u128, which are visited as such
+    wide_integers: bool,
+    // only set when comments are collected, together with the pointers of the values that are
+    // being parsed
+    comments: Option<Comments>,
+    path: Vec<String>,
+    // only set when string styles are collected, like `comments`
+    styles: Option<StringStyles>,
+    // the style of the string that was visited last, recorded once it turns out to be a value
+    string_style: Option<StringStyle>,
+    // only set for the event parser, which reads the line, column and offset of the value that
+    // is being visited or of the end of the current array or object from it
+    marks: Option<Rc<Cell<(usize, usize, usize)>>>,
+    // only set for `from_str_spanned`, which reads the start and end offset of the value that was
+    // parsed last from it
+    spans: Option<Rc<Cell<(usize, usize)>>>,
+    // the last key of each object that is being parsed, by depth, reused to avoid allocations
+    last_keys: Vec<String>,
+    // only set for `into_iter`, the closing brackets of the arrays and objects that an error left
+    // open, innermost first, so the rest of the element that failed can be skipped
+    unclosed: Option<Vec<u8>>,
+    // where a custom error raised by a visitor is, collected while it is returned from the
+    // innermost value outwards and added to its message once it leaves the root
+    error_path: Vec<PathSegment>,
+}
+
+// A step into an array or object on the way to the value that failed.
+enum PathSegment {
+    Index(usize),
+    Key(String),
 }
 
 // macro_rules! try_or_invalid {
@@ -42,90 +132,469 @@ where
     /// Creates the Hjson parser from an `std::iter::Iterator`.
     #[inline]
     pub fn new(rdr: Iter) -> Deserializer<Iter> {
+        Deserializer::with_source(rdr, ())
+    }
+
+    /// Creates the Hjson parser from an `std::iter::Iterator`, accepting a root object without
+    /// braces (see `root_braces_optional`).
+    #[inline]
+    pub fn new_for_root(rdr: Iter) -> Deserializer<Iter> {
+        Deserializer::new(rdr).root_braces_optional(true)
+    }
+}
+
+impl<'de> Deserializer<str::Bytes<'de>, &'de [u8]> {
+    /// Creates the Hjson parser from a `&str`. Strings without escape sequences are borrowed from
+    /// `s`, so they can be deserialized into `&'de str`.
+    #[allow(clippy::should_implement_trait)]
+    #[inline]
+    pub fn from_str(s: &'de str) -> Self {
+        Deserializer::with_source(s.bytes(), s.as_bytes())
+    }
+}
+
+impl<Iter, Src> Deserializer<Iter, Src>
+where
+    Iter: Iterator<Item = u8>,
+{
+    fn with_source(rdr: Iter, src: Src) -> Self {
         Deserializer {
-            rdr: StringReader::new(rdr),
-            str_buf: Vec::with_capacity(128),
+            lexer: Lexer::new(rdr),
+            src,
             state: State::Normal,
+            deny_duplicate_keys: false,
+            trailing_commas: true,
+            max_depth: 128,
+            capacity_hint: None,
+            depth: 0,
+            #[cfg(feature = "arbitrary_precision")]
+            literal_numbers: false,
+            wide_integers: false,
+            comments: None,
+            styles: None,
+            string_style: None,
+            path: Vec::new(),
+            marks: None,
+            spans: None,
+            last_keys: Vec::new(),
+            unclosed: None,
+            error_path: Vec::new(),
         }
     }
 
-    /// Creates the Hjson parser from an `std::iter::Iterator`.
+    /// Turns the parser into an `Iterator` that deserializes the elements of a top-level array one
+    /// at a time, so the whole array never has to be held in memory.
+    ///
+    /// Hjson's optional commas and comments between elements are handled like in any other
+    /// array. An element that cannot be deserialized into `T` is an `Err` item and the iterator
+    /// goes on with the next element; it stops after the closing `]` (and checks that only
+    /// whitespace follows) or after the first syntax error. Input that is not an array fails
+    /// with `ErrorCode::ExpectedArray`.
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use std::io::{BufReader, Read};
+    ///
+    /// use serde_hjson::{Deserializer, Value};
+    ///
+    /// let reader = BufReader::new(File::open("log.hjson").unwrap());
+    /// let de = Deserializer::new(reader.bytes().map(|b| b.unwrap()));
+    /// for entry in de.into_iter::<Value>() {
+    ///     println!("{}", entry.unwrap());
+    /// }
+    /// ```
+    #[allow(clippy::should_implement_trait)]
+    pub fn into_iter<T>(self) -> ArrayDeserializer<T, Iter, Src>
+    where
+        T: de::DeserializeOwned,
+    {
+        ArrayDeserializer::new(self)
+    }
+
+    /// Parse the quoteless values `NaN`, `Infinity` and `-Infinity` as floats instead of strings.
+    /// This is off by default; note that the values cannot be represented in strict JSON.
     #[inline]
-    pub fn new_for_root(rdr: Iter) -> Deserializer<Iter> {
-        let mut res = Deserializer::new(rdr);
-        res.state = State::Root;
+    pub fn allow_non_finite(mut self, allow: bool) -> Self {
+        self.lexer.allow_non_finite = allow;
+        self
+    }
+
+    /// Fail with `ErrorCode::DuplicateKey` when a key appears more than once in the same object,
+    /// instead of letting the last value win. This is off by default.
+    #[inline]
+    pub fn deny_duplicate_keys(mut self, deny: bool) -> Self {
+        self.deny_duplicate_keys = deny;
+        self
+    }
+
+    /// Accept a comma right before the `]` or `}` that closes an array or object, like
+    /// `[1, 2, 3,]`. This is on by default like in Hjson, and off in `strict` mode, where such a
+    /// comma fails with `ErrorCode::TrailingComma`.
+    #[inline]
+    pub fn allow_trailing_comma(mut self, allow: bool) -> Self {
+        self.trailing_commas = allow;
+        self
+    }
+
+    /// Only accept strict JSON: comments, quoteless and multiline strings, unquoted keys, missing
+    /// or trailing commas and a root object without braces are reported as errors. This is off
+    /// by default, see also `from_str_strict`.
+    #[inline]
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.lexer.strict = strict;
+        self.lexer.rdr.deny_comments(strict);
+        self
+    }
+
+    /// Accept a root object whose braces are omitted, so the input can start directly with
+    /// `key: value` lines; empty input is then an empty object. The root is only taken to be such
+    /// an object when it starts with a key followed by `:`, anything else is parsed as a regular
+    /// value. This is off for a `Deserializer` created with `new`, while `from_str` and the other
+    /// functions of this module enable it and, like the reference implementation, fall back to a
+    /// regular value when the braceless object fails to parse.
+    #[inline]
+    pub fn root_braces_optional(mut self, optional: bool) -> Self {
+        self.state = if optional { State::Root } else { State::Normal };
+        self
+    }
+
+    /// Also accept the escapes `\xHH` (for the code points up to U+00FF) and `\u{H...}` (with one
+    /// to six hex digits naming any Unicode scalar value) in quoted strings. This is off by
+    /// default, where they fail with `ErrorCode::InvalidEscape`.
+    #[inline]
+    pub fn relaxed_escapes(mut self, relaxed: bool) -> Self {
+        self.lexer.relaxed_escapes = relaxed;
+        self
+    }
+
+    /// Choose what to do with unknown escapes in quoted strings, see `EscapePolicy`. The default
+    /// is `EscapePolicy::Strict`; the other policies help with input from systems that escape
+    /// more loosely. Known escapes that are malformed, like `\u12`, always fail.
+    #[inline]
+    pub fn escape_policy(mut self, policy: EscapePolicy) -> Self {
+        self.lexer.escape_policy = policy;
+        self
+    }
+
+    /// Accept `_` between the digits of a number, like `1_000_000`. A number with an `_` at its
+    /// start or end, next to another `_`, the decimal point or the exponent fails with
+    /// `ErrorCode::InvalidNumber`. This is off by default, where such values are quoteless
+    /// strings.
+    #[inline]
+    pub fn allow_number_separators(mut self, allow: bool) -> Self {
+        self.lexer.number_separators = allow;
+        self
+    }
+
+    /// Accept integers with the prefixes `0x` (hexadecimal), `0o` (octal) and `0b` (binary),
+    /// optionally after a `-`, like `0xFF`, `0o755` or `-0x10`. Invalid digits or an integer that
+    /// does not fit into 64 bits fail with `ErrorCode::InvalidNumber`. This is off by default,
+    /// where such values are quoteless strings.
+    #[inline]
+    pub fn allow_radix_literals(mut self, allow: bool) -> Self {
+        self.lexer.radix_literals = allow;
+        self
+    }
+
+    /// Accept numbers with leading zeros, like `007` or `-00.5`, and read them as decimal: the
+    /// zeros are ignored, also together with `allow_radix_literals`, where octal needs the `0o`
+    /// prefix. This is off by default like in JSON, where such values are quoteless strings, or
+    /// fail with `ErrorCode::InvalidNumber` in `strict` mode.
+    #[inline]
+    pub fn allow_leading_zeros(mut self, allow: bool) -> Self {
+        self.lexer.leading_zeros = allow;
+        self
+    }
+
+    /// Accept numbers with a leading `+` and decimal points without digits on one side, like
+    /// `+1`, `.5`, `5.` or `+.5e3`; a decimal point makes them floats, so `5.` is read as `5.0`.
+    /// This is off by default like in JSON, where such values are quoteless strings, or fail with
+    /// `ErrorCode::InvalidNumber` in `strict` mode.
+    #[inline]
+    pub fn lenient_numbers(mut self, lenient: bool) -> Self {
+        self.lexer.lenient_numbers = lenient;
+        self
+    }
+
+    /// Skip a UTF-8 byte order mark (U+FEFF) at the start of the input, as some editors write
+    /// one. This is on by default; the mark does not count as a column in error positions, but
+    /// byte offsets still include it. Anywhere else, a mark outside of a quoted string fails
+    /// with `ErrorCode::UnexpectedByteOrderMark`.
+    #[inline]
+    pub fn strip_bom(mut self, strip: bool) -> Self {
+        self.lexer.rdr.strip_bom(strip);
+        self
+    }
+
+    /// Fail with `ErrorCode::RecursionLimitExceeded` when arrays and objects are nested more than
+    /// `depth` levels deep, instead of running out of stack on hostile input. The default is 128.
+    #[inline]
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Fail with `ErrorCode::InputTooLarge` once more than `size` bytes have been read, to
+    /// bound the memory used for untrusted input. There is no limit by default.
+    #[inline]
+    pub fn max_input_size(mut self, size: usize) -> Self {
+        self.lexer.rdr.max_input_size(size);
+        self
+    }
+
+    /// Fail with `ErrorCode::StringTooLong` when a string or key is longer than `length` bytes
+    /// (after unescaping). There is no limit by default.
+    #[inline]
+    pub fn max_string_length(mut self, length: usize) -> Self {
+        self.lexer.max_string_length = length;
+        self
+    }
+
+    /// Tell the collection that the root array or object is parsed into to reserve room for
+    /// `len` elements up front, because it is known to be large. The hint is not checked against
+    /// the input, and the arrays and objects nested in the root do not get it. There is no hint
+    /// by default, where collections grow as their elements are parsed.
+    ///
+    /// The collection decides how much of the hint it uses; serde's own implementations for
+    /// `Vec` and the maps reserve at most 1 MiB up front.
+    #[inline]
+    pub fn capacity_hint(mut self, len: usize) -> Self {
+        self.capacity_hint = Some(len);
+        self
+    }
+
+    // The `size_hint` of the array or object that is being parsed.
+    fn size_hint(&self) -> Option<usize> {
+        if self.depth == 1 {
+            self.capacity_hint
+        } else {
+            None
+        }
+    }
+
+    // Records that a custom error was raised in the element or member `segment` of the array or
+    // object being parsed.
+    fn error_in<T, F>(&mut self, res: Result<T>, segment: F) -> Result<T>
+    where
+        F: FnOnce(&Self) -> PathSegment,
+    {
+        if let Err(Error::Syntax(ErrorCode::Custom(_), ..)) = res {
+            let segment = segment(self);
+            self.error_path.push(segment);
+        }
+        res
+    }
+
+    // Called with the result of an array or object that is closed by `closer`, adds the path of a
+    // custom error to its message when the root is left, like `servers[0].port: invalid type: ...`.
+    fn leave<T>(&mut self, res: Result<T>, closer: u8) -> Result<T> {
+        self.depth -= 1;
+        if let (&Err(_), Some(unclosed)) = (&res, self.unclosed.as_mut()) {
+            unclosed.push(closer);
+        }
+        if self.depth > 0 || self.error_path.is_empty() {
+            return res;
+        }
+        let mut path = String::new();
+        for segment in self.error_path.drain(..).rev() {
+            match segment {
+                PathSegment::Index(index) => path.push_str(&format!("[{}]", index)),
+                PathSegment::Key(key) => {
+                    if !path.is_empty() {
+                        path.push('.');
+                    }
+                    path.push_str(&key);
+                }
+            }
+        }
+        match res {
+            Err(Error::Syntax(ErrorCode::Custom(msg), line, col, offset)) => Err(Error::Syntax(
+                ErrorCode::Custom(format!("{}: {}", path, msg)),
+                line,
+                col,
+                offset,
+            )),
+            res => res,
+        }
+    }
+
+    // Called when an array or object is entered, `depth` has to be decremented again when it is
+    // left.
+    fn enter(&mut self) -> Result<()> {
+        if self.depth >= self.max_depth {
+            return Err(Error::Syntax(ErrorCode::RecursionLimitExceeded, 0, 0, 0));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn deserialize_wide_integer<'de, V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+        Src: Source<'de>,
+    {
+        self.wide_integers = true;
+        let res = self.parse_value(visitor);
+        self.wide_integers = false;
         res
     }
 
+    fn collect_comments(&mut self) {
+        self.lexer.rdr.collect_comments();
+        self.comments = Some(Comments::new());
+    }
+
+    fn collect_styles(&mut self) {
+        self.styles = Some(StringStyles::new());
+    }
+
+    // Whether the pointers of the values that are being parsed are kept in `path`.
+    fn tracks_path(&self) -> bool {
+        self.comments.is_some() || self.styles.is_some()
+    }
+
+    // Records the style of the string that was visited last for the current value, if that value
+    // was a string.
+    fn record_style(&mut self) {
+        if let (Some(style), Some(styles)) = (self.string_style.take(), self.styles.as_mut()) {
+            styles.insert(self.path.last().map_or("", |p| &p[..]), style);
+        }
+    }
+
+    // Attaches the comments skipped since the previous member: those on the same line as the
+    // previous value trail it, the rest lead into the next member (or are dropped when there is
+    // none).
+    fn attach_comments(&mut self, prev: Option<&str>, next: Option<&str>) {
+        let comments = match self.comments {
+            Some(ref mut comments) => comments,
+            None => return,
+        };
+        let mut pending = self.lexer.rdr.take_comments().into_iter().peekable();
+        if let Some(prev) = prev {
+            while let Some(&(false, _)) = pending.peek() {
+                let (_, text) = pending.next().unwrap();
+                let after = &mut comments.entry(prev).after;
+                *after = Some(match after.take() {
+                    Some(before) => format!("{} {}", before, text),
+                    None => text,
+                });
+            }
+        }
+        if let Some(next) = next {
+            let before: Vec<_> = pending.map(|(_, text)| text).collect();
+            if !before.is_empty() {
+                comments.entry(next).before.extend(before);
+            }
+        }
+    }
+
+    /// Returns the line and column (both starting at 1, as in errors) and the byte offset where
+    /// parsing continues, after skipping any whitespace and comments. Between two values this is
+    /// where the next one starts.
+    pub fn position(&mut self) -> (usize, usize, usize) {
+        // the column is only known once the next character has been read; an error is reported
+        // again by the next parse
+        let _ = self
+            .lexer
+            .rdr
+            .parse_whitespace()
+            .and_then(|()| self.lexer.rdr.peek());
+        let (line, col) = self.lexer.rdr.pos();
+        (line, col, self.lexer.rdr.offset())
+    }
+
+    // used by the events module
+    #[cfg(feature = "std")]
+    pub(crate) fn share_marks(&mut self) -> Rc<Cell<(usize, usize, usize)>> {
+        self.marks.get_or_insert_with(Default::default).clone()
+    }
+
+    // used by the spanned module
+    pub(crate) fn share_spans(&mut self) -> Rc<Cell<(usize, usize)>> {
+        self.lexer.track_ends = true;
+        self.spans.get_or_insert_with(Default::default).clone()
+    }
+
+    fn mark(&mut self) {
+        if self.marks.is_some() {
+            let position = self.position();
+            self.marks.as_ref().unwrap().set(position);
+        }
+    }
+
+    fn current_pointer(&self) -> &str {
+        self.path.last().map_or("", |p| &p[..])
+    }
     /// The `Deserializer::end` method should be called after a value has been fully deserialized.
     /// This allows the `Deserializer` to validate that the input stream is at the end or that it
-    /// only has trailing whitespace.
+    /// only has trailing whitespace and comments.
     #[inline]
     pub fn end(&mut self) -> Result<()> {
-        self.rdr.parse_whitespace()?;
-        if self.rdr.eof()? {
+        self.lexer.rdr.parse_whitespace()?;
+        if self.lexer.rdr.eof()? {
             Ok(())
         } else {
-            Err(self.rdr.error(ErrorCode::TrailingCharacters))
+            Err(self.lexer.rdr.error(ErrorCode::TrailingCharacters))
         }
     }
 
-    fn is_punctuator_char(&mut self, ch: u8) -> bool {
-        match ch {
-            b'{' | b'}' | b'[' | b']' | b',' | b':' => true,
-            _ => false,
-        }
+    fn parse_keyname<'de, V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+        Src: Source<'de>,
+    {
+        let start = self.lexer.rdr.offset();
+        self.lexer.parse_keyname()?;
+        let len = self.lexer.buf.len();
+        self.visit_buf(start, 0..len, visitor)
     }
 
-    fn parse_keyname<'de, V>(&mut self, visitor: V) -> Result<V::Value>
+    fn parse_value<'de, V>(&mut self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
+        Src: Source<'de>,
     {
-        // quotes for keys are optional in Hjson
-        // unless they include {}[],: or whitespace.
-        // assume whitespace was already eaten
+        self.lexer.rdr.parse_whitespace()?;
 
-        self.str_buf.clear();
+        let braceless_root = match self.state {
+            State::Root => !self.lexer.strict,
+            _ => false,
+        };
+        if self.lexer.rdr.eof()? && !braceless_root {
+            return Err(self.lexer.rdr.error(ErrorCode::EOFWhileParsingValue));
+        }
 
-        let mut space: Option<usize> = None;
-        loop {
-            let ch = self.rdr.next_char_or_null()?;
-
-            if ch == b':' {
-                if self.str_buf.len() == 0 {
-                    return Err(self.rdr.error(ErrorCode::Custom(
-                        "Found ':' but no key name (for an empty key name use quotes)".to_string(),
-                    )));
-                } else if space.is_some() && space.unwrap() != self.str_buf.len() {
-                    return Err(self.rdr.error(ErrorCode::Custom(
-                        "Found whitespace in your key name (use quotes to include)".to_string(),
-                    )));
-                }
-                self.rdr.uneat_char(ch);
-                let s = str::from_utf8(&self.str_buf).unwrap();
-                return visitor.visit_str(s);
-            } else if ch <= b' ' {
-                if ch == 0 {
-                    return Err(self.rdr.error(ErrorCode::EOFWhileParsingObject));
-                } else if space.is_none() {
-                    space = Some(self.str_buf.len());
-                }
-            } else if self.is_punctuator_char(ch) {
-                return Err(self.rdr.error(ErrorCode::Custom("Found a punctuator where a key name was expected (check your syntax or use quotes if the key name includes {}[],: or whitespace)".to_string())));
-            } else {
-                self.str_buf.push(ch);
-            }
+        // errors raised by the visitor carry no position, report them at the
+        // start of the value
+        let (line, col) = self.lexer.rdr.pos();
+        let offset = self.lexer.rdr.offset();
+        self.mark();
+        let res = match self.parse_value_inner(visitor) {
+            Err(Error::Syntax(code, 0, 0, 0)) => Err(Error::Syntax(code, line, col, offset)),
+            res => res,
+        };
+        let end = self
+            .lexer
+            .token_end
+            .take()
+            .unwrap_or_else(|| self.lexer.rdr.offset());
+        if let Some(ref spans) = self.spans {
+            spans.set((offset, end));
         }
+        res
     }
 
-    fn parse_value<'de, V>(&mut self, visitor: V) -> Result<V::Value>
+    fn parse_value_inner<'de, V>(&mut self, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
+        Src: Source<'de>,
     {
-        self.rdr.parse_whitespace()?;
-
-        if self.rdr.eof()? {
-            return Err(self.rdr.error(ErrorCode::EOFWhileParsingValue));
+        // only a number itself takes the literal, not the values inside an array or object
+        #[cfg(feature = "arbitrary_precision")]
+        let literal_numbers = self.literal_numbers;
+        #[cfg(feature = "arbitrary_precision")]
+        {
+            self.literal_numbers = false;
         }
 
         match self.state {
@@ -135,15 +604,17 @@ where
             }
             State::Root => {
                 self.state = State::Normal;
-                return self.visit_map(true, visitor);
+                if !self.lexer.strict && self.lexer.root_key_ahead()? {
+                    return self.visit_map(true, visitor);
+                }
             }
             _ => {}
         }
 
-        match self.rdr.peek_or_null()? {
+        match self.lexer.rdr.peek_or_null()? {
             /*
             b'-' => {
-                self.rdr.eat_char();
+                self.lexer.rdr.eat_char();
                 self.parse_integer(false, visitor)
             }
             b'0' ... b'9' => {
@@ -151,26 +622,47 @@ where
             }
             */
             b'"' => {
-                self.rdr.eat_char();
-                self.parse_string()?;
-                let s = str::from_utf8(&self.str_buf).unwrap();
-                visitor.visit_str(s)
+                self.lexer.rdr.eat_char();
+                let start = self.lexer.rdr.offset();
+                self.lexer.parse_string(b'"')?;
+                let len = self.lexer.buf.len();
+                self.string_style = Some(StringStyle::Quoted);
+                self.visit_buf(start, 0..len, visitor)
+            }
+            // a single-quoted string, unless it is the start of a multiline string
+            b'\'' if !self.lexer.strict && !self.lexer.ml_string_ahead()? => {
+                self.lexer.rdr.eat_char();
+                let start = self.lexer.rdr.offset();
+                self.lexer.parse_string(b'\'')?;
+                let len = self.lexer.buf.len();
+                self.string_style = Some(StringStyle::SingleQuoted);
+                self.visit_buf(start, 0..len, visitor)
             }
             b'[' => {
-                self.rdr.eat_char();
-                let ret = visitor.visit_seq(SeqVisitor::new(self))?;
-                self.rdr.parse_whitespace()?;
-                match self.rdr.next_char()? {
+                self.lexer.rdr.eat_char();
+                self.enter()?;
+                let ret = visitor.visit_seq(SeqVisitor::new(self));
+                let ret = self.leave(ret, b']')?;
+                self.lexer.rdr.parse_whitespace()?;
+                match self.lexer.rdr.next_char()? {
                     Some(b']') => Ok(ret),
-                    Some(_) => Err(self.rdr.error(ErrorCode::TrailingCharacters)),
-                    None => Err(self.rdr.error(ErrorCode::EOFWhileParsingList)),
+                    Some(_) => Err(self.lexer.rdr.error(ErrorCode::TrailingCharacters)),
+                    None => Err(self.lexer.rdr.error(ErrorCode::EOFWhileParsingList)),
                 }
             }
             b'{' => {
-                self.rdr.eat_char();
+                self.lexer.rdr.eat_char();
                 self.visit_map(false, visitor)
             }
-            b'\x00' => Err(self.rdr.error(ErrorCode::ExpectedSomeValue)),
+            b'\x00' => Err(self.lexer.rdr.error(ErrorCode::ExpectedSomeValue)),
+            #[cfg(feature = "arbitrary_precision")]
+            _ => {
+                self.literal_numbers = literal_numbers;
+                let res = self.parse_tfnns(visitor);
+                self.literal_numbers = false;
+                res
+            }
+            #[cfg(not(feature = "arbitrary_precision"))]
             _ => self.parse_tfnns(visitor),
         }
     }
@@ -178,328 +670,408 @@ where
     fn visit_map<'de, V>(&mut self, root: bool, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
+        Src: Source<'de>,
     {
-        let ret = visitor.visit_map(MapVisitor::new(self, root))?;
-        self.rdr.parse_whitespace()?;
-        match self.rdr.next_char()? {
+        self.enter()?;
+        let ret = visitor.visit_map(MapVisitor::new(self, root));
+        let ret = self.leave(ret, b'}')?;
+        self.lexer.rdr.parse_whitespace()?;
+        match self.lexer.rdr.next_char()? {
             Some(b'}') => {
                 if !root {
                     Ok(ret)
                 } else {
-                    Err(self.rdr.error(ErrorCode::TrailingCharacters))
+                    Err(self.lexer.rdr.error(ErrorCode::TrailingCharacters))
                 } // todo
             }
-            Some(_) => Err(self.rdr.error(ErrorCode::TrailingCharacters)),
+            Some(_) => Err(self.lexer.rdr.error(ErrorCode::TrailingCharacters)),
             None => {
                 if root {
                     Ok(ret)
                 } else {
-                    Err(self.rdr.error(ErrorCode::EOFWhileParsingObject))
+                    Err(self.lexer.rdr.error(ErrorCode::EOFWhileParsingObject))
                 }
             }
         }
     }
 
-    fn parse_ident(&mut self, ident: &[u8]) -> Result<()> {
-        for c in ident {
-            if Some(*c) != self.rdr.next_char()? {
-                return Err(self.rdr.error(ErrorCode::ExpectedSomeIdent));
+    // Parses an enum in the externally tagged form: a unit variant is a string with its name,
+    // the other variants are an object with the name as its only key.
+    fn parse_enum<'de, V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+        Src: Source<'de>,
+    {
+        self.lexer.rdr.parse_whitespace()?;
+        let root = match self.state {
+            State::Root => {
+                self.state = State::Normal;
+                !self.lexer.strict && self.lexer.root_key_ahead()?
             }
+            _ => false,
+        };
+        if !root {
+            if self.lexer.rdr.peek()? != Some(b'{') {
+                return visitor.visit_enum(UnitVariantAccess { de: self });
+            }
+            self.lexer.rdr.eat_char();
         }
 
-        Ok(())
+        self.enter()?;
+        let ret = visitor.visit_enum(&mut *self);
+        let ret = self.leave(ret, b'}')?;
+        self.lexer.rdr.parse_whitespace()?;
+        if !self.lexer.strict && self.lexer.rdr.peek()? == Some(b',') {
+            self.lexer.rdr.eat_char();
+            self.lexer.rdr.parse_whitespace()?;
+            if !self.trailing_commas && self.lexer.rdr.peek()? == Some(b'}') {
+                return Err(self.lexer.rdr.error(ErrorCode::TrailingComma));
+            }
+        }
+        match self.lexer.rdr.next_char()? {
+            Some(b'}') if !root => Ok(ret),
+            Some(_) => Err(self.lexer.rdr.error(ErrorCode::TrailingCharacters)),
+            None if root => Ok(ret),
+            None => Err(self.lexer.rdr.error(ErrorCode::EOFWhileParsingObject)),
+        }
     }
 
-    fn parse_tfnns<'de, V>(&mut self, visitor: V) -> Result<V::Value>
+    // Visits `range` of the lexer's `buf`, which holds a string whose first byte was at `start` in
+    // the input. The string is borrowed from the input when it has the same bytes there, that is
+    // when it contained no escapes.
+    fn visit_buf<'de, V>(&self, start: usize, range: Range<usize>, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
+        Src: Source<'de>,
     {
-        // Hjson strings can be quoteless
-        // returns string, true, false, or null.
-        self.str_buf.clear();
-
-        let first = self.rdr.peek()?.unwrap();
-
-        if self.is_punctuator_char(first) {
-            return Err(self.rdr.error(ErrorCode::PunctuatorInQlString));
+        let buf = &self.lexer.buf[range.clone()];
+        match self.src.slice(start + range.start, start + range.end) {
+            Some(raw) if raw == buf => visitor.visit_borrowed_str(as_str(raw)?),
+            _ => visitor.visit_str(as_str(buf)?),
         }
+    }
 
-        loop {
-            let ch = self.rdr.next_char_or_null()?;
+    // Parses a value and visits its text in the input, for `RawValue`.
+    fn parse_raw_value<'de, V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+        Src: Source<'de>,
+    {
+        self.lexer.rdr.parse_whitespace()?;
+        let start = self.lexer.rdr.offset();
+        let _: de::IgnoredAny = de::Deserialize::deserialize(&mut *self)?;
+        let raw = match self.src.slice(start, self.lexer.rdr.offset()) {
+            // a quoteless value ends with the whitespace up to the end of its line
+            Some(raw) => as_str(raw)?.trim_end(),
+            None => {
+                return Err(Error::Syntax(
+                    ErrorCode::Custom(
+                        "a RawValue needs a Deserializer created from a str or slice".to_string(),
+                    ),
+                    0,
+                    0,
+                    0,
+                ))
+            }
+        };
+        visitor.visit_newtype_struct(de::IntoDeserializer::into_deserializer(raw))
+    }
 
-            let is_eol = ch == b'\r' || ch == b'\n' || ch == b'\x00';
-            let is_comment = ch == b'#'
-                || ch == b'/' && {
-                    let next = self.rdr.peek_or_null()?;
-                    next == b'/' || next == b'*'
-                };
-            if is_eol || is_comment || ch == b',' || ch == b'}' || ch == b']' {
-                let chf = self.str_buf[0];
-                match chf {
-                    b'f' => {
-                        if str::from_utf8(&self.str_buf).unwrap().trim() == "false" {
-                            self.rdr.uneat_char(ch);
-                            return visitor.visit_bool(false);
-                        }
-                    }
-                    b'n' => {
-                        if str::from_utf8(&self.str_buf).unwrap().trim() == "null" {
-                            self.rdr.uneat_char(ch);
-                            return visitor.visit_unit();
-                        }
+    // Parses a number, keyword or quoteless string.
+    fn parse_tfnns<'de, V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+        Src: Source<'de>,
+    {
+        let start = self.lexer.rdr.offset();
+        match self.lexer.parse_scalar()? {
+            Scalar::Null => visitor.visit_unit(),
+            Scalar::Bool(v) => visitor.visit_bool(v),
+            Scalar::Number(number) => visit_number(number, visitor),
+            Scalar::Decimal(number) => {
+                let literal = self.lexer.number();
+                if self.wide_integers && !literal.contains(['.', 'e', 'E']) {
+                    if let Ok(v) = literal.parse::<u128>() {
+                        return visitor.visit_u128(v);
                     }
-                    b't' => {
-                        if str::from_utf8(&self.str_buf).unwrap().trim() == "true" {
-                            self.rdr.uneat_char(ch);
-                            return visitor.visit_bool(true);
-                        }
+                    if let Ok(v) = literal.parse::<i128>() {
+                        return visitor.visit_i128(v);
                     }
-                    _ => {
-                        if chf == b'-' || chf >= b'0' && chf <= b'9' {
-                            let mut pn = ParseNumber::new(self.str_buf.iter().map(|b| *b));
-                            match pn.parse(false) {
-                                Ok(Number::F64(v)) => {
-                                    self.rdr.uneat_char(ch);
-                                    return visitor.visit_f64(v);
-                                }
-                                Ok(Number::U64(v)) => {
-                                    self.rdr.uneat_char(ch);
-                                    return visitor.visit_u64(v);
-                                }
-                                Ok(Number::I64(v)) => {
-                                    self.rdr.uneat_char(ch);
-                                    return visitor.visit_i64(v);
-                                }
-                                Err(_) => {} // not a number, continue
-                            }
+                }
+                #[cfg(feature = "arbitrary_precision")]
+                {
+                    if let Some(literal) = inexact_literal(literal, &number) {
+                        if self.literal_numbers {
+                            return visitor.visit_newtype_struct(
+                                de::IntoDeserializer::into_deserializer(literal),
+                            );
                         }
                     }
                 }
-                if is_eol {
-                    // remove any whitespace at the end (ignored in quoteless strings)
-                    return visitor.visit_str(str::from_utf8(&self.str_buf).unwrap().trim());
-                }
+                visit_number(number, visitor)
             }
-            self.str_buf.push(ch);
-
-            if self.str_buf == b"'''" {
-                return self.parse_ml_string(visitor);
+            Scalar::Quoteless(range) => {
+                self.string_style = Some(StringStyle::Quoteless);
+                self.visit_buf(start, range, visitor)
+            }
+            Scalar::Multiline => {
+                self.string_style = Some(StringStyle::Multiline);
+                visitor.visit_str(as_str(&self.lexer.buf)?)
             }
         }
     }
 
-    fn decode_hex_escape(&mut self) -> Result<u16> {
-        let mut i = 0;
-        let mut n = 0u16;
-        while i < 4 && !self.rdr.eof()? {
-            n = match self.rdr.next_char_or_null()? {
-                c @ b'0'..=b'9' => n * 16_u16 + ((c as u16) - (b'0' as u16)),
-                b'a' | b'A' => n * 16_u16 + 10_u16,
-                b'b' | b'B' => n * 16_u16 + 11_u16,
-                b'c' | b'C' => n * 16_u16 + 12_u16,
-                b'd' | b'D' => n * 16_u16 + 13_u16,
-                b'e' | b'E' => n * 16_u16 + 14_u16,
-                b'f' | b'F' => n * 16_u16 + 15_u16,
-                _ => {
-                    return Err(self.rdr.error(ErrorCode::InvalidEscape));
+    // Parses what follows an array element. In Hjson the comma is optional and trailing commas are
+    // allowed, but only a newline can take its place.
+    fn parse_list_separator(&mut self) -> Result<()> {
+        let line = self.lexer.rdr.consumed_line();
+        self.lexer.rdr.parse_whitespace()?;
+        match self.lexer.rdr.peek()? {
+            Some(b',') => {
+                self.lexer.rdr.eat_char();
+                self.lexer.rdr.parse_whitespace()?;
+                let denied = self.lexer.strict || !self.trailing_commas;
+                if denied && self.lexer.rdr.peek()? == Some(b']') {
+                    return Err(self.lexer.rdr.error(ErrorCode::TrailingComma));
                 }
-            };
-
-            i += 1;
+            }
+            Some(b']') | None => {}
+            Some(_) if self.lexer.strict || self.lexer.rdr.pos().0 == line => {
+                return Err(self.lexer.rdr.error(ErrorCode::ExpectedListCommaOrEnd));
+            }
+            Some(_) => {}
         }
+        Ok(())
+    }
 
-        // Error out if we didn't parse 4 digits.
-        if i != 4 {
-            return Err(self.rdr.error(ErrorCode::InvalidEscape));
-        }
+    fn parse_object_colon(&mut self) -> Result<()> {
+        self.lexer.rdr.parse_whitespace()?;
 
-        Ok(n)
+        match self.lexer.rdr.next_char()? {
+            Some(b':') => Ok(()),
+            Some(_) => Err(self.lexer.rdr.error(ErrorCode::ExpectedColon)),
+            None => Err(self.lexer.rdr.error(ErrorCode::EOFWhileParsingObject)),
+        }
     }
+}
 
-    fn ml_skip_white(&mut self) -> Result<bool> {
-        match self.rdr.peek_or_null()? {
-            b' ' | b'\t' | b'\r' => {
-                self.rdr.eat_char();
-                return Ok(true);
+// Error recovery for `from_str_recovering`: arrays and objects are parsed here instead of by the
+// visitors, so a member or element that fails can be reported and skipped.
+impl<Iter> Deserializer<Iter>
+where
+    Iter: Iterator<Item = u8>,
+{
+    fn recover_root(&mut self, errors: &mut Vec<Error>) -> Option<Value> {
+        let res = self
+            .lexer
+            .rdr
+            .parse_whitespace()
+            .and_then(|()| self.lexer.root_key_ahead());
+        let value = match res {
+            Ok(true) => Some(self.recover_object(true, errors)),
+            Ok(false) => self.recover_value(false, errors),
+            Err(err) => {
+                errors.push(err);
+                return None;
             }
-            _ => return Ok(false),
+        };
+        if let Err(err) = self.end() {
+            errors.push(err);
         }
+        value
     }
 
-    fn ml_skip_indent(&mut self, indent: usize) -> Result<()> {
-        let mut skip = indent;
-        while self.ml_skip_white()? && skip > 0 {
-            skip -= 1;
+    // Returns `None` when the value failed and was skipped. `in_object` tells whether the value is
+    // inside of an object with braces.
+    fn recover_value(&mut self, in_object: bool, errors: &mut Vec<Error>) -> Option<Value> {
+        let res = self
+            .lexer
+            .rdr
+            .parse_whitespace()
+            .and_then(|()| self.lexer.rdr.peek());
+        match res {
+            Ok(Some(b'[')) | Ok(Some(b'{')) if self.enter().is_err() => {
+                errors.push(self.lexer.rdr.error(ErrorCode::RecursionLimitExceeded));
+                self.skip_to_boundary();
+                return None;
+            }
+            Ok(Some(b'[')) => {
+                self.lexer.rdr.eat_char();
+                let value = self.recover_array(in_object, errors);
+                self.depth -= 1;
+                return Some(value);
+            }
+            Ok(Some(b'{')) => {
+                self.lexer.rdr.eat_char();
+                let value = self.recover_object(false, errors);
+                self.depth -= 1;
+                return Some(value);
+            }
+            _ => {}
         }
-        Ok(())
-    }
-
-    fn parse_ml_string<'de, V>(&mut self, visitor: V) -> Result<V::Value>
-    where
-        V: de::Visitor<'de>,
-    {
-        self.str_buf.clear();
-
-        // Parse a multiline string value.
-        let mut triple = 0;
-
-        // we are at ''' +1 - get indent
-        let (_, col) = self.rdr.pos();
-        let indent = col - 4;
-
-        // skip white/to (newline)
-        while self.ml_skip_white()? {}
-        if self.rdr.peek_or_null()? == b'\n' {
-            self.rdr.eat_char();
-            self.ml_skip_indent(indent)?;
+        match de::Deserialize::deserialize(&mut *self) {
+            Ok(value) => Some(value),
+            Err(err) => {
+                errors.push(err);
+                self.skip_to_boundary();
+                None
+            }
         }
+    }
 
-        // When parsing multiline string values, we must look for ' characters.
+    fn recover_array(&mut self, in_object: bool, errors: &mut Vec<Error>) -> Value {
+        let mut elements = Vec::new();
         loop {
-            if self.rdr.eof()? {
-                return Err(self.rdr.error(ErrorCode::EOFWhileParsingString));
-            } // todo error("Bad multiline string");
-            let ch = self.rdr.next_char_or_null()?;
-
-            if ch == b'\'' {
-                triple += 1;
-                if triple == 3 {
-                    if self.str_buf.last() == Some(&b'\n') {
-                        self.str_buf.pop();
+            let start = self.lexer.rdr.offset();
+            let res = self
+                .lexer
+                .rdr
+                .parse_whitespace()
+                .and_then(|()| self.lexer.rdr.peek());
+            match res {
+                Ok(Some(b']')) => {
+                    self.lexer.rdr.eat_char();
+                    break;
+                }
+                Ok(Some(b'}')) => {
+                    errors.push(self.lexer.rdr.error(ErrorCode::ExpectedListCommaOrEnd));
+                    if in_object {
+                        // the array is not closed, leave the rest to the enclosing object
+                        break;
                     }
-                    let res = str::from_utf8(&self.str_buf).unwrap();
-                    //todo if (self.str_buf.slice(-1) === '\n') self.str_buf=self.str_buf.slice(0, -1); // remove last EOL
-                    return visitor.visit_str(res);
-                } else {
+                    self.lexer.rdr.eat_char();
+                    continue;
+                }
+                Ok(None) => {
+                    errors.push(self.lexer.rdr.error(ErrorCode::EOFWhileParsingList));
+                    break;
+                }
+                Ok(Some(_)) => {}
+                Err(err) => {
+                    errors.push(err);
+                    self.skip_to_boundary();
+                    self.recover_comma(start);
                     continue;
                 }
             }
-
-            while triple > 0 {
-                self.str_buf.push(b'\'');
-                triple -= 1;
-            }
-
-            if ch != b'\r' {
-                self.str_buf.push(ch);
-            }
-            if ch == b'\n' {
-                self.ml_skip_indent(indent)?;
+            if let Some(value) = self.recover_value(in_object, errors) {
+                elements.push(value);
             }
+            self.recover_comma(start);
         }
+        Value::Array(elements)
     }
 
-    fn parse_string(&mut self) -> Result<()> {
-        self.str_buf.clear();
-
+    fn recover_object(&mut self, root: bool, errors: &mut Vec<Error>) -> Value {
+        let mut members = Map::new();
         loop {
-            let ch = match self.rdr.next_char()? {
-                Some(ch) => ch,
-                None => {
-                    return Err(self.rdr.error(ErrorCode::EOFWhileParsingString));
+            let start = self.lexer.rdr.offset();
+            let res = self
+                .lexer
+                .rdr
+                .parse_whitespace()
+                .and_then(|()| self.lexer.rdr.peek());
+            match res {
+                Ok(Some(b'}')) if !root => {
+                    self.lexer.rdr.eat_char();
+                    break;
                 }
-            };
-
-            match ch {
-                b'"' => {
-                    return Ok(());
+                Ok(None) if root => break,
+                Ok(None) => {
+                    errors.push(self.lexer.rdr.error(ErrorCode::EOFWhileParsingObject));
+                    break;
                 }
-                b'\\' => {
-                    let ch = match self.rdr.next_char()? {
-                        Some(ch) => ch,
-                        None => {
-                            return Err(self.rdr.error(ErrorCode::EOFWhileParsingString));
-                        }
+                Ok(Some(ch)) => {
+                    let quoted =
+                        ch == b'"' || ch == b'\'' && !self.lexer.ml_string_ahead().unwrap_or(true);
+                    self.state = if quoted {
+                        State::Normal
+                    } else {
+                        State::Keyname
                     };
-
-                    match ch {
-                        b'"' => self.str_buf.push(b'"'),
-                        b'\\' => self.str_buf.push(b'\\'),
-                        b'/' => self.str_buf.push(b'/'),
-                        b'b' => self.str_buf.push(b'\x08'),
-                        b'f' => self.str_buf.push(b'\x0c'),
-                        b'n' => self.str_buf.push(b'\n'),
-                        b'r' => self.str_buf.push(b'\r'),
-                        b't' => self.str_buf.push(b'\t'),
-                        b'u' => {
-                            let c = match self.decode_hex_escape()? {
-                                0xDC00..=0xDFFF => {
-                                    return Err(self
-                                        .rdr
-                                        .error(ErrorCode::LoneLeadingSurrogateInHexEscape));
-                                }
-
-                                // Non-BMP characters are encoded as a sequence of
-                                // two hex escapes, representing UTF-16 surrogates.
-                                n1 @ 0xD800..=0xDBFF => {
-                                    match (self.rdr.next_char()?, self.rdr.next_char()?) {
-                                        (Some(b'\\'), Some(b'u')) => (),
-                                        _ => {
-                                            return Err(self
-                                                .rdr
-                                                .error(ErrorCode::UnexpectedEndOfHexEscape));
-                                        }
-                                    }
-
-                                    let n2 = self.decode_hex_escape()?;
-
-                                    if n2 < 0xDC00 || n2 > 0xDFFF {
-                                        return Err(self
-                                            .rdr
-                                            .error(ErrorCode::LoneLeadingSurrogateInHexEscape));
-                                    }
-
-                                    let n = (((n1 - 0xD800) as u32) << 10 | (n2 - 0xDC00) as u32)
-                                        + 0x1_0000;
-
-                                    match char::from_u32(n as u32) {
-                                        Some(c) => c,
-                                        None => {
-                                            return Err(self
-                                                .rdr
-                                                .error(ErrorCode::InvalidUnicodeCodePoint));
-                                        }
-                                    }
-                                }
-
-                                n => match char::from_u32(n as u32) {
-                                    Some(c) => c,
-                                    None => {
-                                        return Err(self
-                                            .rdr
-                                            .error(ErrorCode::InvalidUnicodeCodePoint));
-                                    }
-                                },
-                            };
-
-                            self.str_buf.extend(c.encode_utf8(&mut [0; 4]).as_bytes());
+                    let key = de::Deserialize::deserialize(&mut *self)
+                        .and_then(|key: String| self.parse_object_colon().map(|()| key));
+                    match key {
+                        Ok(key) => {
+                            if let Some(value) = self.recover_value(!root, errors) {
+                                members.insert(key, value);
+                            }
                         }
-                        _ => {
-                            return Err(self.rdr.error(ErrorCode::InvalidEscape));
+                        Err(err) => {
+                            self.state = State::Normal;
+                            errors.push(err);
+                            self.skip_to_boundary();
                         }
                     }
                 }
-                ch => {
-                    self.str_buf.push(ch);
+                Err(err) => {
+                    errors.push(err);
+                    self.skip_to_boundary();
                 }
             }
+            self.recover_comma(start);
         }
+        Value::Object(members)
     }
 
-    fn parse_object_colon(&mut self) -> Result<()> {
-        self.rdr.parse_whitespace()?;
+    // Eats the optional comma after a member or element, and makes sure that the loops above
+    // make progress when nothing could be parsed.
+    fn recover_comma(&mut self, start: usize) {
+        if let Ok(Some(b',')) = self
+            .lexer
+            .rdr
+            .parse_whitespace()
+            .and_then(|()| self.lexer.rdr.peek())
+        {
+            self.lexer.rdr.eat_char();
+        } else if self.lexer.rdr.offset() == start {
+            let _ = self.lexer.rdr.next_char();
+        }
+    }
 
-        match self.rdr.next_char()? {
-            Some(b':') => Ok(()),
-            Some(_) => Err(self.rdr.error(ErrorCode::ExpectedColon)),
-            None => Err(self.rdr.error(ErrorCode::EOFWhileParsingObject)),
+    // Skips the rest of a member or element that failed to parse, up to the next comma, line
+    // break or closing bracket.
+    fn skip_to_boundary(&mut self) {
+        while let Ok(Some(ch)) = self.lexer.rdr.peek() {
+            match ch {
+                b',' | b'\n' | b'\r' | b']' | b'}' => break,
+                _ => self.lexer.rdr.eat_char(),
+            };
         }
     }
 }
 
-impl<'de, 'a, Iter> de::Deserializer<'de> for &'a mut Deserializer<Iter>
+fn visit_number<'de, V>(number: Number, visitor: V) -> Result<V::Value>
+where
+    V: de::Visitor<'de>,
+{
+    match number {
+        Number::U64(v) => visitor.visit_u64(v),
+        Number::I64(v) => visitor.visit_i64(v),
+        Number::F64(v) => visitor.visit_f64(v),
+    }
+}
+
+// Returns the literal of a number that would be written differently after parsing it, or that does
+// not fit into 64 bits, so `Value` keeps it as text.
+#[cfg(feature = "arbitrary_precision")]
+fn inexact_literal(literal: &str, number: &Number) -> Option<String> {
+    let exact = match *number {
+        Number::U64(v) => v.to_string() == literal,
+        Number::I64(v) => v.to_string() == literal,
+        Number::F64(v) => super::ser::to_string(&v).ok().as_deref() == Some(literal),
+    };
+    if exact {
+        None
+    } else {
+        Some(literal.to_string())
+    }
+}
+
+impl<'de, 'a, Iter, Src> de::Deserializer<'de> for &'a mut Deserializer<Iter, Src>
 where
     Iter: Iterator<Item = u8>,
+    Src: Source<'de>,
 {
     type Error = Error;
 
@@ -521,12 +1093,12 @@ where
     where
         V: de::Visitor<'de>,
     {
-        self.rdr.parse_whitespace()?;
+        self.lexer.rdr.parse_whitespace()?;
 
-        match self.rdr.peek_or_null()? {
+        match self.lexer.rdr.peek_or_null()? {
             b'n' => {
-                self.rdr.eat_char();
-                self.parse_ident(b"ull")?;
+                self.lexer.rdr.eat_char();
+                self.lexer.parse_ident(b"ull")?;
                 visitor.visit_none()
             }
             _ => visitor.visit_some(self),
@@ -535,124 +1107,387 @@ where
 
     /// Parses a newtype struct as the underlying value.
     #[inline]
-    fn deserialize_newtype_struct<V>(self, _name: &str, visitor: V) -> Result<V::Value>
+    fn deserialize_newtype_struct<V>(self, name: &str, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
+        if name == raw::RAW_TOKEN {
+            return self.parse_raw_value(visitor);
+        }
+        #[cfg(feature = "arbitrary_precision")]
+        {
+            if name == super::value::NUMBER_TOKEN {
+                self.literal_numbers = true;
+                let res = visitor.visit_newtype_struct(&mut *self);
+                self.literal_numbers = false;
+                return res;
+            }
+        }
         visitor.visit_newtype_struct(self)
     }
 
+    /// Parses an array of integers from 0 to 255 as a byte buffer, anything else is handed to
+    /// `deserialize_any`.
+    #[inline]
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    #[inline]
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.lexer.rdr.parse_whitespace()?;
+        if self.lexer.rdr.peek()? != Some(b'[') {
+            return self.deserialize_any(visitor);
+        }
+        let bytes = self.deserialize_seq(BytesVisitor)?;
+        visitor.visit_byte_buf(bytes)
+    }
+
+    /// Parses a unit variant from its name, quoted or not, and the other variants from an
+    /// object like `{Variant: value}`.
+    #[inline]
+    fn deserialize_enum<V>(
+        self,
+        _name: &str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.lexer.rdr.parse_whitespace()?;
+        // like in parse_value, errors raised by the visitor are reported at the start
+        let (line, col) = self.lexer.rdr.pos();
+        let offset = self.lexer.rdr.offset();
+        match self.parse_enum(visitor) {
+            Err(Error::Syntax(code, 0, 0, 0)) => Err(Error::Syntax(code, line, col, offset)),
+            res => res,
+        }
+    }
+
+    /// Parses `null` as the unit type. An empty object `{}` is no unit, like in JSON.
+    #[inline]
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_any(visitor)
+    }
+
+    /// Parses `null` as a unit struct, see `deserialize_unit`.
+    #[inline]
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    /// Parses integers of up to 128 bits, which `deserialize_any` only reads as integers when
+    /// they fit into 64 bits.
+    #[inline]
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_wide_integer(visitor)
+    }
+
+    /// Parses integers of up to 128 bits, see `deserialize_i128`.
+    #[inline]
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_wide_integer(visitor)
+    }
+
     serde::forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
-        bytes byte_buf unit unit_struct seq tuple map
-        tuple_struct struct enum identifier ignored_any
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string
+        seq tuple map
+        tuple_struct struct identifier ignored_any
+    }
+}
+
+// Collects the elements of an array into the buffer of `deserialize_byte_buf`.
+struct BytesVisitor;
+
+impl<'de> de::Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of bytes")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> result::Result<Vec<u8>, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(Byte(byte)) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(bytes)
+    }
+}
+
+// An element of a byte array, an integer that has to fit into a `u8`.
+struct Byte(u8);
+
+impl<'de> de::Deserialize<'de> for Byte {
+    fn deserialize<D>(deserializer: D) -> result::Result<Byte, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_u8(ByteVisitor)
+    }
+}
+
+struct ByteVisitor;
+
+impl<'de> de::Visitor<'de> for ByteVisitor {
+    type Value = Byte;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a byte from 0 to 255")
+    }
+
+    fn visit_u64<E>(self, value: u64) -> result::Result<Byte, E>
+    where
+        E: de::Error,
+    {
+        if value > 255 {
+            return Err(E::invalid_value(de::Unexpected::Unsigned(value), &self));
+        }
+        Ok(Byte(value as u8))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> result::Result<Byte, E>
+    where
+        E: de::Error,
+    {
+        if !(0..=255).contains(&value) {
+            return Err(E::invalid_value(de::Unexpected::Signed(value), &self));
+        }
+        Ok(Byte(value as u8))
     }
 }
 
-struct SeqVisitor<'a, Iter: 'a + Iterator<Item = u8>> {
-    de: &'a mut Deserializer<Iter>,
+struct SeqVisitor<'a, Iter: 'a + Iterator<Item = u8>, Src: 'a> {
+    de: &'a mut Deserializer<Iter, Src>,
+    index: usize,
 }
 
-impl<'a, Iter: Iterator<Item = u8>> SeqVisitor<'a, Iter> {
-    fn new(de: &'a mut Deserializer<Iter>) -> Self {
-        SeqVisitor { de: de }
+impl<'a, Iter: Iterator<Item = u8>, Src> SeqVisitor<'a, Iter, Src> {
+    fn new(de: &'a mut Deserializer<Iter, Src>) -> Self {
+        SeqVisitor { de: de, index: 0 }
     }
 }
 
-impl<'de, 'a, Iter> de::SeqAccess<'de> for SeqVisitor<'a, Iter>
+impl<'de, 'a, Iter, Src> de::SeqAccess<'de> for SeqVisitor<'a, Iter, Src>
 where
     Iter: Iterator<Item = u8>,
+    Src: Source<'de>,
 {
     type Error = Error;
 
+    fn size_hint(&self) -> Option<usize> {
+        self.de.size_hint()
+    }
+
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
     where
         T: de::DeserializeSeed<'de>,
     {
-        self.de.rdr.parse_whitespace()?;
+        self.de.lexer.rdr.parse_whitespace()?;
+        // pointer of the previous element, when comments are collected
+        let prev = match self.index {
+            0 => None,
+            _ if !self.de.tracks_path() => None,
+            index => Some(comments::pointer_push(
+                self.de.current_pointer(),
+                &(index - 1).to_string(),
+            )),
+        };
 
-        match self.de.rdr.peek()? {
+        match self.de.lexer.rdr.peek()? {
             Some(b']') => {
+                self.de.mark();
+                self.de.attach_comments(prev.as_ref().map(|p| &p[..]), None);
                 return Ok(None);
             }
             Some(_) => {}
             None => {
-                return Err(self.de.rdr.error(ErrorCode::EOFWhileParsingList));
+                return Err(self.de.lexer.rdr.error(ErrorCode::EOFWhileParsingList));
             }
         }
 
-        let value = seed.deserialize(&mut *self.de)?;
-
-        // in Hjson the comma is optional and trailing commas are allowed
-        self.de.rdr.parse_whitespace()?;
-        if self.de.rdr.peek()? == Some(b',') {
-            self.de.rdr.eat_char();
-            self.de.rdr.parse_whitespace()?;
+        let tracks_path = self.de.tracks_path();
+        if tracks_path {
+            let pointer =
+                comments::pointer_push(self.de.current_pointer(), &self.index.to_string());
+            self.de
+                .attach_comments(prev.as_ref().map(|p| &p[..]), Some(&pointer));
+            self.de.path.push(pointer);
         }
-
+        self.index += 1;
+        self.de.string_style = None;
+        let value = seed.deserialize(&mut *self.de);
+        if tracks_path {
+            self.de.record_style();
+            self.de.path.pop();
+        }
+        let index = self.index - 1;
+        let value = self.de.error_in(value, |_| PathSegment::Index(index))?;
+        self.de.parse_list_separator()?;
         Ok(Some(value))
     }
 }
 
-struct MapVisitor<'a, Iter: 'a + Iterator<Item = u8>> {
-    de: &'a mut Deserializer<Iter>,
+struct MapVisitor<'a, Iter: 'a + Iterator<Item = u8>, Src: 'a> {
+    de: &'a mut Deserializer<Iter, Src>,
     first: bool,
     root: bool,
+    // pointer of the last member, when comments are collected
+    member: Option<String>,
+    // keys seen so far, when duplicate keys are denied
+    keys: Option<BTreeSet<String>>,
 }
 
-impl<'a, Iter: Iterator<Item = u8>> MapVisitor<'a, Iter> {
-    fn new(de: &'a mut Deserializer<Iter>, root: bool) -> Self {
+impl<'a, Iter: Iterator<Item = u8>, Src> MapVisitor<'a, Iter, Src> {
+    fn new(de: &'a mut Deserializer<Iter, Src>, root: bool) -> Self {
+        let keys = if de.deny_duplicate_keys {
+            Some(BTreeSet::new())
+        } else {
+            None
+        };
         MapVisitor {
             de: de,
             first: true,
             root: root,
+            member: None,
+            keys,
         }
     }
 }
 
-impl<'de, 'a, Iter> de::MapAccess<'de> for MapVisitor<'a, Iter>
+impl<'de, 'a, Iter, Src> de::MapAccess<'de> for MapVisitor<'a, Iter, Src>
 where
     Iter: Iterator<Item = u8>,
+    Src: Source<'de>,
 {
     type Error = Error;
 
+    fn size_hint(&self) -> Option<usize> {
+        self.de.size_hint()
+    }
+
     fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
     where
         K: de::DeserializeSeed<'de>,
     {
-        self.de.rdr.parse_whitespace()?;
+        let line = self.de.lexer.rdr.consumed_line();
+        self.de.lexer.rdr.parse_whitespace()?;
 
         if self.first {
             self.first = false;
-        } else if self.de.rdr.peek()? == Some(b',') {
+        } else if self.de.lexer.rdr.peek()? == Some(b',') {
             // in Hjson the comma is optional and trailing commas are allowed
-            self.de.rdr.eat_char();
-            self.de.rdr.parse_whitespace()?;
+            self.de.lexer.rdr.eat_char();
+            self.de.lexer.rdr.parse_whitespace()?;
+            let denied = self.de.lexer.strict || !self.de.trailing_commas;
+            if denied && self.de.lexer.rdr.peek()? == Some(b'}') {
+                return Err(self.de.lexer.rdr.error(ErrorCode::TrailingComma));
+            }
+        } else {
+            let next = self.de.lexer.rdr.peek()?;
+            // without a comma the next member has to start on a new line
+            let same_line = next.is_some() && self.de.lexer.rdr.pos().0 == line;
+            if next != Some(b'}') && (self.de.lexer.strict || same_line) {
+                return Err(self.de.lexer.rdr.error(ErrorCode::ExpectedObjectCommaOrEnd));
+            }
         }
 
-        match self.de.rdr.peek()? {
-            Some(b'}') => return Ok(None), // handled later for root
+        let prev = self.member.take();
+        match self.de.lexer.rdr.peek()? {
+            Some(b'}') => {
+                // handled later for root
+                self.de.mark();
+                self.de.attach_comments(prev.as_ref().map(|p| &p[..]), None);
+                return Ok(None);
+            }
             Some(_) => {}
             None => {
                 if self.root {
+                    self.de.mark();
+                    self.de.attach_comments(prev.as_ref().map(|p| &p[..]), None);
                     return Ok(None);
                 } else {
-                    return Err(self.de.rdr.error(ErrorCode::EOFWhileParsingObject));
+                    return Err(self.de.lexer.rdr.error(ErrorCode::EOFWhileParsingObject));
                 }
             }
         }
 
-        match self.de.rdr.peek()? {
+        let (line, col) = self.de.lexer.rdr.pos();
+        let offset = self.de.lexer.rdr.offset();
+        let key = match self.de.lexer.rdr.peek()? {
+            Some(ch) if self.de.lexer.strict && ch != b'"' => {
+                return Err(self.de.lexer.rdr.error(ErrorCode::KeyMustBeAString));
+            }
             Some(ch) => {
-                self.de.state = if ch == b'"' {
+                let quoted = ch == b'"' || ch == b'\'' && !self.de.lexer.ml_string_ahead()?;
+                self.de.state = if quoted {
                     State::Normal
                 } else {
                     State::Keyname
                 };
-                Ok(Some(seed.deserialize(&mut *self.de)?))
+                match seed.deserialize(MapKey { de: &mut *self.de }) {
+                    Err(Error::Syntax(code, 0, 0, 0)) => {
+                        return Err(Error::Syntax(code, line, col, offset))
+                    }
+                    res => res?,
+                }
+            }
+            None => return Err(self.de.lexer.rdr.error(ErrorCode::EOFWhileParsingValue)),
+        };
+
+        // kept for the path of errors in the value
+        let depth = self.de.depth;
+        if self.de.last_keys.len() < depth {
+            self.de.last_keys.resize(depth, String::new());
+        }
+        let last = &mut self.de.last_keys[depth - 1];
+        last.clear();
+        last.push_str(as_str(&self.de.lexer.buf)?);
+
+        if let Some(ref mut keys) = self.keys {
+            // the key is still in the buffer it was parsed into
+            let name = as_str(&self.de.lexer.buf)?;
+            if !keys.insert(name.to_string()) {
+                return Err(Error::Syntax(
+                    ErrorCode::DuplicateKey(name.to_string()),
+                    line,
+                    col,
+                    offset,
+                ));
             }
-            None => Err(self.de.rdr.error(ErrorCode::EOFWhileParsingValue)),
         }
+
+        if self.de.tracks_path() {
+            // the key is still in the buffer it was parsed into
+            let pointer =
+                comments::pointer_push(self.de.current_pointer(), as_str(&self.de.lexer.buf)?);
+            self.de
+                .attach_comments(prev.as_ref().map(|p| &p[..]), Some(&pointer));
+            self.member = Some(pointer);
+        }
+        Ok(Some(key))
     }
 
     fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
@@ -661,13 +1496,186 @@ where
     {
         self.de.parse_object_colon()?;
 
-        Ok(seed.deserialize(&mut *self.de)?)
+        if let Some(ref member) = self.member {
+            self.de.path.push(member.clone());
+        }
+        self.de.string_style = None;
+        let value = seed.deserialize(&mut *self.de);
+        if self.member.is_some() {
+            self.de.record_style();
+            self.de.path.pop();
+        }
+        self.de.error_in(value, |de| {
+            PathSegment::Key(de.last_keys[de.depth - 1].clone())
+        })
+    }
+}
+
+// An object key is always a string in Hjson, so keys of integer or bool types are parsed from
+// its text. Everything else is left to the `Deserializer`, which reads the key as a string.
+struct MapKey<'a, Iter: 'a + Iterator<Item = u8>, Src: 'a> {
+    de: &'a mut Deserializer<Iter, Src>,
+}
+
+macro_rules! deserialize_parsed_keys {
+    ($($method:ident => $visit:ident($ty:ty))*) => {
+        $(
+            #[inline]
+            fn $method<V>(self, visitor: V) -> Result<V::Value>
+            where
+                V: de::Visitor<'de>,
+            {
+                let key: String = de::Deserialize::deserialize(&mut *self.de)?;
+                match key.parse::<$ty>() {
+                    Ok(value) => visitor.$visit(value),
+                    // lets the visitor report the type it expected
+                    Err(_) => visitor.visit_str(&key),
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! forward_keys {
+    ($($method:ident)*) => {
+        $(
+            #[inline]
+            fn $method<V>(self, visitor: V) -> Result<V::Value>
+            where
+                V: de::Visitor<'de>,
+            {
+                self.de.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de, 'a, Iter, Src> de::Deserializer<'de> for MapKey<'a, Iter, Src>
+where
+    Iter: Iterator<Item = u8>,
+    Src: Source<'de>,
+{
+    type Error = Error;
+
+    deserialize_parsed_keys! {
+        deserialize_bool => visit_bool(bool)
+        deserialize_i8 => visit_i8(i8)
+        deserialize_i16 => visit_i16(i16)
+        deserialize_i32 => visit_i32(i32)
+        deserialize_i64 => visit_i64(i64)
+        deserialize_i128 => visit_i128(i128)
+        deserialize_u8 => visit_u8(u8)
+        deserialize_u16 => visit_u16(u16)
+        deserialize_u32 => visit_u32(u32)
+        deserialize_u64 => visit_u64(u64)
+        deserialize_u128 => visit_u128(u128)
+    }
+
+    forward_keys! {
+        deserialize_any deserialize_f32 deserialize_f64 deserialize_char deserialize_str
+        deserialize_string deserialize_bytes deserialize_byte_buf deserialize_option
+        deserialize_unit deserialize_seq deserialize_map deserialize_identifier
+        deserialize_ignored_any
+    }
+
+    #[inline]
+    fn deserialize_unit_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.de.deserialize_unit_struct(name, visitor)
+    }
+
+    #[inline]
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.de.deserialize_newtype_struct(name, visitor)
+    }
+
+    #[inline]
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.de.deserialize_tuple(len, visitor)
+    }
+
+    #[inline]
+    fn deserialize_tuple_struct<V>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.de.deserialize_tuple_struct(name, len, visitor)
+    }
+
+    #[inline]
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.de.deserialize_struct(name, fields, visitor)
+    }
+
+    #[inline]
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.de.deserialize_enum(name, variants, visitor)
+    }
+}
+
+impl<'de, Iter, Src> de::EnumAccess<'de> for &mut Deserializer<Iter, Src>
+where
+    Iter: Iterator<Item = u8>,
+    Src: Source<'de>,
+{
+    type Error = Error;
+    type Variant = Self;
+
+    // reads the key of `{Variant: value}`
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        self.lexer.rdr.parse_whitespace()?;
+        match self.lexer.rdr.peek()? {
+            Some(b'"') => {}
+            Some(_) if self.lexer.strict => {
+                return Err(self.lexer.rdr.error(ErrorCode::KeyMustBeAString))
+            }
+            Some(b'\'') if !self.lexer.ml_string_ahead()? => {}
+            Some(b'}') => return Err(self.lexer.rdr.error(ErrorCode::ExpectedSomeValue)),
+            Some(_) => self.state = State::Keyname,
+            None => return Err(self.lexer.rdr.error(ErrorCode::EOFWhileParsingObject)),
+        }
+        let variant = seed.deserialize(&mut *self)?;
+        self.parse_object_colon()?;
+        Ok((variant, self))
     }
 }
 
-impl<'de, 'a, Iter> de::VariantAccess<'de> for &'a mut Deserializer<Iter>
+impl<'de, 'a, Iter, Src> de::VariantAccess<'de> for &'a mut Deserializer<Iter, Src>
 where
     Iter: Iterator<Item = u8>,
+    Src: Source<'de>,
 {
     type Error = Error;
 
@@ -697,6 +1705,70 @@ where
     }
 }
 
+// A unit variant that is written as a string with its name.
+struct UnitVariantAccess<'a, Iter: 'a + Iterator<Item = u8>, Src: 'a> {
+    de: &'a mut Deserializer<Iter, Src>,
+}
+
+impl<'de, 'a, Iter, Src> de::EnumAccess<'de> for UnitVariantAccess<'a, Iter, Src>
+where
+    Iter: Iterator<Item = u8>,
+    Src: Source<'de>,
+{
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(&mut *self.de)?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de, 'a, Iter, Src> de::VariantAccess<'de> for UnitVariantAccess<'a, Iter, Src>
+where
+    Iter: Iterator<Item = u8>,
+    Src: Source<'de>,
+{
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, _seed: T) -> Result<T::Value>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        Err(de::Error::invalid_type(
+            de::Unexpected::UnitVariant,
+            &"newtype variant",
+        ))
+    }
+
+    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(de::Error::invalid_type(
+            de::Unexpected::UnitVariant,
+            &"tuple variant",
+        ))
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], _visitor: V) -> Result<V::Value>
+    where
+        V: de::Visitor<'de>,
+    {
+        Err(de::Error::invalid_type(
+            de::Unexpected::UnitVariant,
+            &"struct variant",
+        ))
+    }
+}
+
 //////////////////////////////////////////////////////////////////////////////
 
 /// Iterator that deserializes a stream into multiple Hjson values.
@@ -735,11 +1807,11 @@ where
         // skip whitespaces, if any
         // this helps with trailing whitespaces, since whitespaces between
         // values are handled for us.
-        if let Err(e) = self.deser.rdr.parse_whitespace() {
+        if let Err(e) = self.deser.lexer.rdr.parse_whitespace() {
             return Some(Err(e));
         };
 
-        match self.deser.rdr.eof() {
+        match self.deser.lexer.rdr.eof() {
             Ok(true) => None,
             Ok(false) => match de::Deserialize::deserialize(&mut self.deser) {
                 Ok(v) => Some(Ok(v)),
@@ -752,74 +1824,347 @@ where
 
 //////////////////////////////////////////////////////////////////////////////
 
-/// Decodes a Hjson value from an iterator over an iterator
-/// `Iterator<Item=u8>`.
-pub fn from_iter<I, T>(iter: I) -> Result<T>
+/// Iterator that deserializes the elements of a top-level Hjson array, see
+/// `Deserializer::into_iter`.
+pub struct ArrayDeserializer<T, Iter, Src = ()>
 where
-    I: Iterator<Item = io::Result<u8>>,
+    Iter: Iterator<Item = u8>,
     T: de::DeserializeOwned,
 {
-    let fold: io::Result<Vec<_>> = iter.collect();
-    if fold.is_err() {
-        return Err(Error::Io(fold.unwrap_err()));
-    }
-
-    let bytes = fold.unwrap();
-
-    // deserialize tries first to decode with legacy support (new_for_root)
-    // and then with the standard method if this fails.
-    // todo: add compile switch
-
-    // deserialize and make sure the whole stream has been consumed
-    let mut de = Deserializer::new_for_root(bytes.iter().map(|b| *b));
-    de::Deserialize::deserialize(&mut de)
-        .and_then(|x| de.end().map(|()| x))
-        .or_else(|_| {
-            let mut de2 = Deserializer::new(bytes.iter().map(|b| *b));
-            de::Deserialize::deserialize(&mut de2).and_then(|x| de2.end().map(|()| x))
-        })
-
-    /* without legacy support:
-    // deserialize and make sure the whole stream has been consumed
-    let mut de = Deserializer::new(bytes.iter().map(|b| *b));
-    let value = match de::Deserialize::deserialize(&mut de)
-        .and_then(|x| { try!(de.end()); Ok(x) })
-    {
-        Ok(v) => Ok(v),
-        Err(e) => Err(e),
-    };
-    */
+    deser: Deserializer<Iter, Src>,
+    started: bool,
+    done: bool,
+    _marker: PhantomData<T>,
 }
 
-/// Decodes a Hjson value from a `std::io::Read`.
-pub fn from_reader<R, T>(rdr: R) -> Result<T>
+impl<T, Iter, Src> ArrayDeserializer<T, Iter, Src>
 where
-    R: io::Read,
+    Iter: Iterator<Item = u8>,
     T: de::DeserializeOwned,
 {
-    from_iter(rdr.bytes())
+    fn new(mut deser: Deserializer<Iter, Src>) -> ArrayDeserializer<T, Iter, Src> {
+        deser.unclosed = Some(Vec::new());
+        ArrayDeserializer {
+            deser,
+            started: false,
+            done: false,
+            _marker: PhantomData,
+        }
+    }
 }
 
-/// Decodes a Hjson value from a byte slice `&[u8]`.
-pub fn from_slice<T>(v: &[u8]) -> Result<T>
+impl<'de, T, Iter, Src> ArrayDeserializer<T, Iter, Src>
 where
+    Iter: Iterator<Item = u8>,
+    Src: Source<'de>,
     T: de::DeserializeOwned,
 {
-    from_iter(v.iter().map(|byte| Ok(*byte)))
-}
+    fn next_element(&mut self) -> Result<Option<T>> {
+        if self.started {
+            // what follows the previous element, checked now so that the element is returned
+            self.deser.parse_list_separator()?;
+        } else {
+            let rdr = &mut self.deser.lexer.rdr;
+            rdr.parse_whitespace()?;
+            match rdr.peek()? {
+                Some(b'[') => {
+                    rdr.eat_char();
+                }
+                Some(_) => return Err(rdr.error(ErrorCode::ExpectedArray)),
+                None => return Err(rdr.error(ErrorCode::EOFWhileParsingValue)),
+            }
+            self.started = true;
+        }
 
-/// Decodes a Hjson value from a `&str`.
-pub fn from_str<T>(s: &str) -> Result<T>
-where
+        let rdr = &mut self.deser.lexer.rdr;
+        rdr.parse_whitespace()?;
+        match rdr.peek()? {
+            Some(b']') => {
+                rdr.eat_char();
+                self.deser.end()?;
+                return Ok(None);
+            }
+            Some(_) => {}
+            None => return Err(rdr.error(ErrorCode::EOFWhileParsingList)),
+        }
+
+        let start = self.deser.lexer.rdr.offset();
+        let value = match de::Deserialize::deserialize(&mut self.deser) {
+            Err(Error::Syntax(ErrorCode::Custom(msg), line, col, offset)) => {
+                // the visitor failed, but the input is fine: skip the rest of the element, so
+                // the next one can be read
+                self.skip_element(start)?;
+                return Err(Error::Syntax(ErrorCode::Custom(msg), line, col, offset));
+            }
+            value => value?,
+        };
+        Ok(Some(value))
+    }
+
+    // Skips what is left of an element that started at `start` and failed in a visitor. The
+    // input is then at the end of a token, inside of the arrays and objects in `unclosed`.
+    fn skip_element(&mut self, start: usize) -> Result<()> {
+        let unclosed = match self.deser.unclosed {
+            Some(ref mut unclosed) => mem::take(unclosed),
+            None => Vec::new(),
+        };
+        if unclosed.is_empty() && self.deser.lexer.rdr.offset() == start {
+            // the visitor failed before anything was read
+            let _: de::IgnoredAny = de::Deserialize::deserialize(&mut self.deser)?;
+        }
+        for closer in unclosed {
+            loop {
+                self.deser.lexer.rdr.parse_whitespace()?;
+                match self.deser.lexer.rdr.peek()? {
+                    Some(ch) if ch == closer => {
+                        self.deser.lexer.rdr.eat_char();
+                        break;
+                    }
+                    Some(b',') => {
+                        self.deser.lexer.rdr.eat_char();
+                    }
+                    Some(b':') => {
+                        self.deser.lexer.rdr.eat_char();
+                        let _: de::IgnoredAny = de::Deserialize::deserialize(&mut self.deser)?;
+                    }
+                    Some(ch) => {
+                        // an object member starts with its key
+                        let quoted =
+                            ch == b'"' || ch == b'\'' && !self.deser.lexer.ml_string_ahead()?;
+                        if closer == b'}' && !quoted {
+                            self.deser.state = State::Keyname;
+                        }
+                        let _: de::IgnoredAny = de::Deserialize::deserialize(&mut self.deser)?;
+                    }
+                    None if closer == b']' => {
+                        return Err(self.deser.lexer.rdr.error(ErrorCode::EOFWhileParsingList))
+                    }
+                    None => {
+                        return Err(self.deser.lexer.rdr.error(ErrorCode::EOFWhileParsingObject))
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'de, T, Iter, Src> Iterator for ArrayDeserializer<T, Iter, Src>
+where
+    Iter: Iterator<Item = u8>,
+    Src: Source<'de>,
+    T: de::DeserializeOwned,
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        if self.done {
+            return None;
+        }
+        match self.next_element() {
+            Ok(Some(v)) => Some(Ok(v)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            // the element that a visitor failed on has been skipped
+            Err(e @ Error::Syntax(ErrorCode::Custom(_), ..)) => Some(Err(e)),
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+/// Decodes a Hjson value from an iterator over an iterator
+/// `Iterator<Item=u8>`.
+#[cfg(feature = "std")]
+pub fn from_iter<I, T>(iter: I) -> Result<T>
+where
+    I: Iterator<Item = io::Result<u8>>,
+    T: de::DeserializeOwned,
+{
+    let fold: io::Result<Vec<_>> = iter.collect();
+    if fold.is_err() {
+        return Err(Error::Io(fold.unwrap_err()));
+    }
+
+    let bytes = fold.unwrap();
+    from_slice(&bytes)
+}
+
+/// Decodes a Hjson value from a byte slice `&[u8]`. Strings without escape sequences are
+/// borrowed from `v`. The bytes are checked to be UTF-8 as they are parsed, an invalid sequence
+/// fails with `Error::FromUtf8` at its position.
+pub fn from_slice<'a, T>(v: &'a [u8]) -> Result<T>
+where
+    T: de::Deserialize<'a>,
+{
+    // a root object without braces is tried first and a regular value when this fails, like the
+    // reference implementation; when both fail the first error is reported
+
+    // deserialize and make sure the whole stream has been consumed
+    let mut de = Deserializer::with_source(v.iter().cloned(), v).root_braces_optional(true);
+    de::Deserialize::deserialize(&mut de)
+        .and_then(|x| de.end().map(|()| x))
+        .or_else(|err| {
+            let mut de2 = Deserializer::with_source(v.iter().cloned(), v);
+            de::Deserialize::deserialize(&mut de2)
+                .and_then(|x| de2.end().map(|()| x))
+                .map_err(|_| err)
+        })
+
+    /* without legacy support:
+    // deserialize and make sure the whole stream has been consumed
+    let mut de = Deserializer::new(bytes.iter().map(|b| *b));
+    let value = match de::Deserialize::deserialize(&mut de)
+        .and_then(|x| { try!(de.end()); Ok(x) })
+    {
+        Ok(v) => Ok(v),
+        Err(e) => Err(e),
+    };
+    */
+}
+
+/// Decodes a strict JSON value from a `&str`, rejecting the Hjson extensions (see
+/// `Deserializer::strict`).
+pub fn from_str_strict<T>(s: &str) -> Result<T>
+where
     T: de::DeserializeOwned,
+{
+    let mut de = Deserializer::from_str(s).strict(true);
+    let value = de::Deserialize::deserialize(&mut de)?;
+    de.end()?;
+    Ok(value)
+}
+
+/// Decodes a Hjson value from a `&str` and also returns the comments attached to its object
+/// members and array elements, so they can be written back with `ser::to_string_with_comments`.
+///
+/// ```rust
+/// use serde_hjson::Value;
+///
+/// let text = "{\n  # the port to listen on\n  port: 8080\n}";
+/// let (mut value, comments): (Value, _) = serde_hjson::from_str_with_comments(text).unwrap();
+/// value["timeout"] = Value::U64(30);
+///
+/// let out = serde_hjson::to_string_with_comments(&value, &comments).unwrap();
+/// assert_eq!(out, "{\n  # the port to listen on\n  port: 8080\n  timeout: 30\n}");
+/// ```
+pub fn from_str_with_comments<T>(s: &str) -> Result<(T, Comments)>
+where
+    T: de::DeserializeOwned,
+{
+    let parse = |root: bool| {
+        let mut de = Deserializer::from_str(s).root_braces_optional(root);
+        de.collect_comments();
+        let value = de::Deserialize::deserialize(&mut de)?;
+        de.end()?;
+        Ok((value, de.comments.take().unwrap_or_default()))
+    };
+    parse(true).or_else(|err: Error| parse(false).map_err(|_| err))
+}
+
+/// Decodes a Hjson value from a `&str` like `from_str`, and also returns how its string values
+/// were written, so they can be written back the same way with `Serializer::string_styles`.
+///
+/// ```rust
+/// use serde_hjson::{StringStyle, Value};
+///
+/// let text = "{\n  name: quoteless text\n  tags: [\"quoted\", 'single']\n}";
+/// let (_, styles): (Value, _) = serde_hjson::from_str_with_styles(text).unwrap();
+/// assert_eq!(styles.get("/name"), Some(StringStyle::Quoteless));
+/// assert_eq!(styles.get("/tags/1"), Some(StringStyle::SingleQuoted));
+/// ```
+pub fn from_str_with_styles<T>(s: &str) -> Result<(T, StringStyles)>
+where
+    T: de::DeserializeOwned,
+{
+    let parse = |root: bool| {
+        let mut de = Deserializer::from_str(s).root_braces_optional(root);
+        de.collect_styles();
+        let value = de::Deserialize::deserialize(&mut de)?;
+        de.record_style();
+        de.end()?;
+        Ok((value, de.styles.take().unwrap_or_default()))
+    };
+    parse(true).or_else(|err: Error| parse(false).map_err(|_| err))
+}
+
+/// Decodes a Hjson value from a `&str` like `from_str`, but instead of stopping at the first
+/// syntax error it reports the error, skips to the next comma, line break or closing bracket and
+/// continues, so all problems of a document can be listed at once.
+///
+/// The members and elements that failed are left out of the returned value; it is `None` when
+/// not even the root could be parsed. A `}` in an array closes the object around it, if there is
+/// one, and is skipped otherwise.
+///
+/// ```rust
+/// let (value, errors) = serde_hjson::from_str_recovering("{\n  a: 1\n  b c: 2\n  d: 3\n}");
+/// assert_eq!(errors.len(), 1);
+/// let value = value.unwrap();
+/// assert_eq!(value["d"].as_u64(), Some(3));
+/// assert!(value.find("b c").is_none());
+/// ```
+pub fn from_str_recovering(s: &str) -> (Option<Value>, Vec<Error>) {
+    if let Ok(value) = from_str(s) {
+        return (Some(value), Vec::new());
+    }
+    let mut errors = Vec::new();
+    let value = Deserializer::new(s.bytes()).recover_root(&mut errors);
+    (value, errors)
+}
+
+/// Decodes a Hjson value from a `std::io::Read`. The reader is buffered internally, so there is
+/// no need to wrap it in a `BufReader` first. Input that is not UTF-8 fails with
+/// `Error::FromUtf8` and a failed read with `Error::Io`.
+#[cfg(feature = "std")]
+pub fn from_reader<R, T>(rdr: R) -> Result<T>
+where
+    R: io::Read,
+    T: de::DeserializeOwned,
+{
+    from_iter(io::Read::bytes(io::BufReader::new(rdr)))
+}
+
+/// Decodes a Hjson value from a `&str`. Strings without escape sequences are borrowed from `s`.
+pub fn from_str<'a, T>(s: &'a str) -> Result<T>
+where
+    T: de::Deserialize<'a>,
 {
     from_slice(s.as_bytes())
 }
 
+/// Checks that `s` is a valid Hjson document without building a value from it, returning the
+/// error `from_str` would report for it. Strings and numbers are parsed and then dropped, so this
+/// allocates far less than building a `Value`, and nothing of what was checked is kept.
+///
+/// ```rust
+/// assert!(serde_hjson::validate("a: 1\nb: [true, \"x\"]\n").is_ok());
+/// assert!(serde_hjson::validate("{a: 1").is_err());
+/// ```
+pub fn validate(s: &str) -> Result<()> {
+    from_str::<de::IgnoredAny>(s).map(|_| ())
+}
+
+/// Checks that the Hjson document read from a `std::io::Read` is valid like `validate`. The
+/// whole input is read into memory first, like `from_reader` does, because a root object without
+/// braces is only recognized by trying.
+#[cfg(feature = "std")]
+pub fn validate_reader<R>(rdr: R) -> Result<()>
+where
+    R: io::Read,
+{
+    from_reader::<R, de::IgnoredAny>(rdr).map(|_| ())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use Value;
+    use {Map, Value};
 
     #[test]
     fn out_of_bound_negative_numbers_dont_cause_crashes() {
@@ -832,4 +2177,1230 @@ mod test {
         let value = "[18446744073709551616]".to_string();
         let _ = from_str::<Value>(&value);
     }
+
+    #[test]
+    fn integers_and_floats() {
+        let parse = |text: &str| from_str::<Value>(text).unwrap();
+        assert_eq!(parse("5"), Value::U64(5));
+        assert_eq!(parse("-3"), Value::I64(-3));
+        assert_eq!(parse("18446744073709551615"), Value::U64(u64::MAX));
+        assert_eq!(parse("-9223372036854775808"), Value::I64(i64::MIN));
+        #[cfg(not(feature = "arbitrary_precision"))]
+        {
+            assert_eq!(parse("[1e2]"), Value::Array(vec![Value::F64(100.0)]));
+            assert_eq!(parse("5.0"), Value::F64(5.0));
+            assert_eq!(parse("-2.5E-1"), Value::F64(-0.25));
+            // like JSON, integers out of the 64 bit range are floats
+            assert_eq!(parse("99999999999999999999"), Value::F64(1e20));
+            assert_eq!(
+                parse("-9223372036854775809"),
+                Value::F64(-9223372036854775809.0)
+            );
+        }
+        assert_eq!(from_str::<f64>("99999999999999999999").unwrap(), 1e20);
+        assert_eq!(
+            from_str::<u128>("99999999999999999999").unwrap(),
+            99999999999999999999
+        );
+        #[cfg(feature = "arbitrary_precision")]
+        {
+            assert_eq!(parse("5.0"), Value::Number("5.0".to_string()));
+            assert_eq!(
+                parse("99999999999999999999"),
+                Value::Number("99999999999999999999".to_string())
+            );
+        }
+        // a string that looks like one is quoted to keep it a string
+        let text = ::ser::to_string(&Value::String("99999999999999999999".to_string())).unwrap();
+        assert_eq!(text, "\"99999999999999999999\"");
+    }
+
+    #[test]
+    fn array_iterator() {
+        let value = "# log\n[\n  1\n  2, // two\n  /* three */ 3,\n]\n";
+        let de = Deserializer::new(value.bytes());
+        let items: Vec<u64> = de.into_iter().map(|v| v.unwrap()).collect();
+        assert_eq!(items, [1, 2, 3]);
+
+        let de = Deserializer::new("[]".bytes());
+        assert_eq!(de.into_iter::<Value>().count(), 0);
+
+        // an element of the wrong type is skipped
+        let mut it = Deserializer::new("[1\nx\n3]".bytes()).into_iter::<u64>();
+        assert_eq!(it.next().unwrap().unwrap(), 1);
+        match it.next() {
+            Some(Err(Error::Syntax(ErrorCode::Custom(_), 2, _, _))) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(it.next().unwrap().unwrap(), 3);
+        assert!(it.next().is_none());
+
+        // also when a visitor failed inside of it
+        use alloc::collections::BTreeMap;
+        let text =
+            "[\n  {1: [1]}\n  {2: [2, \"x\", {b: \"]}\"}, 3], 5: []}, {c: [{}]\n d: 1}\n  [4]\n  {}\n]";
+        let items: Vec<_> = Deserializer::from_str(text)
+            .into_iter::<BTreeMap<u32, Vec<u32>>>()
+            .map(|v| v.ok().map(|map| map.into_iter().collect::<Vec<_>>()))
+            .collect();
+        assert_eq!(
+            items,
+            [Some(vec![(1, vec![1])]), None, None, None, Some(vec![])]
+        );
+
+        // but not after a syntax error
+        let mut it = Deserializer::new("[1, {a: 2 3]".bytes()).into_iter::<Value>();
+        assert_eq!(it.next().unwrap().unwrap(), Value::U64(1));
+        assert!(it.next().unwrap().is_err());
+        assert!(it.next().is_none());
+
+        let mut it = Deserializer::new("[1] x".bytes()).into_iter::<u64>();
+        assert_eq!(it.next().unwrap().unwrap(), 1);
+        assert!(it.next().unwrap().is_err());
+
+        let mut it = Deserializer::from_str("\n  {a: 1}").into_iter::<Value>();
+        match it.next() {
+            Some(Err(Error::Syntax(ErrorCode::ExpectedArray, 2, 3, 3))) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert!(it.next().is_none());
+    }
+
+    // A byte buffer like `serde_bytes::ByteBuf`.
+    #[derive(Debug, PartialEq)]
+    struct ByteBuf(Vec<u8>);
+
+    impl ::serde::Serialize for ByteBuf {
+        fn serialize<S: ::serde::Serializer>(
+            &self,
+            serializer: S,
+        ) -> result::Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(&self.0)
+        }
+    }
+
+    impl<'de> de::Deserialize<'de> for ByteBuf {
+        fn deserialize<D>(deserializer: D) -> result::Result<ByteBuf, D::Error>
+        where
+            D: de::Deserializer<'de>,
+        {
+            struct BufVisitor;
+
+            impl<'de> de::Visitor<'de> for BufVisitor {
+                type Value = ByteBuf;
+
+                fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                    formatter.write_str("bytes")
+                }
+
+                fn visit_byte_buf<E>(self, value: Vec<u8>) -> result::Result<ByteBuf, E> {
+                    Ok(ByteBuf(value))
+                }
+
+                fn visit_str<E>(self, value: &str) -> result::Result<ByteBuf, E> {
+                    Ok(ByteBuf(value.as_bytes().to_vec()))
+                }
+            }
+
+            deserializer.deserialize_byte_buf(BufVisitor)
+        }
+    }
+
+    #[test]
+    fn byte_buf() {
+        let buf = ByteBuf(vec![0, 7, 255]);
+        let text = ::ser::to_string(&buf).unwrap();
+        assert_eq!(text, "[\n  0\n  7\n  255\n]");
+        assert_eq!(from_str::<ByteBuf>(&text).unwrap(), buf);
+        assert_eq!(
+            from_str::<Vec<ByteBuf>>("[[1, 2], [], \"ab\"]").unwrap(),
+            vec![
+                ByteBuf(vec![1, 2]),
+                ByteBuf(vec![]),
+                ByteBuf(b"ab".to_vec())
+            ]
+        );
+
+        for &(text, col) in [("[1, 256]", 5), ("[-1]", 2), ("[1.5]", 2), ("[x\n]", 2)].iter() {
+            match from_str::<ByteBuf>(text) {
+                Err(Error::Syntax(ErrorCode::Custom(ref msg), 1, c, _)) if c == col => {
+                    assert!(msg.ends_with("expected a byte from 0 to 255"), "{}", msg);
+                }
+                other => panic!("{}: unexpected result {:?}", text, other),
+            }
+        }
+    }
+
+    #[test]
+    fn hash_comments() {
+        let value = "# header\n{\n  # start\n  a: 1 # after\n  b: x # kept\n  c: \"#q\"\n  d: [true # t\n  ]\n  # end\n}";
+        let v: Value = from_str(value).unwrap();
+        assert_eq!(v["a"].as_u64(), Some(1));
+        assert_eq!(v["b"].as_str(), Some("x # kept"));
+        assert_eq!(v["c"].as_str(), Some("#q"));
+        assert_eq!(v["d"][0].as_bool(), Some(true));
+        assert_eq!(v.as_object().unwrap().len(), 4);
+
+        let v: Value = from_str("a: 1 # root\n# end").unwrap();
+        assert_eq!(v["a"].as_u64(), Some(1));
+    }
+
+    #[test]
+    fn block_comments() {
+        let value = "/* header */{\n  a: /* one */ 1\n  /*\n    b: 2\n  */\n  c: \"/* not a comment */\"\n  d: x /* kept */\n  e: /**/ [/* empty */]\n}";
+        let v: Value = from_str(value).unwrap();
+        assert_eq!(v["a"].as_u64(), Some(1));
+        assert!(v.find("b").is_none());
+        assert_eq!(v["c"].as_str(), Some("/* not a comment */"));
+        assert_eq!(v["d"].as_str(), Some("x /* kept */"));
+        assert_eq!(v["e"].as_array().map(|a| a.len()), Some(0));
+
+        // block comments do not nest
+        assert!(from_str::<Value>("[1 /* a /* b */ c */]").is_err());
+
+        match from_str::<Value>("{\n  a: 1 /* open\n}") {
+            Err(Error::Syntax(ErrorCode::EOFWhileParsingComment, ..)) => {}
+            other => panic!("unexpected result {:?}", other.map_err(|e| e.to_string())),
+        }
+    }
+
+    #[test]
+    fn errors_report_byte_offset() {
+        let value = "{\n  a: 1.5\n  b: 1.2.3\n}";
+        match from_str::<Map<String, f64>>(value) {
+            Err(Error::Syntax(ErrorCode::Custom(ref msg), line, col, offset)) => {
+                assert!(
+                    msg.starts_with("b: invalid type: string \"1.2.3\""),
+                    "{}",
+                    msg
+                );
+                assert_eq!((line, col, offset), (3, 6, 16));
+                assert_eq!(&value[offset..offset + 5], "1.2.3");
+            }
+            other => panic!("unexpected result {:?}", other.map_err(|e| e.to_string())),
+        }
+
+        let value = "{\n  \"a\": [1, 007]\n}";
+        match from_str_strict::<Value>(value) {
+            Err(Error::Syntax(ErrorCode::InvalidNumber, line, col, offset)) => {
+                assert_eq!((line, col, offset), (2, 12, 13));
+                assert_eq!(&value[offset..offset + 3], "007");
+            }
+            other => panic!("unexpected result {:?}", other.map_err(|e| e.to_string())),
+        }
+    }
+
+    // hands out at most three bytes per read, then optionally fails
+    #[cfg(feature = "std")]
+    struct Trickle<'a>(&'a [u8], bool);
+
+    #[cfg(feature = "std")]
+    impl<'a> io::Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() && self.1 {
+                return Err(io::Error::other("broken pipe"));
+            }
+            let n = self.0.len().min(buf.len()).min(3);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn reader() {
+        let text = "# config\n{\n  name: täst\n  list: [1, 2, 3]\n}\n";
+        let expected: Value = from_str(text).unwrap();
+        assert_eq!(expected["name"], Value::String("täst".to_string()));
+
+        let v: Value = from_reader(io::Cursor::new(text)).unwrap();
+        assert_eq!(v, expected);
+        let v: Value = from_reader(Trickle(text.as_bytes(), false)).unwrap();
+        assert_eq!(v, expected);
+
+        let bad = "{\n  a: 1\n  b: [1,\n  2}\n}";
+        match from_reader::<_, Value>(Trickle(bad.as_bytes(), false)) {
+            Err(Error::Syntax(_, line, col, _)) => assert_eq!((line, col), (4, 4)),
+            other => panic!("unexpected result {:?}", other.map_err(|e| e.to_string())),
+        }
+
+        match from_reader::<_, Value>(Trickle(b"{ a: \"\xff\" }", false)) {
+            Err(Error::FromUtf8(ref e, 1, 7, 6)) if e.as_bytes() == b"\xff" => {}
+            other => panic!("unexpected result {:?}", other.map_err(|e| e.to_string())),
+        }
+        match from_reader::<_, Value>(Trickle(b"{ a: 1 }", true)) {
+            Err(Error::Io(_)) => {}
+            other => panic!("unexpected result {:?}", other.map_err(|e| e.to_string())),
+        }
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let mut text = String::from("{\n  list: [\n");
+        while text.len() < 10 * 1024 {
+            text.push_str("    \"gr\u{fc}\u{df}e \u{1f600}\"\n");
+        }
+        let valid = format!("{}  ]\n}}", text);
+        let v: Value = from_slice(valid.as_bytes()).unwrap();
+        assert_eq!(
+            v["list"][0],
+            Value::String("gr\u{fc}\u{df}e \u{1f600}".to_string())
+        );
+
+        let lines = text.lines().count() + 1;
+        for (bad, at, seq) in &[
+            (&b"    \"\xff\"\n"[..], 5, &b"\xff"[..]),
+            (b"    \"\xc3\"\n", 5, b"\xc3\""),
+            (b"    \"\xe2\x82\"\n", 5, b"\xe2\x82\""),
+            (b"    x\xed\xa0\x80\n", 5, b"\xed\xa0"), // surrogate
+            (b"    \xc0\xaf\n", 4, b"\xc0"),          // overlong
+            (b"    \x80\n", 4, b"\x80"),
+        ] {
+            let mut input = text.clone().into_bytes();
+            input.extend_from_slice(bad);
+            input.extend_from_slice(b"  ]\n}");
+            match from_slice::<Value>(&input) {
+                Err(Error::FromUtf8(ref e, line, col, offset)) => {
+                    assert_eq!((line, col, offset), (lines, at + 1, text.len() + at));
+                    assert_eq!(e.as_bytes(), *seq);
+                    assert_eq!(e.utf8_error().valid_up_to(), 0);
+                }
+                other => panic!("unexpected result {:?}", other.map_err(|e| e.to_string())),
+            }
+            #[cfg(feature = "std")]
+            assert_eq!(
+                from_iter::<_, Value>(input.iter().map(|&b| Ok(b))),
+                from_slice::<Value>(&input)
+            );
+        }
+
+        // a sequence cut off by the end of the input
+        match from_slice::<Value>(b"a: \xe2\x82") {
+            Err(Error::FromUtf8(ref e, 1, 4, 3)) => assert!(e.utf8_error().error_len().is_none()),
+            other => panic!("unexpected result {:?}", other.map_err(|e| e.to_string())),
+        }
+        let err = from_slice::<Value>(b"a: 1\nb: \xff").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid utf-8 sequence of 1 bytes from index 0 at line 2 column 4"
+        );
+    }
+
+    #[test]
+    fn collect_comments() {
+        let text = "# header\n{\n  // the name\n  name: \"x\" # trailing\n  list: [\n    # element\n    {\n      # deep\n      a: 1 /* c */\n    }\n  ]\n  /* lost */\n}\n";
+        let (v, comments): (Value, _) = from_str_with_comments(text).unwrap();
+        assert_eq!(v, from_str::<Value>(text).unwrap());
+
+        let name = comments.get("/name").unwrap();
+        assert_eq!(name.before, vec!["# header", "// the name"]);
+        assert_eq!(name.after, Some("# trailing".to_string()));
+        let a = comments.get("/list/0/a").unwrap();
+        assert_eq!(a.before, vec!["# deep"]);
+        assert_eq!(a.after, Some("/* c */".to_string()));
+        assert_eq!(comments.get("/list/0").unwrap().before, vec!["# element"]);
+        assert_eq!(comments.iter().count(), 3);
+
+        let (_, comments): (Value, _) = from_str_with_comments("# a\na: 1 # b\nb: 2\n").unwrap();
+        assert_eq!(comments.get("/a").unwrap().before, vec!["# a"]);
+        assert_eq!(comments.get("/a").unwrap().after, Some("# b".to_string()));
+        assert_eq!(comments.get("/b"), None);
+    }
+
+    #[test]
+    fn validate() {
+        for text in &[
+            "a: 1\nb: [true, x]\n",
+            "{a: {b: [1, 2, '''ml''']}}",
+            "\"text\"",
+            "[]",
+            "-1.5e300",
+            "",
+            "{a: 1",
+            "[1, 2,,]",
+            "{a: 1}}",
+            "a: \"\\q\"",
+            "{a: 1, a: 2}",
+            "18446744073709551616",
+        ] {
+            let expected = from_str::<Value>(text).map(|_| ());
+            assert_eq!(
+                format!("{:?}", super::validate(text)),
+                format!("{:?}", expected),
+                "{}",
+                text
+            );
+            #[cfg(feature = "std")]
+            assert_eq!(
+                format!("{:?}", validate_reader(text.as_bytes())),
+                format!("{:?}", expected),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn collect_styles() {
+        let text = "{\n  name: quoteless text\n  \"title\": \"quoted\"\n  list: [\n    'single'\n    '''\n    multi\n    line\n    '''\n    {a: x\n    }\n    1\n  ]\n}";
+        let (v, styles): (Value, _) = from_str_with_styles(text).unwrap();
+        assert_eq!(v, from_str::<Value>(text).unwrap());
+
+        assert_eq!(styles.get("/name"), Some(StringStyle::Quoteless));
+        assert_eq!(styles.get("/title"), Some(StringStyle::Quoted));
+        assert_eq!(styles.get("/list/0"), Some(StringStyle::SingleQuoted));
+        assert_eq!(styles.get("/list/1"), Some(StringStyle::Multiline));
+        assert_eq!(styles.get("/list/2/a"), Some(StringStyle::Quoteless));
+        // neither keys nor other values have a style
+        assert_eq!(styles.get("/list"), None);
+        assert_eq!(styles.get("/list/3"), None);
+        assert_eq!(styles.iter().count(), 5);
+
+        let (_, styles): (Value, _) = from_str_with_styles("a: 'x'\nb: y\n").unwrap();
+        assert_eq!(styles.get("/a"), Some(StringStyle::SingleQuoted));
+        assert_eq!(styles.get("/b"), Some(StringStyle::Quoteless));
+        let (_, styles): (String, _) = from_str_with_styles("\"root\"").unwrap();
+        assert_eq!(styles.get(""), Some(StringStyle::Quoted));
+    }
+
+    #[test]
+    fn comments_for() {
+        let text = "{\n  // the  port \n  port: 8080\n  # unused\n\n  ## host name\n  host: x\n  /* a\n  block */\n  mode: y\n  debug: true # trailing only\n}";
+        let (_, comments): (Value, _) = from_str_with_comments(text).unwrap();
+        assert_eq!(comments.comments_for("/port"), Some("the  port"));
+        assert_eq!(comments.comments_for("/host"), Some("# host name"));
+        assert_eq!(comments.comments_for("/mode"), Some("a\n  block"));
+        assert_eq!(comments.comments_for("/debug"), None);
+        assert_eq!(comments.comments_for("/missing"), None);
+
+        let (_, comments): (Value, _) = from_str_with_comments("{a: 1}").unwrap();
+        assert_eq!(comments.comments_for("/a"), None);
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        let text = "{\n  a: 1\n  b: 2\n  a: 3\n}";
+        let value: Value = from_str(text).unwrap();
+        assert_eq!(value["a"].as_u64(), Some(3));
+
+        let mut de = Deserializer::new(text.bytes()).deny_duplicate_keys(true);
+        match de::Deserialize::deserialize(&mut de) as Result<Value> {
+            Err(Error::Syntax(ErrorCode::DuplicateKey(key), line, col, offset)) => {
+                assert_eq!(key, "a");
+                assert_eq!((line, col), (4, 3));
+                assert_eq!(&text[offset..offset + 4], "a: 3");
+            }
+            other => panic!("unexpected result {:?}", other.map_err(|e| e.to_string())),
+        }
+
+        // the same key in different objects is fine
+        let text = "{a: {a: 1}, b: {a: 2}}";
+        let mut de = Deserializer::new(text.bytes()).deny_duplicate_keys(true);
+        let value: Value = de::Deserialize::deserialize(&mut de).unwrap();
+        assert_eq!(value["b"]["a"].as_u64(), Some(2));
+    }
+
+    #[test]
+    fn parsed_keys() {
+        use alloc::collections::BTreeMap;
+
+        let text = "{\n  1: a\n  -2: b\n  \"3\": c\n}";
+        let map: BTreeMap<i8, String> = from_str(text).unwrap();
+        assert_eq!(map.keys().cloned().collect::<Vec<_>>(), vec![-2, 1, 3]);
+
+        // a key that is not a number is reported where it starts
+        let text = "{\n  1: a\n  two: b\n}";
+        match from_str::<BTreeMap<u32, String>>(text) {
+            Err(Error::Syntax(ErrorCode::Custom(msg), line, col, offset)) => {
+                assert_eq!(msg, "invalid type: string \"two\", expected u32");
+                assert_eq!((line, col), (3, 3));
+                assert_eq!(&text[offset..offset + 3], "two");
+            }
+            other => panic!("unexpected result {:?}", other.map_err(|e| e.to_string())),
+        }
+        assert!(from_str::<BTreeMap<u8, String>>("{256: a}").is_err());
+    }
+
+    #[test]
+    fn capacity_hint() {
+        let text = "[\n  [1, 2]\n  [3]\n]";
+        let mut de = Deserializer::new(text.bytes()).capacity_hint(100);
+        let lists: Vec<Vec<u32>> = de::Deserialize::deserialize(&mut de).unwrap();
+        assert_eq!(lists, vec![vec![1, 2], vec![3]]);
+        assert!(lists.capacity() >= 100);
+        assert!(lists[0].capacity() < 100);
+
+        let text = "a: 1\nb: [2]\n";
+        let mut de = Deserializer::from_str(text)
+            .root_braces_optional(true)
+            .capacity_hint(10);
+        let value: Value = de::Deserialize::deserialize(&mut de).unwrap();
+        assert_eq!(value, from_str::<Value>(text).unwrap());
+    }
+
+    #[test]
+    fn max_depth() {
+        let text = format!("{}{}", "[".repeat(10000), "]".repeat(10000));
+        match from_str::<Value>(&text) {
+            Err(Error::Syntax(ErrorCode::RecursionLimitExceeded, 1, _, 128)) => {}
+            other => panic!("unexpected result {:?}", other.map_err(|e| e.to_string())),
+        }
+
+        let text = "{a: [{b: []}]}";
+        assert!(from_str::<Value>(text).is_ok());
+        let mut de = Deserializer::new(text.bytes()).max_depth(3);
+        match de::Deserialize::deserialize(&mut de) as Result<Value> {
+            Err(Error::Syntax(ErrorCode::RecursionLimitExceeded, _, _, offset)) => {
+                assert_eq!(&text[offset..], "[]}]}");
+            }
+            other => panic!("unexpected result {:?}", other.map_err(|e| e.to_string())),
+        }
+        let mut de = Deserializer::new(text.bytes()).max_depth(4);
+        assert!((de::Deserialize::deserialize(&mut de) as Result<Value>).is_ok());
+    }
+
+    #[test]
+    fn multiline_indentation() {
+        let ml = |text: &str| from_str::<Value>(text).unwrap()["a"].clone();
+        let string = |s: &str| Value::String(s.to_string());
+
+        // the opening quotes are at column 4 on lines indented with spaces, tabs or both
+        let spaces = "{\n  a:\n    '''\n    one\n      two\n\n    '''\n}";
+        assert_eq!(ml(spaces), string("one\n  two\n"));
+        let tabs = "{\n\ta:\n\t\t\t\t'''\n\t\t\t\tone\n\t\t\t\t\t\ttwo\n\n\t\t\t\t'''\n}";
+        assert_eq!(ml(tabs), string("one\n\t\ttwo\n"));
+        let mixed = "{\n  a:\n  \t '''\n\t\tone\n  \t  two\n  three\n \t\t'''\n}";
+        assert_eq!(ml(mixed), string("one\n two\nthree"));
+
+        // after a key the column includes the key
+        let text = "{\n  a: '''\n         x\n       y\n  z\n  '''\n}";
+        assert_eq!(ml(text), string("    x\n  y\nz"));
+
+        // at the start of a line nothing is removed
+        let root: Value = from_str("'''\n  indented\nnot\n'''").unwrap();
+        assert_eq!(root, string("  indented\nnot"));
+        let list: Vec<String> = from_str("[\r\n  '''\r\n  a\r\n  \r\n    b\r\n  '''\r\n]").unwrap();
+        assert_eq!(list, vec!["a\n\n  b"]);
+    }
+
+    #[test]
+    fn trailing_commas() {
+        let texts = [
+            "[1, 2, 3,]",
+            "{a: 1,}",
+            "{\n  a: [\n    1,\n  ],\n}",
+            "a: 1,\nb: 2,\n",
+        ];
+        for text in texts.iter() {
+            assert!(from_str::<Value>(text).is_ok(), "{}", text);
+        }
+
+        for text in texts[..3].iter() {
+            let mut de = Deserializer::new(text.bytes()).allow_trailing_comma(false);
+            match de::Deserialize::deserialize(&mut de) as Result<Value> {
+                Err(Error::Syntax(ErrorCode::TrailingComma, ..)) => {}
+                other => panic!("{}: unexpected result {:?}", text, other),
+            }
+        }
+        let text = "[1, 2,\n]";
+        let mut de = Deserializer::new(text.bytes()).allow_trailing_comma(false);
+        match de::Deserialize::deserialize(&mut de) as Result<Value> {
+            Err(Error::Syntax(ErrorCode::TrailingComma, line, col, _)) => {
+                assert_eq!((line, col), (2, 1))
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        // commas between the elements are still fine, and so are missing ones
+        let text = "[1, 2\n3]";
+        let mut de = Deserializer::new(text.bytes()).allow_trailing_comma(false);
+        let value: Value = de::Deserialize::deserialize(&mut de).unwrap();
+        assert_eq!(value, from_str::<Value>(text).unwrap());
+
+        let items: Result<Vec<u32>> = Deserializer::new("[1, 2,]".bytes())
+            .allow_trailing_comma(false)
+            .into_iter()
+            .collect();
+        match items {
+            Err(Error::Syntax(ErrorCode::TrailingComma, ..)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn strict() {
+        let json = "{\"a\": [1, -2.5, true, null, \"x\"], \"b\": {}}";
+        let value: Value = from_str_strict(json).unwrap();
+        assert_eq!(value, from_str::<Value>(json).unwrap());
+
+        let errors = [
+            (
+                "{\"a\": 1 # comment\n}",
+                ErrorCode::ExpectedObjectCommaOrEnd,
+            ),
+            ("// comment\n{\"a\": 1}", ErrorCode::ExpectedSomeValue),
+            ("{\"a\": 1} /* comment */", ErrorCode::TrailingCharacters),
+            ("[1, 2,]", ErrorCode::TrailingComma),
+            ("{\"a\": 1,}", ErrorCode::TrailingComma),
+            ("[1\n2]", ErrorCode::ExpectedListCommaOrEnd),
+            ("{\"a\": 1\n\"b\": 2}", ErrorCode::ExpectedObjectCommaOrEnd),
+            ("{a: 1}", ErrorCode::KeyMustBeAString),
+            ("{\"a\": text\n}", ErrorCode::ExpectedSomeValue),
+            ("[truthy\n]", ErrorCode::ExpectedSomeValue),
+            ("[1 2\n]", ErrorCode::ExpectedSomeValue),
+            ("{\"a\": '''x'''}", ErrorCode::ExpectedSomeValue),
+            ("['x']", ErrorCode::ExpectedSomeValue),
+            ("\"a\": 1\n", ErrorCode::TrailingCharacters),
+        ];
+        for &(text, ref code) in errors.iter() {
+            assert!(from_str::<Value>(text).is_ok(), "{}", text);
+            match from_str_strict::<Value>(text) {
+                Err(Error::Syntax(ref c, _, _, _)) if c == code => {}
+                other => panic!("{}: unexpected result {:?}", text, other),
+            }
+        }
+    }
+
+    #[test]
+    fn borrowed_str() {
+        let text = "[\"plain\", \"esc\\\"aped\", quoteless  \n]";
+        match from_str::<Vec<&str>>(text) {
+            Err(Error::Syntax(ErrorCode::Custom(ref msg), ..)) if msg.contains("borrowed") => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        let strings: Vec<String> = from_str(text).unwrap();
+        assert_eq!(strings, vec!["plain", "esc\"aped", "quoteless"]);
+
+        let strings: Vec<&str> = from_str("[\"plain\", quoteless  \n]").unwrap();
+        assert_eq!(strings, vec!["plain", "quoteless"]);
+
+        // input read from an iterator can't be borrowed from
+        let mut de = Deserializer::new("[plain\n]".bytes());
+        match <Vec<&str> as de::Deserialize>::deserialize(&mut de) {
+            Err(Error::Syntax(ErrorCode::Custom(ref msg), ..)) if msg.contains("borrowed") => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn braceless_root() {
+        let expected: Value = from_str("{a: 1, \"b c\": [true]}").unwrap();
+        assert_eq!(expected["a"].as_u64(), Some(1));
+        for text in [
+            "a: 1\n\"b c\": [true]",
+            "# header\na: 1, \"b c\" : [true]\n",
+        ]
+        .iter()
+        {
+            assert_eq!(from_str::<Value>(text).unwrap(), expected, "{}", text);
+        }
+        assert_eq!(from_str::<Value>("").unwrap(), Value::Object(Map::new()));
+        assert_eq!(
+            from_str::<Value>("  # nothing\n").unwrap(),
+            Value::Object(Map::new())
+        );
+
+        // scalars at the root, including one that only fails as a braceless object
+        assert_eq!(from_str::<Value>("1").unwrap(), Value::U64(1));
+        assert_eq!(from_str::<Value>("true").unwrap(), Value::Bool(true));
+        assert_eq!(
+            from_str::<Value>("\"a\"").unwrap(),
+            Value::String("a".into())
+        );
+        assert_eq!(
+            from_str::<Value>("two words").unwrap(),
+            Value::String("two words".into())
+        );
+        assert_eq!(
+            from_str::<Value>("http://x").unwrap(),
+            Value::String("http://x".into())
+        );
+
+        // the error of the braceless object is reported
+        match from_str::<Value>("a: 1\nb: [2") {
+            Err(Error::Syntax(ErrorCode::EOFWhileParsingList, 2, _, _)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        // looking ahead for the colon does not move the position of errors
+        match from_str::<Value>("\"a\\q\"  x") {
+            Err(Error::Syntax(ErrorCode::InvalidEscape, 1, 5, 4)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        // without the option the line is a quoteless string
+        let mut de = Deserializer::new("a: 1".bytes());
+        let value: Value = de::Deserialize::deserialize(&mut de).unwrap();
+        assert_eq!(value.as_str(), Some("a: 1"));
+        let mut de = Deserializer::new("a: 1".bytes()).root_braces_optional(true);
+        let value: Value = de::Deserialize::deserialize(&mut de).unwrap();
+        de.end().unwrap();
+        assert_eq!(value["a"].as_u64(), Some(1));
+        let mut de = Deserializer::new("[1]".bytes()).root_braces_optional(true);
+        assert_eq!(de::Deserialize::deserialize(&mut de).ok(), Some(vec![1]));
+    }
+
+    #[test]
+    fn recovering() {
+        let text =
+            "{\n  a: 1\n  b c: 2\n  d: \"\\q\"\n  list: [1, \"x\\y\", 3]\n  \"e\" 4\n  f: 5\n}";
+        let (value, errors) = from_str_recovering(text);
+        let positions: Vec<_> = errors
+            .iter()
+            .map(|err| match *err {
+                Error::Syntax(ref code, line, col, _) => (code.clone(), line, col),
+                ref other => panic!("unexpected error {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            positions,
+            vec![
+                (
+                    ErrorCode::Custom(
+                        "Found whitespace in your key name (use quotes to include)".to_string()
+                    ),
+                    3,
+                    6
+                ),
+                (ErrorCode::InvalidEscape, 4, 8),
+                (ErrorCode::InvalidEscape, 5, 16),
+                (ErrorCode::ExpectedColon, 6, 7),
+            ]
+        );
+        let expected: Value = from_str("{a: 1, list: [1, 3], f: 5}").unwrap();
+        assert_eq!(value, Some(expected));
+
+        let (value, errors) = from_str_recovering("a: [1, 2\nb: 2");
+        assert_eq!(errors.len(), 1);
+        assert!(value.is_some());
+
+        // a stray `}` is skipped in an array, but closes the object around an array
+        let codes = |errors: Vec<Error>| -> Vec<_> {
+            errors
+                .into_iter()
+                .map(|err| match err {
+                    Error::Syntax(code, line, col, _) => (code, line, col),
+                    other => panic!("unexpected error {:?}", other),
+                })
+                .collect()
+        };
+        let (value, errors) = from_str_recovering("[\n 1\n }\n 2\n ]\n ]");
+        assert_eq!(
+            codes(errors),
+            vec![
+                (ErrorCode::ExpectedListCommaOrEnd, 3, 2),
+                (ErrorCode::TrailingCharacters, 6, 2),
+            ]
+        );
+        assert_eq!(
+            value,
+            Some(Value::Array(vec![Value::U64(1), Value::U64(2)]))
+        );
+        let (value, errors) = from_str_recovering("{\n  a: [1\n}\nb: 2");
+        assert_eq!(
+            codes(errors),
+            vec![
+                (ErrorCode::ExpectedListCommaOrEnd, 3, 1),
+                (ErrorCode::TrailingCharacters, 4, 1)
+            ]
+        );
+        assert_eq!(value, from_str("{a: [1]}").ok());
+
+        assert_eq!(from_str_recovering("\"\\q\"").0, None);
+        assert_eq!(from_str_recovering("[1]\n").1.len(), 0);
+    }
+
+    #[test]
+    fn position() {
+        let text = "[1, 2] {\n  a: 1\n} # end";
+        let mut de = Deserializer::new(text.bytes());
+        assert_eq!(de.position(), (1, 1, 0));
+        let first: Vec<u64> = de::Deserialize::deserialize(&mut de).unwrap();
+        assert_eq!(first, [1, 2]);
+        assert_eq!(de.position(), (1, 8, 7));
+        assert_eq!(&text[7..8], "{");
+        let second: Value = de::Deserialize::deserialize(&mut de).unwrap();
+        assert_eq!(second["a"].as_u64(), Some(1));
+        de.end().unwrap();
+        assert_eq!(de.position(), (3, 7, text.len()));
+    }
+
+    #[test]
+    fn relaxed_escapes() {
+        fn parse(text: &str) -> Result<String> {
+            let mut de = Deserializer::new(text.bytes()).relaxed_escapes(true);
+            let value = de::Deserialize::deserialize(&mut de)?;
+            de.end()?;
+            Ok(value)
+        }
+
+        assert_eq!(
+            parse(r#""\x41\xe9 \u{1F600}\u{41} \u0041""#).unwrap(),
+            "A\u{e9} \u{1F600}A A"
+        );
+        for text in [r#""\x41""#, r#""\u{1F600}""#].iter() {
+            match from_str::<String>(text) {
+                Err(Error::Syntax(ErrorCode::InvalidEscape, ..)) => {}
+                other => panic!("{}: unexpected result {:?}", text, other),
+            }
+        }
+
+        let errors = [
+            (r#""\u{D800}""#, ErrorCode::InvalidUnicodeCodePoint),
+            (r#""\u{110000}""#, ErrorCode::InvalidUnicodeCodePoint),
+            (r#""\u{}""#, ErrorCode::InvalidEscape),
+            (r#""\u{1234567}""#, ErrorCode::InvalidEscape),
+            (r#""\x4""#, ErrorCode::InvalidEscape),
+        ];
+        for &(text, ref code) in errors.iter() {
+            match parse(text) {
+                Err(Error::Syntax(ref c, 1, _, _)) if c == code => {}
+                other => panic!("{}: unexpected result {:?}", text, other),
+            }
+        }
+    }
+
+    #[test]
+    fn escape_policy() {
+        fn parse(text: &str, policy: EscapePolicy) -> Result<Value> {
+            let mut de = Deserializer::from_str(text).escape_policy(policy);
+            let value = de::Deserialize::deserialize(&mut de)?;
+            de.end()?;
+            Ok(value)
+        }
+
+        let text = r#"{path: "a\q\n", 'key\q': 1}"#;
+        match parse(text, EscapePolicy::Strict) {
+            Err(Error::Syntax(ErrorCode::InvalidEscape, 1, 11, 11)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        let v = parse(text, EscapePolicy::PassThrough).unwrap();
+        assert_eq!(v["path"], Value::String("a\\q\n".to_string()));
+        assert_eq!(v["key\\q"], Value::U64(1));
+        let v = parse(text, EscapePolicy::DropBackslash).unwrap();
+        assert_eq!(v["path"], Value::String("aq\n".to_string()));
+        assert_eq!(v["keyq"], Value::U64(1));
+
+        // a borrowed string keeps the backslash too
+        let mut de = Deserializer::from_str(r#""\q""#).escape_policy(EscapePolicy::PassThrough);
+        let s: &str = de::Deserialize::deserialize(&mut de).unwrap();
+        assert_eq!(s, "\\q");
+        // malformed escapes still fail
+        assert!(parse(r#""\u12""#, EscapePolicy::PassThrough).is_err());
+    }
+
+    #[test]
+    fn single_quoted_strings() {
+        assert_eq!(from_str::<String>("'hello'").unwrap(), "hello");
+        assert_eq!(
+            from_str::<String>(r#"'it\'s "quoted"'"#).unwrap(),
+            "it's \"quoted\""
+        );
+        assert_eq!(from_str::<String>(r#""it\'s""#).unwrap(), "it's");
+        assert_eq!(from_str::<String>("''").unwrap(), "");
+
+        let v: Value =
+            from_str("{\n  'a b': 'x, # y'\n  c: '''\n    multi\n    line\n    '''\n  d: 'e'\n}")
+                .unwrap();
+        assert_eq!(v["a b"], Value::String("x, # y".to_string()));
+        assert_eq!(v["c"], Value::String("multi\nline".to_string()));
+        assert_eq!(v["d"], Value::String("e".to_string()));
+        // the root object without braces can start with a single-quoted key
+        let v: Value = from_str("'k': 1").unwrap();
+        assert_eq!(v["k"].as_u64(), Some(1));
+        assert_eq!(from_str::<String>("'''x'''").unwrap(), "x");
+
+        match from_str::<String>("'open") {
+            Err(Error::Syntax(ErrorCode::EOFWhileParsingString, ..)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn byte_order_mark() {
+        let text = "\u{feff}{\n  a: 1\n}";
+        let v: Value = from_str(text).unwrap();
+        assert_eq!(v["a"].as_u64(), Some(1));
+        assert_eq!(
+            from_str::<Value>("\u{feff}").unwrap(),
+            Value::Object(Map::new())
+        );
+        assert_eq!(from_str::<Value>("\u{feff}a: 1").unwrap(), v);
+
+        let text = "\u{feff}{\n  a: 1\n  b c: 2\n}";
+        match from_str::<Value>(text) {
+            Err(Error::Syntax(ErrorCode::Custom(_), 3, 6, offset)) => {
+                assert_eq!(offset, text.find("c:").unwrap() + 2);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+        match from_str::<Vec<u64>>("\u{feff}[1, x]") {
+            Err(Error::Syntax(_, 1, 5, 7)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        // only at the start, anywhere else outside of quotes it is an error at its position
+        match from_str::<Vec<u64>>("[1, \u{feff}2]") {
+            Err(Error::Syntax(ErrorCode::UnexpectedByteOrderMark, 1, 5, 4)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        match from_str::<Value>("{\n  a: x\u{feff}y\n}") {
+            Err(Error::Syntax(ErrorCode::UnexpectedByteOrderMark, 2, 7, 8)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        match from_str::<Value>("a: 1\nb\u{feff}: 2") {
+            Err(Error::Syntax(ErrorCode::UnexpectedByteOrderMark, 2, 2, 6)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        let mut de = Deserializer::new("\u{feff}1\n".bytes()).strip_bom(false);
+        match <Value as de::Deserialize>::deserialize(&mut de) {
+            Err(Error::Syntax(ErrorCode::UnexpectedByteOrderMark, 1, 1, 0)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        // quoted strings may hold one
+        let v: Value =
+            from_str("{\n  a: \"x\u{feff}\"\n  \"\u{feff}b\": '''\u{feff}'''\n}").unwrap();
+        assert_eq!(v["a"], Value::String("x\u{feff}".to_string()));
+        assert_eq!(v["\u{feff}b"], Value::String("\u{feff}".to_string()));
+    }
+
+    #[test]
+    fn size_limits() {
+        fn parse(text: &str, input: usize, string: usize) -> Result<Value> {
+            let mut de = Deserializer::new(text.bytes())
+                .max_input_size(input)
+                .max_string_length(string);
+            let value = de::Deserialize::deserialize(&mut de)?;
+            de.end()?;
+            Ok(value)
+        }
+
+        let text = "{a: \"12345\", \"bcd\": 12345\n e: [1, 2, 3]\n}";
+        assert!(parse(text, text.len(), 5).is_ok());
+        // numbers and keywords are no strings
+        let text = "[123456, -1.5e-10, false, 12345 # c\n]";
+        assert!(parse(text, text.len(), 5).is_ok());
+        for text in [
+            "{a: \"123456\"}",
+            "{a: 123456 x\n}",
+            "{a: '''\n  123456\n  '''}",
+            "{abcdef: 1}",
+        ]
+        .iter()
+        {
+            match parse(text, text.len(), 5) {
+                Err(Error::Syntax(ErrorCode::StringTooLong, ..)) => {}
+                other => panic!("{}: unexpected result {:?}", text, other),
+            }
+        }
+
+        let text = "[1, 2, 3, 4, 5, 6]";
+        assert!(parse(text, text.len(), 5).is_ok());
+        match parse(text, 12, 5) {
+            Err(Error::Syntax(ErrorCode::InputTooLarge, 1, 12, 12)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn trailing_comments() {
+        for text in [
+            "{a: 1} // end",
+            "{a: 1}\n# end\n",
+            "[1] /* end */\n\n",
+            "a: 1\n// end",
+            "\"s\" /* one */ # two",
+        ]
+        .iter()
+        {
+            let mut de = Deserializer::new(text.bytes());
+            let _: Value = de::Deserialize::deserialize(&mut de).unwrap();
+            assert!(de.end().is_ok(), "{}", text);
+            assert!(from_str::<Value>(text).is_ok(), "{}", text);
+        }
+
+        for text in [
+            "{a: 1} x",
+            "{a: 1}\n# end\n]",
+            "[1] /* end */ /",
+            "[1] /* end",
+        ]
+        .iter()
+        {
+            assert!(from_str::<Value>(text).is_err(), "{}", text);
+        }
+        match from_str::<Value>("{a: 1}\n// end\ngarbage") {
+            Err(Error::Syntax(ErrorCode::TrailingCharacters, 3, 1, 14)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn number_separators() {
+        fn parse(text: &str) -> Result<Value> {
+            let mut de = Deserializer::new(text.bytes()).allow_number_separators(true);
+            let value = de::Deserialize::deserialize(&mut de)?;
+            de.end()?;
+            Ok(value)
+        }
+
+        assert_eq!(parse("1_000").unwrap(), Value::U64(1000));
+        assert_eq!(parse("-1_000_000").unwrap(), Value::I64(-1000000));
+        assert_eq!(
+            parse("[1_0.2_5e1_0, 7]").unwrap()[0].as_f64(),
+            Some(10.25e10)
+        );
+        assert_eq!(
+            parse("{a: _x\n}").unwrap()["a"],
+            Value::String("_x".to_string())
+        );
+        assert_eq!(
+            from_str::<Value>("1_000").unwrap(),
+            Value::String("1_000".to_string())
+        );
+
+        for text in [
+            "1__0", "_1", "1_", "1_.5", "1._5", "1_e5", "1e_5", "[1_0_]", "1_0e",
+        ]
+        .iter()
+        {
+            match parse(text) {
+                Err(Error::Syntax(ErrorCode::InvalidNumber, 1, _, _)) => {}
+                other => panic!("{}: unexpected result {:?}", text, other),
+            }
+        }
+    }
+
+    #[test]
+    fn radix_literals() {
+        fn parse(text: &str) -> Result<Value> {
+            let mut de = Deserializer::new(text.bytes()).allow_radix_literals(true);
+            let value = de::Deserialize::deserialize(&mut de)?;
+            de.end()?;
+            Ok(value)
+        }
+
+        let v =
+            parse("{mask: 0xFF, mode: 0o755, flags: 0b1010, delta: -0x10, zero: 0x0\n}").unwrap();
+        assert_eq!(v["mask"], Value::U64(255));
+        assert_eq!(v["mode"], Value::U64(0o755));
+        assert_eq!(v["mode"].as_u64(), Some(493));
+        assert_eq!(v["flags"], Value::U64(10));
+        assert_eq!(v["delta"], Value::I64(-16));
+        assert_eq!(v["zero"], Value::U64(0));
+        assert_eq!(
+            parse("[0xffffffffffffffff, -0x8000000000000000]").unwrap(),
+            Value::Array(vec![Value::U64(u64::MAX), Value::I64(i64::MIN)])
+        );
+        let mode: u32 = de::Deserialize::deserialize(
+            &mut Deserializer::new("0o755".bytes()).allow_radix_literals(true),
+        )
+        .unwrap();
+        assert_eq!(mode, 0o755);
+        assert_eq!(
+            from_str::<Value>("0xFF").unwrap(),
+            Value::String("0xFF".to_string())
+        );
+
+        for text in [
+            "0xG",
+            "0x",
+            "0o8",
+            "0b102",
+            "0x+1",
+            "-0x8000000000000001",
+            "0x1_0",
+        ]
+        .iter()
+        {
+            match parse(text) {
+                Err(Error::Syntax(ErrorCode::InvalidNumber, 1, _, _)) => {}
+                other => panic!("{}: unexpected result {:?}", text, other),
+            }
+        }
+    }
+
+    #[test]
+    fn lenient_numbers() {
+        fn parse(text: &str, strict: bool) -> Result<Value> {
+            let mut de = Deserializer::new(text.bytes())
+                .lenient_numbers(true)
+                .strict(strict);
+            let value = de::Deserialize::deserialize(&mut de)?;
+            de.end()?;
+            Ok(value)
+        }
+
+        let numbers = [
+            ("+1", Value::U64(1)),
+            ("+0", Value::U64(0)),
+            (".5", Value::F64(0.5)),
+            ("-.5", Value::F64(-0.5)),
+            ("5.", Value::F64(5.0)),
+            ("-5.", Value::F64(-5.0)),
+            ("+.5e3", Value::F64(500.0)),
+            ("5.E-1", Value::F64(0.5)),
+            ("+2.5", Value::F64(2.5)),
+        ];
+        for &(text, ref value) in numbers.iter() {
+            // by default they are quoteless strings, or errors in strict mode
+            assert_eq!(
+                from_str::<Value>(text).unwrap(),
+                Value::String(text.to_string())
+            );
+            match from_str_strict::<Value>(&format!("[{}]", text)) {
+                Err(Error::Syntax(ErrorCode::InvalidNumber, 1, _, _)) => {}
+                other => panic!("{}: unexpected result {:?}", text, other),
+            }
+            // with `arbitrary_precision` a float like `5.` keeps its literal `5.0`
+            for &strict in [false, true].iter() {
+                let parsed = parse(text, strict).unwrap();
+                assert!(parsed.is_number(), "{}", text);
+                assert_eq!(parsed.as_f64(), value.as_f64(), "{}", text);
+            }
+        }
+        assert_eq!(parse("[+1, .5\n]", false).unwrap()[1].as_f64(), Some(0.5));
+
+        // anything else that starts like a number is still a string
+        for text in [
+            "+", ".", "+.", ".e3", "+-1", "+1e", ".hidden", "+1 more", "1..2",
+        ]
+        .iter()
+        {
+            assert_eq!(parse(text, false).unwrap(), Value::String(text.to_string()));
+        }
+    }
+
+    #[test]
+    fn leading_zeros() {
+        fn parse(text: &str, strict: bool) -> Result<Value> {
+            let mut de = Deserializer::new(text.bytes())
+                .allow_leading_zeros(true)
+                .strict(strict);
+            let value = de::Deserialize::deserialize(&mut de)?;
+            de.end()?;
+            Ok(value)
+        }
+
+        // by default they are quoteless strings, or errors in strict mode
+        assert_eq!(
+            from_str::<Value>("0123").unwrap(),
+            Value::String("0123".to_string())
+        );
+        assert_eq!(
+            from_str::<Value>("[00\n]").unwrap(),
+            Value::Array(vec![Value::String("00".to_string())])
+        );
+        for text in ["0123", "00", "[-01]", "{\"a\": 00.5}"].iter() {
+            match from_str_strict::<Value>(text) {
+                Err(Error::Syntax(ErrorCode::InvalidNumber, 1, _, _)) => {}
+                other => panic!("{}: unexpected result {:?}", text, other),
+            }
+        }
+        for &(text, ref value) in [
+            ("0", Value::U64(0)),
+            ("0.5", Value::F64(0.5)),
+            ("-0.5", Value::F64(-0.5)),
+        ]
+        .iter()
+        {
+            assert_eq!(&from_str::<Value>(text).unwrap(), value);
+            assert_eq!(&from_str_strict::<Value>(text).unwrap(), value);
+        }
+
+        for &strict in [false, true].iter() {
+            assert_eq!(parse("0123", strict).unwrap(), Value::U64(123));
+            assert_eq!(parse("00", strict).unwrap(), Value::U64(0));
+            assert_eq!(parse("0.5", strict).unwrap(), Value::F64(0.5));
+            assert_eq!(parse("0", strict).unwrap(), Value::U64(0));
+            assert_eq!(
+                parse("[-007, 00.25, 01.5]", strict).unwrap(),
+                Value::Array(vec![Value::I64(-7), Value::F64(0.25), Value::F64(1.5)])
+            );
+        }
+        // still decimal with radix literals
+        let mut de = Deserializer::new("[017, 0o17]".bytes())
+            .allow_leading_zeros(true)
+            .allow_radix_literals(true);
+        let value: Value = de::Deserialize::deserialize(&mut de).unwrap();
+        assert_eq!(value, Value::Array(vec![Value::U64(17), Value::U64(15)]));
+    }
+
+    #[test]
+    fn omitted_commas() {
+        let text = "{\n  a: \"x\"\n  b: [\"a\"\n    quoteless\n    {} # comment\n    []\n  ]\n  c: {}\n  d: '''m'''\n  e: 1 /* comment */\n  f: 2\n}";
+        let value: Value = from_str(text).unwrap();
+        assert_eq!(value["b"].as_array().map(|a| a.len()), Some(4));
+        assert_eq!(value["f"], Value::U64(2));
+        assert!(from_str::<Value>("a: x\nb: 2").is_ok());
+
+        let errors = [
+            ("[\"a\" \"b\"]", ErrorCode::ExpectedListCommaOrEnd, 6),
+            ("[{} {}]", ErrorCode::ExpectedListCommaOrEnd, 5),
+            ("[[] 1]", ErrorCode::ExpectedListCommaOrEnd, 5),
+            ("[1 /* c */ 2]", ErrorCode::ExpectedListCommaOrEnd, 12),
+            ("{a: \"x\" b: 2}", ErrorCode::ExpectedObjectCommaOrEnd, 9),
+            ("{a: {} b: 1}", ErrorCode::ExpectedObjectCommaOrEnd, 8),
+            (
+                "{\"a\": [1] \"b\": 2}",
+                ErrorCode::ExpectedObjectCommaOrEnd,
+                11,
+            ),
+            ("{a: '''x''' b: 1}", ErrorCode::ExpectedObjectCommaOrEnd, 13),
+        ];
+        for &(text, ref code, col) in errors.iter() {
+            match from_str::<Value>(text) {
+                Err(Error::Syntax(ref c, 1, column, _)) if c == code && column == col => {}
+                other => panic!("{}: unexpected result {:?}", text, other),
+            }
+        }
+
+        // the same rule when the elements are streamed
+        for &(text, ref code, col) in errors[..4].iter() {
+            let items = Deserializer::from_str(text).into_iter::<Value>();
+            match items.last() {
+                Some(Err(Error::Syntax(ref c, 1, column, _))) if c == code && column == col => {}
+                other => panic!("{}: unexpected result {:?}", text, other),
+            }
+        }
+        let items = Deserializer::new("[{a: 1}\n{b: 2} # c\n3]".bytes()).into_iter::<Value>();
+        assert_eq!(items.filter(|item| item.is_ok()).count(), 3);
+        let mut items = Deserializer::new("[1\n2]".bytes())
+            .strict(true)
+            .into_iter::<Value>();
+        assert_eq!(items.next().unwrap().unwrap(), Value::U64(1));
+        match items.next() {
+            Some(Err(Error::Syntax(ErrorCode::ExpectedListCommaOrEnd, 2, 1, _))) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn punctuation_in_keys() {
+        let text = "{\n  content-type: text/html\n  a.b.c: 1\n  @x-y_z!?/\\'<>=+*&%$~: 2\n  a#b: 3\n    padded   : 4\n}";
+        let value: Value = from_str(text).unwrap();
+        assert_eq!(
+            value["content-type"],
+            Value::String("text/html".to_string())
+        );
+        assert_eq!(value["a.b.c"], Value::U64(1));
+        assert_eq!(value["@x-y_z!?/\\'<>=+*&%$~"], Value::U64(2));
+        assert_eq!(value["a#b"], Value::U64(3));
+        assert_eq!(value["padded"], Value::U64(4));
+
+        // the same keys in a braceless root
+        let value: Value = from_str("content-type: text/html\na.b.c: 1").unwrap();
+        assert_eq!(
+            value["content-type"],
+            Value::String("text/html".to_string())
+        );
+        assert_eq!(value["a.b.c"], Value::U64(1));
+
+        // whitespace inside a key and the characters {}[], still need quotes
+        for text in ["{a b: 1}", "{a,b: 1}", "{a[b: 1}", "{: 1}"].iter() {
+            assert!(from_str::<Value>(text).is_err(), "{}", text);
+        }
+    }
 }
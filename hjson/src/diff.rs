@@ -0,0 +1,216 @@
+//! Hjson Diff
+//!
+//! This module provides the type `ValueDiff`, which describes how two `Value`s differ. It is
+//! returned by `Value::diff`.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::ser::{self, SerializeMap, SerializeSeq};
+
+use super::value::{Map, Value};
+
+/// The structural difference between two `Value`s, see `Value::diff`.
+///
+/// A `ValueDiff` can be serialized (for example with `to_value` or `to_string`) to log it:
+/// `Unchanged` is written as the string `"unchanged"` and the other variants as an object with a
+/// single key named after the variant, like `{"added": 1}` or
+/// `{"changed": {"old": 1, "new": 2}}`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValueDiff {
+    /// Both values are equal.
+    Unchanged,
+    /// The object member or array element only exists in the new value.
+    Added(Value),
+    /// The object member or array element only exists in the old value.
+    Removed(Value),
+    /// The value was replaced, because it is a scalar with a different value or because the
+    /// type changed.
+    Changed {
+        /// The old value.
+        old: Value,
+        /// The new value.
+        new: Value,
+    },
+    /// Both values are objects. Only the members that differ are included: first those of the
+    /// old object in its order, then the added ones.
+    Object(Map<String, ValueDiff>),
+    /// Both values are arrays. Holds one entry per index of the longer array; the elements past
+    /// the end of the shorter one are `Added` or `Removed`.
+    Array(Vec<ValueDiff>),
+}
+
+impl ValueDiff {
+    /// Returns true if the values are equal.
+    pub fn is_unchanged(&self) -> bool {
+        *self == ValueDiff::Unchanged
+    }
+}
+
+pub(crate) fn diff(old: &Value, new: &Value) -> ValueDiff {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let mut members = Map::new();
+            for (key, value) in old.iter() {
+                let delta = match new.get(key) {
+                    Some(other) => diff(value, other),
+                    None => ValueDiff::Removed(value.clone()),
+                };
+                if !delta.is_unchanged() {
+                    members.insert(key.clone(), delta);
+                }
+            }
+            for (key, value) in new.iter() {
+                if !old.contains_key(key) {
+                    members.insert(key.clone(), ValueDiff::Added(value.clone()));
+                }
+            }
+            if members.is_empty() {
+                ValueDiff::Unchanged
+            } else {
+                ValueDiff::Object(members)
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            let mut elements: Vec<ValueDiff> = old
+                .iter()
+                .zip(new.iter())
+                .map(|(a, b)| diff(a, b))
+                .collect();
+            elements.extend(old.iter().skip(new.len()).cloned().map(ValueDiff::Removed));
+            elements.extend(new.iter().skip(old.len()).cloned().map(ValueDiff::Added));
+            if elements.iter().all(ValueDiff::is_unchanged) {
+                ValueDiff::Unchanged
+            } else {
+                ValueDiff::Array(elements)
+            }
+        }
+        (old, new) if old == new => ValueDiff::Unchanged,
+        (old, new) => ValueDiff::Changed {
+            old: old.clone(),
+            new: new.clone(),
+        },
+    }
+}
+
+impl ser::Serialize for ValueDiff {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        let mut map = match *self {
+            ValueDiff::Unchanged => return serializer.serialize_str("unchanged"),
+            _ => serializer.serialize_map(Some(1))?,
+        };
+        match *self {
+            ValueDiff::Unchanged => unreachable!(),
+            ValueDiff::Added(ref value) => map.serialize_entry("added", value)?,
+            ValueDiff::Removed(ref value) => map.serialize_entry("removed", value)?,
+            ValueDiff::Changed { ref old, ref new } => {
+                map.serialize_entry("changed", &Changed { old, new })?
+            }
+            ValueDiff::Object(ref members) => map.serialize_entry("object", members)?,
+            ValueDiff::Array(ref elements) => map.serialize_entry("array", &Elements(elements))?,
+        }
+        map.end()
+    }
+}
+
+struct Changed<'a> {
+    old: &'a Value,
+    new: &'a Value,
+}
+
+impl<'a> ser::Serialize for Changed<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("old", self.old)?;
+        map.serialize_entry("new", self.new)?;
+        map.end()
+    }
+}
+
+struct Elements<'a>(&'a [ValueDiff]);
+
+impl<'a> ser::Serialize for Elements<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+        for element in self.0 {
+            seq.serialize_element(element)?;
+        }
+        seq.end()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ValueDiff;
+    use de::from_str;
+    use value::{to_value, Value};
+
+    fn parse(s: &str) -> Value {
+        from_str(s).unwrap()
+    }
+
+    #[test]
+    fn nested_objects() {
+        let old = parse("{name: \"a\", db: {host: \"x\", port: 1}, gone: true}");
+        let new = parse("{name: \"a\", db: {host: \"y\", port: 1, user: \"u\"}}");
+        assert!(old.diff(&old).is_unchanged());
+
+        let delta = old.diff(&new);
+        let expected = parse(
+            r#"{
+                object: {
+                    db: {
+                        object: {
+                            host: {changed: {old: "x", new: "y"}}
+                            user: {added: "u"}
+                        }
+                    }
+                    gone: {removed: true}
+                }
+            }"#,
+        );
+        assert_eq!(to_value(&delta).unwrap(), expected);
+        match delta {
+            ValueDiff::Object(ref members) => {
+                assert_eq!(members.keys().collect::<Vec<_>>(), vec!["db", "gone"]);
+            }
+            ref other => panic!("unexpected diff {:?}", other),
+        }
+    }
+
+    #[test]
+    fn arrays() {
+        let old = parse("[1, 2, 3]");
+        assert_eq!(
+            old.diff(&parse("[1, 5]")),
+            ValueDiff::Array(vec![
+                ValueDiff::Unchanged,
+                ValueDiff::Changed {
+                    old: Value::U64(2),
+                    new: Value::U64(5),
+                },
+                ValueDiff::Removed(Value::U64(3)),
+            ])
+        );
+        assert_eq!(
+            to_value(&old.diff(&parse("[1, 2, 3, 4]"))).unwrap(),
+            parse("{array: [\"unchanged\", \"unchanged\", \"unchanged\", {added: 4}]}")
+        );
+        assert_eq!(
+            old.diff(&parse("{a: 1}")),
+            ValueDiff::Changed {
+                old: old.clone(),
+                new: parse("{a: 1}"),
+            }
+        );
+    }
+}
@@ -3,22 +3,31 @@
 //! This module is centered around the `Error` and `ErrorCode` types, which represents all possible
 //! `serde_hjson` errors.
 
+use alloc::string::{FromUtf8Error, String, ToString};
+use core::fmt;
+use core::num::ParseIntError;
+use core::result;
+#[cfg(feature = "std")]
 use std::error;
-use std::fmt;
-use std::io;
-use std::result;
-use std::string::FromUtf8Error;
-use std::num::ParseIntError;
 
 use serde::de;
 use serde::ser;
 
+use io;
+
 /// The errors that can arise while parsing a JSON stream.
+///
+/// New variants may be added, matches from other crates need a wildcard arm. `message` and
+/// `Display` describe any variant.
 #[derive(Clone, PartialEq)]
+#[non_exhaustive]
 pub enum ErrorCode {
     /// Catchall for syntax error messages
     Custom(String),
 
+    /// A key appeared twice in an object while `Deserializer::deny_duplicate_keys` was set.
+    DuplicateKey(String),
+
     /// EOF while parsing a list.
     EOFWhileParsingList,
 
@@ -31,6 +40,12 @@ pub enum ErrorCode {
     /// EOF while parsing a JSON value.
     EOFWhileParsingValue,
 
+    /// EOF while parsing a block comment.
+    EOFWhileParsingComment,
+
+    /// Expected this character to start an array, see `Deserializer::into_iter`.
+    ExpectedArray,
+
     /// Expected this character to be a `':'`.
     ExpectedColon,
 
@@ -46,6 +61,12 @@ pub enum ErrorCode {
     /// Expected this character to start a JSON value.
     ExpectedSomeValue,
 
+    /// The input is longer than `Deserializer::max_input_size` allows.
+    InputTooLarge,
+
+    /// A NaN or infinite float was serialized while `NonFiniteFloats::Error` was set.
+    FloatMustBeFinite,
+
     /// Invalid hex escape code.
     InvalidEscape,
 
@@ -58,12 +79,26 @@ pub enum ErrorCode {
     /// Object key is not a string.
     KeyMustBeAString,
 
+    /// Arrays and objects are nested deeper than `Deserializer::max_depth` allows.
+    RecursionLimitExceeded,
+
+    /// A string or key is longer than `Deserializer::max_string_length` allows.
+    StringTooLong,
+
     /// Lone leading surrogate in hex escape.
     LoneLeadingSurrogateInHexEscape,
 
     /// JSON has non-whitespace trailing characters after the value.
     TrailingCharacters,
 
+    /// A comma was followed by the end of the array or object while `Deserializer::strict` was
+    /// set.
+    TrailingComma,
+
+    /// A byte order mark (U+FEFF) was found outside of a quoted string, anywhere but at the
+    /// start of the input.
+    UnexpectedByteOrderMark,
+
     /// Unexpected end of hex excape.
     UnexpectedEndOfHexEscape,
 
@@ -71,61 +106,180 @@ pub enum ErrorCode {
     PunctuatorInQlString,
 }
 
+impl ErrorCode {
+    /// A short description of the error for users, without the text carried by `Custom` and
+    /// `DuplicateKey`.
+    pub fn message(&self) -> &'static str {
+        match *self {
+            ErrorCode::Custom(_) => "custom error",
+            ErrorCode::DuplicateKey(_) => "duplicate key",
+            ErrorCode::EOFWhileParsingList => "EOF while parsing a list",
+            ErrorCode::EOFWhileParsingObject => "EOF while parsing an object",
+            ErrorCode::EOFWhileParsingString => "EOF while parsing a string",
+            ErrorCode::EOFWhileParsingValue => "EOF while parsing a value",
+            ErrorCode::EOFWhileParsingComment => "EOF while parsing a comment",
+            ErrorCode::ExpectedArray => "expected an array",
+            ErrorCode::ExpectedColon => "expected `:`",
+            ErrorCode::ExpectedListCommaOrEnd => "expected `,` or `]`",
+            ErrorCode::ExpectedObjectCommaOrEnd => "expected `,` or `}`",
+            ErrorCode::ExpectedSomeIdent => "expected ident",
+            ErrorCode::ExpectedSomeValue => "expected value",
+            ErrorCode::FloatMustBeFinite => "float must be finite",
+            ErrorCode::InputTooLarge => "input too large",
+            ErrorCode::InvalidEscape => "invalid escape",
+            ErrorCode::InvalidNumber => "invalid number",
+            ErrorCode::InvalidUnicodeCodePoint => "invalid unicode code point",
+            ErrorCode::KeyMustBeAString => "key must be a string",
+            ErrorCode::LoneLeadingSurrogateInHexEscape => "lone leading surrogate in hex escape",
+            ErrorCode::RecursionLimitExceeded => "recursion limit exceeded",
+            ErrorCode::StringTooLong => "string too long",
+            ErrorCode::TrailingCharacters => "trailing characters",
+            ErrorCode::TrailingComma => "trailing comma",
+            ErrorCode::UnexpectedByteOrderMark => "unexpected byte order mark",
+            ErrorCode::UnexpectedEndOfHexEscape => "unexpected end of hex escape",
+            ErrorCode::PunctuatorInQlString => {
+                "found a punctuator character when expecting a quoteless string"
+            }
+        }
+    }
+
+    /// A number for the kind of error that does not change with the message, for logging and
+    /// metrics. `Custom` is 1000, the other variants count up from 1 and new ones get the next
+    /// free number, so a code is never reused:
+    ///
+    /// ```rust
+    /// use serde_hjson::ErrorCode;
+    ///
+    /// let err = serde_hjson::from_str::<Vec<u32>>("[1, 2").unwrap_err();
+    /// match err {
+    ///     serde_hjson::Error::Syntax(ref code, ..) => assert_eq!(code.code(), 2),
+    ///     _ => unreachable!(),
+    /// }
+    /// assert_eq!(ErrorCode::Custom("x".to_string()).code(), 1000);
+    /// ```
+    pub fn code(&self) -> u32 {
+        match *self {
+            ErrorCode::Custom(_) => 1000,
+            ErrorCode::DuplicateKey(_) => 1,
+            ErrorCode::EOFWhileParsingList => 2,
+            ErrorCode::EOFWhileParsingObject => 3,
+            ErrorCode::EOFWhileParsingString => 4,
+            ErrorCode::EOFWhileParsingValue => 5,
+            ErrorCode::EOFWhileParsingComment => 6,
+            ErrorCode::ExpectedColon => 7,
+            ErrorCode::ExpectedListCommaOrEnd => 8,
+            ErrorCode::ExpectedObjectCommaOrEnd => 9,
+            ErrorCode::ExpectedSomeIdent => 10,
+            ErrorCode::ExpectedSomeValue => 11,
+            ErrorCode::FloatMustBeFinite => 12,
+            ErrorCode::InputTooLarge => 13,
+            ErrorCode::InvalidEscape => 14,
+            ErrorCode::InvalidNumber => 15,
+            ErrorCode::InvalidUnicodeCodePoint => 16,
+            ErrorCode::KeyMustBeAString => 17,
+            ErrorCode::LoneLeadingSurrogateInHexEscape => 18,
+            ErrorCode::RecursionLimitExceeded => 19,
+            ErrorCode::StringTooLong => 20,
+            ErrorCode::TrailingCharacters => 21,
+            ErrorCode::TrailingComma => 22,
+            ErrorCode::UnexpectedByteOrderMark => 23,
+            ErrorCode::UnexpectedEndOfHexEscape => 24,
+            ErrorCode::PunctuatorInQlString => 25,
+            ErrorCode::ExpectedArray => 26,
+        }
+    }
+}
+
 impl fmt::Debug for ErrorCode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        //use std::fmt::Debug;
+        fmt::Display::fmt(self, f)
+    }
+}
 
+/// Writes the `message`, or the text of `Custom` and the key of `DuplicateKey`.
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            ErrorCode::Custom(ref msg) => write!(f, "{}", msg),
-            ErrorCode::EOFWhileParsingList => "EOF while parsing a list".fmt(f),
-            ErrorCode::EOFWhileParsingObject => "EOF while parsing an object".fmt(f),
-            ErrorCode::EOFWhileParsingString => "EOF while parsing a string".fmt(f),
-            ErrorCode::EOFWhileParsingValue => "EOF while parsing a value".fmt(f),
-            ErrorCode::ExpectedColon => "expected `:`".fmt(f),
-            ErrorCode::ExpectedListCommaOrEnd => "expected `,` or `]`".fmt(f),
-            ErrorCode::ExpectedObjectCommaOrEnd => "expected `,` or `}`".fmt(f),
-            ErrorCode::ExpectedSomeIdent => "expected ident".fmt(f),
-            ErrorCode::ExpectedSomeValue => "expected value".fmt(f),
-            ErrorCode::InvalidEscape => "invalid escape".fmt(f),
-            ErrorCode::InvalidNumber => "invalid number".fmt(f),
-            ErrorCode::InvalidUnicodeCodePoint => "invalid unicode code point".fmt(f),
-            ErrorCode::KeyMustBeAString => "key must be a string".fmt(f),
-            ErrorCode::LoneLeadingSurrogateInHexEscape => {
-                "lone leading surrogate in hex escape".fmt(f)
-            }
-            ErrorCode::TrailingCharacters => "trailing characters".fmt(f),
-            ErrorCode::UnexpectedEndOfHexEscape => "unexpected end of hex escape".fmt(f),
-            ErrorCode::PunctuatorInQlString => {
-                "found a punctuator character when expecting a quoteless string".fmt(f)
-            }
+            ErrorCode::Custom(ref msg) => f.write_str(msg),
+            ErrorCode::DuplicateKey(ref key) => write!(f, "duplicate key `{}`", key),
+            ref code => f.write_str(code.message()),
         }
     }
 }
 
 /// This type represents all possible errors that can occur when serializing or deserializing a
 /// value into JSON.
+///
+/// Errors can be cloned and compared, for example in tests. As `io::Error` supports neither, two
+/// `Io` errors are equal when they have the same `ErrorKind`, and the clone of an `Io` error is a
+/// new error with the kind and the message of the original, without its source.
 #[derive(Debug)]
 pub enum Error {
-    /// The JSON value had some syntatic error.
-    Syntax(ErrorCode, usize, usize),
+    /// The JSON value had some syntatic error. The fields are the error code, the line, the
+    /// column and the byte offset into the input.
+    ///
+    /// A `Custom` error raised by the type a value in an array or object is deserialized into,
+    /// like a type mismatch or a missing field, has the path to that value at the start of its
+    /// message, like `servers[0].port: invalid type: ...`.
+    Syntax(ErrorCode, usize, usize, usize),
 
     /// Some IO error occurred when serializing or deserializing a value.
     Io(io::Error),
 
-    /// Some UTF8 error occurred while serializing or deserializing a value.
-    FromUtf8(FromUtf8Error),
+    /// Some UTF8 error occurred while serializing or deserializing a value. The other fields are
+    /// the line, the column and the byte offset of the invalid sequence in the input, or zero
+    /// when the error does not come from reading input.
+    ///
+    /// For invalid input the bytes of the `FromUtf8Error` run from the start of the invalid
+    /// sequence to the byte that made it invalid, or to the end of the input if it was cut off.
+    FromUtf8(FromUtf8Error, usize, usize, usize),
 
     /// Some error occurred while deserializing a number.
     ParseIntError(ParseIntError),
 }
 
+impl Clone for Error {
+    fn clone(&self) -> Error {
+        match *self {
+            Error::Syntax(ref code, line, col, offset) => {
+                Error::Syntax(code.clone(), line, col, offset)
+            }
+            #[cfg(feature = "std")]
+            Error::Io(ref error) => Error::Io(io::Error::new(error.kind(), error.to_string())),
+            #[cfg(not(feature = "std"))]
+            Error::Io(ref error) => Error::Io(error.clone()),
+            Error::FromUtf8(ref error, line, col, offset) => {
+                Error::FromUtf8(error.clone(), line, col, offset)
+            }
+            Error::ParseIntError(ref error) => Error::ParseIntError(error.clone()),
+        }
+    }
+}
+
+impl PartialEq for Error {
+    fn eq(&self, other: &Error) -> bool {
+        match (self, other) {
+            (Error::Syntax(a, a1, a2, a3), Error::Syntax(b, b1, b2, b3)) => {
+                a == b && (a1, a2, a3) == (b1, b2, b3)
+            }
+            (Error::Io(a), Error::Io(b)) => a.kind() == b.kind(),
+            (Error::FromUtf8(a, a1, a2, a3), Error::FromUtf8(b, b1, b2, b3)) => {
+                a == b && (a1, a2, a3) == (b1, b2, b3)
+            }
+            (Error::ParseIntError(a), Error::ParseIntError(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
 impl error::Error for Error {
     #[allow(deprecated)]
     fn description(&self) -> &str {
         match *self {
             Error::Syntax(..) => "syntax error",
             Error::Io(ref error) => error.description(),
-            Error::FromUtf8(ref error) => error.description(),
+            Error::FromUtf8(ref error, ..) => error.description(),
             Error::ParseIntError(ref error) => error.description(),
         }
     }
@@ -133,21 +287,28 @@ impl error::Error for Error {
     fn cause(&self) -> Option<&dyn error::Error> {
         match *self {
             Error::Io(ref error) => Some(error),
-            Error::FromUtf8(ref error) => Some(error),
+            Error::FromUtf8(ref error, ..) => Some(error),
             Error::ParseIntError(ref error) => Some(error),
             _ => None,
         }
     }
 }
 
+// serde requires the errors to implement `core::error::Error` without std
+#[cfg(not(feature = "std"))]
+impl de::StdError for Error {}
+
 impl fmt::Display for Error {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            Error::Syntax(ref code, line, col) => {
+            Error::Syntax(ref code, line, col, _) => {
                 write!(fmt, "{:?} at line {} column {}", code, line, col)
             }
             Error::Io(ref error) => fmt::Display::fmt(error, fmt),
-            Error::FromUtf8(ref error) => fmt::Display::fmt(error, fmt),
+            Error::FromUtf8(ref error, 0, _, _) => fmt::Display::fmt(error, fmt),
+            Error::FromUtf8(ref error, line, col, _) => {
+                write!(fmt, "{} at line {} column {}", error, line, col)
+            }
             Error::ParseIntError(ref error) => fmt::Display::fmt(error, fmt),
         }
     }
@@ -161,7 +322,7 @@ impl From<io::Error> for Error {
 
 impl From<FromUtf8Error> for Error {
     fn from(error: FromUtf8Error) -> Error {
-        Error::FromUtf8(error)
+        Error::FromUtf8(error, 0, 0, 0)
     }
 }
 
@@ -173,16 +334,80 @@ impl From<ParseIntError> for Error {
 
 impl de::Error for Error {
     fn custom<T: fmt::Display>(msg: T) -> Error {
-        Error::Syntax(ErrorCode::Custom(msg.to_string()), 0, 0)
+        Error::Syntax(ErrorCode::Custom(msg.to_string()), 0, 0, 0)
     }
 }
 
 impl ser::Error for Error {
     /// Raised when there is general error when deserializing a type.
     fn custom<T: fmt::Display>(msg: T) -> Error {
-        Error::Syntax(ErrorCode::Custom(msg.to_string()), 0, 0)
+        Error::Syntax(ErrorCode::Custom(msg.to_string()), 0, 0, 0)
     }
 }
 
 /// Helper alias for `Result` objects that return a JSON `Error`.
 pub type Result<T> = result::Result<T, Error>;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn codes() {
+        assert_eq!(ErrorCode::Custom("a".to_string()).code(), 1000);
+        assert_eq!(ErrorCode::DuplicateKey("a".to_string()).code(), 1);
+        assert_eq!(ErrorCode::ExpectedColon.code(), 7);
+        assert_eq!(ErrorCode::InvalidNumber.code(), 15);
+        assert_eq!(ErrorCode::PunctuatorInQlString.code(), 25);
+        assert_eq!(ErrorCode::ExpectedArray.code(), 26);
+    }
+
+    #[test]
+    fn messages() {
+        assert_eq!(ErrorCode::ExpectedColon.message(), "expected `:`");
+        assert_eq!(ErrorCode::TrailingComma.message(), "trailing comma");
+        assert_eq!(
+            ErrorCode::DuplicateKey("a".to_string()).message(),
+            "duplicate key"
+        );
+
+        let code = ErrorCode::DuplicateKey("a".to_string());
+        assert_eq!(code.to_string(), "duplicate key `a`");
+        assert_eq!(format!("{:?}", code), "duplicate key `a`");
+        assert_eq!(ErrorCode::Custom("x".to_string()).to_string(), "x");
+        assert_eq!(
+            Error::Syntax(ErrorCode::InvalidNumber, 2, 5, 9).to_string(),
+            "invalid number at line 2 column 5"
+        );
+    }
+
+    #[test]
+    fn compare_and_clone() {
+        let parse = |text: &str| ::de::from_str::<::value::Value>(text).unwrap_err();
+        let err = parse("{a: [1, 2}");
+        assert_eq!(err, parse("{a: [1, 2}"));
+        assert_eq!(err.clone(), err);
+        assert!(err != parse("{a: [1,\n  2}"));
+        assert!(err != Error::Syntax(ErrorCode::ExpectedSomeValue, 1, 10, 9));
+
+        let custom = Error::Syntax(ErrorCode::Custom("bad".to_string()), 0, 0, 0);
+        assert_eq!(custom.clone(), custom);
+
+        let int = "x".parse::<u8>().unwrap_err();
+        assert_eq!(Error::from(int.clone()), Error::ParseIntError(int));
+        let utf8 = String::from_utf8(vec![0xff]).unwrap_err();
+        assert_eq!(
+            Error::from(utf8.clone()),
+            Error::FromUtf8(utf8.clone(), 0, 0, 0)
+        );
+        assert!(Error::FromUtf8(utf8.clone(), 1, 2, 1) != Error::FromUtf8(utf8, 1, 3, 2));
+
+        // I/O errors only compare their kind
+        let io = |kind, msg| Error::Io(io::Error::new(kind, msg));
+        assert_eq!(io(io::ErrorKind::Other, "a"), io(io::ErrorKind::Other, "b"));
+        assert!(io(io::ErrorKind::Other, "a") != io(io::ErrorKind::WriteZero, "a"));
+        let cloned = io(io::ErrorKind::WriteZero, "full").clone();
+        assert_eq!(cloned.to_string(), "full");
+        assert!(cloned != custom);
+    }
+}
@@ -0,0 +1,300 @@
+//! Hjson Events
+//!
+//! This module provides `parse_events`, which reports the structure of a document to an
+//! `EventHandler` as it is parsed, for tools that want to inspect or validate Hjson without
+//! building a `Value` for it.
+
+use std::cell::Cell;
+use std::fmt;
+use std::io;
+use std::rc::Rc;
+
+use serde::de;
+
+use super::de::Deserializer;
+use super::error::{Error, Result};
+use super::value::{self, Value};
+
+/// A location in the input: the line and column as reported in errors and the byte offset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Position {
+    /// The line, starting at 1.
+    pub line: usize,
+    /// The column, starting at 1.
+    pub column: usize,
+    /// The byte offset, starting at 0.
+    pub offset: usize,
+}
+
+/// An event reported by `parse_events`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// The start of an object, at its `{` (or at the first key for a root object without
+    /// braces).
+    BeginObject,
+    /// The key of an object member, followed by the events of its value.
+    Key(String),
+    /// A scalar, that is anything but an array or an object. Quoteless and multiline strings are
+    /// reported as `Value::String`.
+    Value(Value),
+    /// The end of an object, at its `}` (or at the end of the input).
+    EndObject,
+    /// The start of an array, at its `[`.
+    BeginArray,
+    /// The end of an array, at its `]`.
+    EndArray,
+}
+
+/// Receives the events of `parse_events`.
+pub trait EventHandler {
+    /// Called for every event with the position where it starts. Returning an error stops the
+    /// parser; an `Error::Syntax` without a position (line 0) is reported at the event instead.
+    fn handle(&mut self, event: Event, position: Position) -> Result<()>;
+}
+
+/// Parses Hjson from a `std::io::Read` and reports its structure to `handler`, without building
+/// a `Value`.
+///
+/// The input is accepted like by `from_reader`, except that a root that starts like an object
+/// without braces (a key followed by `:`) is always parsed as one.
+///
+/// ```rust
+/// use serde_hjson::events::{parse_events, Event, EventHandler, Position};
+///
+/// struct KeyCounter(usize);
+///
+/// impl EventHandler for KeyCounter {
+///     fn handle(&mut self, event: Event, _: Position) -> serde_hjson::Result<()> {
+///         if let Event::Key(_) = event {
+///             self.0 += 1;
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// let mut counter = KeyCounter(0);
+/// parse_events("name: x\nlist: [{a: 1}, {b: 2}]".as_bytes(), &mut counter).unwrap();
+/// assert_eq!(counter.0, 4);
+/// ```
+pub fn parse_events<R, H>(reader: R, handler: &mut H) -> Result<()>
+where
+    R: io::Read,
+    H: EventHandler,
+{
+    let bytes: io::Result<Vec<u8>> = io::Read::bytes(io::BufReader::new(reader)).collect();
+    let bytes = bytes.map_err(Error::Io)?;
+
+    let mut de = Deserializer::new(bytes.iter().cloned()).root_braces_optional(true);
+    let mut events = Events {
+        handler,
+        marks: de.share_marks(),
+        error: None,
+    };
+    let res = de::DeserializeSeed::deserialize(EventSeed(&mut events), &mut de);
+    match events.error.take() {
+        Some(err) => Err(err),
+        None => res.and_then(|()| de.end()),
+    }
+}
+
+struct Events<'h, H: 'h> {
+    handler: &'h mut H,
+    marks: Rc<Cell<(usize, usize, usize)>>,
+    // the error returned by the handler, which has to be passed through the generic visitor
+    // error type
+    error: Option<Error>,
+}
+
+impl<'h, H: EventHandler> Events<'h, H> {
+    fn emit<E: de::Error>(&mut self, event: Event) -> ::std::result::Result<(), E> {
+        let (line, column, offset) = self.marks.get();
+        let position = Position {
+            line,
+            column,
+            offset,
+        };
+        match self.handler.handle(event, position) {
+            Ok(()) => Ok(()),
+            Err(err) => {
+                let err = match err {
+                    Error::Syntax(code, 0, _, _) => Error::Syntax(code, line, column, offset),
+                    err => err,
+                };
+                self.error = Some(err);
+                Err(E::custom("stopped by the event handler"))
+            }
+        }
+    }
+}
+
+struct EventSeed<'a, 'h: 'a, H: 'h>(&'a mut Events<'h, H>);
+
+impl<'de, 'a, 'h, H: EventHandler> de::DeserializeSeed<'de> for EventSeed<'a, 'h, H> {
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> ::std::result::Result<(), D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        value::deserialize_any_with_literals(deserializer, self)
+    }
+}
+
+impl<'de, 'a, 'h, H: EventHandler> de::Visitor<'de> for EventSeed<'a, 'h, H> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any valid Hjson value")
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> ::std::result::Result<(), E> {
+        self.0.emit(Event::Value(Value::Bool(value)))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> ::std::result::Result<(), E> {
+        self.0.emit(Event::Value(Value::I64(value)))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> ::std::result::Result<(), E> {
+        self.0.emit(Event::Value(Value::U64(value)))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> ::std::result::Result<(), E> {
+        self.0.emit(Event::Value(Value::F64(value)))
+    }
+
+    // the Hjson deserializer hands over number literals as a newtype
+    #[cfg(feature = "arbitrary_precision")]
+    fn visit_newtype_struct<D>(self, deserializer: D) -> ::std::result::Result<(), D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let literal: String = de::Deserialize::deserialize(deserializer)?;
+        self.0.emit(Event::Value(Value::Number(literal)))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> ::std::result::Result<(), E> {
+        self.0.emit(Event::Value(Value::String(value.to_string())))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> ::std::result::Result<(), E> {
+        self.0.emit(Event::Value(Value::Null))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> ::std::result::Result<(), A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        self.0.emit(Event::BeginArray)?;
+        while let Some(()) = seq.next_element_seed(EventSeed(&mut *self.0))? {}
+        self.0.emit(Event::EndArray)
+    }
+
+    fn visit_map<A>(self, mut map: A) -> ::std::result::Result<(), A::Error>
+    where
+        A: de::MapAccess<'de>,
+    {
+        self.0.emit(Event::BeginObject)?;
+        while let Some(key) = map.next_key::<String>()? {
+            self.0.emit(Event::Key(key))?;
+            map.next_value_seed(EventSeed(&mut *self.0))?;
+        }
+        self.0.emit(Event::EndObject)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use error::ErrorCode;
+
+    // line, column and offset
+    type Mark = (usize, usize, usize);
+
+    #[derive(Default)]
+    struct Recorder(Vec<(Event, Mark)>);
+
+    impl EventHandler for Recorder {
+        fn handle(&mut self, event: Event, position: Position) -> Result<()> {
+            if event == Event::Key("stop".to_string()) {
+                return Err(Error::Syntax(
+                    ErrorCode::Custom("stop".to_string()),
+                    0,
+                    0,
+                    0,
+                ));
+            }
+            let position = (position.line, position.column, position.offset);
+            self.0.push((event, position));
+            Ok(())
+        }
+    }
+
+    fn events(text: &str) -> Result<Vec<(Event, Mark)>> {
+        let mut recorder = Recorder::default();
+        parse_events(text.as_bytes(), &mut recorder)?;
+        Ok(recorder.0)
+    }
+
+    fn string(s: &str) -> Event {
+        Event::Value(Value::String(s.to_string()))
+    }
+
+    #[test]
+    fn event_sequence() {
+        let text = "{\n  # comment\n  name: quoteless text\n  text:\n    '''\n    multi\n    line\n    '''\n  list: [1, \"two\", null]\n}\n";
+        assert_eq!(
+            events(text).unwrap(),
+            vec![
+                (Event::BeginObject, (1, 1, 0)),
+                (Event::Key("name".to_string()), (3, 3, 16)),
+                (string("quoteless text"), (3, 9, 22)),
+                (Event::Key("text".to_string()), (4, 3, 39)),
+                (string("multi\nline"), (5, 5, 49)),
+                (Event::Key("list".to_string()), (9, 3, 82)),
+                (Event::BeginArray, (9, 9, 88)),
+                (Event::Value(Value::U64(1)), (9, 10, 89)),
+                (string("two"), (9, 13, 92)),
+                (Event::Value(Value::Null), (9, 20, 99)),
+                (Event::EndArray, (9, 24, 103)),
+                (Event::EndObject, (10, 1, 105)),
+            ]
+        );
+        assert_eq!(&text[22..36], "quoteless text");
+        assert_eq!(&text[103..104], "]");
+    }
+
+    #[test]
+    fn braceless_root_and_errors() {
+        let list: Vec<Event> = events("a: [{}]")
+            .unwrap()
+            .into_iter()
+            .map(|e| e.0)
+            .collect();
+        assert_eq!(
+            list,
+            vec![
+                Event::BeginObject,
+                Event::Key("a".to_string()),
+                Event::BeginArray,
+                Event::BeginObject,
+                Event::EndObject,
+                Event::EndArray,
+                Event::EndObject,
+            ]
+        );
+        assert_eq!(
+            events("true").unwrap(),
+            vec![(Event::Value(Value::Bool(true)), (1, 1, 0))]
+        );
+
+        match events("[1, 2") {
+            Err(Error::Syntax(ErrorCode::EOFWhileParsingList, ..)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        match events("{\n  a: 1\n  stop: 2\n}") {
+            Err(Error::Syntax(ErrorCode::Custom(ref msg), 3, 3, 11)) if msg == "stop" => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
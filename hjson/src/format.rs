@@ -0,0 +1,341 @@
+//! Hjson Formatting
+//!
+//! This module provides `format_str`, which re-indents a document without going through `Value`,
+//! so comments and the way each string is written are kept.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::de::from_str;
+use super::error::Result;
+use super::tokenizer::{Token, TokenKind, Tokenizer};
+use super::value::Value;
+
+/// Re-indents and normalizes the layout of a Hjson document while keeping its comments and the
+/// style of its strings and numbers.
+///
+/// Every object member and array element is written on its own line with two spaces of
+/// indentation per level, like `to_string` does, and commas are dropped. Comments stay where
+/// they were: on their own line before a member, or at the end of the line they ended. Quoted,
+/// quoteless and multiline strings keep their style (multiline strings are re-indented), numbers
+/// are kept as they were written and a single blank line between members is preserved.
+///
+/// The document is parsed first, invalid input returns the parser's error.
+///
+/// ```rust
+/// let text = "{\n# server\nhost: localhost, port: 8080 // default\n    list: [1,2]\n}";
+/// assert_eq!(
+///     serde_hjson::format_str(text).unwrap(),
+///     "{\n  # server\n  host: localhost, port: 8080 // default\n  list:\n  [\n    1\n    2\n  ]\n}"
+/// );
+/// ```
+pub fn format_str(input: &str) -> Result<String> {
+    let value: Value = from_str(input)?;
+
+    let input = input.trim_start_matches('\u{feff}');
+    let mut f = Format {
+        src: input,
+        tokens: Tokenizer::new(input).collect::<Result<_>>()?,
+        next: 0,
+        out: String::with_capacity(input.len()),
+        indent: 0,
+        fresh: true,
+    };
+    let trivia = f.trivia();
+    f.comments(&trivia.comments);
+    let braceless = value.is_object() && f.peek() != Some(TokenKind::BeginObject);
+    if braceless {
+        f.members(None);
+    } else {
+        f.line(false);
+        f.value(false);
+        let trivia = f.trivia();
+        f.comments(&trivia.comments);
+    }
+    if input.ends_with('\n') {
+        f.out.push('\n');
+    }
+    Ok(f.out)
+}
+
+// A comment found between two tokens.
+struct Comment<'a> {
+    text: &'a str,
+    // starts on a new line
+    own_line: bool,
+    // is preceded by an empty line
+    blank: bool,
+}
+
+// Whitespace and comments between two tokens.
+struct Trivia<'a> {
+    comments: Vec<Comment<'a>>,
+    // the next token is preceded by an empty line
+    blank: bool,
+}
+
+struct Format<'a> {
+    src: &'a str,
+    tokens: Vec<Token>,
+    // the index of the next token
+    next: usize,
+    out: String,
+    indent: usize,
+    // nothing was written since the last opening bracket, no blank line goes here
+    fresh: bool,
+}
+
+impl<'a> Format<'a> {
+    fn peek(&self) -> Option<TokenKind> {
+        self.tokens.get(self.next).map(|token| token.kind)
+    }
+
+    // Returns the text of the next token and moves past it.
+    fn take(&mut self) -> &'a str {
+        let text = self.tokens[self.next].text(self.src);
+        self.next += 1;
+        text
+    }
+
+    fn line(&mut self, blank: bool) {
+        if !self.out.is_empty() {
+            if blank && !self.fresh {
+                self.out.push('\n');
+            }
+            self.out.push('\n');
+        }
+        for _ in 0..self.indent {
+            self.out.push_str("  ");
+        }
+        self.fresh = false;
+    }
+
+    // Skips whitespace and collects the comments up to the next token.
+    fn trivia(&mut self) -> Trivia<'a> {
+        let mut comments = Vec::new();
+        let mut newlines = 0;
+        loop {
+            match self.peek() {
+                Some(TokenKind::Whitespace) => newlines += self.take().matches('\n').count(),
+                Some(TokenKind::Comment) => {
+                    comments.push(Comment {
+                        text: self.take().trim_end(),
+                        own_line: newlines > 0,
+                        blank: newlines > 1,
+                    });
+                    newlines = 0;
+                }
+                _ => break,
+            }
+        }
+        Trivia {
+            comments,
+            blank: newlines > 1,
+        }
+    }
+
+    fn comments(&mut self, comments: &[Comment]) {
+        for comment in comments {
+            if comment.own_line || self.out.is_empty() {
+                self.line(comment.blank);
+            } else {
+                self.out.push(' ');
+            }
+            self.out.push_str(comment.text);
+        }
+    }
+
+    // Writes the members of an object up to `close`, or to the end of the input for a root
+    // object without braces.
+    fn members(&mut self, close: Option<TokenKind>) {
+        loop {
+            let trivia = self.trivia();
+            self.comments(&trivia.comments);
+            match self.peek() {
+                None => return,
+                Some(TokenKind::Comma) => {
+                    self.next += 1;
+                    continue;
+                }
+                kind if kind == close => {
+                    self.next += 1;
+                    return;
+                }
+                _ => {}
+            }
+
+            let key = self.take();
+            let mut comments = self.trivia().comments;
+            // the colon
+            self.next += 1;
+            comments.extend(self.trivia().comments);
+            // comments around the colon are moved before the member
+            for comment in &mut comments {
+                comment.own_line = true;
+            }
+            if let Some(first) = comments.first_mut() {
+                first.blank = trivia.blank;
+            }
+            let blank = trivia.blank && comments.is_empty();
+            self.comments(&comments);
+            self.line(blank);
+            self.out.push_str(key);
+            self.out.push(':');
+            self.value(true);
+        }
+    }
+
+    fn elements(&mut self) {
+        loop {
+            let trivia = self.trivia();
+            self.comments(&trivia.comments);
+            match self.peek() {
+                None => return,
+                Some(TokenKind::Comma) => {
+                    self.next += 1;
+                    continue;
+                }
+                Some(TokenKind::EndArray) => {
+                    self.next += 1;
+                    return;
+                }
+                _ => {}
+            }
+            self.line(trivia.blank);
+            self.value(false);
+        }
+    }
+
+    // Writes the value at the current position, `after_key` tells that it follows `key:` on the
+    // same line.
+    fn value(&mut self, after_key: bool) {
+        let (open, close, end) = match self.peek() {
+            Some(TokenKind::BeginObject) => ('{', '}', TokenKind::EndObject),
+            Some(TokenKind::BeginArray) => ('[', ']', TokenKind::EndArray),
+            _ => {
+                // strings, numbers and keywords are written as they are, only multiline strings
+                // are re-indented
+                let start = self.tokens[self.next].start;
+                let raw = self.take();
+                if raw.starts_with("'''") && raw.contains('\n') {
+                    self.ml_string(after_key, start, raw);
+                } else {
+                    self.raw(after_key, raw);
+                }
+                return;
+            }
+        };
+        self.next += 1;
+        let start = self.next;
+        let empty = self.trivia().comments.is_empty() && self.peek() == Some(end);
+        if empty {
+            self.next += 1;
+            if after_key {
+                self.out.push(' ');
+            }
+            self.out.push(open);
+            self.out.push(close);
+            return;
+        }
+        self.next = start;
+        if after_key {
+            self.line(false);
+        }
+        self.out.push(open);
+        self.indent += 1;
+        self.fresh = true;
+        if open == '{' {
+            self.members(Some(end));
+        } else {
+            self.elements();
+        }
+        self.indent -= 1;
+        self.line(false);
+        self.out.push(close);
+    }
+
+    fn raw(&mut self, after_key: bool, text: &str) {
+        if after_key {
+            self.out.push(' ');
+        }
+        self.out.push_str(text);
+    }
+
+    // Writes the multiline string block `raw`, found at `start` in the input, re-indented like
+    // the serializer does.
+    fn ml_string(&mut self, after_key: bool, start: usize, raw: &str) {
+        // the parser strips as much indentation as the column of the opening quotes, parse it in
+        // an array at the same column to get the content
+        let line_start = self.src[..start].rfind('\n').map_or(0, |i| i + 1);
+        let mut text = String::from("[\n");
+        text.extend((line_start..start).map(|_| ' '));
+        text.push_str(raw);
+        text.push_str("\n]");
+        let content = match from_str::<Vec<String>>(&text) {
+            Ok(mut list) => list.pop().unwrap_or_default(),
+            // cannot happen for a document that parsed
+            Err(_) => return self.raw(after_key, raw),
+        };
+
+        if after_key {
+            self.indent += 1;
+            self.line(false);
+        }
+        self.out.push_str("'''");
+        for line in content.split('\n') {
+            if line.is_empty() {
+                self.out.push('\n');
+            } else {
+                self.line(false);
+                self.out.push_str(line);
+            }
+        }
+        self.line(false);
+        self.out.push_str("'''");
+        if after_key {
+            self.indent -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use de::from_str;
+    use error::{Error, ErrorCode};
+
+    #[test]
+    fn messy_document() {
+        let text = "// config\n{\n      # the server\n   server: {host: \"localhost\",   port: 8080 # default\n  },\n\n\n  name:   quoteless, text\n\tlist: [ 1,2.50 , true,null /* none */ ,[] ,{}],\n      ml:\n      '''\n      first\n        indented\n\n      last\n      '''\n  inline: '''x'''\n    empty: { # nothing\n    }\n /* end\n    of object */\n}\n";
+        let expected = "// config\n{\n  # the server\n  server:\n  {\n    host: \"localhost\"\n    port: 8080 # default\n  }\n\n  name: quoteless, text\n  list:\n  [\n    1\n    2.50\n    true\n    null /* none */\n    []\n    {}\n  ]\n  ml:\n    '''\n    first\n      indented\n\n    last\n    '''\n  inline: '''x'''\n  empty:\n  { # nothing\n  }\n  /* end\n    of object */\n}\n";
+        let formatted = format_str(text).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(
+            from_str::<Value>(&formatted).unwrap(),
+            from_str::<Value>(text).unwrap()
+        );
+        assert_eq!(format_str(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn roots_and_errors() {
+        assert_eq!(
+            format_str("# top\na: 1, b: [x\n]\n\nc:'''y'''\n# bottom").unwrap(),
+            "# top\na: 1\nb:\n[\n  x\n]\n\nc: '''y'''\n# bottom"
+        );
+        assert_eq!(
+            format_str("  [1,\"a\"] // end").unwrap(),
+            "[\n  1\n  \"a\"\n] // end"
+        );
+        assert_eq!(format_str("true").unwrap(), "true");
+        assert_eq!(
+            format_str("[\n '''\n a\n  b\n '''\n]").unwrap(),
+            "[\n  '''\n  a\n   b\n  '''\n]"
+        );
+
+        match format_str("{a: 1") {
+            Err(Error::Syntax(ErrorCode::EOFWhileParsingObject, ..)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
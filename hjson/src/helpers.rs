@@ -0,0 +1,580 @@
+//! Hjson Helpers
+//!
+//! This module provides `Deserialize` adapters for values that configuration files often write as
+//! quoteless strings with a unit, like `timeout: 30s` or `cache: 512MiB`, and with the `time`
+//! feature an adapter for RFC 3339 timestamps like `created: 2024-05-01T12:30:00Z`.
+
+#[cfg(feature = "time")]
+use alloc::string::String;
+#[cfg(feature = "time")]
+use core::convert::TryFrom;
+use core::fmt;
+use core::result;
+use core::time::Duration;
+#[cfg(feature = "time")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::de;
+#[cfg(feature = "time")]
+use serde::ser;
+
+/// A `Duration` written as numbers with units, like `30s`, `1h30m` or `1.5ms`.
+///
+/// The units are `ns`, `us`, `ms`, `s`, `m`, `h` and `d`, and every number needs one. Fractions
+/// are rounded down to whole nanoseconds.
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use serde_hjson::helpers::HumanDuration;
+///
+/// let timeout: HumanDuration = serde_hjson::from_str("1h30m").unwrap();
+/// assert_eq!(timeout.0, Duration::from_secs(5400));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HumanDuration(pub Duration);
+
+/// A number of bytes written with an optional unit, like `512MiB`, `2 kB` or `4096`.
+///
+/// The units are `B`, the decimal `kB` (or `KB`), `MB`, `GB`, `TB` and `PB`, and the binary
+/// `KiB`, `MiB`, `GiB`, `TiB` and `PiB`. Fractions are rounded down to whole bytes.
+///
+/// ```rust
+/// use serde_hjson::helpers::HumanSize;
+///
+/// let cache: HumanSize = serde_hjson::from_str("512MiB").unwrap();
+/// assert_eq!(cache.0, 512 * 1024 * 1024);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HumanSize(pub u64);
+
+/// A `SystemTime` written as an RFC 3339 timestamp, like `2024-05-01T12:30:00Z` or
+/// `2024-05-01 14:30:00.25+02:00`, quoted or not. Only available with the `time` feature.
+///
+/// It is serialized in UTC with `T` and `Z`, and with as many fractional digits as needed, so
+/// every `Timestamp` is written the same way. A leap second `:60` is read as the first second of
+/// the next minute, and years outside 0000 to 9999 cannot be written.
+///
+/// ```rust
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// use serde_hjson::helpers::Timestamp;
+///
+/// let created: Timestamp = serde_hjson::from_str("2001-09-09T03:46:40+02:00").unwrap();
+/// assert_eq!(created.0, UNIX_EPOCH + Duration::from_secs(1_000_000_000));
+/// assert_eq!(serde_hjson::to_string(&created).unwrap(), "2001-09-09T01:46:40Z");
+/// ```
+#[cfg(feature = "time")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(pub SystemTime);
+
+impl<'de> de::Deserialize<'de> for HumanDuration {
+    fn deserialize<D>(deserializer: D) -> result::Result<HumanDuration, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_str(DurationVisitor)
+    }
+}
+
+impl<'de> de::Deserialize<'de> for HumanSize {
+    fn deserialize<D>(deserializer: D) -> result::Result<HumanSize, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_str(SizeVisitor)
+    }
+}
+
+#[cfg(feature = "time")]
+impl<'de> de::Deserialize<'de> for Timestamp {
+    fn deserialize<D>(deserializer: D) -> result::Result<Timestamp, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_str(TimestampVisitor)
+    }
+}
+
+#[cfg(feature = "time")]
+impl ser::Serialize for Timestamp {
+    fn serialize<S>(&self, serializer: S) -> result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        match format_timestamp(self.0) {
+            Some(text) => serializer.serialize_str(&text),
+            None => Err(ser::Error::custom(
+                "timestamp is outside of the years 0000 to 9999",
+            )),
+        }
+    }
+}
+
+struct DurationVisitor;
+
+impl<'de> de::Visitor<'de> for DurationVisitor {
+    type Value = HumanDuration;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a duration like 30s or 1h30m")
+    }
+
+    fn visit_str<E>(self, text: &str) -> result::Result<HumanDuration, E>
+    where
+        E: de::Error,
+    {
+        let mut rest = text.trim();
+        if rest.is_empty() {
+            return Err(E::custom(format_args!("invalid duration `{}`", text)));
+        }
+        let mut nanos = 0u128;
+        while !rest.is_empty() {
+            let (int, frac, unit, next) = match component(rest) {
+                Some(component) => component,
+                None => return Err(E::custom(format_args!("invalid duration `{}`", text))),
+            };
+            let scale: u128 = match unit {
+                "ns" => 1,
+                "us" => 1_000,
+                "ms" => 1_000_000,
+                "s" => 1_000_000_000,
+                "m" => 60_000_000_000,
+                "h" => 3_600_000_000_000,
+                "d" => 86_400_000_000_000,
+                "" => {
+                    return Err(E::custom(format_args!(
+                        "missing unit in duration `{}`",
+                        text
+                    )))
+                }
+                _ => {
+                    return Err(E::custom(format_args!(
+                        "unknown unit `{}` in duration `{}`",
+                        unit, text
+                    )))
+                }
+            };
+            nanos = match scaled(int, frac, scale).and_then(|n| nanos.checked_add(n)) {
+                Some(nanos) => nanos,
+                None => return Err(E::custom(format_args!("duration `{}` is too long", text))),
+            };
+            rest = next;
+        }
+        let secs = nanos / 1_000_000_000;
+        if secs > u64::MAX as u128 {
+            return Err(E::custom(format_args!("duration `{}` is too long", text)));
+        }
+        Ok(HumanDuration(Duration::new(
+            secs as u64,
+            (nanos % 1_000_000_000) as u32,
+        )))
+    }
+}
+
+struct SizeVisitor;
+
+impl<'de> de::Visitor<'de> for SizeVisitor {
+    type Value = HumanSize;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a size like 4096 or 512MiB")
+    }
+
+    fn visit_u64<E>(self, value: u64) -> result::Result<HumanSize, E>
+    where
+        E: de::Error,
+    {
+        Ok(HumanSize(value))
+    }
+
+    fn visit_str<E>(self, text: &str) -> result::Result<HumanSize, E>
+    where
+        E: de::Error,
+    {
+        let (int, frac, unit) = match component(text.trim()) {
+            Some((int, frac, unit, "")) => (int, frac, unit),
+            _ => return Err(E::custom(format_args!("invalid size `{}`", text))),
+        };
+        let scale: u128 = match unit {
+            "" | "B" => 1,
+            "kB" | "KB" => 1_000,
+            "MB" => 1_000_000,
+            "GB" => 1_000_000_000,
+            "TB" => 1_000_000_000_000,
+            "PB" => 1_000_000_000_000_000,
+            "KiB" => 1 << 10,
+            "MiB" => 1 << 20,
+            "GiB" => 1 << 30,
+            "TiB" => 1 << 40,
+            "PiB" => 1 << 50,
+            _ => {
+                return Err(E::custom(format_args!(
+                    "unknown unit `{}` in size `{}`",
+                    unit, text
+                )))
+            }
+        };
+        match scaled(int, frac, scale) {
+            Some(bytes) if bytes <= u64::MAX as u128 => Ok(HumanSize(bytes as u64)),
+            _ => Err(E::custom(format_args!("size `{}` is too large", text))),
+        }
+    }
+}
+
+#[cfg(feature = "time")]
+struct TimestampVisitor;
+
+#[cfg(feature = "time")]
+impl<'de> de::Visitor<'de> for TimestampVisitor {
+    type Value = Timestamp;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an RFC 3339 timestamp like 2024-05-01T12:30:00Z")
+    }
+
+    fn visit_str<E>(self, text: &str) -> result::Result<Timestamp, E>
+    where
+        E: de::Error,
+    {
+        match parse_timestamp(text.trim()) {
+            Some(time) => Ok(Timestamp(time)),
+            None => Err(E::custom(format_args!("invalid timestamp `{}`", text))),
+        }
+    }
+}
+
+// Seconds from 1970-01-01 to the start of the day `year-month-day` of the proleptic Gregorian
+// calendar, and back (the algorithms from http://howardhinnant.github.io/date_algorithms.html).
+#[cfg(feature = "time")]
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(feature = "time")]
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(feature = "time")]
+fn parse_timestamp(text: &str) -> Option<SystemTime> {
+    // the fixed width fields, with the separators between them
+    let bytes = text.as_bytes();
+    let number = |range: core::ops::Range<usize>| -> Option<u32> {
+        let digits = bytes.get(range)?;
+        if !digits.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        Some(digits.iter().fold(0, |n, d| n * 10 + u32::from(d - b'0')))
+    };
+    let separators = [(4, b"-"), (7, b"-"), (13, b":"), (16, b":")];
+    if bytes.len() < 20
+        || !separators.iter().all(|&(i, sep)| bytes[i] == sep[0])
+        || !b"Tt ".contains(&bytes[10])
+    {
+        return None;
+    }
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let month_days = [
+        31,
+        if leap { 29 } else { 28 },
+        31,
+        30,
+        31,
+        30,
+        31,
+        31,
+        30,
+        31,
+        30,
+        31,
+    ];
+    if month == 0 || month > 12 || day == 0 || day > month_days[month as usize - 1] {
+        return None;
+    }
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let mut rest = &text[19..];
+    let mut nanos = 0;
+    if let Some(frac) = rest.strip_prefix('.') {
+        let len = frac
+            .find(|ch: char| !ch.is_ascii_digit())
+            .unwrap_or(frac.len());
+        if len == 0 {
+            return None;
+        }
+        // digits after the ninth are below a nanosecond and dropped
+        for (i, d) in frac[..len.min(9)].bytes().enumerate() {
+            nanos += u32::from(d - b'0') * 10u32.pow(8 - i as u32);
+        }
+        rest = &frac[len..];
+    }
+    let offset = match rest.as_bytes() {
+        b"Z" | b"z" => 0,
+        &[sign @ (b'+' | b'-'), h1, h2, b':', m1, m2] => {
+            let digits = [h1, h2, m1, m2];
+            if !digits.iter().all(u8::is_ascii_digit) {
+                return None;
+            }
+            let hours = i64::from((h1 - b'0') * 10 + h2 - b'0');
+            let minutes = i64::from((m1 - b'0') * 10 + m2 - b'0');
+            if hours > 23 || minutes > 59 {
+                return None;
+            }
+            let offset = hours * 3600 + minutes * 60;
+            if sign == b'-' {
+                -offset
+            } else {
+                offset
+            }
+        }
+        _ => return None,
+    };
+
+    let days = days_from_civil(i64::from(year), month, day);
+    let secs = days * 86_400 + i64::from(hour * 3600 + minute * 60 + second) - offset;
+    if secs >= 0 {
+        UNIX_EPOCH.checked_add(Duration::new(secs as u64, nanos))
+    } else {
+        UNIX_EPOCH
+            .checked_sub(Duration::from_secs(secs.unsigned_abs()))?
+            .checked_add(Duration::new(0, nanos))
+    }
+}
+
+// Formats `time` like `2024-05-01T12:30:00.25Z`, or returns None when its year does not have four
+// digits.
+#[cfg(feature = "time")]
+fn format_timestamp(time: SystemTime) -> Option<String> {
+    let (secs, nanos) = match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => (i64::try_from(since.as_secs()).ok()?, since.subsec_nanos()),
+        Err(err) => {
+            let before = err.duration();
+            let secs = -i64::try_from(before.as_secs()).ok()?;
+            match before.subsec_nanos() {
+                0 => (secs, 0),
+                nanos => (secs - 1, 1_000_000_000 - nanos),
+            }
+        }
+    };
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    if !(0..=9999).contains(&year) {
+        return None;
+    }
+    let time_of_day = secs.rem_euclid(86_400);
+    let mut text = format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        time_of_day / 3600,
+        time_of_day / 60 % 60,
+        time_of_day % 60
+    );
+    if nanos > 0 {
+        let frac = format!("{:09}", nanos);
+        text.push('.');
+        text.push_str(frac.trim_end_matches('0'));
+    }
+    text.push('Z');
+    Some(text)
+}
+
+// Splits the number at the start of `text` from the unit after it, returning the digits before
+// and after the decimal point, the unit and the rest of `text`.
+fn component(text: &str) -> Option<(&str, &str, &str, &str)> {
+    let digits = |text: &str| {
+        text.find(|ch: char| !ch.is_ascii_digit())
+            .unwrap_or(text.len())
+    };
+    let (int, rest) = text.split_at(digits(text));
+    let (frac, rest) = match rest.strip_prefix('.') {
+        Some(rest) => rest.split_at(digits(rest)),
+        None => ("", rest),
+    };
+    if int.is_empty() && frac.is_empty() {
+        return None;
+    }
+    let rest = rest.trim_start();
+    let len = rest
+        .find(|ch: char| !ch.is_ascii_alphabetic())
+        .unwrap_or(rest.len());
+    let (unit, rest) = rest.split_at(len);
+    Some((int, frac, unit, rest.trim_start()))
+}
+
+// The number `int.frac` times `scale`, rounded down, or None when it does not fit.
+fn scaled(int: &str, frac: &str, scale: u128) -> Option<u128> {
+    let whole = match int {
+        "" => 0,
+        _ => int.parse::<u128>().ok()?.checked_mul(scale)?,
+    };
+    if frac.is_empty() {
+        return Some(whole);
+    }
+    let digits = frac.len().min(24);
+    let frac = frac[..digits].parse::<u128>().ok()?;
+    let part = frac.checked_mul(scale)? / 10u128.pow(digits as u32);
+    whole.checked_add(part)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use de::from_str;
+    use error::{Error, ErrorCode};
+
+    fn custom_error<T: fmt::Debug>(res: ::error::Result<T>) -> String {
+        match res {
+            Err(Error::Syntax(ErrorCode::Custom(msg), ..)) => msg,
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn durations() {
+        let parse = |text: &str| from_str::<HumanDuration>(text).map(|d| d.0);
+        assert_eq!(parse("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse("1h30m").unwrap(), Duration::from_secs(5400));
+        assert_eq!(parse("1h 30m 15s").unwrap(), Duration::from_secs(5415));
+        assert_eq!(parse("1.5ms").unwrap(), Duration::from_micros(1500));
+        assert_eq!(parse("\"2d\"").unwrap(), Duration::from_secs(172_800));
+        assert_eq!(parse("250 us").unwrap(), Duration::from_micros(250));
+        assert_eq!(parse("0.000000001s").unwrap(), Duration::from_nanos(1));
+
+        let timeouts: Vec<HumanDuration> = from_str("[\n  10s\n  5m\n]").unwrap();
+        assert_eq!(
+            timeouts,
+            vec![
+                HumanDuration(Duration::from_secs(10)),
+                HumanDuration(Duration::from_secs(300))
+            ]
+        );
+
+        assert_eq!(
+            custom_error(parse("30xyz")),
+            "unknown unit `xyz` in duration `30xyz`"
+        );
+        assert_eq!(
+            custom_error(parse("\"30\"")),
+            "missing unit in duration `30`"
+        );
+        assert_eq!(custom_error(parse("\"s\"")), "invalid duration `s`");
+        assert_eq!(
+            custom_error(parse("99999999999999999999999d")),
+            "duration `99999999999999999999999d` is too long"
+        );
+        // a number is no duration
+        assert!(parse("30").is_err());
+    }
+
+    #[test]
+    fn sizes() {
+        let parse = |text: &str| from_str::<HumanSize>(text).map(|s| s.0);
+        assert_eq!(parse("512MiB").unwrap(), 512 << 20);
+        assert_eq!(parse("4096").unwrap(), 4096);
+        assert_eq!(parse("2 kB").unwrap(), 2000);
+        assert_eq!(parse("1.5KiB").unwrap(), 1536);
+        assert_eq!(parse("10B").unwrap(), 10);
+        assert_eq!(parse("3GB").unwrap(), 3_000_000_000);
+
+        assert_eq!(
+            custom_error(parse("30xyz")),
+            "unknown unit `xyz` in size `30xyz`"
+        );
+        assert_eq!(custom_error(parse("1MiB 2KiB")), "invalid size `1MiB 2KiB`");
+        assert_eq!(
+            custom_error(parse("20000PiB")),
+            "size `20000PiB` is too large"
+        );
+        assert!(parse("-1").is_err());
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn timestamps() {
+        use alloc::collections::BTreeMap;
+        use ser::to_string;
+
+        // quoteless, a root value with colons would be read as an object without braces
+        let parse = |text: &str| from_str::<Timestamp>(&format!("'{}'", text)).map(|t| t.0);
+        let at = |secs: u64, nanos: u32| UNIX_EPOCH + Duration::new(secs, nanos);
+        assert_eq!(parse("1970-01-01T00:00:00Z").unwrap(), UNIX_EPOCH);
+        assert_eq!(
+            parse("2024-02-29T12:30:00.25Z").unwrap(),
+            at(1_709_209_800, 250_000_000)
+        );
+        assert_eq!(
+            parse("2024-02-29 14:30:00+02:00").unwrap(),
+            at(1_709_209_800, 0)
+        );
+        assert_eq!(
+            parse("2024-02-29t07:00:00.0000000019-05:30").unwrap(),
+            at(1_709_209_800, 1)
+        );
+        assert_eq!(parse("1998-12-31T23:59:60Z").unwrap(), at(915_148_800, 0));
+        assert_eq!(
+            parse("1969-12-31T23:59:59.5Z").unwrap(),
+            UNIX_EPOCH - Duration::from_millis(500)
+        );
+
+        // written in UTC, quoteless
+        let mut config = BTreeMap::new();
+        config.insert("created", Timestamp(at(1_709_209_800, 250_000_000)));
+        config.insert("old", Timestamp(UNIX_EPOCH - Duration::from_millis(500)));
+        let text = to_string(&config).unwrap();
+        assert_eq!(
+            text,
+            "{\n  created: 2024-02-29T12:30:00.25Z\n  old: 1969-12-31T23:59:59.5Z\n}"
+        );
+        assert_eq!(
+            from_str::<BTreeMap<&str, Timestamp>>(&text).unwrap(),
+            config
+        );
+        assert!(to_string(&Timestamp(at(253_402_300_800, 0))).is_err());
+
+        let text = "{\n  created: 2023-02-29T00:00:00Z\n}";
+        match from_str::<BTreeMap<&str, Timestamp>>(text) {
+            Err(Error::Syntax(ErrorCode::Custom(msg), line, col, _)) => {
+                assert_eq!(msg, "created: invalid timestamp `2023-02-29T00:00:00Z`");
+                assert_eq!((line, col), (2, 12));
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+        for text in &[
+            "2024-01-01",
+            "2024-01-01T00:00:00",
+            "2024-13-01T00:00:00Z",
+            "2024-01-01T24:00:00Z",
+            "2024-01-01T00:00:00.Z",
+            "2024-01-01T00:00:00+0200",
+            "2024-01-01T00:00:00+24:00",
+            "24-01-01T00:00:00Z",
+        ] {
+            assert_eq!(
+                custom_error(parse(text)),
+                format!("invalid timestamp `{}`", text)
+            );
+        }
+    }
+}
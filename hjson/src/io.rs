@@ -0,0 +1,142 @@
+//! Hjson I/O
+//!
+//! The serializer writes to the `Write` trait of this module. With the `std` feature these are
+//! the types of `std::io`, without it a minimal replacement that is implemented for `Vec<u8>`.
+
+#[cfg(feature = "std")]
+pub use std::io::{Error, ErrorKind, Result, Write};
+
+#[cfg(not(feature = "std"))]
+pub use self::imp::{Error, ErrorKind, Result, Write};
+
+#[cfg(not(feature = "std"))]
+mod imp {
+    use alloc::vec::Vec;
+    use core::fmt;
+    use core::result;
+
+    /// The kind of an `Error`, a subset of `std::io::ErrorKind`.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum ErrorKind {
+        /// The writer did not accept any more bytes.
+        WriteZero,
+        /// Any other error.
+        Other,
+    }
+
+    /// An error reported by a `Write` implementation.
+    #[derive(Clone, Debug)]
+    pub struct Error {
+        kind: ErrorKind,
+        message: &'static str,
+    }
+
+    impl Error {
+        /// Creates an error from its kind and a message.
+        pub fn new(kind: ErrorKind, message: &'static str) -> Error {
+            Error { kind, message }
+        }
+
+        /// The kind of the error.
+        pub fn kind(&self) -> ErrorKind {
+            self.kind
+        }
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str(self.message)
+        }
+    }
+
+    /// The `Result` of a `Write` implementation.
+    pub type Result<T> = result::Result<T, Error>;
+
+    /// A sink for bytes, a subset of `std::io::Write`.
+    pub trait Write {
+        /// Writes some of `buf` and returns how many bytes were written.
+        fn write(&mut self, buf: &[u8]) -> Result<usize>;
+
+        /// Flushes buffered bytes to their destination.
+        fn flush(&mut self) -> Result<()>;
+
+        /// Writes all of `buf`.
+        fn write_all(&mut self, mut buf: &[u8]) -> Result<()> {
+            while !buf.is_empty() {
+                match self.write(buf)? {
+                    0 => {
+                        return Err(Error::new(
+                            ErrorKind::WriteZero,
+                            "failed to write whole buffer",
+                        ))
+                    }
+                    n => buf = &buf[n..],
+                }
+            }
+            Ok(())
+        }
+
+        /// Writes formatted text, used by `write!`.
+        fn write_fmt(&mut self, args: fmt::Arguments) -> Result<()> {
+            // keeps the error of the writer, `fmt::Error` does not carry one
+            struct Adapter<'a, W: ?Sized + 'a> {
+                inner: &'a mut W,
+                error: Option<Error>,
+            }
+
+            impl<'a, W: Write + ?Sized> fmt::Write for Adapter<'a, W> {
+                fn write_str(&mut self, s: &str) -> fmt::Result {
+                    self.inner.write_all(s.as_bytes()).map_err(|err| {
+                        self.error = Some(err);
+                        fmt::Error
+                    })
+                }
+            }
+
+            let mut adapter = Adapter {
+                inner: self,
+                error: None,
+            };
+            match fmt::write(&mut adapter, args) {
+                Ok(()) => Ok(()),
+                Err(_) => Err(adapter
+                    .error
+                    .unwrap_or_else(|| Error::new(ErrorKind::Other, "formatter error"))),
+            }
+        }
+    }
+
+    impl Write for Vec<u8> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            self.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+            self.extend_from_slice(buf);
+            Ok(())
+        }
+    }
+
+    impl<'a, W: Write + ?Sized> Write for &'a mut W {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            (**self).write(buf)
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            (**self).flush()
+        }
+
+        fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+            (**self).write_all(buf)
+        }
+
+        fn write_fmt(&mut self, args: fmt::Arguments) -> Result<()> {
+            (**self).write_fmt(args)
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! Conversions between `Value` and `serde_json::Value`, enabled by the `serde_json` feature.
+
+use serde_json;
+
+use super::value::{Map, Value};
+
+/// Converts a Hjson `Value` to a `serde_json::Value`.
+///
+/// JSON cannot represent `NaN` and the infinities, these floats become `Null` like when
+/// serde_json serializes them. With the `arbitrary_precision` feature a `Value::Number` keeps its
+/// literal as far as serde_json does.
+impl From<Value> for serde_json::Value {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Null => serde_json::Value::Null,
+            Value::Bool(v) => serde_json::Value::Bool(v),
+            Value::I64(v) => serde_json::Value::Number(v.into()),
+            Value::U64(v) => serde_json::Value::Number(v.into()),
+            Value::F64(v) => serde_json::Number::from_f64(v)
+                .map_or(serde_json::Value::Null, serde_json::Value::Number),
+            Value::Number(literal) => match literal.parse() {
+                Ok(number) => serde_json::Value::Number(number),
+                Err(_) => serde_json::Value::String(literal),
+            },
+            Value::String(v) => serde_json::Value::String(v),
+            Value::Array(v) => serde_json::Value::Array(v.into_iter().map(Into::into).collect()),
+            Value::Object(v) => serde_json::Value::Object(
+                v.into_iter()
+                    .map(|(key, value)| (key, value.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Converts a `serde_json::Value` to a Hjson `Value`. Numbers become `U64` or `I64` when they
+/// are integers that fit, like when they are parsed, and `F64` otherwise.
+impl From<serde_json::Value> for Value {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => Value::Null,
+            serde_json::Value::Bool(v) => Value::Bool(v),
+            serde_json::Value::Number(v) => {
+                if let Some(v) = v.as_u64() {
+                    Value::U64(v)
+                } else if let Some(v) = v.as_i64() {
+                    Value::I64(v)
+                } else {
+                    Value::F64(v.as_f64().unwrap_or(f64::NAN))
+                }
+            }
+            serde_json::Value::String(v) => Value::String(v),
+            serde_json::Value::Array(v) => Value::Array(v.into_iter().map(Into::into).collect()),
+            serde_json::Value::Object(v) => Value::Object(
+                v.into_iter()
+                    .map(|(key, value)| (key, value.into()))
+                    .collect::<Map<_, _>>(),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json;
+
+    use de::from_str;
+    use value::Value;
+
+    #[test]
+    fn round_trip() {
+        let text =
+            "{\n  name: quoteless\n  nested: {empty: {}, list: [1, -2, 2.5, true, null, \"x\"]}\n}";
+        let value: Value = from_str(text).unwrap();
+
+        let json: serde_json::Value = value.clone().into();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "name": "quoteless",
+                "nested": {"empty": {}, "list": [1, -2, 2.5, true, null, "x"]}
+            })
+        );
+        // serde_json sorts the members unless its `preserve_order` feature is enabled, the
+        // input is sorted already
+        assert_eq!(Value::from(json), value);
+
+        let json: serde_json::Value = Value::F64(f64::NAN).into();
+        assert_eq!(json, serde_json::Value::Null);
+    }
+
+    #[test]
+    fn deserialize_json_value() {
+        // serde_json values get plain numbers, also with the `arbitrary_precision` feature
+        let json: serde_json::Value = from_str("{a: 1e3, b: [2.50, 3]}").unwrap();
+        assert_eq!(json, serde_json::json!({"a": 1000.0, "b": [2.5, 3]}));
+    }
+}
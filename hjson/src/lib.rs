@@ -16,6 +16,23 @@
 //! * `Object`: equivalent to rust's `serde_hjson::Map<String, serde_hjson::Value>`
 //! * `Null`
 //!
+//! With the default `preserve_order` feature objects keep their keys in insertion order, so
+//! parsing and serializing a document does not reorder its members. Without the feature keys are
+//! sorted.
+//!
+//! With the `arbitrary_precision` feature a `Value` keeps the literal of numbers that do not fit
+//! into a `u64`, `i64` or `f64` (or that would be written differently after parsing, like `1e3`)
+//! in `Value::Number`, so they are written back exactly as they were read. Other types, including
+//! those that deserialize any value like untagged enums, get the parsed numbers as before.
+//!
+//! The default `std` feature can be disabled for `no_std` targets that have `alloc`. `Value`, the
+//! builders and serializing to and parsing from strings and byte slices are still available, while
+//! the APIs that work with `std::io` readers and writers (`from_reader`, `to_writer`, the `events`
+//! module) require it. `preserve_order` depends on `std`. Without it the serializer writes to the
+//! `io::Write` trait of this crate, which is implemented for `Vec<u8>`.
+//!
+//! The `serde_json` feature adds conversions between `Value` and `serde_json::Value` (with
+//! `From`), for code that works with serde_json's type.
 //!
 //! # Examples of use
 //!
@@ -50,27 +67,71 @@
 //! ```
 
 #![deny(missing_docs)]
+// the tests use std also without the `std` feature
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
-#[macro_use]
-extern crate lazy_static;
-
+#[cfg_attr(not(feature = "std"), macro_use)]
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
 extern crate core;
 #[cfg(feature = "preserve_order")]
 extern crate linked_hash_map;
 extern crate num_traits;
-extern crate regex;
 extern crate serde;
+#[cfg(feature = "serde_json")]
+extern crate serde_json;
 
+pub use self::comments::Comments;
+#[cfg(feature = "std")]
+pub use self::de::{from_iter, from_reader, validate_reader};
 pub use self::de::{
-    from_iter, from_reader, from_slice, from_str, Deserializer, StreamDeserializer,
+    from_slice, from_str, from_str_recovering, from_str_strict, from_str_with_comments,
+    from_str_with_styles, validate, ArrayDeserializer, Deserializer, EscapePolicy,
+    StreamDeserializer,
 };
 pub use self::error::{Error, ErrorCode, Result};
-pub use self::ser::{to_string, to_vec, to_writer, Serializer};
+#[cfg(feature = "std")]
+pub use self::events::{parse_events, Event, EventHandler};
+pub use self::format::format_str;
+pub use self::raw::RawValue;
+pub use self::ser::{
+    hjson_to_json, hjson_to_json_pretty, to_string, to_string_canonical, to_string_pretty,
+    to_string_pretty_with_indent, to_string_sorted, to_string_with_comments, to_vec, to_vec_pretty,
+    Newline, NonFiniteFloats, Serializer,
+};
+#[cfg(feature = "std")]
+pub use self::ser::{to_writer, to_writer_pretty, to_writer_pretty_with_indent};
+pub use self::spanned::{from_str_spanned, Spanned, SpannedValue};
+pub use self::styles::{StringStyle, StringStyles};
+pub use self::tokenizer::{Token, TokenKind, Tokenizer};
 pub use self::value::{from_value, to_value, Map, Value};
 
+#[macro_use]
+mod macros;
+
 pub mod builder;
+pub mod comments;
+mod content;
 pub mod de;
+pub mod diff;
 pub mod error;
+#[cfg(feature = "std")]
+pub mod events;
+pub mod format;
+pub mod helpers;
+pub mod io;
+#[cfg(feature = "serde_json")]
+mod json;
+pub mod raw;
 pub mod ser;
+pub mod spanned;
+pub mod styles;
+pub mod tokenizer;
 mod util;
 pub mod value;
+
+// used by `hjson!`, which cannot rely on `vec!` being in scope without std
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::vec;
+}
@@ -0,0 +1,169 @@
+/// Constructs a `Value` from a JSON-like literal.
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate serde_hjson;
+///
+/// fn main() {
+///     let port = 8080;
+///     let value = hjson!({
+///         "name": "x",
+///         "ports": [80, 443, port],
+///         "enabled": true,
+///         "parent": null,
+///     });
+///     assert_eq!(value["ports"][2].as_u64(), Some(8080));
+/// }
+/// ```
+///
+/// Any Rust expression can be interpolated as a value, as long as it implements `Serialize`.
+/// Keys are string literals or expressions that convert into a `String`; a key that is a
+/// variable needs to be wrapped in parentheses. Trailing commas are allowed.
+#[macro_export]
+macro_rules! hjson {
+    // Builds the elements of an array: the elements so far are kept in brackets, followed by the
+    // tokens that are still to be parsed.
+    (@array [$($elems:expr,)*]) => {
+        $crate::__private::vec![$($elems,)*]
+    };
+    (@array [$($elems:expr),*]) => {
+        $crate::__private::vec![$($elems),*]
+    };
+    (@array [$($elems:expr,)*] null $($rest:tt)*) => {
+        $crate::hjson!(@array [$($elems,)* $crate::hjson!(null)] $($rest)*)
+    };
+    (@array [$($elems:expr,)*] true $($rest:tt)*) => {
+        $crate::hjson!(@array [$($elems,)* $crate::hjson!(true)] $($rest)*)
+    };
+    (@array [$($elems:expr,)*] false $($rest:tt)*) => {
+        $crate::hjson!(@array [$($elems,)* $crate::hjson!(false)] $($rest)*)
+    };
+    (@array [$($elems:expr,)*] [$($array:tt)*] $($rest:tt)*) => {
+        $crate::hjson!(@array [$($elems,)* $crate::hjson!([$($array)*])] $($rest)*)
+    };
+    (@array [$($elems:expr,)*] {$($map:tt)*} $($rest:tt)*) => {
+        $crate::hjson!(@array [$($elems,)* $crate::hjson!({$($map)*})] $($rest)*)
+    };
+    (@array [$($elems:expr,)*] $next:expr, $($rest:tt)*) => {
+        $crate::hjson!(@array [$($elems,)* $crate::hjson!($next),] $($rest)*)
+    };
+    (@array [$($elems:expr,)*] $last:expr) => {
+        $crate::hjson!(@array [$($elems,)* $crate::hjson!($last)])
+    };
+    (@array [$($elems:expr),*] , $($rest:tt)*) => {
+        $crate::hjson!(@array [$($elems,)*] $($rest)*)
+    };
+
+    // Inserts the members of an object into `$object`: first the key tokens are collected in
+    // parentheses, then the value is parsed and the entry inserted.
+    (@object $object:ident () ()) => {};
+    (@object $object:ident [$($key:tt)+] ($value:expr) , $($rest:tt)*) => {
+        let _ = $object.insert(($($key)+).into(), $value);
+        $crate::hjson!(@object $object () ($($rest)*));
+    };
+    (@object $object:ident [$($key:tt)+] ($value:expr)) => {
+        let _ = $object.insert(($($key)+).into(), $value);
+    };
+    (@object $object:ident ($($key:tt)+) (: null $($rest:tt)*)) => {
+        $crate::hjson!(@object $object [$($key)+] ($crate::hjson!(null)) $($rest)*);
+    };
+    (@object $object:ident ($($key:tt)+) (: true $($rest:tt)*)) => {
+        $crate::hjson!(@object $object [$($key)+] ($crate::hjson!(true)) $($rest)*);
+    };
+    (@object $object:ident ($($key:tt)+) (: false $($rest:tt)*)) => {
+        $crate::hjson!(@object $object [$($key)+] ($crate::hjson!(false)) $($rest)*);
+    };
+    (@object $object:ident ($($key:tt)+) (: [$($array:tt)*] $($rest:tt)*)) => {
+        $crate::hjson!(@object $object [$($key)+] ($crate::hjson!([$($array)*])) $($rest)*);
+    };
+    (@object $object:ident ($($key:tt)+) (: {$($map:tt)*} $($rest:tt)*)) => {
+        $crate::hjson!(@object $object [$($key)+] ($crate::hjson!({$($map)*})) $($rest)*);
+    };
+    (@object $object:ident ($($key:tt)+) (: $value:expr , $($rest:tt)*)) => {
+        $crate::hjson!(@object $object [$($key)+] ($crate::hjson!($value)) , $($rest)*);
+    };
+    (@object $object:ident ($($key:tt)+) (: $value:expr)) => {
+        $crate::hjson!(@object $object [$($key)+] ($crate::hjson!($value)));
+    };
+    (@object $object:ident ($($key:tt)*) ($tt:tt $($rest:tt)*)) => {
+        $crate::hjson!(@object $object ($($key)* $tt) ($($rest)*));
+    };
+
+    (null) => {
+        $crate::Value::Null
+    };
+    (true) => {
+        $crate::Value::Bool(true)
+    };
+    (false) => {
+        $crate::Value::Bool(false)
+    };
+    ([]) => {
+        $crate::Value::Array($crate::__private::vec![])
+    };
+    ([ $($tt:tt)+ ]) => {
+        $crate::Value::Array($crate::hjson!(@array [] $($tt)+))
+    };
+    ({}) => {
+        $crate::Value::Object($crate::Map::new())
+    };
+    ({ $($tt:tt)+ }) => {
+        $crate::Value::Object({
+            let mut object = $crate::Map::new();
+            $crate::hjson!(@object object () ($($tt)+));
+            object
+        })
+    };
+    ($other:expr) => {
+        $crate::to_value(&$other).expect("failed to serialize")
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use builder::{ArrayBuilder, ObjectBuilder};
+    use Value;
+
+    #[test]
+    fn literals() {
+        assert_eq!(hjson!(null), Value::Null);
+        assert_eq!(hjson!(true), Value::Bool(true));
+        assert_eq!(hjson!(false), Value::Bool(false));
+        assert_eq!(hjson!(-3), Value::I64(-3));
+        assert_eq!(hjson!(1.5), Value::F64(1.5));
+        assert_eq!(hjson!("x"), Value::String("x".to_string()));
+        assert_eq!(hjson!([]), ArrayBuilder::new().unwrap());
+        assert_eq!(hjson!({}), ObjectBuilder::new().unwrap());
+    }
+
+    #[test]
+    fn nested() {
+        let port = 8080u16;
+        let key = "dynamic";
+        let value = hjson!({
+            "name": "x",
+            "ports": [80, 443, port,],
+            "enabled": true,
+            "nothing": null,
+            "nested": { "list": [[], {}, [false, null]], "sum": 1 + 2 },
+            (key): format!("{}-{}", "a", "b"),
+        });
+
+        let expected = ObjectBuilder::new()
+            .insert("name", "x")
+            .insert_array("ports", |b| b.push(80).push(443).push(8080))
+            .insert("enabled", true)
+            .insert("nothing", ())
+            .insert_object("nested", |b| {
+                b.insert_array("list", |b| {
+                    b.push_array(|b| b)
+                        .push_object(|b| b)
+                        .push_array(|b| b.push(false).push(()))
+                })
+                .insert("sum", 3)
+            })
+            .insert("dynamic", "a-b")
+            .unwrap();
+        assert_eq!(value, expected);
+    }
+}
@@ -0,0 +1,211 @@
+//! Hjson Raw Values
+//!
+//! This module provides `RawValue`, which holds the text of a Hjson value, so that it can be
+//! written into a document or taken out of one without being parsed into a `Value`.
+
+use alloc::string::String;
+use core::fmt;
+use core::result;
+
+use serde::{de, ser};
+
+use super::de::Deserializer;
+use super::error::Result;
+
+// Newtype name used to pass the text of a `RawValue` through serde.
+pub(crate) const RAW_TOKEN: &str = "$serde_hjson::private::RawValue";
+
+/// The text of a Hjson value, which is serialized as it is and deserialized from the text of the
+/// value in the input, including its quotes, brackets and comments inside.
+///
+/// Only the Hjson serializer and deserializer of this crate keep the text. `to_value` parses it,
+/// and a `RawValue` deserialized from a `Value` gets the text `to_string` writes for it. The text
+/// is written verbatim at the position of the value, the lines of a multiline value are not
+/// indented to match the rest of the document.
+///
+/// Output that quotes every string, like that of `Serializer::compact`, `to_string_canonical` or
+/// `Serializer::force_quote_strings`, gets the parsed value instead, so it stays valid; the
+/// comments and layout of the text are lost there. `Serializer::compact_width` only puts an
+/// array or object holding a `RawValue` on one line if its text is written that way already.
+///
+/// ```rust
+/// use serde_hjson::{Map, RawValue};
+///
+/// let mut config = Map::new();
+/// let ports = RawValue::from_string("[80, 443] # defaults".to_string()).unwrap();
+/// config.insert("ports".to_string(), ports);
+/// let text = serde_hjson::to_string(&config).unwrap();
+/// assert_eq!(text, "{\n  ports: [80, 443] # defaults\n}");
+///
+/// let config: Map<String, RawValue> = serde_hjson::from_str(&text).unwrap();
+/// assert_eq!(config["ports"].get(), "[80, 443]");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RawValue {
+    text: String,
+}
+
+impl RawValue {
+    /// Creates a `RawValue` from the text of a Hjson value, which has to be valid on its own:
+    /// a single value, not an object without braces. Whitespace around the value is dropped.
+    pub fn from_string(text: String) -> Result<RawValue> {
+        {
+            let mut de = Deserializer::from_str(&text);
+            let _: de::IgnoredAny = de::Deserialize::deserialize(&mut de)?;
+            de.end()?;
+        }
+        let trimmed = text.trim();
+        let text = if trimmed.len() == text.len() {
+            text
+        } else {
+            String::from(trimmed)
+        };
+        Ok(RawValue { text })
+    }
+
+    /// Returns the text of the value.
+    pub fn get(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the text of the value as a `String`.
+    pub fn into_string(self) -> String {
+        self.text
+    }
+}
+
+impl fmt::Display for RawValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl ser::Serialize for RawValue {
+    fn serialize<S>(&self, serializer: S) -> result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.serialize_newtype_struct(RAW_TOKEN, &self.text)
+    }
+}
+
+impl<'de> de::Deserialize<'de> for RawValue {
+    fn deserialize<D>(deserializer: D) -> result::Result<RawValue, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(RAW_TOKEN, RawValueVisitor)
+    }
+}
+
+struct RawValueVisitor;
+
+impl<'de> de::Visitor<'de> for RawValueVisitor {
+    type Value = RawValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any valid Hjson value")
+    }
+
+    // the deserializers of this crate hand over the text as a newtype
+    fn visit_newtype_struct<D>(self, deserializer: D) -> result::Result<RawValue, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let text: String = de::Deserialize::deserialize(deserializer)?;
+        Ok(RawValue { text })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec::Vec;
+    use de::from_str;
+    use error::{Error, ErrorCode};
+    use ser::to_string;
+    use value::{from_value, to_value, Map, Value};
+
+    #[test]
+    fn serialize() {
+        let mut map = Map::new();
+        let raw = RawValue::from_string(" {a: 1, b: '''x'''} \n".to_string()).unwrap();
+        assert_eq!(raw.get(), "{a: 1, b: '''x'''}");
+        map.insert("raw".to_string(), raw.clone());
+        assert_eq!(to_string(&map).unwrap(), "{\n  raw: {a: 1, b: '''x'''}\n}");
+        assert_eq!(
+            to_string(&vec![raw.clone()]).unwrap(),
+            "[\n  {a: 1, b: '''x'''}\n]"
+        );
+
+        // to_value parses the text
+        let value: Value = from_str("{a: 1, b: x\n}").unwrap();
+        assert_eq!(to_value(&raw).unwrap(), value);
+
+        for text in ["{a: 1", "{a: 1}}", "", "[1] 2"].iter() {
+            assert!(RawValue::from_string(text.to_string()).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn serialize_quoted() {
+        use ser::{to_string_canonical, Serializer};
+
+        // quoteless strings and comments would break output that only has quoted strings
+        let list = vec![raw("x # c"), raw("{b: 1 # c\na: [true, '''y''']}")];
+        assert_eq!(
+            to_string_canonical(&list).unwrap(),
+            "[\"x # c\",{\"a\":[true,\"y\"],\"b\":1}]"
+        );
+        let mut out = Vec::new();
+        serde::Serialize::serialize(&list, &mut Serializer::compact(&mut out)).unwrap();
+        let compact = String::from_utf8(out).unwrap();
+        assert!(compact.starts_with("[\"x # c\",{"), "{}", compact);
+        assert_eq!(
+            from_str::<Value>(&compact).unwrap(),
+            to_value(&list).unwrap()
+        );
+
+        let mut out = Vec::new();
+        let mut ser = Serializer::new(&mut out).force_quote_strings(true);
+        serde::Serialize::serialize(&raw("x # c"), &mut ser).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\"x # c\"");
+    }
+
+    #[test]
+    fn deserialize() {
+        let text = "{\n  a: { x: [1,2] }   // c\n  b: quoteless text  \n  c: \"q\\\"\" , d: 1.50\n  e:\n    '''\n    ml\n    '''\n}";
+        let map: Map<String, RawValue> = from_str(text).unwrap();
+        let texts: Vec<(&str, &str)> = map.iter().map(|(k, v)| (&k[..], v.get())).collect();
+        let mut expected = vec![
+            ("a", "{ x: [1,2] }"),
+            ("b", "quoteless text"),
+            ("c", "\"q\\\"\""),
+            ("d", "1.50"),
+            ("e", "'''\n    ml\n    '''"),
+        ];
+        expected.sort_by_key(|&(key, _)| key);
+        assert_eq!(texts, expected);
+
+        let list: Vec<RawValue> = from_str("  [ true, {} ]").unwrap();
+        assert_eq!(list, vec![raw("true"), raw("{}")]);
+        let root: RawValue = from_str("a: 1\nb: 2\n").unwrap();
+        assert_eq!(root.get(), "a: 1\nb: 2");
+
+        // a value is written back as Hjson
+        let value: Value = from_str("{a: [1, 2]}").unwrap();
+        let written: RawValue = from_value(value).unwrap();
+        assert_eq!(written.get(), "{\n  a:\n  [\n    1\n    2\n  ]\n}");
+
+        // the text is only available when the input is
+        let mut de = Deserializer::new("[1]".bytes());
+        match <RawValue as de::Deserialize>::deserialize(&mut de) {
+            Err(Error::Syntax(ErrorCode::Custom(_), ..)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    fn raw(text: &str) -> RawValue {
+        RawValue::from_string(text.to_string()).unwrap()
+    }
+}
@@ -2,21 +2,79 @@
 //!
 //! This module provides for Hjson serialization with the type `Serializer`.
 
-use std::fmt::{Display, LowerExp};
-use std::io;
-use std::num::FpCategory;
-
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{Display, LowerExp};
+use core::num::FpCategory;
+use core::str;
+
+use super::comments::{self, Comments};
+use super::content::{self, Content};
+use super::de::from_str;
 use super::error::{Error, ErrorCode, Result};
+use super::io;
+use super::raw;
+use super::styles::{StringStyle, StringStyles};
 use serde::ser;
 
 use super::util::ParseNumber;
+use super::value::{self, Value};
+
+/// How the serializer writes floats that are NaN or infinite.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NonFiniteFloats {
+    /// Write `null` (the default), like for JSON.
+    Null,
+    /// Write the quoteless tokens `NaN`, `Infinity` and `-Infinity`. They are read back as floats
+    /// by `Deserializer::allow_non_finite`, but the output is no longer valid strict JSON.
+    Token,
+    /// Fail with `ErrorCode::FloatMustBeFinite`.
+    Error,
+}
+
+/// The line separator written by the pretty serializer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Newline {
+    /// `\n` (the default).
+    Lf,
+    /// `\r\n`, as usual on Windows.
+    Crlf,
+}
 
-use regex::Regex;
+impl Newline {
+    fn as_bytes(self) -> &'static [u8] {
+        match self {
+            Newline::Lf => b"\n",
+            Newline::Crlf => b"\r\n",
+        }
+    }
+}
 
 /// A structure for serializing Rust values into Hjson.
 pub struct Serializer<W, F> {
     writer: W,
     formatter: F,
+    sort_keys: bool,
+    skip_none: bool,
+    align_colons: bool,
+    quoteless_keys: bool,
+    quoteless_values: bool,
+    multiline: bool,
+    force_quote_keys: bool,
+    force_quote_strings: bool,
+    ascii_only: bool,
+    trailing_newline: bool,
+    line_end: Newline,
+    // arrays and objects up to this width are written on one line, 0 when disabled
+    compact_width: usize,
+    // number of arrays and objects that are currently being written
+    depth: usize,
+    non_finite: NonFiniteFloats,
+    float_precision: Option<usize>,
+    // the pointers of the values being written, only tracked when there are comments or styles
+    comments: Option<Comments>,
+    styles: Option<StringStyles>,
+    path: Vec<String>,
 }
 
 impl<'a, W> Serializer<W, HjsonFormatter<'a>>
@@ -28,6 +86,68 @@ where
     pub fn new(writer: W) -> Self {
         Serializer::with_formatter(writer, HjsonFormatter::new())
     }
+
+    /// Creates a new Hjson serializer that uses `indent` for each level of indentation. The
+    /// indent should only consist of spaces and tabs.
+    #[inline]
+    pub fn with_indent(writer: W, indent: &'a [u8]) -> Self {
+        Serializer::with_formatter(writer, HjsonFormatter::with_indent(indent))
+    }
+
+    /// Choose the line separator, see `Newline`. It is used between the lines of the output,
+    /// including those of multiline strings, and for the `trailing_newline`.
+    #[inline]
+    pub fn newline(mut self, newline: Newline) -> Self {
+        self.formatter.line_end = newline.as_bytes();
+        self.line_end = newline;
+        self
+    }
+
+    /// Always quote keys, whatever `quoteless` and `quoteless_keys` are set to. Together with
+    /// `force_quote_strings` the output is strict JSON, as long as there are no `comments` and
+    /// non-finite floats are written as `null`: the commas that Hjson leaves out are written as
+    /// well.
+    #[inline]
+    pub fn force_quote_keys(mut self, force: bool) -> Self {
+        self.force_quote_keys = force;
+        self.formatter.commas = self.force_quote_keys && self.force_quote_strings;
+        self
+    }
+
+    /// Always write strings in double quotes, neither quoteless nor in the ''' form, whatever
+    /// `quoteless`, `quoteless_values` and `multiline_strings` are set to. See also
+    /// `force_quote_keys`.
+    #[inline]
+    pub fn force_quote_strings(mut self, force: bool) -> Self {
+        self.force_quote_strings = force;
+        self.formatter.commas = self.force_quote_keys && self.force_quote_strings;
+        self
+    }
+
+    /// Pad the members of every object so that their values start at the same column, like
+    /// `name:  x` next to `count: 1`. Values written on the lines after their key, like arrays
+    /// and objects, are not padded, and nested objects are aligned on their own. This is off by
+    /// default; when on, every object is held in memory until its end to measure its keys.
+    #[inline]
+    pub fn align_colons(mut self, align: bool) -> Self {
+        self.align_colons = align;
+        self
+    }
+}
+
+impl<W> Serializer<W, CompactFormatter>
+where
+    W: io::Write,
+{
+    /// Creates a Hjson serializer that writes everything on one line, with every key and string
+    /// quoted since quoteless strings extend to the end of the line. The output is valid JSON
+    /// unless `non_finite` floats are written as tokens.
+    #[inline]
+    pub fn compact(writer: W) -> Self {
+        Serializer::with_formatter(writer, CompactFormatter)
+            .quoteless(false)
+            .multiline_strings(false)
+    }
 }
 
 impl<W, F> Serializer<W, F>
@@ -42,14 +162,381 @@ where
         Serializer {
             writer: writer,
             formatter: formatter,
+            sort_keys: false,
+            skip_none: false,
+            align_colons: false,
+            quoteless_keys: true,
+            quoteless_values: true,
+            multiline: true,
+            force_quote_keys: false,
+            force_quote_strings: false,
+            ascii_only: false,
+            trailing_newline: false,
+            line_end: Newline::Lf,
+            compact_width: 0,
+            depth: 0,
+            non_finite: NonFiniteFloats::Null,
+            float_precision: None,
+            comments: None,
+            styles: None,
+            path: Vec::new(),
         }
     }
 
+    /// Write `comments` next to the object members and array elements they are attached to,
+    /// usually after they were collected with `de::from_str_with_comments`.
+    #[inline]
+    pub fn comments(mut self, comments: Comments) -> Self {
+        self.comments = Some(comments);
+        self
+    }
+
+    /// Write the strings that have a style in `styles` that way, usually after they were
+    /// collected with `de::from_str_with_styles`. A style is only used where it can represent
+    /// the string: a quoteless string that would not be read back as the same string is quoted,
+    /// and so is a multiline one that needs escapes. `force_quote_strings` still quotes every
+    /// string.
+    #[inline]
+    pub fn string_styles(mut self, styles: StringStyles) -> Self {
+        self.styles = Some(styles);
+        self
+    }
+
+    /// Write strings and keys without quotes where this is unambiguous (the default). When
+    /// disabled every key and string is quoted; strings that span multiple lines may still be
+    /// written in the ''' form. This sets both `quoteless_keys` and `quoteless_values`.
+    #[inline]
+    pub fn quoteless(mut self, quoteless: bool) -> Self {
+        self.quoteless_keys = quoteless;
+        self.quoteless_values = quoteless;
+        self
+    }
+
+    /// Write keys without quotes where this is unambiguous (the default), like `quoteless` but
+    /// leaving strings alone.
+    #[inline]
+    pub fn quoteless_keys(mut self, quoteless: bool) -> Self {
+        self.quoteless_keys = quoteless;
+        self
+    }
+
+    /// Write strings without quotes where this is unambiguous (the default), like `quoteless`
+    /// but leaving keys alone.
+    #[inline]
+    pub fn quoteless_values(mut self, quoteless: bool) -> Self {
+        self.quoteless_values = quoteless;
+        self
+    }
+
+    /// Write strings that contain newlines in the ''' form (the default). When disabled they are
+    /// quoted and the line breaks escaped.
+    #[inline]
+    pub fn multiline_strings(mut self, multiline: bool) -> Self {
+        self.multiline = multiline;
+        self
+    }
+
+    /// Write every character above U+007F as a `\uXXXX` escape (a surrogate pair for those
+    /// outside the Basic Multilingual Plane), so the output is pure ASCII. Keys and strings that
+    /// contain such characters are then always quoted. Off by default.
+    #[inline]
+    pub fn ascii_only(mut self, ascii_only: bool) -> Self {
+        self.ascii_only = ascii_only;
+        self
+    }
+
+    /// End the output with a newline, as expected for text files. Off by default.
+    #[inline]
+    pub fn trailing_newline(mut self, trailing_newline: bool) -> Self {
+        self.trailing_newline = trailing_newline;
+        self
+    }
+
+    /// Choose how NaN and infinite floats are written, see `NonFiniteFloats`.
+    #[inline]
+    pub fn non_finite(mut self, policy: NonFiniteFloats) -> Self {
+        self.non_finite = policy;
+        self
+    }
+
+    /// Write floats rounded to `precision` significant digits (at least one), so `Some(3)` writes
+    /// `0.1 + 0.2` as `0.3`. With `None` (the default) floats are written in the shortest form
+    /// that reads back to the same value, like `0.30000000000000004`.
+    #[inline]
+    pub fn float_precision(mut self, precision: Option<usize>) -> Self {
+        self.float_precision = precision;
+        self
+    }
+
+    /// Sort the keys of every object (byte-wise) before writing it, instead of keeping the
+    /// order in which they are serialized.
+    #[inline]
+    pub fn sort_keys(mut self, sort: bool) -> Self {
+        self.sort_keys = sort;
+        self
+    }
+
+    /// Leave out the members of maps and structs whose value is `None`, instead of writing them
+    /// as `null`.
+    #[inline]
+    pub fn skip_none(mut self, skip: bool) -> Self {
+        self.skip_none = skip;
+        self
+    }
+
+    /// Write an array or object on a single line, like `[1, 2, 3]` or `{x: 1, y: 2}`, when that
+    /// takes at most `width` characters; nested arrays and objects that fit are inlined too.
+    /// Strings on such a line are always quoted. 0 (the default) disables this, and it is also
+    /// disabled when writing `comments` or `string_styles`. Otherwise every array and object is
+    /// written as it is serialized, while this holds them in memory until it is known whether
+    /// they fit.
+    #[inline]
+    pub fn compact_width(mut self, width: usize) -> Self {
+        self.compact_width = width;
+        self
+    }
+
     /// Unwrap the `Writer` from the `Serializer`.
     #[inline]
     pub fn into_inner(self) -> W {
         self.writer
     }
+
+    // Writes the key of an object member, preceded by its leading comments. Every call has to be
+    // matched by a call to `end_member` once the value has been written.
+    fn begin_member(&mut self, key: &str) -> Result<()> {
+        self.begin_comments(key)?;
+        self.write_key(key)
+    }
+
+    // Writes the leading comments of the member or element `token` of the current value, and
+    // makes it the current value until `end_member`.
+    fn begin_comments(&mut self, token: &str) -> Result<()> {
+        if !self.tracks_path() {
+            return Ok(());
+        }
+        let pointer = comments::pointer_push(self.current_pointer(), token);
+        if let Some(member) = self.comments.as_ref().and_then(|c| c.get(&pointer)) {
+            for comment in &member.before {
+                self.writer.write_all(comment.as_bytes())?;
+                self.formatter.newline(&mut self.writer, 0)?;
+            }
+        }
+        self.path.push(pointer);
+        Ok(())
+    }
+
+    fn end_member(&mut self) -> Result<()> {
+        if !self.tracks_path() {
+            return Ok(());
+        }
+        let pointer = self
+            .path
+            .pop()
+            .expect("end_member called without begin_member");
+        if let Some(ref comments) = self.comments {
+            if let Some(after) = comments.get(&pointer).and_then(|m| m.after.as_ref()) {
+                self.writer.write_all(b" ")?;
+                self.writer.write_all(after.as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    // Whether the pointers of the values that are being written are kept in `path`.
+    fn tracks_path(&self) -> bool {
+        self.comments.is_some() || self.styles.is_some()
+    }
+
+    fn current_pointer(&self) -> &str {
+        self.path.last().map_or("", |p| &p[..])
+    }
+
+    // Writes a string that has a style in `styles`. Returns false, without writing anything, when
+    // the string has none or that style cannot represent it.
+    fn write_styled_str(&mut self, value: &str) -> Result<bool> {
+        let style = match self
+            .styles
+            .as_ref()
+            .and_then(|s| s.get(self.current_pointer()))
+        {
+            Some(style) if !self.force_quote_strings => style,
+            _ => return Ok(false),
+        };
+        let escape_non_ascii = self.ascii_only && !value.is_ascii();
+        match style {
+            StringStyle::Quoteless => quote_str_with(
+                &mut self.writer,
+                &mut self.formatter,
+                value,
+                true,
+                self.multiline,
+                self.ascii_only,
+            )?,
+            StringStyle::Quoted => {
+                self.formatter.start_value(&mut self.writer)?;
+                escape_str(&mut self.writer, value, escape_non_ascii)?;
+            }
+            StringStyle::SingleQuoted => {
+                self.formatter.start_value(&mut self.writer)?;
+                escape_single_quoted(&mut self.writer, value, escape_non_ascii)?;
+            }
+            // the first line of a multiline string loses its leading whitespace, and a quote at
+            // the end would run into the closing ones
+            StringStyle::Multiline
+                if !escape_non_ascii
+                    && !needs_escape_ml(value)
+                    && !value.starts_with(char::is_whitespace)
+                    && !value.ends_with('\'') =>
+            {
+                ml_str(&mut self.writer, &mut self.formatter, value)?
+            }
+            StringStyle::Multiline => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    // Called after every value that was written, writes the trailing newline after the root
+    // value.
+    fn end_value(&mut self) -> Result<()> {
+        if self.depth == 0 && self.trailing_newline {
+            self.writer.write_all(self.line_end.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    // Writes a NaN or infinite float according to the `non_finite` policy.
+    fn write_non_finite(&mut self, value: f64) -> Result<()> {
+        let token: &[u8] = match self.non_finite {
+            NonFiniteFloats::Null => b"null",
+            NonFiniteFloats::Token if value.is_nan() => b"NaN",
+            NonFiniteFloats::Token if value > 0.0 => b"Infinity",
+            NonFiniteFloats::Token => b"-Infinity",
+            NonFiniteFloats::Error => {
+                return Err(Error::Syntax(ErrorCode::FloatMustBeFinite, 0, 0, 0));
+            }
+        };
+        self.writer.write_all(token).map_err(From::from)
+    }
+
+    fn write_key(&mut self, key: &str) -> Result<()> {
+        let quoteless = self.unquoted_keys();
+        escape_key_with(&mut self.writer, key, quoteless, self.ascii_only)
+    }
+
+    // The number of characters `write_key` writes for `key`.
+    fn key_width(&self, key: &str) -> Result<usize> {
+        let mut out = Vec::new();
+        escape_key_with(&mut out, key, self.unquoted_keys(), self.ascii_only)?;
+        Ok(String::from_utf8_lossy(&out).chars().count())
+    }
+
+    fn unquoted_keys(&self) -> bool {
+        self.quoteless_keys && !self.force_quote_keys
+    }
+
+    // Whether arrays and objects are held back until their end, to find out if they fit on one
+    // line.
+    fn defers_collections(&self) -> bool {
+        self.compact_width > 0 && !self.tracks_path()
+    }
+
+    // Writes `line`, a collection rendered by `inline_items` or `inline_entries`, if it is at most
+    // `compact_width` characters long. Returns whether it did.
+    fn write_inline(&mut self, line: &[u8]) -> Result<bool> {
+        let width = str::from_utf8(line).map_or(usize::MAX, |s| s.chars().count());
+        if width > self.compact_width {
+            return Ok(false);
+        }
+        self.formatter.start_value(&mut self.writer)?;
+        self.writer.write_all(line)?;
+        Ok(true)
+    }
+
+    // Renders `value` on one line, like `[1, 2]` or `{x: 1, y: 2}`. Returns false when it cannot
+    // be, because it holds a `RawValue` whose text would be changed.
+    fn inline_value(&self, out: &mut Vec<u8>, value: &Content) -> Result<bool> {
+        match *value {
+            Content::Seq(_, ref items) => self.inline_items(out, items),
+            Content::Map(_, ref entries) => {
+                let entries = entries.iter().map(|(key, value)| (key.clone(), value));
+                self.inline_entries(out, entries.collect())
+            }
+            Content::Some(ref value) => self.inline_value(out, value),
+            Content::NewtypeStruct(name, ref value) if !is_verbatim_token(name) => {
+                self.inline_value(out, value)
+            }
+            // the text of a `RawValue` is only kept if it is already written like the rest of
+            // the line
+            Content::NewtypeStruct(name, ref text) if name == raw::RAW_TOKEN => match **text {
+                Content::Str(ref text) => {
+                    let parsed = content::to_content(&from_str::<Value>(text)?)?;
+                    let mut line = Vec::new();
+                    if !self.inline_value(&mut line, &parsed)? || line != text.as_bytes() {
+                        return Ok(false);
+                    }
+                    out.extend_from_slice(&line);
+                    Ok(true)
+                }
+                _ => Ok(false),
+            },
+            // variants are written like an object with a single member
+            Content::NewtypeVariant(variant, ref value) => {
+                self.inline_entries(out, vec![(variant.to_string(), &**value)])
+            }
+            // quoteless strings would extend to the end of the line
+            _ => {
+                ser::Serialize::serialize(
+                    value,
+                    &mut Serializer::compact(&mut *out)
+                        .non_finite(self.non_finite)
+                        .float_precision(self.float_precision)
+                        .ascii_only(self.ascii_only),
+                )?;
+                Ok(true)
+            }
+        }
+    }
+
+    fn inline_items(&self, out: &mut Vec<u8>, items: &[Content]) -> Result<bool> {
+        out.push(b'[');
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                out.extend_from_slice(b", ");
+            }
+            if !self.inline_value(out, item)? {
+                return Ok(false);
+            }
+        }
+        out.push(b']');
+        Ok(true)
+    }
+
+    fn inline_entries(
+        &self,
+        out: &mut Vec<u8>,
+        mut entries: Vec<(String, &Content)>,
+    ) -> Result<bool> {
+        if self.skip_none {
+            entries.retain(|entry| !matches!(*entry.1, Content::None));
+        }
+        if self.sort_keys {
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+        out.push(b'{');
+        for (i, (key, value)) in entries.into_iter().enumerate() {
+            if i > 0 {
+                out.extend_from_slice(b", ");
+            }
+            escape_key_with(&mut *out, &key, self.unquoted_keys(), self.ascii_only)?;
+            out.extend_from_slice(b": ");
+            if !self.inline_value(out, value)? {
+                return Ok(false);
+            }
+        }
+        out.push(b'}');
+        Ok(true)
+    }
 }
 
 #[doc(hidden)]
@@ -64,6 +551,122 @@ pub enum State {
 pub struct Compound<'a, W, F> {
     ser: &'a mut Serializer<W, F>,
     state: State,
+    // position of the next element of a sequence
+    index: usize,
+    // map entries held back until `end` because the keys need to be sorted first or because the
+    // map may fit on one line
+    entries: Option<Vec<(String, Content)>>,
+    // sequence elements held back until `end` because the sequence may fit on one line
+    items: Option<Vec<Content>>,
+    // the opening bracket is only written in `end`, see `Serializer::compact_width`
+    deferred: bool,
+    // the key of the map entry being serialized, held back until its value turns out not to be
+    // `None`, see `Serializer::skip_none`
+    key: Option<String>,
+    // the opening brace is only written with the first member that is not `None`, so a map
+    // without any is written as `{}`, see `Serializer::skip_none`
+    brace_pending: bool,
+}
+
+impl<'a, W, F> Compound<'a, W, F>
+where
+    W: io::Write,
+    F: Formatter,
+{
+    // Writes what is left of a sequence, up to and including the `]`.
+    fn end_seq(&mut self) -> Result<()> {
+        if let Some(items) = self.items.take() {
+            let mut line = Vec::new();
+            if self.ser.inline_items(&mut line, &items)? && self.ser.write_inline(&line)? {
+                return Ok(());
+            }
+            self.ser.formatter.open(&mut self.ser.writer, b'[')?;
+            for (i, item) in items.iter().enumerate() {
+                self.ser.formatter.comma(&mut self.ser.writer, i == 0)?;
+                ser::Serialize::serialize(item, &mut *self.ser)?;
+            }
+        }
+
+        match self.state {
+            State::Empty => Ok(()),
+            _ => self.ser.formatter.close(&mut self.ser.writer, b']'),
+        }
+    }
+
+    // Writes the key of a map entry, along with the comma before it and the colon after it.
+    fn write_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.open_pending()?;
+        self.ser
+            .formatter
+            .comma(&mut self.ser.writer, self.state == State::First)?;
+        self.state = State::Rest;
+
+        let key = key_text(key)?;
+        self.ser.begin_member(&key)?;
+
+        self.ser.formatter.colon(&mut self.ser.writer)
+    }
+
+    // Writes what is left of a map, up to and including the `}`.
+    fn end_map(&mut self) -> Result<()> {
+        if let Some(mut entries) = self.entries.take() {
+            if self.ser.sort_keys {
+                // sort_by is stable and String compares byte-wise
+                entries.sort_by(|a, b| a.0.cmp(&b.0));
+            }
+            if self.deferred {
+                let mut line = Vec::new();
+                let inline = entries.iter().map(|e| (e.0.clone(), &e.1)).collect();
+                if self.ser.inline_entries(&mut line, inline)? && self.ser.write_inline(&line)? {
+                    return Ok(());
+                }
+                self.ser.formatter.open(&mut self.ser.writer, b'{')?;
+            }
+            if !entries.is_empty() {
+                self.open_pending()?;
+            }
+            let widths = if self.ser.align_colons {
+                entries
+                    .iter()
+                    .map(|entry| self.ser.key_width(&entry.0))
+                    .collect::<Result<Vec<_>>>()?
+            } else {
+                Vec::new()
+            };
+            let max_width = widths.iter().cloned().max().unwrap_or(0);
+            for (i, (key, value)) in entries.iter().enumerate() {
+                self.ser.formatter.comma(&mut self.ser.writer, i == 0)?;
+                self.ser.begin_member(key)?;
+                self.ser.formatter.colon(&mut self.ser.writer)?;
+                if let Some(width) = widths.get(i) {
+                    self.ser.formatter.pad(max_width - width);
+                }
+                ser::Serialize::serialize(value, &mut *self.ser)?;
+                self.ser.end_member()?;
+            }
+        }
+
+        match self.state {
+            State::Empty => Ok(()),
+            _ if self.brace_pending => {
+                self.ser.formatter.start_value(&mut self.ser.writer)?;
+                self.ser.writer.write_all(b"{}").map_err(From::from)
+            }
+            _ => self.ser.formatter.close(&mut self.ser.writer, b'}'),
+        }
+    }
+
+    // Writes the opening brace of a map if it was held back, see `brace_pending`.
+    fn open_pending(&mut self) -> Result<()> {
+        if self.brace_pending {
+            self.brace_pending = false;
+            self.ser.formatter.open(&mut self.ser.writer, b'{')?;
+        }
+        Ok(())
+    }
 }
 
 impl<'a, W, F> ser::Serializer for &'a mut Serializer<W, F>
@@ -86,83 +689,134 @@ where
     fn serialize_bool(self, value: bool) -> Result<()> {
         self.formatter.start_value(&mut self.writer)?;
         if value {
-            self.writer.write_all(b"true").map_err(From::from)
+            self.writer.write_all(b"true")?;
         } else {
-            self.writer.write_all(b"false").map_err(From::from)
+            self.writer.write_all(b"false")?;
         }
+        self.end_value()
     }
 
     #[inline]
     fn serialize_i8(self, value: i8) -> Result<()> {
         self.formatter.start_value(&mut self.writer)?;
-        write!(&mut self.writer, "{}", value).map_err(From::from)
+        write!(&mut self.writer, "{}", value)?;
+        self.end_value()
     }
 
     #[inline]
     fn serialize_i16(self, value: i16) -> Result<()> {
         self.formatter.start_value(&mut self.writer)?;
-        write!(&mut self.writer, "{}", value).map_err(From::from)
+        write!(&mut self.writer, "{}", value)?;
+        self.end_value()
     }
 
     #[inline]
     fn serialize_i32(self, value: i32) -> Result<()> {
         self.formatter.start_value(&mut self.writer)?;
-        write!(&mut self.writer, "{}", value).map_err(From::from)
+        write!(&mut self.writer, "{}", value)?;
+        self.end_value()
     }
 
     #[inline]
     fn serialize_i64(self, value: i64) -> Result<()> {
         self.formatter.start_value(&mut self.writer)?;
-        write!(&mut self.writer, "{}", value).map_err(From::from)
+        write!(&mut self.writer, "{}", value)?;
+        self.end_value()
+    }
+
+    #[inline]
+    fn serialize_i128(self, value: i128) -> Result<()> {
+        self.formatter.start_value(&mut self.writer)?;
+        write!(&mut self.writer, "{}", value)?;
+        self.end_value()
     }
 
     #[inline]
     fn serialize_u8(self, value: u8) -> Result<()> {
         self.formatter.start_value(&mut self.writer)?;
-        write!(&mut self.writer, "{}", value).map_err(From::from)
+        write!(&mut self.writer, "{}", value)?;
+        self.end_value()
     }
 
     #[inline]
     fn serialize_u16(self, value: u16) -> Result<()> {
         self.formatter.start_value(&mut self.writer)?;
-        write!(&mut self.writer, "{}", value).map_err(From::from)
+        write!(&mut self.writer, "{}", value)?;
+        self.end_value()
     }
 
     #[inline]
     fn serialize_u32(self, value: u32) -> Result<()> {
         self.formatter.start_value(&mut self.writer)?;
-        write!(&mut self.writer, "{}", value).map_err(From::from)
+        write!(&mut self.writer, "{}", value)?;
+        self.end_value()
     }
 
     #[inline]
     fn serialize_u64(self, value: u64) -> Result<()> {
         self.formatter.start_value(&mut self.writer)?;
-        write!(&mut self.writer, "{}", value).map_err(From::from)
+        write!(&mut self.writer, "{}", value)?;
+        self.end_value()
+    }
+
+    #[inline]
+    fn serialize_u128(self, value: u128) -> Result<()> {
+        self.formatter.start_value(&mut self.writer)?;
+        write!(&mut self.writer, "{}", value)?;
+        self.end_value()
     }
 
     #[inline]
     fn serialize_f32(self, value: f32) -> Result<()> {
         self.formatter.start_value(&mut self.writer)?;
-        fmt_f32_or_null(&mut self.writer, if value == -0f32 { 0f32 } else { value })
-            .map_err(From::from)
+        if !value.is_finite() {
+            self.write_non_finite(value as f64)?;
+            return self.end_value();
+        }
+        let value = match self.float_precision {
+            Some(digits) => round_significant(value, digits),
+            None => value,
+        };
+        fmt_f32_or_null(&mut self.writer, if value == -0f32 { 0f32 } else { value })?;
+        self.end_value()
     }
 
     #[inline]
     fn serialize_f64(self, value: f64) -> Result<()> {
         self.formatter.start_value(&mut self.writer)?;
-        fmt_f64_or_null(&mut self.writer, if value == -0f64 { 0f64 } else { value })
-            .map_err(From::from)
+        if !value.is_finite() {
+            self.write_non_finite(value)?;
+            return self.end_value();
+        }
+        let value = match self.float_precision {
+            Some(digits) => round_significant(value, digits),
+            None => value,
+        };
+        fmt_f64_or_null(&mut self.writer, if value == -0f64 { 0f64 } else { value })?;
+        self.end_value()
     }
 
     #[inline]
     fn serialize_char(self, value: char) -> Result<()> {
         self.formatter.start_value(&mut self.writer)?;
-        escape_char(&mut self.writer, value).map_err(From::from)
+        escape_char(&mut self.writer, value, self.ascii_only)?;
+        self.end_value()
     }
 
     #[inline]
     fn serialize_str(self, value: &str) -> Result<()> {
-        quote_str(&mut self.writer, &mut self.formatter, value).map_err(From::from)
+        if self.write_styled_str(value)? {
+            return self.end_value();
+        }
+        quote_str_with(
+            &mut self.writer,
+            &mut self.formatter,
+            value,
+            self.quoteless_values && !self.force_quote_strings,
+            self.multiline && !self.force_quote_strings,
+            self.ascii_only,
+        )?;
+        self.end_value()
     }
 
     #[inline]
@@ -177,7 +831,8 @@ where
     #[inline]
     fn serialize_unit(self) -> Result<()> {
         self.formatter.start_value(&mut self.writer)?;
-        self.writer.write_all(b"null").map_err(From::from)
+        self.writer.write_all(b"null")?;
+        self.end_value()
     }
 
     #[inline]
@@ -197,10 +852,30 @@ where
 
     /// Serialize newtypes without an object wrapper.
     #[inline]
-    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<()>
     where
         T: ?Sized + ser::Serialize,
     {
+        // the text of a `RawValue` and number literals kept by `Value` are written as they are
+        if name == raw::RAW_TOKEN {
+            if let Value::String(text) = value::to_value(value)? {
+                // the text may hold quoteless strings and comments, which only fit into output
+                // that has them as well
+                if !self.quoteless_values || self.force_quote_strings {
+                    return ser::Serialize::serialize(&from_str::<Value>(&text)?, self);
+                }
+                self.formatter.start_value(&mut self.writer)?;
+                self.writer.write_all(text.as_bytes())?;
+                return self.end_value();
+            }
+        }
+        if name == value::NUMBER_TOKEN {
+            if let Value::String(literal) = value::to_value(value)? {
+                self.formatter.start_value(&mut self.writer)?;
+                self.writer.write_all(literal.as_bytes())?;
+                return self.end_value();
+            }
+        }
         value.serialize(self)
     }
 
@@ -216,11 +891,14 @@ where
         T: ?Sized + ser::Serialize,
     {
         self.formatter.open(&mut self.writer, b'{')?;
+        self.depth += 1;
         self.formatter.comma(&mut self.writer, true)?;
-        escape_key(&mut self.writer, variant)?;
+        self.write_key(variant)?;
         self.formatter.colon(&mut self.writer)?;
         value.serialize(&mut *self)?;
-        self.formatter.close(&mut self.writer, b'}')
+        self.formatter.close(&mut self.writer, b'}')?;
+        self.depth -= 1;
+        self.end_value()
     }
 
     #[inline]
@@ -238,15 +916,28 @@ where
 
     #[inline]
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        let deferred = len != Some(0) && self.defers_collections();
         let state = if len == Some(0) {
             self.formatter.start_value(&mut self.writer)?;
             self.writer.write_all(b"[]")?;
             State::Empty
         } else {
-            self.formatter.open(&mut self.writer, b'[')?;
+            if !deferred {
+                self.formatter.open(&mut self.writer, b'[')?;
+            }
             State::First
         };
-        Ok(Compound { ser: self, state })
+        self.depth += 1;
+        Ok(Compound {
+            ser: self,
+            state,
+            index: 0,
+            entries: None,
+            items: if deferred { Some(Vec::new()) } else { None },
+            deferred,
+            key: None,
+            brace_pending: false,
+        })
     }
 
     #[inline]
@@ -272,23 +963,44 @@ where
         len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
         self.formatter.open(&mut self.writer, b'{')?;
+        self.depth += 1;
         self.formatter.comma(&mut self.writer, true)?;
-        escape_key(&mut self.writer, variant)?;
+        self.write_key(variant)?;
         self.formatter.colon(&mut self.writer)?;
         self.serialize_seq(Some(len))
     }
 
     #[inline]
     fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
+        let deferred = len != Some(0) && self.defers_collections();
+        let brace_pending = len != Some(0) && !deferred && self.skip_none;
         let state = if len == Some(0) {
             self.formatter.start_value(&mut self.writer)?;
             self.writer.write_all(b"{}")?;
             State::Empty
         } else {
-            self.formatter.open(&mut self.writer, b'{')?;
+            if !deferred && !brace_pending {
+                self.formatter.open(&mut self.writer, b'{')?;
+            }
             State::First
         };
-        Ok(Compound { ser: self, state })
+        self.depth += 1;
+        let entries = if (self.sort_keys || self.align_colons || deferred) && state != State::Empty
+        {
+            Some(Vec::new())
+        } else {
+            None
+        };
+        Ok(Compound {
+            ser: self,
+            state,
+            index: 0,
+            entries,
+            items: None,
+            deferred,
+            key: None,
+            brace_pending,
+        })
     }
 
     #[inline]
@@ -305,8 +1017,9 @@ where
         len: usize,
     ) -> Result<Self::SerializeStructVariant> {
         self.formatter.open(&mut self.writer, b'{')?;
+        self.depth += 1;
         self.formatter.comma(&mut self.writer, true)?;
-        escape_key(&mut self.writer, variant)?;
+        self.write_key(variant)?;
         self.formatter.colon(&mut self.writer)?;
         self.serialize_map(Some(len))
     }
@@ -324,18 +1037,30 @@ where
     where
         T: serde::Serialize,
     {
+        if let Some(ref mut items) = self.items {
+            self.state = State::Rest;
+            items.push(content::to_content(value)?);
+            return Ok(());
+        }
+
         self.ser
             .formatter
             .comma(&mut self.ser.writer, self.state == State::First)?;
         self.state = State::Rest;
-        value.serialize(&mut *self.ser)
+        if !self.ser.tracks_path() {
+            return value.serialize(&mut *self.ser);
+        }
+
+        self.ser.begin_comments(&self.index.to_string())?;
+        self.index += 1;
+        value.serialize(&mut *self.ser)?;
+        self.ser.end_member()
     }
 
-    fn end(self) -> Result<Self::Ok> {
-        match self.state {
-            State::Empty => Ok(()),
-            _ => self.ser.formatter.close(&mut self.ser.writer, b']'),
-        }
+    fn end(mut self) -> Result<Self::Ok> {
+        self.end_seq()?;
+        self.ser.depth -= 1;
+        self.ser.end_value()
     }
 }
 
@@ -394,12 +1119,11 @@ where
         ser::SerializeSeq::serialize_element(self, value)
     }
 
-    fn end(self) -> Result<Self::Ok> {
-        match self.state {
-            State::Empty => {}
-            _ => self.ser.formatter.close(&mut self.ser.writer, b']')?,
-        }
-        self.ser.formatter.close(&mut self.ser.writer, b'}')
+    fn end(mut self) -> Result<Self::Ok> {
+        self.end_seq()?;
+        self.ser.formatter.close(&mut self.ser.writer, b'}')?;
+        self.ser.depth -= 2;
+        self.ser.end_value()
     }
 }
 
@@ -415,28 +1139,49 @@ where
     where
         T: serde::Serialize,
     {
-        self.ser
-            .formatter
-            .comma(&mut self.ser.writer, self.state == State::First)?;
-        self.state = State::Rest;
-
-        key.serialize(MapKeySerializer { ser: self.ser })?;
+        if let Some(ref mut entries) = self.entries {
+            self.state = State::Rest;
+            entries.push((key_text(key)?, Content::Unit));
+            return Ok(());
+        }
+        if self.ser.skip_none {
+            self.key = Some(key_text(key)?);
+            return Ok(());
+        }
 
-        self.ser.formatter.colon(&mut self.ser.writer)
+        self.write_key(key)
     }
 
     fn serialize_value<T: ?Sized>(&mut self, value: &T) -> Result<()>
     where
         T: serde::Serialize,
     {
-        value.serialize(&mut *self.ser)
+        if let Some(ref mut entries) = self.entries {
+            if self.ser.skip_none && is_none(value) {
+                entries.pop();
+                return Ok(());
+            }
+            let entry = entries
+                .last_mut()
+                .expect("serialize_value called before serialize_key");
+            entry.1 = content::to_content(value)?;
+            return Ok(());
+        }
+        if let Some(key) = self.key.take() {
+            if is_none(value) {
+                return Ok(());
+            }
+            self.write_key(&key)?;
+        }
+
+        value.serialize(&mut *self.ser)?;
+        self.ser.end_member()
     }
 
-    fn end(self) -> Result<Self::Ok> {
-        match self.state {
-            State::Empty => Ok(()),
-            _ => self.ser.formatter.close(&mut self.ser.writer, b'}'),
-        }
+    fn end(mut self) -> Result<Self::Ok> {
+        self.end_map()?;
+        self.ser.depth -= 1;
+        self.ser.end_value()
     }
 }
 
@@ -475,114 +1220,130 @@ where
         ser::SerializeStruct::serialize_field(self, key, value)
     }
 
-    fn end(self) -> Result<Self::Ok> {
-        match self.state {
-            State::Empty => {}
-            _ => self.ser.formatter.close(&mut self.ser.writer, b'}')?,
-        }
-        self.ser.formatter.close(&mut self.ser.writer, b'}')
+    fn end(mut self) -> Result<Self::Ok> {
+        self.end_map()?;
+        self.ser.formatter.close(&mut self.ser.writer, b'}')?;
+        self.ser.depth -= 2;
+        self.ser.end_value()
     }
 }
 
-struct MapKeySerializer<'a, W: 'a, F: 'a> {
-    ser: &'a mut Serializer<W, F>,
-}
+// The text of object keys: strings as they are, and booleans, numbers, chars and unit variants as
+// their text, like serde_json does for maps with integer keys.
+struct MapKeySerializer;
 
-impl<'a, W, F> ser::Serializer for MapKeySerializer<'a, W, F>
+pub(crate) fn key_text<T>(key: &T) -> Result<String>
 where
-    W: io::Write,
-    F: Formatter,
+    T: ?Sized + ser::Serialize,
 {
-    type Ok = ();
+    key.serialize(MapKeySerializer)
+}
+
+impl ser::Serializer for MapKeySerializer {
+    type Ok = String;
     type Error = Error;
 
     #[inline]
-    fn serialize_str(self, value: &str) -> Result<()> {
-        escape_key(&mut self.ser.writer, value).map_err(From::from)
+    fn serialize_str(self, value: &str) -> Result<String> {
+        Ok(value.to_string())
     }
 
-    type SerializeSeq = ser::Impossible<(), Error>;
-    type SerializeTuple = ser::Impossible<(), Error>;
-    type SerializeTupleStruct = ser::Impossible<(), Error>;
-    type SerializeTupleVariant = ser::Impossible<(), Error>;
-    type SerializeMap = ser::Impossible<(), Error>;
-    type SerializeStruct = ser::Impossible<(), Error>;
-    type SerializeStructVariant = ser::Impossible<(), Error>;
+    type SerializeSeq = ser::Impossible<String, Error>;
+    type SerializeTuple = ser::Impossible<String, Error>;
+    type SerializeTupleStruct = ser::Impossible<String, Error>;
+    type SerializeTupleVariant = ser::Impossible<String, Error>;
+    type SerializeMap = ser::Impossible<String, Error>;
+    type SerializeStruct = ser::Impossible<String, Error>;
+    type SerializeStructVariant = ser::Impossible<String, Error>;
 
-    fn serialize_bool(self, _value: bool) -> Result<()> {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+    fn serialize_bool(self, value: bool) -> Result<String> {
+        Ok(value.to_string())
     }
 
-    fn serialize_i8(self, _value: i8) -> Result<()> {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+    fn serialize_i8(self, value: i8) -> Result<String> {
+        Ok(value.to_string())
     }
 
-    fn serialize_i16(self, _value: i16) -> Result<()> {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+    fn serialize_i16(self, value: i16) -> Result<String> {
+        Ok(value.to_string())
     }
 
-    fn serialize_i32(self, _value: i32) -> Result<()> {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+    fn serialize_i32(self, value: i32) -> Result<String> {
+        Ok(value.to_string())
     }
 
-    fn serialize_i64(self, _value: i64) -> Result<()> {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+    fn serialize_i64(self, value: i64) -> Result<String> {
+        Ok(value.to_string())
     }
 
-    fn serialize_u8(self, _value: u8) -> Result<()> {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+    fn serialize_i128(self, value: i128) -> Result<String> {
+        Ok(value.to_string())
     }
 
-    fn serialize_u16(self, _value: u16) -> Result<()> {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+    fn serialize_u8(self, value: u8) -> Result<String> {
+        Ok(value.to_string())
     }
 
-    fn serialize_u32(self, _value: u32) -> Result<()> {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+    fn serialize_u16(self, value: u16) -> Result<String> {
+        Ok(value.to_string())
     }
 
-    fn serialize_u64(self, _value: u64) -> Result<()> {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+    fn serialize_u32(self, value: u32) -> Result<String> {
+        Ok(value.to_string())
     }
 
-    fn serialize_f32(self, _value: f32) -> Result<()> {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+    fn serialize_u64(self, value: u64) -> Result<String> {
+        Ok(value.to_string())
     }
 
-    fn serialize_f64(self, _value: f64) -> Result<()> {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+    fn serialize_u128(self, value: u128) -> Result<String> {
+        Ok(value.to_string())
     }
 
-    fn serialize_char(self, _value: char) -> Result<()> {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+    fn serialize_f32(self, value: f32) -> Result<String> {
+        if !value.is_finite() {
+            return Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0, 0));
+        }
+        Ok(value.to_string())
     }
 
-    fn serialize_bytes(self, _value: &[u8]) -> Result<()> {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+    fn serialize_f64(self, value: f64) -> Result<String> {
+        if !value.is_finite() {
+            return Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0, 0));
+        }
+        Ok(value.to_string())
     }
 
-    fn serialize_unit(self) -> Result<()> {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+    fn serialize_char(self, value: char) -> Result<String> {
+        Ok(value.to_string())
     }
 
-    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+    fn serialize_bytes(self, _value: &[u8]) -> Result<String> {
+        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0, 0))
+    }
+
+    fn serialize_unit(self) -> Result<String> {
+        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0, 0))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String> {
+        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0, 0))
     }
 
     fn serialize_unit_variant(
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-    ) -> Result<()> {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+        variant: &'static str,
+    ) -> Result<String> {
+        Ok(variant.to_string())
     }
 
-    fn serialize_newtype_struct<T>(self, _name: &'static str, _value: &T) -> Result<()>
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<String>
     where
         T: ?Sized + ser::Serialize,
     {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+        value.serialize(self)
     }
 
     fn serialize_newtype_variant<T>(
@@ -591,30 +1352,30 @@ where
         _variant_index: u32,
         _variant: &'static str,
         _value: &T,
-    ) -> Result<()>
+    ) -> Result<String>
     where
         T: ?Sized + ser::Serialize,
     {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0, 0))
     }
 
-    fn serialize_none(self) -> Result<()> {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+    fn serialize_none(self) -> Result<String> {
+        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0, 0))
     }
 
-    fn serialize_some<T>(self, _value: &T) -> Result<()>
+    fn serialize_some<T>(self, _value: &T) -> Result<String>
     where
         T: ?Sized + ser::Serialize,
     {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0, 0))
     }
 
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeStruct> {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0, 0))
     }
 
     fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0, 0))
     }
 
     fn serialize_tuple_struct(
@@ -622,7 +1383,7 @@ where
         _name: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0, 0))
     }
 
     fn serialize_tuple_variant(
@@ -632,15 +1393,15 @@ where
         _variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0, 0))
     }
 
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0, 0))
     }
 
     fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0, 0))
     }
 
     fn serialize_struct_variant(
@@ -650,12 +1411,205 @@ where
         _variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0))
+        Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0, 0))
     }
 }
 
-/// This trait abstracts away serializing the JSON control characters
-pub trait Formatter {
+// Whether a newtype of this name passes text through that is written as it is, see
+// `serialize_newtype_struct`.
+fn is_verbatim_token(name: &str) -> bool {
+    name == value::NUMBER_TOKEN || name == raw::RAW_TOKEN
+}
+
+// Whether `value` serializes as `None`, see `Serializer::skip_none`.
+pub(crate) fn is_none<T>(value: &T) -> bool
+where
+    T: ?Sized + ser::Serialize,
+{
+    value.serialize(NoneProbe).unwrap_or(false)
+}
+
+// Answers whether a value is `None` without serializing anything. Collections are not, they fail
+// right away instead of being walked.
+struct NoneProbe;
+
+impl NoneProbe {
+    fn collection() -> Error {
+        Error::Syntax(ErrorCode::ExpectedSomeValue, 0, 0, 0)
+    }
+}
+
+impl ser::Serializer for NoneProbe {
+    type Ok = bool;
+    type Error = Error;
+
+    type SerializeSeq = ser::Impossible<bool, Error>;
+    type SerializeTuple = ser::Impossible<bool, Error>;
+    type SerializeTupleStruct = ser::Impossible<bool, Error>;
+    type SerializeTupleVariant = ser::Impossible<bool, Error>;
+    type SerializeMap = ser::Impossible<bool, Error>;
+    type SerializeStruct = ser::Impossible<bool, Error>;
+    type SerializeStructVariant = ser::Impossible<bool, Error>;
+
+    fn serialize_bool(self, _value: bool) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn serialize_i8(self, _value: i8) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn serialize_i16(self, _value: i16) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn serialize_i32(self, _value: i32) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn serialize_i64(self, _value: i64) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn serialize_i128(self, _value: i128) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn serialize_u8(self, _value: u8) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn serialize_u16(self, _value: u16) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn serialize_u32(self, _value: u32) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn serialize_u64(self, _value: u64) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn serialize_u128(self, _value: u128) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn serialize_f32(self, _value: f32) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn serialize_f64(self, _value: f64) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn serialize_char(self, _value: char) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn serialize_str(self, _value: &str) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn serialize_bytes(self, _value: &[u8]) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn serialize_unit(self) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, _value: &T) -> Result<bool>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        Ok(false)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<bool>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        Ok(false)
+    }
+
+    fn serialize_none(self) -> Result<bool> {
+        Ok(true)
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> Result<bool>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        Ok(false)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(NoneProbe::collection())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(NoneProbe::collection())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(NoneProbe::collection())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(NoneProbe::collection())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(NoneProbe::collection())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(NoneProbe::collection())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(NoneProbe::collection())
+    }
+}
+
+/// This trait abstracts away serializing the JSON control characters
+pub trait Formatter {
     /// Called when serializing a '{' or '['.
     fn open<W>(&mut self, writer: &mut W, ch: u8) -> Result<()>
     where
@@ -685,14 +1639,25 @@ pub trait Formatter {
     fn start_value<W>(&mut self, writer: &mut W) -> Result<()>
     where
         W: io::Write;
+
+    /// Called after a ':' with the number of spaces that align the value with those of the
+    /// other members, see `Serializer::align_colons`. They are only written if the value starts
+    /// on the same line.
+    fn pad(&mut self, _width: usize) {}
 }
 
-struct HjsonFormatter<'a> {
+/// The formatter of the pretty Hjson output, see `Serializer::new`.
+pub struct HjsonFormatter<'a> {
     current_indent: usize,
     current_is_array: bool,
     stack: Vec<bool>,
     at_colon: bool,
+    // spaces written before the value after `at_colon`, see `Serializer::align_colons`
+    pad: usize,
     indent: &'a [u8],
+    line_end: &'static [u8],
+    // whether `comma` writes a `,` between the members, set for strict JSON output
+    commas: bool,
     braces_same_line: bool,
 }
 
@@ -709,12 +1674,21 @@ impl<'a> HjsonFormatter<'a> {
             current_is_array: false,
             stack: Vec::new(),
             at_colon: false,
+            pad: 0,
             indent: indent,
+            line_end: b"\n",
+            commas: false,
             braces_same_line: false,
         }
     }
 }
 
+impl<'a> Default for HjsonFormatter<'a> {
+    fn default() -> Self {
+        HjsonFormatter::new()
+    }
+}
+
 impl<'a> Formatter for HjsonFormatter<'a> {
     fn open<W>(&mut self, writer: &mut W, ch: u8) -> Result<()>
     where
@@ -731,11 +1705,14 @@ impl<'a> Formatter for HjsonFormatter<'a> {
         writer.write_all(&[ch]).map_err(From::from)
     }
 
-    fn comma<W>(&mut self, writer: &mut W, _: bool) -> Result<()>
+    fn comma<W>(&mut self, writer: &mut W, first: bool) -> Result<()>
     where
         W: io::Write,
     {
-        writer.write_all(b"\n")?;
+        if self.commas && !first {
+            writer.write_all(b",")?;
+        }
+        writer.write_all(self.line_end)?;
         indent(writer, self.current_indent, self.indent)
     }
 
@@ -755,7 +1732,7 @@ impl<'a> Formatter for HjsonFormatter<'a> {
     {
         self.current_indent -= 1;
         self.current_is_array = self.stack.pop().unwrap();
-        writer.write(b"\n")?;
+        writer.write_all(self.line_end)?;
         indent(writer, self.current_indent, self.indent)?;
         writer.write_all(&[ch]).map_err(From::from)
     }
@@ -765,7 +1742,8 @@ impl<'a> Formatter for HjsonFormatter<'a> {
         W: io::Write,
     {
         self.at_colon = false;
-        writer.write_all(b"\n")?;
+        self.pad = 0;
+        writer.write_all(self.line_end)?;
         let ii = self.current_indent as i32 + add_indent;
         indent(writer, if ii < 0 { 0 } else { ii as usize }, self.indent)
     }
@@ -778,6 +1756,65 @@ impl<'a> Formatter for HjsonFormatter<'a> {
             self.at_colon = false;
             writer.write_all(b" ")?
         }
+        for _ in 0..self.pad {
+            writer.write_all(b" ")?;
+        }
+        self.pad = 0;
+        Ok(())
+    }
+
+    fn pad(&mut self, width: usize) {
+        self.pad = width;
+    }
+}
+
+/// A formatter that writes no insignificant whitespace at all, see `Serializer::compact`.
+pub struct CompactFormatter;
+
+impl Formatter for CompactFormatter {
+    fn open<W>(&mut self, writer: &mut W, ch: u8) -> Result<()>
+    where
+        W: io::Write,
+    {
+        writer.write_all(&[ch]).map_err(From::from)
+    }
+
+    fn comma<W>(&mut self, writer: &mut W, first: bool) -> Result<()>
+    where
+        W: io::Write,
+    {
+        if first {
+            Ok(())
+        } else {
+            writer.write_all(b",").map_err(From::from)
+        }
+    }
+
+    fn colon<W>(&mut self, writer: &mut W) -> Result<()>
+    where
+        W: io::Write,
+    {
+        writer.write_all(b":").map_err(From::from)
+    }
+
+    fn close<W>(&mut self, writer: &mut W, ch: u8) -> Result<()>
+    where
+        W: io::Write,
+    {
+        writer.write_all(&[ch]).map_err(From::from)
+    }
+
+    fn newline<W>(&mut self, _writer: &mut W, _add_indent: i32) -> Result<()>
+    where
+        W: io::Write,
+    {
+        Ok(())
+    }
+
+    fn start_value<W>(&mut self, _writer: &mut W) -> Result<()>
+    where
+        W: io::Write,
+    {
         Ok(())
     }
 }
@@ -801,6 +1838,14 @@ where
             b'\n' => b"\\n",
             b'\r' => b"\\r",
             b'\t' => b"\\t",
+            b'\x00'..=b'\x1f' => {
+                if start < i {
+                    wr.write_all(&bytes[start..i])?;
+                }
+                write!(wr, "\\u{:04x}", byte)?;
+                start = i + 1;
+                continue;
+            }
             _ => {
                 continue;
             }
@@ -823,6 +1868,56 @@ where
     Ok(())
 }
 
+// Like `escape_bytes`, but optionally also escapes every non-ASCII character.
+fn escape_str<W>(wr: &mut W, value: &str, ascii_only: bool) -> Result<()>
+where
+    W: io::Write,
+{
+    if !ascii_only || value.is_ascii() {
+        return escape_bytes(wr, value.as_bytes());
+    }
+    // escape_bytes leaves the non-ASCII characters untouched, so its output is still UTF-8
+    let mut escaped = Vec::with_capacity(value.len() + 2);
+    escape_bytes(&mut escaped, value.as_bytes())?;
+    let mut buf = [0; 2];
+    for ch in String::from_utf8(escaped)?.chars() {
+        if ch.is_ascii() {
+            wr.write_all(&[ch as u8])?;
+        } else {
+            for unit in ch.encode_utf16(&mut buf) {
+                write!(wr, "\\u{:04x}", unit)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Like `escape_str`, but in single quotes, so `"` needs no escape and `'` does.
+fn escape_single_quoted<W>(wr: &mut W, value: &str, ascii_only: bool) -> Result<()>
+where
+    W: io::Write,
+{
+    let mut escaped = Vec::with_capacity(value.len() + 2);
+    escape_str(&mut escaped, value, ascii_only)?;
+    let inner = &escaped[1..escaped.len() - 1];
+    let mut out = Vec::with_capacity(escaped.len());
+    out.push(b'\'');
+    let mut bytes = inner.iter();
+    while let Some(&byte) = bytes.next() {
+        match byte {
+            b'\\' => match bytes.next() {
+                Some(b'"') => out.push(b'"'),
+                Some(&next) => out.extend_from_slice(&[b'\\', next]),
+                None => out.push(b'\\'),
+            },
+            b'\'' => out.extend_from_slice(b"\\'"),
+            _ => out.push(byte),
+        }
+    }
+    out.push(b'\'');
+    wr.write_all(&out).map_err(From::from)
+}
+
 /// Serializes and escapes a `&str` into a Hjson string.
 #[inline]
 pub fn quote_str<W, F>(wr: &mut W, formatter: &mut F, value: &str) -> Result<()>
@@ -830,17 +1925,99 @@ where
     W: io::Write,
     F: Formatter,
 {
-    lazy_static! {
-        // NEEDS_ESCAPE tests if the string can be written without escapes
-        static ref NEEDS_ESCAPE: Regex = Regex::new("[\\\\\"\x00-\x1f\x7f-\u{9f}\u{00ad}\u{0600}-\u{0604}\u{070f}\u{17b4}\u{17b5}\u{200c}-\u{200f}\u{2028}-\u{202f}\u{2060}-\u{206f}\u{feff}\u{fff0}-\u{ffff}]").unwrap();
-        // NEEDS_QUOTES tests if the string can be written as a quoteless string (includes needsEscape but without \\ and \")
-        static ref NEEDS_QUOTES: Regex = Regex::new("^\\s|^\"|^'''|^#|^/\\*|^//|^\\{|^\\}|^\\[|^\\]|^:|^,|\\s$|[\x00-\x1f\x7f-\u{9f}\u{00ad}\u{0600}-\u{0604}\u{070f}\u{17b4}\u{17b5}\u{200c}-\u{200f}\u{2028}-\u{202f}\u{2060}-\u{206f}\u{feff}\u{fff0}-\u{ffff}]").unwrap();
-        // NEEDS_ESCAPEML tests if the string can be written as a multiline string (includes needsEscape but without \n, \r, \\ and \")
-        static ref NEEDS_ESCAPEML: Regex = Regex::new("'''|[\x00-\x09\x0b\x0c\x0e-\x1f\x7f-\u{9f}\u{00ad}\u{0600}-\u{0604}\u{070f}\u{17b4}\u{17b5}\u{200c}-\u{200f}\u{2028}-\u{202f}\u{2060}-\u{206f}\u{feff}\u{fff0}-\u{ffff}]").unwrap();
-        // starts with a keyword and optionally is followed by a comment
-        static ref STARTS_WITH_KEYWORD: Regex = Regex::new(r#"^(true|false|null)\s*((,|\]|\}|#|//|/\*).*)?$"#).unwrap();
+    quote_str_with(wr, formatter, value, true, true, false)
+}
+
+// Characters that are only written escaped: control characters and invisible or format characters
+// that would not survive being copied around.
+fn is_escaped_char(ch: char) -> bool {
+    matches!(
+        ch,
+        '\x00'..='\x1f'
+        | '\x7f'..='\u{9f}'
+        | '\u{00ad}'
+        | '\u{0600}'..='\u{0604}'
+        | '\u{070f}'
+        | '\u{17b4}'
+        | '\u{17b5}'
+        | '\u{200c}'..='\u{200f}'
+        | '\u{2028}'..='\u{202f}'
+        | '\u{2060}'..='\u{206f}'
+        | '\u{feff}'
+        | '\u{fff0}'..='\u{ffff}'
+    )
+}
+
+// Tests if the string can be written without escapes.
+fn needs_escape(value: &str) -> bool {
+    value
+        .chars()
+        .any(|ch| ch == '\\' || ch == '"' || is_escaped_char(ch))
+}
+
+// Tests if the string can be written as a quoteless string (includes `needs_escape` but without
+// `\` and `"`). A string that starts with `'` would be read as a single-quoted one.
+fn needs_quotes(value: &str) -> bool {
+    const STARTS: [&str; 11] = ["\"", "'", "#", "/*", "//", "{", "}", "[", "]", ":", ","];
+    value.starts_with(char::is_whitespace)
+        || value.ends_with(char::is_whitespace)
+        || STARTS.iter().any(|start| value.starts_with(start))
+        || value.chars().any(is_escaped_char)
+}
+
+// Tests if the string can be written as a multiline string (includes `needs_escape` but without
+// `\n`, `\r`, `\` and `"`).
+fn needs_escape_ml(value: &str) -> bool {
+    value.contains("'''")
+        || value
+            .chars()
+            .any(|ch| ch != '\n' && ch != '\r' && is_escaped_char(ch))
+}
+
+// Tests if the string starts with a keyword that is optionally followed by a comment or a
+// punctuator, so it would not be read back as a string.
+fn starts_with_keyword(value: &str) -> bool {
+    let rest = ["true", "false", "null"]
+        .iter()
+        .find(|keyword| value.starts_with(*keyword))
+        .map(|keyword| value[keyword.len()..].trim_start());
+    match rest {
+        Some(rest) => {
+            // what follows the punctuator has to stay on the same line
+            rest.is_empty()
+                || ([",", "]", "}", "#", "//", "/*"]
+                    .iter()
+                    .any(|start| rest.starts_with(start))
+                    && !rest.contains('\n'))
+        }
+        None => false,
     }
+}
+
+// Tests if the key has to be quoted.
+fn needs_escape_name(value: &str) -> bool {
+    value.is_empty()
+        || value.contains("//")
+        || value.contains("/*")
+        || value.contains("'''")
+        || value.starts_with('\'')
+        || value
+            .chars()
+            .any(|ch| ch.is_whitespace() || ",{[}]:#\"".contains(ch))
+}
 
+fn quote_str_with<W, F>(
+    wr: &mut W,
+    formatter: &mut F,
+    value: &str,
+    quoteless: bool,
+    multiline: bool,
+    ascii_only: bool,
+) -> Result<()>
+where
+    W: io::Write,
+    F: Formatter,
+{
     if value.len() == 0 {
         formatter.start_value(wr)?;
         return escape_bytes(wr, value.as_bytes());
@@ -850,22 +2027,27 @@ where
     // see hjson syntax (must not parse as true, false, null or number)
 
     let mut pn = ParseNumber::new(value.bytes());
-    let is_number = match pn.parse(true) {
-        Ok(_) => true,
-        Err(_) => false,
-    };
+    let is_number = pn.parse(true).is_ok();
+
+    // non-ASCII characters can only be escaped in quoted strings
+    let escape_non_ascii = ascii_only && !value.is_ascii();
 
-    if is_number || NEEDS_QUOTES.is_match(value) || STARTS_WITH_KEYWORD.is_match(value) {
+    if !quoteless
+        || escape_non_ascii
+        || is_number
+        || needs_quotes(value)
+        || starts_with_keyword(value)
+    {
         // First check if the string can be expressed in multiline format or
         // we must replace the offending characters with safe escape sequences.
 
-        if NEEDS_ESCAPE.is_match(value) && !NEEDS_ESCAPEML.is_match(value)
+        if multiline && !escape_non_ascii && needs_escape(value) && !needs_escape_ml(value)
         /* && !isRootObject */
         {
             ml_str(wr, formatter, value)
         } else {
             formatter.start_value(wr)?;
-            escape_bytes(wr, value.as_bytes())
+            escape_str(wr, value, escape_non_ascii)
         }
     } else {
         // without quotes
@@ -905,19 +2087,29 @@ where
     Ok(())
 }
 
+// Writes a key quoteless where `escape_key` allows it, or quoted with the non-ASCII characters
+// escaped when `ascii_only` requires it.
+fn escape_key_with<W>(wr: &mut W, key: &str, quoteless: bool, ascii_only: bool) -> Result<()>
+where
+    W: io::Write,
+{
+    if ascii_only && !key.is_ascii() {
+        escape_str(wr, key, true)
+    } else if quoteless {
+        escape_key(wr, key)
+    } else {
+        escape_bytes(wr, key.as_bytes())
+    }
+}
+
 /// Serializes and escapes a `&str` into a Hjson key.
 #[inline]
 pub fn escape_key<W>(wr: &mut W, value: &str) -> Result<()>
 where
     W: io::Write,
 {
-    lazy_static! {
-        static ref NEEDS_ESCAPE_NAME: Regex =
-            Regex::new(r#"[,\{\[\}\]\s:#"]|//|/\*|'''|^$"#).unwrap();
-    }
-
     // Check if we can insert this name without quotes
-    if NEEDS_ESCAPE_NAME.is_match(value) {
+    if needs_escape_name(value) {
         escape_bytes(wr, value.as_bytes()).map_err(From::from)
     } else {
         wr.write_all(value.as_bytes()).map_err(From::from)
@@ -925,7 +2117,7 @@ where
 }
 
 #[inline]
-fn escape_char<W>(wr: &mut W, value: char) -> Result<()>
+fn escape_char<W>(wr: &mut W, value: char, ascii_only: bool) -> Result<()>
 where
     W: io::Write,
 {
@@ -933,7 +2125,7 @@ where
     // rust, which doesn't support encoding a `char` into a stack buffer.
     let mut s = String::new();
     s.push(value);
-    escape_bytes(wr, s.as_bytes())
+    escape_str(wr, &s, ascii_only)
 }
 
 fn fmt_f32_or_null<W>(wr: &mut W, value: f32) -> Result<()>
@@ -971,6 +2163,15 @@ where
     Ok(())
 }
 
+// Rounds a finite float to `digits` significant digits.
+fn round_significant<N>(value: N, digits: usize) -> N
+where
+    N: Copy + LowerExp + str::FromStr,
+{
+    let digits = if digits > 0 { digits - 1 } else { 0 };
+    format!("{:.*e}", digits, value).parse().unwrap_or(value)
+}
+
 // format similar to es6
 fn fmt_small<N>(value: N) -> String
 where
@@ -990,6 +2191,7 @@ where
 }
 
 /// Encode the specified struct into a Hjson `[u8]` writer.
+#[cfg(feature = "std")]
 #[inline]
 pub fn to_writer<W, T>(writer: &mut W, value: &T) -> Result<()>
 where
@@ -1001,6 +2203,50 @@ where
     Ok(())
 }
 
+/// Encode the specified struct into a Hjson `[u8]` writer like `to_writer`, ending with a newline
+/// as expected for text files.
+#[cfg(feature = "std")]
+#[inline]
+pub fn to_writer_pretty<W, T>(writer: &mut W, value: &T) -> Result<()>
+where
+    W: io::Write,
+    T: ser::Serialize,
+{
+    write_pretty_with_indent(writer, value, "  ")
+}
+
+/// Encode the specified struct into a Hjson `[u8]` writer like `to_writer_pretty`, ending with a
+/// newline, using `indent` instead of two spaces for each level of indentation.
+///
+/// Returns an error if `indent` contains anything other than spaces and tabs.
+#[cfg(feature = "std")]
+#[inline]
+pub fn to_writer_pretty_with_indent<W, T>(writer: &mut W, value: &T, indent: &str) -> Result<()>
+where
+    W: io::Write,
+    T: ser::Serialize,
+{
+    write_pretty_with_indent(writer, value, indent)
+}
+
+fn write_pretty_with_indent<W, T>(writer: &mut W, value: &T, indent: &str) -> Result<()>
+where
+    W: io::Write,
+    T: ser::Serialize,
+{
+    if !indent.bytes().all(|b| b == b' ' || b == b'\t') {
+        return Err(Error::Syntax(
+            ErrorCode::Custom("indent must only contain spaces and tabs".to_string()),
+            0,
+            0,
+            0,
+        ));
+    }
+    let mut ser = Serializer::with_indent(writer, indent.as_bytes()).trailing_newline(true);
+    value.serialize(&mut ser)?;
+    Ok(())
+}
+
 /// Encode the specified struct into a Hjson `[u8]` buffer.
 #[inline]
 pub fn to_vec<T>(value: &T) -> Result<Vec<u8>>
@@ -1010,7 +2256,19 @@ where
     // We are writing to a Vec, which doesn't fail. So we can ignore
     // the error.
     let mut writer = Vec::with_capacity(128);
-    to_writer(&mut writer, value)?;
+    value.serialize(&mut Serializer::new(&mut writer))?;
+    Ok(writer)
+}
+
+/// Encode the specified struct into a Hjson `[u8]` buffer like `to_vec`, ending with a newline as
+/// expected for text files.
+#[inline]
+pub fn to_vec_pretty<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: ser::Serialize,
+{
+    let mut writer = Vec::with_capacity(128);
+    value.serialize(&mut Serializer::new(&mut writer).trailing_newline(true))?;
     Ok(writer)
 }
 
@@ -1024,3 +2282,793 @@ where
     let string = String::from_utf8(vec)?;
     Ok(string)
 }
+
+/// Encode the specified struct into a Hjson `String` buffer like `to_string`, ending with a
+/// newline as expected for text files.
+#[inline]
+pub fn to_string_pretty<T>(value: &T) -> Result<String>
+where
+    T: ser::Serialize,
+{
+    to_string_pretty_with_indent(value, "  ")
+}
+
+/// Encode the specified struct into a Hjson `String` buffer, with the keys of every object
+/// sorted.
+#[inline]
+pub fn to_string_sorted<T>(value: &T) -> Result<String>
+where
+    T: ser::Serialize,
+{
+    let mut vec = Vec::with_capacity(128);
+    value.serialize(&mut Serializer::new(&mut vec).sort_keys(true))?;
+    let string = String::from_utf8(vec)?;
+    Ok(string)
+}
+
+/// Encode the specified struct into a Hjson `String` buffer like `to_string_pretty`, ending with
+/// a newline, using `indent` instead of two spaces for each level of indentation.
+///
+/// Returns an error if `indent` contains anything other than spaces and tabs.
+#[inline]
+pub fn to_string_pretty_with_indent<T>(value: &T, indent: &str) -> Result<String>
+where
+    T: ser::Serialize,
+{
+    let mut vec = Vec::with_capacity(128);
+    write_pretty_with_indent(&mut vec, value, indent)?;
+    let string = String::from_utf8(vec)?;
+    Ok(string)
+}
+
+/// Encode the specified struct into a Hjson `String` buffer, writing `comments` next to the object
+/// members and array elements they are attached to. See `de::from_str_with_comments` for an
+/// example.
+#[inline]
+pub fn to_string_with_comments<T>(value: &T, comments: &Comments) -> Result<String>
+where
+    T: ser::Serialize,
+{
+    let mut vec = Vec::with_capacity(128);
+    value.serialize(&mut Serializer::new(&mut vec).comments(comments.clone()))?;
+    let string = String::from_utf8(vec)?;
+    Ok(string)
+}
+
+/// Encode the specified struct into a canonical `String`, for hashing or signing.
+///
+/// Every key and string is quoted, the keys of every object are sorted, no whitespace is written
+/// between tokens and floats are written in their shortest form that reads back to the same
+/// value (`-0` is written as `0`). Values that are equal produce the same output, and the output
+/// is always valid JSON. NaN and infinite floats can't be represented and fail with
+/// `ErrorCode::FloatMustBeFinite`.
+#[inline]
+pub fn to_string_canonical<T>(value: &T) -> Result<String>
+where
+    T: ser::Serialize,
+{
+    let mut vec = Vec::with_capacity(128);
+    value.serialize(
+        &mut Serializer::compact(&mut vec)
+            .sort_keys(true)
+            .non_finite(NonFiniteFloats::Error),
+    )?;
+    let string = String::from_utf8(vec)?;
+    Ok(string)
+}
+
+/// Converts a Hjson document into JSON on one line, for tools that only read JSON.
+///
+/// Every key and string is quoted and comments are dropped. The keys keep the order of the
+/// document with the `preserve_order` feature, and are sorted without it.
+///
+/// ```rust
+/// let json = serde_hjson::hjson_to_json("# server\nhost: localhost\nport: 80\n").unwrap();
+/// assert_eq!(json, r#"{"host":"localhost","port":80}"#);
+/// ```
+#[inline]
+pub fn hjson_to_json(input: &str) -> Result<String> {
+    let value: Value = from_str(input)?;
+    let mut vec = Vec::with_capacity(input.len());
+    ser::Serialize::serialize(&value, &mut Serializer::compact(&mut vec))?;
+    let string = String::from_utf8(vec)?;
+    Ok(string)
+}
+
+/// Converts a Hjson document into indented JSON, like `hjson_to_json`.
+#[inline]
+pub fn hjson_to_json_pretty(input: &str) -> Result<String> {
+    let value: Value = from_str(input)?;
+    let mut vec = Vec::with_capacity(input.len());
+    ser::Serialize::serialize(
+        &value,
+        &mut Serializer::new(&mut vec)
+            .force_quote_keys(true)
+            .force_quote_strings(true),
+    )?;
+    let string = String::from_utf8(vec)?;
+    Ok(string)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use builder::ObjectBuilder;
+    use de::Deserializer;
+    use de::{from_str, from_str_with_comments, from_str_with_styles};
+    use serde::{Deserialize, Serialize};
+    use Value;
+
+    #[test]
+    fn indent() {
+        let v: Value = from_str("{a: {b: [1]}}").unwrap();
+        assert_eq!(
+            to_string(&v).unwrap(),
+            "{\n  a:\n  {\n    b:\n    [\n      1\n    ]\n  }\n}"
+        );
+        assert_eq!(
+            to_string_pretty_with_indent(&v, "\t").unwrap(),
+            "{\n\ta:\n\t{\n\t\tb:\n\t\t[\n\t\t\t1\n\t\t]\n\t}\n}\n"
+        );
+        assert_eq!(
+            to_string_pretty_with_indent(&v, "    ").unwrap(),
+            "{\n    a:\n    {\n        b:\n        [\n            1\n        ]\n    }\n}\n"
+        );
+        assert_eq!(
+            to_string_pretty_with_indent(&v, "  ").unwrap(),
+            to_string_pretty(&v).unwrap()
+        );
+        assert!(to_string_pretty_with_indent(&v, "--").is_err());
+
+        #[cfg(feature = "std")]
+        {
+            let mut vec = Vec::new();
+            to_writer_pretty_with_indent(&mut vec, &v, "\t").unwrap();
+            assert_eq!(
+                vec,
+                to_string_pretty_with_indent(&v, "\t").unwrap().into_bytes()
+            );
+        }
+    }
+
+    #[test]
+    fn multiline_strings() {
+        let mut v = Value::Object(Default::default());
+        v["ml"] = Value::String("first\n  second\n\nlast".to_string());
+        v["quotes"] = Value::String("a\n''' b".to_string());
+        v["nested"]["list"] = Value::Array(vec![Value::String("x\ny\n".to_string())]);
+        v["single"] = Value::String("\"quoted\"".to_string());
+        v["tab"] = Value::String("a\tb\nc".to_string());
+
+        let s = to_string(&v).unwrap();
+        assert!(s.contains("  ml:\n    '''\n    first\n      second\n\n    last\n    '''\n"));
+        assert!(s.contains("  quotes: \"a\\n''' b\"\n"));
+        assert!(s.contains("  single: '''\"quoted\"'''\n"));
+        assert!(s.contains("  tab: \"a\\tb\\nc\"\n"));
+        assert_eq!(from_str::<Value>(&s).unwrap(), v);
+    }
+
+    #[test]
+    fn quoteless() {
+        let mut v = Value::Object(Default::default());
+        for s in &[
+            "true",
+            " spaced ",
+            "123",
+            "-1.5e3",
+            "null // c",
+            "{a}",
+            "# x",
+            "",
+        ] {
+            v[*s] = Value::String(s.to_string());
+        }
+        v["plain"] = Value::String("look ma, no quotes".to_string());
+
+        let s = to_string(&v).unwrap();
+        assert!(s.contains("  true: \"true\"\n"));
+        assert!(s.contains("  \" spaced \": \" spaced \"\n"));
+        assert!(s.contains("  123: \"123\"\n"));
+        assert!(s.contains("  -1.5e3: \"-1.5e3\"\n"));
+        assert!(s.contains("  \"null // c\": \"null // c\"\n"));
+        assert!(s.contains("  \"{a}\": \"{a}\"\n"));
+        assert!(s.contains("  \"# x\": \"# x\"\n"));
+        assert!(s.contains("  \"\": \"\"\n"));
+        assert!(s.contains("  plain: look ma, no quotes\n"));
+        assert_eq!(from_str::<Value>(&s).unwrap(), v);
+
+        let mut vec = Vec::new();
+        v.serialize(&mut Serializer::new(&mut vec).quoteless(false))
+            .unwrap();
+        let s = String::from_utf8(vec).unwrap();
+        assert!(s.contains("  \"plain\": \"look ma, no quotes\"\n"));
+        assert_eq!(from_str::<Value>(&s).unwrap(), v);
+    }
+
+    #[test]
+    fn quoting_rules() {
+        for s in &[
+            " a",
+            "a ",
+            "\"a",
+            "'''",
+            "'a'",
+            "#",
+            "/*x",
+            "//",
+            "{",
+            "]",
+            ":a",
+            ",a",
+            "a\u{2028}",
+        ] {
+            assert!(needs_quotes(s), "{:?}", s);
+        }
+        for s in &["a b", "a:b", "a#b", "a'''", "ä"] {
+            assert!(!needs_quotes(s), "{:?}", s);
+        }
+        for s in &[
+            "true", "null  ", "false,", "true ]", "null # c", "true/* x", "null\n",
+        ] {
+            assert!(starts_with_keyword(s), "{:?}", s);
+        }
+        for s in &["trueish", "true x", "nul", "false, x\ny"] {
+            assert!(!starts_with_keyword(s), "{:?}", s);
+        }
+        assert!(needs_escape("a\\b") && needs_escape("\"") && !needs_escape("a'b"));
+        assert!(needs_escape_ml("a'''b") && needs_escape_ml("a\tb") && !needs_escape_ml("a\r\nb"));
+        for s in &[
+            "", "a b", "a:b", "a,b", "a//b", "a/*", "x'''", "q\"", "{", "'a",
+        ] {
+            assert!(needs_escape_name(s), "{:?}", s);
+        }
+        for s in &["a-b", "a/b", "ä", "a'b"] {
+            assert!(!needs_escape_name(s), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn comments_round_trip() {
+        let text = "{\n  # server settings\n  server:\n  {\n    // listen on all interfaces\n    host: 0.0.0.0\n    port: 8080 # default\n  }\n  users:\n  [\n    {\n      # admin\n      name: root\n    }\n  ]\n}";
+        let (mut v, comments): (Value, _) = from_str_with_comments(text).unwrap();
+        assert_eq!(to_string_with_comments(&v, &comments).unwrap(), text);
+
+        v["server"]["timeout"] = Value::U64(30);
+        let expected = text.replace(
+            "    port: 8080 # default\n",
+            "    port: 8080 # default\n    timeout: 30\n",
+        );
+        assert_eq!(to_string_with_comments(&v, &comments).unwrap(), expected);
+
+        let mut vec = Vec::new();
+        v.serialize(&mut Serializer::new(&mut vec).sort_keys(true).comments(comments))
+            .unwrap();
+        let sorted = String::from_utf8(vec).unwrap();
+        assert!(sorted.contains("    // listen on all interfaces\n    host: 0.0.0.0\n    port: 8080 # default\n    timeout: 30\n"));
+    }
+
+    #[test]
+    fn array_comments_round_trip() {
+        let text = "{\n  hosts:\n  [\n    // primary\n    a.example.com\n    # backup\n    b.example.com\n    /* for tests */\n    localhost\n  ]\n  ports:\n  [\n    80 # http\n    443 # https\n  ]\n}";
+        let (mut v, comments): (Value, _) = from_str_with_comments(text).unwrap();
+        assert_eq!(comments.get("/hosts/1").unwrap().before, vec!["# backup"]);
+        assert_eq!(to_string_with_comments(&v, &comments).unwrap(), text);
+
+        // the comments stay with the positions, not the values
+        v["hosts"].as_array_mut().unwrap().reverse();
+        let expected = text
+            .replace("a.example.com", "_")
+            .replace("localhost", "a.example.com")
+            .replace("_", "localhost");
+        assert_eq!(to_string_with_comments(&v, &comments).unwrap(), expected);
+    }
+
+    #[test]
+    fn styles_round_trip() {
+        let text = "{\n  list:\n  [\n    'it\\'s \"single\"'\n    '''one line'''\n    \"1\"\n  ]\n  name: quoteless text\n  title: \"quoted\"\n}";
+        let (mut v, styles): (Value, _) = from_str_with_styles(text).unwrap();
+        let write = |v: &Value, force_quotes: bool| {
+            let mut vec = Vec::new();
+            let ser = Serializer::new(&mut vec).string_styles(styles.clone());
+            v.serialize(&mut ser.force_quote_strings(force_quotes))
+                .unwrap();
+            String::from_utf8(vec).unwrap()
+        };
+        assert_eq!(write(&v, false), text);
+        assert_eq!(
+            write(&v, true),
+            text.replace("quoteless text", "\"quoteless text\"")
+                .replace("'it\\'s \"single\"'", "\"it's \\\"single\\\"\"")
+                .replace("'''one line'''", "\"one line\"")
+        );
+
+        // a style that cannot represent the new string is ignored
+        v["name"] = Value::String("true".to_string());
+        v["list"][1] = Value::String("ends in '".to_string());
+        let s = write(&v, false);
+        assert!(s.contains("  name: \"true\"\n"));
+        assert!(s.contains("    ends in '\n"));
+        assert_eq!(from_str::<Value>(&s).unwrap(), v);
+    }
+
+    #[test]
+    fn float_precision() {
+        let v = vec![0.1 + 0.2, 0.1, 2.0 / 3.0, 12345.678, -1.0e-7];
+        assert_eq!(
+            to_string(&v).unwrap(),
+            "[\n  0.30000000000000004\n  0.1\n  0.6666666666666666\n  12345.678\n  -1e-7\n]"
+        );
+
+        let mut vec = Vec::new();
+        v.serialize(&mut Serializer::new(&mut vec).float_precision(Some(3)))
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(vec).unwrap(),
+            "[\n  0.3\n  0.1\n  0.667\n  12300\n  -1e-7\n]"
+        );
+
+        let mut vec = Vec::new();
+        (1.0f32 / 3.0, 7u8)
+            .serialize(&mut Serializer::new(&mut vec).float_precision(Some(1)))
+            .unwrap();
+        assert_eq!(String::from_utf8(vec).unwrap(), "[\n  0.3\n  7\n]");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn streaming_seq() {
+        use std::cell::Cell;
+        use std::io;
+        use std::rc::Rc;
+
+        // counts the lines and bytes written, without keeping them
+        struct Counter(Rc<Cell<(usize, usize)>>);
+
+        impl io::Write for Counter {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                let (lines, bytes) = self.0.get();
+                let newlines = buf.iter().filter(|&&b| b == b'\n').count();
+                self.0.set((lines + newlines, bytes + buf.len()));
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        // the numbers up to `len`, produced one at a time
+        struct Lazy(Rc<Cell<(usize, usize)>>, usize);
+
+        impl Serialize for Lazy {
+            fn serialize<S>(&self, serializer: S) -> ::core::result::Result<S::Ok, S::Error>
+            where
+                S: ser::Serializer,
+            {
+                let written = self.0.clone();
+                // the elements before each one have been written already
+                serializer
+                    .collect_seq((0..self.1).inspect(move |&i| assert_eq!(written.get().0, i)))
+            }
+        }
+
+        let written = Rc::new(Cell::new((0, 0)));
+        let len = 100_000;
+        to_writer(&mut Counter(written.clone()), &Lazy(written.clone(), len)).unwrap();
+        // `[`, a line with `  i` for every element, and `\n]`
+        let bytes = 3 + (0..len).map(|i| 3 + i.to_string().len()).sum::<usize>();
+        assert_eq!(written.get(), (len + 1, bytes));
+    }
+
+    #[test]
+    fn non_finite() {
+        let v = Value::Array(vec![
+            Value::F64(f64::NAN),
+            Value::F64(f64::INFINITY),
+            Value::F64(f64::NEG_INFINITY),
+        ]);
+        assert_eq!(to_string(&v).unwrap(), "[\n  null\n  null\n  null\n]");
+
+        let mut vec = Vec::new();
+        v.serialize(&mut Serializer::new(&mut vec).non_finite(NonFiniteFloats::Token))
+            .unwrap();
+        let s = String::from_utf8(vec).unwrap();
+        assert_eq!(s, "[\n  NaN\n  Infinity\n  -Infinity\n]");
+
+        let mut de = Deserializer::new(s.bytes()).allow_non_finite(true);
+        let back: Vec<f64> = Deserialize::deserialize(&mut de).unwrap();
+        assert!(back[0].is_nan());
+        assert_eq!(back[1], f64::INFINITY);
+        assert_eq!(back[2], f64::NEG_INFINITY);
+
+        // without the option the tokens stay strings
+        let back: Vec<String> = from_str(&s).unwrap();
+        assert_eq!(back, vec!["NaN", "Infinity", "-Infinity"]);
+
+        let mut vec = Vec::new();
+        match f32::NAN.serialize(&mut Serializer::new(&mut vec).non_finite(NonFiniteFloats::Error))
+        {
+            Err(Error::Syntax(ErrorCode::FloatMustBeFinite, ..)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn sort_keys() {
+        let v1: Value = from_str("{b: 1, a: {y: 1, x: 2}, B: [{d: 1, c: 2}]}").unwrap();
+        let v2: Value = from_str("{B: [{c: 2, d: 1}], a: {x: 2, y: 1}, b: 1}").unwrap();
+        let sorted = to_string_sorted(&v1).unwrap();
+        assert_eq!(sorted, to_string_sorted(&v2).unwrap());
+        assert_eq!(
+            sorted,
+            "{\n  B:\n  [\n    {\n      c: 2\n      d: 1\n    }\n  ]\n  a:\n  {\n    x: 2\n    y: 1\n  }\n  b: 1\n}"
+        );
+    }
+
+    #[test]
+    fn align_colons() {
+        let to_string = |value: &Value| {
+            let mut out = Vec::new();
+            value
+                .serialize(&mut Serializer::new(&mut out).align_colons(true))
+                .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let value: Value =
+            from_str("{host: example.com\nmax_connections: 100\nport: 80\n}").unwrap();
+        assert_eq!(
+            to_string(&value),
+            "{\n  host:            example.com\n  max_connections: 100\n  port:            80\n}"
+        );
+
+        // nested objects are aligned on their own, values on the next line are not padded
+        let text = "{\n  a:   1\n  bb:\n  {\n    x:    true\n    yyyy: \"\"\n  }\n  ccc:\n    '''\n    two\n    lines\n    '''\n  d:   []\n}";
+        let value: Value = from_str(text).unwrap();
+        assert_eq!(to_string(&value), text);
+        assert_eq!(from_str::<Value>(&to_string(&value)).unwrap(), value);
+    }
+
+    #[test]
+    fn buffered_members() {
+        use alloc::collections::BTreeMap;
+        use raw::RawValue;
+
+        // sort_keys, compact_width and align_colons hold members back until the end of their
+        // object, which must not change how they are written
+        fn write<T: Serialize>(value: &T, mode: usize) -> String {
+            let mut out = Vec::new();
+            let ser = Serializer::new(&mut out);
+            let mut ser = match mode {
+                0 => ser.sort_keys(true),
+                1 => ser.align_colons(true),
+                _ => ser.compact_width(80),
+            };
+            value.serialize(&mut ser).unwrap();
+            String::from_utf8(out).unwrap()
+        }
+        let floats: BTreeMap<&str, Vec<f32>> = vec![("b", vec![0.1]), ("a", vec![0.5, 1e-3])]
+            .into_iter()
+            .collect();
+        let raw = |text: &str| RawValue::from_string(text.to_string()).unwrap();
+        let mut raws = BTreeMap::new();
+        raws.insert("raw", raw("[1, 2] # c"));
+        let mut short = BTreeMap::new();
+        short.insert("raw", raw("[1, 2]"));
+
+        for mode in 0..2 {
+            assert_eq!(
+                write(&floats, mode),
+                "{\n  a:\n  [\n    0.5\n    0.001\n  ]\n  b:\n  [\n    0.1\n  ]\n}"
+            );
+            assert_eq!(write(&raws, mode), "{\n  raw: [1, 2] # c\n}");
+        }
+        assert_eq!(write(&floats, 2), "{a: [0.5, 0.001], b: [0.1]}");
+        // a raw text that would not survive on one line keeps its object from being inlined
+        assert_eq!(write(&raws, 2), "{\n  raw: [1, 2] # c\n}");
+        assert_eq!(write(&short, 2), "{raw: [1, 2]}");
+    }
+
+    #[test]
+    fn canonical() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        let v1 = ObjectBuilder::new()
+            .insert("name", "two\nlines")
+            .insert("list", vec![1.5, -0.0, 1e21])
+            .insert_object("nested", |b| b.insert("b", true).insert("a", ()))
+            .unwrap();
+        let v2 = ObjectBuilder::new()
+            .insert_object("nested", |b| b.insert("a", ()).insert("b", true))
+            .insert("list", vec![1.5, 0.0, 1e21])
+            .insert("name", "two\nlines")
+            .unwrap();
+        #[cfg(feature = "preserve_order")]
+        assert!(to_string(&v1).unwrap() != to_string(&v2).unwrap());
+
+        let canonical = to_string_canonical(&v1).unwrap();
+        assert_eq!(
+            canonical,
+            r#"{"list":[1.5,0,1e+21],"name":"two\nlines","nested":{"a":null,"b":true}}"#
+        );
+        assert_eq!(canonical, to_string_canonical(&v2).unwrap());
+        let parsed: Value = from_str(&canonical).unwrap();
+        assert_eq!(to_string_canonical(&parsed).unwrap(), canonical);
+
+        let hash = |s: &str| {
+            let mut hasher = DefaultHasher::new();
+            s.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&canonical), hash(&to_string_canonical(&v2).unwrap()));
+
+        assert_eq!(to_string_canonical(&"a\u{1}b").unwrap(), r#""a\u0001b""#);
+        match to_string_canonical(&f64::NAN) {
+            Err(Error::Syntax(ErrorCode::FloatMustBeFinite, _, _, _)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn ascii_only() {
+        let v = ObjectBuilder::new()
+            .insert("bmp", "caf\u{e9} \u{20ac}")
+            .insert("char", '\u{e9}')
+            .insert("emoji \u{1f600}", "\u{1f600}\nsecond line")
+            .insert("plain", "abc")
+            .unwrap();
+        let mut vec = Vec::new();
+        v.serialize(&mut Serializer::new(&mut vec).ascii_only(true))
+            .unwrap();
+        let s = String::from_utf8(vec).unwrap();
+        assert!(s.is_ascii());
+        assert_eq!(
+            s,
+            "{\n  bmp: \"caf\\u00e9 \\u20ac\"\n  char: \"\\u00e9\"\n  \"emoji \\ud83d\\ude00\": \
+             \"\\ud83d\\ude00\\nsecond line\"\n  plain: abc\n}"
+        );
+        assert_eq!(from_str::<Value>(&s).unwrap(), v);
+
+        // the default keeps UTF-8
+        assert!(to_string(&v).unwrap().contains("caf\u{e9}"));
+    }
+
+    #[test]
+    fn trailing_newline() {
+        let v: Value = from_str("{a: [1, {}], b: x\n}").unwrap();
+        let pretty = "{\n  a:\n  [\n    1\n    {}\n  ]\n  b: x\n}";
+        assert_eq!(to_string(&v).unwrap(), pretty);
+        assert_eq!(to_string_pretty(&v).unwrap(), format!("{}\n", pretty));
+
+        let write = |v: &Value, compact: bool| {
+            let mut vec = Vec::new();
+            if compact {
+                v.serialize(&mut Serializer::compact(&mut vec).trailing_newline(true))
+            } else {
+                v.serialize(&mut Serializer::new(&mut vec).trailing_newline(true))
+            }
+            .unwrap();
+            String::from_utf8(vec).unwrap()
+        };
+        assert_eq!(write(&v, true), "{\"a\":[1,{}],\"b\":\"x\"}\n");
+        assert_eq!(write(&Value::U64(1), false), "1\n");
+        assert_eq!(write(&Value::Array(vec![]), false), "[]\n");
+        assert_eq!(v.to_string(), "{\"a\":[1,{}],\"b\":\"x\"}");
+    }
+
+    #[test]
+    fn byte_output() {
+        let v: Value = from_str("{name: \"é\", list: [1, 2]}").unwrap();
+        assert_eq!(to_vec(&v).unwrap(), to_string(&v).unwrap().into_bytes());
+        assert_eq!(
+            to_vec_pretty(&v).unwrap(),
+            to_string_pretty(&v).unwrap().into_bytes()
+        );
+
+        #[cfg(feature = "std")]
+        {
+            let mut out = Vec::new();
+            to_writer(&mut out, &v).unwrap();
+            assert_eq!(out, to_vec(&v).unwrap());
+            let mut out = Vec::new();
+            to_writer_pretty(&mut out, &v).unwrap();
+            assert_eq!(out, to_vec_pretty(&v).unwrap());
+            assert_eq!(out.last(), Some(&b'\n'));
+        }
+    }
+
+    #[test]
+    fn scalar_keys() {
+        use std::collections::BTreeMap;
+
+        let mut map = BTreeMap::new();
+        map.insert(-1, "minus one".to_string());
+        map.insert(42, "answer".to_string());
+        let expected = "{\n  -1: minus one\n  42: answer\n}";
+        assert_eq!(to_string(&map).unwrap(), expected);
+        assert_eq!(to_string_sorted(&map).unwrap(), expected);
+        let v = value::to_value(&map).unwrap();
+        assert_eq!(v["42"].as_str(), Some("answer"));
+
+        let mut map = BTreeMap::new();
+        map.insert(true, 'x');
+        map.insert(false, 'y');
+        assert_eq!(
+            to_string_canonical(&map).unwrap(),
+            "{\"false\":\"y\",\"true\":\"x\"}"
+        );
+
+        let mut map = BTreeMap::new();
+        map.insert((1, 2), 3);
+        for res in [to_string(&map), to_string_sorted(&map)].iter() {
+            match *res {
+                Err(Error::Syntax(ErrorCode::KeyMustBeAString, ..)) => {}
+                ref other => panic!("unexpected result {:?}", other),
+            }
+        }
+        assert!(value::to_value(&map).is_err());
+    }
+
+    #[test]
+    fn compact_width() {
+        let write = |v: &Value, width: usize| {
+            let mut vec = Vec::new();
+            v.serialize(&mut Serializer::new(&mut vec).compact_width(width))
+                .unwrap();
+            String::from_utf8(vec).unwrap()
+        };
+
+        let v: Value = from_str("[1, 2, 3]").unwrap();
+        assert_eq!(write(&v, 40), "[1, 2, 3]");
+        assert_eq!(write(&v, 8), "[\n  1\n  2\n  3\n]");
+
+        let v: Value = from_str(
+            "{\n  long: [1000000, 2000000, 3000000, 4000000, 5000000, 6000000]\n  point: {x: 1, y: 2}\n  tags: [\"a b\", \"c\"]\n}",
+        )
+        .unwrap();
+        let text = write(&v, 40);
+        assert_eq!(
+            text,
+            "{\n  long:\n  [\n    1000000\n    2000000\n    3000000\n    4000000\n    5000000\n    6000000\n  ]\n  point: {x: 1, y: 2}\n  tags: [\"a b\", \"c\"]\n}"
+        );
+        assert_eq!(from_str::<Value>(&text).unwrap(), v);
+
+        // the object does not fit, its nested collections do
+        let v: Value = from_str("{a: [[1, 2], {b: []}]}").unwrap();
+        assert_eq!(write(&v, 100), "{a: [[1, 2], {b: []}]}");
+        assert_eq!(
+            write(&v, 12),
+            "{\n  a:\n  [\n    [1, 2]\n    {b: []}\n  ]\n}"
+        );
+    }
+
+    #[test]
+    fn newline() {
+        let v: Value = from_str("{a: 1, b: \"x\\ny\"}").unwrap();
+        let write = |newline: Newline| {
+            let mut vec = Vec::new();
+            v.serialize(
+                &mut Serializer::new(&mut vec)
+                    .newline(newline)
+                    .trailing_newline(true),
+            )
+            .unwrap();
+            String::from_utf8(vec).unwrap()
+        };
+        let lf = write(Newline::Lf);
+        assert_eq!(lf, "{\n  a: 1\n  b:\n    '''\n    x\n    y\n    '''\n}\n");
+        assert_eq!(lf, to_string_pretty(&v).unwrap());
+        let crlf = write(Newline::Crlf);
+        assert_eq!(crlf, lf.replace("\n", "\r\n"));
+        assert_eq!(from_str::<Value>(&crlf).unwrap(), v);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn reuse_serializer() {
+        use de::StreamDeserializer;
+
+        let first: Value = from_str("{a: 1}").unwrap();
+        let second: Value = from_str("[\"x\", 2]").unwrap();
+
+        let mut ser = Serializer::new(std::io::BufWriter::new(Vec::new())).trailing_newline(true);
+        first.serialize(&mut ser).unwrap();
+        second.serialize(&mut ser).unwrap();
+        let text = String::from_utf8(ser.into_inner().into_inner().unwrap()).unwrap();
+        assert_eq!(
+            text,
+            format!(
+                "{}{}",
+                to_string_pretty(&first).unwrap(),
+                to_string_pretty(&second).unwrap()
+            )
+        );
+        let values: Vec<Value> = StreamDeserializer::new(text.bytes())
+            .map(|v| v.unwrap())
+            .collect();
+        assert_eq!(values, [first.clone(), second.clone()]);
+
+        let mut ser = Serializer::compact(Vec::new());
+        first.serialize(&mut ser).unwrap();
+        second.serialize(&mut ser).unwrap();
+        assert_eq!(ser.into_inner(), b"{\"a\":1}[\"x\",2]");
+    }
+
+    #[test]
+    fn quoteless_keys_and_values() {
+        let mut v = Value::Object(Default::default());
+        v["host-name"] = Value::String("main host".to_string());
+        v["notes"] = Value::String("a\nb".to_string());
+        let write = |keys: bool, values: bool| {
+            let mut vec = Vec::new();
+            let mut ser = Serializer::new(&mut vec)
+                .quoteless_keys(keys)
+                .quoteless_values(values);
+            v.serialize(&mut ser).unwrap();
+            String::from_utf8(vec).unwrap()
+        };
+        let notes = "'''\n    a\n    b\n    '''";
+        assert_eq!(
+            write(true, true),
+            format!("{{\n  host-name: main host\n  notes:\n    {}\n}}", notes)
+        );
+        assert_eq!(
+            write(true, false),
+            format!(
+                "{{\n  host-name: \"main host\"\n  notes:\n    {}\n}}",
+                notes
+            )
+        );
+        assert_eq!(
+            write(false, true),
+            format!(
+                "{{\n  \"host-name\": main host\n  \"notes\":\n    {}\n}}",
+                notes
+            )
+        );
+        assert_eq!(
+            write(false, false),
+            format!(
+                "{{\n  \"host-name\": \"main host\"\n  \"notes\":\n    {}\n}}",
+                notes
+            )
+        );
+        for &(keys, values) in [(true, true), (true, false), (false, true), (false, false)].iter() {
+            assert_eq!(from_str::<Value>(&write(keys, values)).unwrap(), v);
+        }
+
+        // `quoteless` sets both, and the later call wins
+        let mut vec = Vec::new();
+        let mut ser = Serializer::new(&mut vec)
+            .quoteless(false)
+            .quoteless_values(true);
+        v.serialize(&mut ser).unwrap();
+        assert_eq!(String::from_utf8(vec).unwrap(), write(false, true));
+    }
+
+    #[test]
+    fn force_quotes() {
+        let v: Value = from_str("{key: \"plain\", list: [1, \"x\\ny\"]}").unwrap();
+        let write = |keys: bool, strings: bool| {
+            let mut vec = Vec::new();
+            let ser = Serializer::new(&mut vec)
+                .force_quote_keys(keys)
+                .force_quote_strings(strings);
+            v.serialize(&mut ser.compact_width(0)).unwrap();
+            String::from_utf8(vec).unwrap()
+        };
+        assert_eq!(
+            write(true, false),
+            "{\n  \"key\": plain\n  \"list\":\n  [\n    1\n    \n      '''\n      x\n      y\n      '''\n  ]\n}"
+        );
+        assert_eq!(
+            write(false, true),
+            "{\n  key: \"plain\"\n  list:\n  [\n    1\n    \"x\\ny\"\n  ]\n}"
+        );
+        let json = write(true, true);
+        assert_eq!(
+            json,
+            "{\n  \"key\": \"plain\",\n  \"list\":\n  [\n    1,\n    \"x\\ny\"\n  ]\n}"
+        );
+    }
+}
@@ -0,0 +1,292 @@
+//! Hjson Spans
+//!
+//! This module provides `from_str_spanned`, which parses a document into a tree that records
+//! where every value and key is in the input, for tools like editors that point into the source.
+
+use alloc::rc::Rc;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::cell::Cell;
+use core::fmt;
+use core::result;
+
+use serde::de;
+
+use super::de::Deserializer;
+use super::error::{Error, Result};
+use super::value::{self, Map, Value};
+
+/// A value together with the byte offsets of its text in the input, so that
+/// `&input[start..end]` is how it was written.
+///
+/// Quoted strings include their quotes, quoteless strings, numbers and keywords end at their
+/// last character that is not whitespace. Arrays and objects span from their opening to their
+/// closing bracket, a root object without braces from its first key to the end of the input.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spanned<T> {
+    /// The offset of the first byte.
+    pub start: usize,
+    /// The offset after the last byte.
+    pub end: usize,
+    /// The value.
+    pub value: T,
+}
+
+/// A Hjson value like `Value`, whose array elements, object keys and object values are all
+/// `Spanned`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SpannedValue {
+    /// A null value.
+    Null,
+    /// A boolean.
+    Bool(bool),
+    /// A signed integer.
+    I64(i64),
+    /// An unsigned integer.
+    U64(u64),
+    /// A floating point number.
+    F64(f64),
+    /// A number by its literal, see `Value::Number`.
+    Number(String),
+    /// A string.
+    String(String),
+    /// An array.
+    Array(Vec<Spanned<SpannedValue>>),
+    /// An object, with its members in the order of the input. A key that appears more than once
+    /// is kept every time.
+    Object(Vec<(Spanned<String>, Spanned<SpannedValue>)>),
+}
+
+impl SpannedValue {
+    /// If the value is an object, returns the value of the member with `key` (the last one if
+    /// there are several). Otherwise, returns None.
+    pub fn get(&self, key: &str) -> Option<&Spanned<SpannedValue>> {
+        match *self {
+            SpannedValue::Object(ref members) => members
+                .iter()
+                .rev()
+                .find(|member| member.0.value == key)
+                .map(|member| &member.1),
+            _ => None,
+        }
+    }
+
+    /// Drops the spans, giving the `Value` that `from_str` parses from the same input.
+    pub fn into_value(self) -> Value {
+        match self {
+            SpannedValue::Null => Value::Null,
+            SpannedValue::Bool(v) => Value::Bool(v),
+            SpannedValue::I64(v) => Value::I64(v),
+            SpannedValue::U64(v) => Value::U64(v),
+            SpannedValue::F64(v) => Value::F64(v),
+            SpannedValue::Number(literal) => Value::Number(literal),
+            SpannedValue::String(v) => Value::String(v),
+            SpannedValue::Array(items) => Value::Array(
+                items
+                    .into_iter()
+                    .map(|item| item.value.into_value())
+                    .collect(),
+            ),
+            SpannedValue::Object(members) => {
+                let mut map = Map::new();
+                for (key, value) in members {
+                    map.insert(key.value, value.value.into_value());
+                }
+                Value::Object(map)
+            }
+        }
+    }
+}
+
+/// Parses a Hjson document like `from_str` does into a `Value`, but records the span of every
+/// value and key in the input.
+///
+/// ```rust
+/// use serde_hjson::spanned::from_str_spanned;
+///
+/// let text = "{\n  name: \"hjson\"\n  tags: [1, 2]\n}";
+/// let root = from_str_spanned(text).unwrap();
+/// let name = root.value.get("name").unwrap();
+/// assert_eq!(&text[name.start..name.end], "\"hjson\"");
+/// let tags = root.value.get("tags").unwrap();
+/// assert_eq!(&text[tags.start..tags.end], "[1, 2]");
+/// ```
+pub fn from_str_spanned(input: &str) -> Result<Spanned<SpannedValue>> {
+    // like `from_str`, a root object without braces is tried first
+    let parse = |root: bool| {
+        let mut de = Deserializer::from_str(input).root_braces_optional(root);
+        let spans = de.share_spans();
+        let value = de::DeserializeSeed::deserialize(ValueSeed(&spans), &mut de)?;
+        de.end()?;
+        Ok(value)
+    };
+    parse(true).or_else(|err: Error| parse(false).map_err(|_| err))
+}
+
+struct ValueSeed<'a>(&'a Rc<Cell<(usize, usize)>>);
+
+impl<'de, 'a> de::DeserializeSeed<'de> for ValueSeed<'a> {
+    type Value = Spanned<SpannedValue>;
+
+    fn deserialize<D>(self, deserializer: D) -> result::Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let value = value::deserialize_any_with_literals(deserializer, ValueVisitor(self.0))?;
+        // the deserializer has recorded the span once it was done with the value
+        let (start, end) = self.0.get();
+        Ok(Spanned { start, end, value })
+    }
+}
+
+struct KeySeed<'a>(&'a Rc<Cell<(usize, usize)>>);
+
+impl<'de, 'a> de::DeserializeSeed<'de> for KeySeed<'a> {
+    type Value = Spanned<String>;
+
+    fn deserialize<D>(self, deserializer: D) -> result::Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let value: String = de::Deserialize::deserialize(deserializer)?;
+        let (start, end) = self.0.get();
+        Ok(Spanned { start, end, value })
+    }
+}
+
+struct ValueVisitor<'a>(&'a Rc<Cell<(usize, usize)>>);
+
+impl<'de, 'a> de::Visitor<'de> for ValueVisitor<'a> {
+    type Value = SpannedValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any valid Hjson value")
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> result::Result<SpannedValue, E> {
+        Ok(SpannedValue::Bool(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> result::Result<SpannedValue, E> {
+        Ok(SpannedValue::I64(value))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> result::Result<SpannedValue, E> {
+        Ok(SpannedValue::U64(value))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> result::Result<SpannedValue, E> {
+        Ok(SpannedValue::F64(value))
+    }
+
+    // the Hjson deserializer hands over number literals as a newtype
+    #[cfg(feature = "arbitrary_precision")]
+    fn visit_newtype_struct<D>(self, deserializer: D) -> result::Result<SpannedValue, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let literal: String = de::Deserialize::deserialize(deserializer)?;
+        Ok(SpannedValue::Number(literal))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> result::Result<SpannedValue, E> {
+        Ok(SpannedValue::String(value.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, value: String) -> result::Result<SpannedValue, E> {
+        Ok(SpannedValue::String(value))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> result::Result<SpannedValue, E> {
+        Ok(SpannedValue::Null)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> result::Result<SpannedValue, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element_seed(ValueSeed(self.0))? {
+            items.push(item);
+        }
+        Ok(SpannedValue::Array(items))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> result::Result<SpannedValue, A::Error>
+    where
+        A: de::MapAccess<'de>,
+    {
+        let mut members = Vec::new();
+        while let Some(key) = map.next_key_seed(KeySeed(self.0))? {
+            let value = map.next_value_seed(ValueSeed(self.0))?;
+            members.push((key, value));
+        }
+        Ok(SpannedValue::Object(members))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use de::from_str;
+
+    // the text of every member of the root object: key, value
+    fn members<'a>(text: &'a str, root: &Spanned<SpannedValue>) -> Vec<(&'a str, &'a str)> {
+        match root.value {
+            SpannedValue::Object(ref members) => members
+                .iter()
+                .map(|(key, value)| (&text[key.start..key.end], &text[value.start..value.end]))
+                .collect(),
+            _ => panic!("not an object"),
+        }
+    }
+
+    #[test]
+    fn object_members() {
+        let text = "{\n  \"quoted key\": \"a \\\" b\"\n  plain  :  quoteless text   \n  num: 10.5e3 # comment\n  yes: true,no:false\n  nothing: null\n  list: [ 1, [], {a: x\n} ]\n  ml:\n    '''\n    line\n    '''\n  empty: {}\n}";
+        let root = from_str_spanned(text).unwrap();
+        assert_eq!((root.start, root.end), (0, text.len()));
+        assert_eq!(
+            members(text, &root),
+            vec![
+                ("\"quoted key\"", "\"a \\\" b\""),
+                ("plain", "quoteless text"),
+                ("num", "10.5e3"),
+                ("yes", "true"),
+                ("no", "false"),
+                ("nothing", "null"),
+                ("list", "[ 1, [], {a: x\n} ]"),
+                ("ml", "'''\n    line\n    '''"),
+                ("empty", "{}"),
+            ]
+        );
+
+        let list = root.value.get("list").unwrap();
+        match list.value {
+            SpannedValue::Array(ref items) => {
+                let texts: Vec<_> = items.iter().map(|i| &text[i.start..i.end]).collect();
+                assert_eq!(texts, vec!["1", "[]", "{a: x\n}"]);
+                let x = items[2].value.get("a").unwrap();
+                assert_eq!(&text[x.start..x.end], "x");
+            }
+            _ => panic!("not an array"),
+        }
+
+        assert_eq!(root.value.into_value(), from_str::<Value>(text).unwrap());
+    }
+
+    #[test]
+    fn roots() {
+        let text = "# config\nname: x\nport: 80 \n";
+        let root = from_str_spanned(text).unwrap();
+        assert_eq!((root.start, root.end), (9, text.len()));
+        assert_eq!(members(text, &root), vec![("name", "x"), ("port", "80")]);
+
+        let text = "  \"string\" ";
+        let root = from_str_spanned(text).unwrap();
+        assert_eq!((root.start, root.end), (2, 10));
+        assert_eq!(root.value, SpannedValue::String("string".to_string()));
+
+        assert!(from_str_spanned("{a: [1}").is_err());
+    }
+}
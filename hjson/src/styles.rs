@@ -0,0 +1,64 @@
+//! Hjson String Styles
+//!
+//! This module provides the type `StringStyles`, which records how the strings of a document were
+//! written, for tools like linters that check it and to write them back the same way after the
+//! parsed value has been edited. Styles are collected by `from_str_with_styles` and written by
+//! `Serializer::string_styles`.
+
+use alloc::collections::{btree_map, BTreeMap};
+use alloc::string::{String, ToString};
+
+/// How a string value was written in the input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StringStyle {
+    /// Without quotes, up to the end of the line.
+    Quoteless,
+    /// In double quotes.
+    Quoted,
+    /// In single quotes.
+    SingleQuoted,
+    /// In the `'''` form, usually over several lines.
+    Multiline,
+}
+
+/// The styles of the string values of a document, keyed by the JSON Pointer (see
+/// `Value::pointer`) of the value.
+///
+/// Only values are recorded, not keys. Strings that became another type while deserializing, like
+/// a quoteless `30s` read by `helpers::HumanDuration`, are recorded too.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StringStyles {
+    strings: BTreeMap<String, StringStyle>,
+}
+
+impl StringStyles {
+    /// Creates an empty set of styles.
+    pub fn new() -> Self {
+        StringStyles::default()
+    }
+
+    /// Returns true if no string has a style.
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Returns the style of the string at `pointer`.
+    pub fn get(&self, pointer: &str) -> Option<StringStyle> {
+        self.strings.get(pointer).cloned()
+    }
+
+    /// Sets the style of the string at `pointer`, returning the previous one.
+    pub fn insert(&mut self, pointer: &str, style: StringStyle) -> Option<StringStyle> {
+        self.strings.insert(pointer.to_string(), style)
+    }
+
+    /// Removes and returns the style of the string at `pointer`.
+    pub fn remove(&mut self, pointer: &str) -> Option<StringStyle> {
+        self.strings.remove(pointer)
+    }
+
+    /// Iterates over the strings that have a style, ordered by pointer.
+    pub fn iter(&self) -> btree_map::Iter<'_, String, StringStyle> {
+        self.strings.iter()
+    }
+}
@@ -0,0 +1,1080 @@
+//! Hjson Tokenizer
+//!
+//! This module provides `Tokenizer`, which splits a document into its tokens, including comments
+//! and whitespace, for tools like syntax highlighters that work with the text rather than with
+//! values. The strings, keys and scalars are read by the same lexer as the deserializer uses.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::char;
+use core::ops::Range;
+use core::str;
+
+use super::de::EscapePolicy;
+use super::error::{Error, ErrorCode, Result};
+use super::util::StringReader;
+use super::util::{Number, ParseNumber};
+
+/// The kind of a `Token`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    /// `{`
+    BeginObject,
+    /// `}`
+    EndObject,
+    /// `[`
+    BeginArray,
+    /// `]`
+    EndArray,
+    /// `:`
+    Colon,
+    /// `,`
+    Comma,
+    /// A quoted string like `"text"` or `'text'`, or a multiline string like `'''text'''`, as a
+    /// key or a value.
+    String,
+    /// A key without quotes, or a value without quotes that is no number and no keyword. A
+    /// quoteless value ends at the end of its line.
+    QuotelessString,
+    /// A number like `-1.5e3`.
+    Number,
+    /// `true`, `false` or `null`. They are values of their own rather than strings, so they are
+    /// told apart from quoteless strings, which highlighters tend to show differently. As a key,
+    /// or with more text on their line like `true story`, they are a `QuotelessString`.
+    Literal,
+    /// A comment, with its `#`, `//` or `/* */` markers.
+    Comment,
+    /// Spaces, tabs and newlines, and the byte order mark at the start of the input.
+    Whitespace,
+}
+
+/// A token of a Hjson document, found at `&input[start..end]`.
+///
+/// Quoteless strings, numbers and literals end at their last character that is not whitespace,
+/// line comments before the newline that ends them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Token {
+    /// What the token is.
+    pub kind: TokenKind,
+    /// The offset of the first byte.
+    pub start: usize,
+    /// The offset after the last byte.
+    pub end: usize,
+}
+
+impl Token {
+    /// The text of the token in `input`, the string it was read from.
+    pub fn text<'a>(&self, input: &'a str) -> &'a str {
+        &input[self.start..self.end]
+    }
+}
+
+/// An iterator over the tokens of a Hjson document.
+///
+/// Some tokens depend on where they are: `a b` is a quoteless string up to the end of the line as
+/// a value, but a key ends at its colon, and `1` is a number as a value but a quoteless string as
+/// a key. The tokenizer follows the brackets to tell keys from values, the other structure of the
+/// document is not checked, so that `{]` gives two tokens. A root object without braces is
+/// recognized when the input starts with a key followed by a colon, like the deserializer does.
+///
+/// The tokens are read like the deserializer reads them, so strings, keys and numbers follow its
+/// rules: a key without a colon after it, an unterminated string or block comment, or a byte
+/// order mark outside of a string anywhere but at the start is an error, after which the
+/// iterator ends.
+///
+/// ```rust
+/// use serde_hjson::tokenizer::{TokenKind, Tokenizer};
+///
+/// let text = "port: 8080 # default";
+/// let kinds: Vec<TokenKind> = Tokenizer::new(text).map(|t| t.unwrap().kind).collect();
+/// assert_eq!(
+///     kinds,
+///     vec![
+///         TokenKind::QuotelessString,
+///         TokenKind::Colon,
+///         TokenKind::Whitespace,
+///         TokenKind::Number,
+///         TokenKind::Whitespace,
+///         TokenKind::Comment,
+///     ]
+/// );
+/// ```
+pub struct Tokenizer<'a> {
+    src: &'a str,
+    lexer: Lexer<str::Bytes<'a>>,
+    // the end of the last token; the reader may be ahead, after the whitespace that ends a key or
+    // quoteless value
+    pos: usize,
+    // the containers around the position, true for objects; a root object without braces is
+    // pushed before its first key
+    stack: Vec<bool>,
+    // the next string is a key
+    key_next: bool,
+    // something other than whitespace and comments has been read
+    started: bool,
+    failed: bool,
+}
+
+impl<'a> Tokenizer<'a> {
+    /// Creates a tokenizer for `input`.
+    pub fn new(input: &'a str) -> Self {
+        let mut lexer = Lexer::new(input.bytes());
+        // comments are tokens of their own
+        lexer.rdr.deny_comments(true);
+        lexer.track_ends = true;
+        Tokenizer {
+            src: input,
+            lexer,
+            pos: 0,
+            stack: Vec::new(),
+            key_next: false,
+            started: false,
+            failed: false,
+        }
+    }
+
+    // After a value, an object member with a key may follow.
+    fn value_done(&mut self) {
+        self.key_next = self.stack.last() == Some(&true);
+    }
+
+    // Reads the next token, returning its kind and end.
+    fn lex(&mut self) -> Result<(TokenKind, usize)> {
+        let rest = &self.src[self.pos..];
+        let bytes = rest.as_bytes();
+
+        if b" \t\r\n".contains(&bytes[0]) || self.pos == 0 && rest.starts_with('\u{feff}') {
+            self.lexer.rdr.parse_whitespace()?;
+            return Ok((TokenKind::Whitespace, self.lexer.rdr.offset()));
+        }
+        if rest.starts_with('#') || rest.starts_with("//") || rest.starts_with("/*") {
+            let mut text = Vec::new();
+            self.lexer.rdr.parse_comment(&mut text)?;
+            // a line comment ends before the newline
+            let end = self.lexer.rdr.offset();
+            let end = if text.last() == Some(&b'\r') {
+                end - 1
+            } else {
+                end
+            };
+            return Ok((TokenKind::Comment, end));
+        }
+
+        if !self.started {
+            self.started = true;
+            if bytes[0] != b'{' && bytes[0] != b'[' && self.lexer.root_key_ahead()? {
+                self.stack.push(true);
+                self.key_next = true;
+            }
+        }
+        let punctuator = match bytes[0] {
+            b'{' => Some(TokenKind::BeginObject),
+            b'}' => Some(TokenKind::EndObject),
+            b'[' => Some(TokenKind::BeginArray),
+            b']' => Some(TokenKind::EndArray),
+            b':' => Some(TokenKind::Colon),
+            b',' => Some(TokenKind::Comma),
+            _ => None,
+        };
+        if let Some(kind) = punctuator {
+            match kind {
+                TokenKind::BeginObject => {
+                    self.stack.push(true);
+                    self.key_next = true;
+                }
+                TokenKind::BeginArray => {
+                    self.stack.push(false);
+                    self.key_next = false;
+                }
+                TokenKind::EndObject | TokenKind::EndArray => {
+                    self.stack.pop();
+                    self.value_done();
+                }
+                TokenKind::Colon => self.key_next = false,
+                _ => self.value_done(),
+            }
+            self.lexer.rdr.next_char()?;
+            return Ok((kind, self.lexer.rdr.offset()));
+        }
+
+        let quoted = bytes[0] == b'"' || bytes[0] == b'\'';
+        let start = self.lexer.rdr.offset();
+        if self.key_next {
+            self.key_next = false;
+            if quoted && !rest.starts_with("'''") {
+                return self.lex_string();
+            }
+            self.lexer.parse_keyname()?;
+            let end = self.lexer.token_end.take().unwrap_or(start);
+            return Ok((TokenKind::QuotelessString, end));
+        }
+        self.value_done();
+        if quoted {
+            return self.lex_string();
+        }
+        let kind = match self.lexer.parse_scalar()? {
+            Scalar::Null | Scalar::Bool(_) => TokenKind::Literal,
+            Scalar::Number(_) | Scalar::Decimal(_) => TokenKind::Number,
+            Scalar::Quoteless(_) => TokenKind::QuotelessString,
+            Scalar::Multiline => return Ok((TokenKind::String, self.lexer.rdr.offset())),
+        };
+        Ok((kind, self.lexer.token_end.take().unwrap_or(start)))
+    }
+
+    // Reads a quoted or multiline string.
+    fn lex_string(&mut self) -> Result<(TokenKind, usize)> {
+        if self.lexer.ml_string_ahead()? {
+            for _ in 0..3 {
+                self.lexer.rdr.eat_char();
+            }
+            self.lexer.parse_ml_string()?;
+        } else {
+            let quote = self.lexer.rdr.eat_char();
+            self.lexer.parse_string(quote)?;
+        }
+        Ok((TokenKind::String, self.lexer.rdr.offset()))
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<Token>;
+
+    fn next(&mut self) -> Option<Result<Token>> {
+        if self.failed || self.pos == self.src.len() {
+            return None;
+        }
+        let start = self.pos;
+        match self.lex() {
+            Ok((kind, end)) => {
+                self.pos = end;
+                Some(Ok(Token { kind, start, end }))
+            }
+            Err(err) => {
+                self.failed = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+// A value without quotes, as read by `Lexer::parse_scalar`.
+pub(crate) enum Scalar {
+    Null,
+    Bool(bool),
+    // a non-finite float, or an integer with a radix prefix
+    Number(Number),
+    // a decimal number, whose text is in `Lexer::number`
+    Decimal(Number),
+    // a quoteless string, the range of `buf` without the whitespace around it
+    Quoteless(Range<usize>),
+    // a multiline string where a quoteless value was expected, its text is in `buf`
+    Multiline,
+}
+
+// Reads the strings, keys and scalars of a document one at a time, for the `Deserializer`, which
+// parses the structure around them, and for `Tokenizer`. The `parse_` methods start after any
+// whitespace and leave the text of the string or key they read in `buf`, unescaped.
+pub(crate) struct Lexer<Iter: Iterator<Item = u8>> {
+    pub(crate) rdr: StringReader<Iter>,
+    pub(crate) buf: Vec<u8>,
+    // the text of the last `Scalar::Decimal`, without separators and leading zeros and in the
+    // form of JSON
+    number: String,
+    // the options of the `Deserializer` that concern single tokens, see its methods
+    pub(crate) strict: bool,
+    pub(crate) allow_non_finite: bool,
+    pub(crate) relaxed_escapes: bool,
+    pub(crate) escape_policy: EscapePolicy,
+    pub(crate) number_separators: bool,
+    pub(crate) radix_literals: bool,
+    pub(crate) leading_zeros: bool,
+    pub(crate) lenient_numbers: bool,
+    pub(crate) max_string_length: usize,
+    // set when `token_end` is needed
+    pub(crate) track_ends: bool,
+    // the end of the last key or quoteless value, whose trailing whitespace has been consumed
+    pub(crate) token_end: Option<usize>,
+}
+
+impl<Iter> Lexer<Iter>
+where
+    Iter: Iterator<Item = u8>,
+{
+    pub(crate) fn new(rdr: Iter) -> Self {
+        Lexer {
+            rdr: StringReader::new(rdr),
+            buf: Vec::with_capacity(128),
+            number: String::new(),
+            strict: false,
+            allow_non_finite: false,
+            relaxed_escapes: false,
+            escape_policy: EscapePolicy::Strict,
+            number_separators: false,
+            radix_literals: false,
+            leading_zeros: false,
+            lenient_numbers: false,
+            max_string_length: usize::MAX,
+            track_ends: false,
+            token_end: None,
+        }
+    }
+
+    pub(crate) fn number(&self) -> &str {
+        &self.number
+    }
+
+    fn check_string_length(&mut self) -> Result<()> {
+        if self.buf.len() > self.max_string_length {
+            return Err(self.rdr.error(ErrorCode::StringTooLong));
+        }
+        Ok(())
+    }
+
+    // Fails when `ch`, just consumed, starts a byte order mark: editors hide it, so a key or
+    // quoteless string would silently include it. The error points at the mark itself.
+    fn check_byte_order_mark(&mut self, ch: u8) -> Result<()> {
+        if ch == 0xef
+            && self.rdr.peek_next(0)? == Some(0xbb)
+            && self.rdr.peek_next(1)? == Some(0xbf)
+        {
+            return Err(Error::Syntax(
+                ErrorCode::UnexpectedByteOrderMark,
+                self.rdr.consumed_line(),
+                self.rdr.consumed_col(),
+                self.rdr.offset() - 1,
+            ));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn parse_keyname(&mut self) -> Result<()> {
+        // quotes for keys are optional in Hjson
+        // unless they include {}[],: or whitespace.
+        // assume whitespace was already eaten
+
+        self.buf.clear();
+        let start = self.rdr.offset();
+
+        let mut space: Option<usize> = None;
+        loop {
+            self.check_string_length()?;
+            let ch = self.rdr.next_char_or_null()?;
+            self.check_byte_order_mark(ch)?;
+
+            if ch == b':' {
+                if self.buf.len() == 0 {
+                    return Err(self.rdr.error(ErrorCode::Custom(
+                        "Found ':' but no key name (for an empty key name use quotes)".to_string(),
+                    )));
+                } else if space.is_some() && space.unwrap() != self.buf.len() {
+                    return Err(self.rdr.error(ErrorCode::Custom(
+                        "Found whitespace in your key name (use quotes to include)".to_string(),
+                    )));
+                }
+                self.rdr.uneat_char(ch);
+                if self.track_ends {
+                    self.token_end = Some(start + self.buf.len());
+                }
+                return Ok(());
+            } else if ch <= b' ' {
+                if ch == 0 {
+                    return Err(self.rdr.error(ErrorCode::EOFWhileParsingObject));
+                } else if space.is_none() {
+                    space = Some(self.buf.len());
+                }
+            } else if is_punctuator_char(ch) {
+                return Err(self.rdr.error(ErrorCode::Custom("Found a punctuator where a key name was expected (check your syntax or use quotes if the key name includes {}[],: or whitespace)".to_string())));
+            } else {
+                self.buf.push(ch);
+            }
+        }
+    }
+
+    // Looks ahead (without consuming anything) whether the root value starts with a key, quoted
+    // or not, followed by a colon. Empty input counts as an empty root object.
+    pub(crate) fn root_key_ahead(&mut self) -> Result<bool> {
+        let mut idx = 0;
+        match self.rdr.peek()? {
+            None => return Ok(true),
+            Some(quote) if quote == b'"' || quote == b'\'' && !self.ml_string_ahead()? => loop {
+                idx += 1;
+                match self.rdr.peek_next(idx)? {
+                    Some(b'\\') => idx += 1,
+                    Some(ch) if ch == quote => {
+                        idx += 1;
+                        break;
+                    }
+                    Some(b'\n') | None => return Ok(false),
+                    Some(_) => {}
+                }
+            },
+            Some(_) => loop {
+                match self.rdr.peek_next(idx)? {
+                    Some(ch) if ch > b' ' && !is_punctuator_char(ch) => idx += 1,
+                    _ => break,
+                }
+            },
+        }
+        while let Some(b' ') | Some(b'\t') = self.rdr.peek_next(idx)? {
+            idx += 1;
+        }
+        Ok(idx > 0 && self.rdr.peek_next(idx)? == Some(b':'))
+    }
+
+    pub(crate) fn parse_ident(&mut self, ident: &[u8]) -> Result<()> {
+        for c in ident {
+            if Some(*c) != self.rdr.next_char()? {
+                return Err(self.rdr.error(ErrorCode::ExpectedSomeIdent));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Reads a number, keyword or quoteless string, or a multiline string that starts where one of
+    // them was expected.
+    pub(crate) fn parse_scalar(&mut self) -> Result<Scalar> {
+        // Hjson strings can be quoteless
+        // returns string, true, false, or null.
+        self.buf.clear();
+        let start = self.rdr.offset();
+
+        let first = self.rdr.peek()?.unwrap();
+
+        if is_punctuator_char(first) {
+            return Err(self.rdr.error(ErrorCode::PunctuatorInQlString));
+        }
+        // only numbers and keywords are unquoted in JSON, `+` and `.` for `lenient_numbers`
+        if self.strict && !(first.is_ascii_digit() || b"-+.tfn".contains(&first)) {
+            return Err(self.rdr.error(ErrorCode::ExpectedSomeValue));
+        }
+
+        // the length is checked once the value turns out to be a string, numbers and keywords are
+        // not limited
+        loop {
+            let ch = self.rdr.next_char_or_null()?;
+            self.check_byte_order_mark(ch)?;
+
+            let is_eol = ch == b'\r' || ch == b'\n' || ch == b'\x00';
+            let is_comment = ch == b'#'
+                || ch == b'/' && {
+                    let next = self.rdr.peek_or_null()?;
+                    next == b'/' || next == b'*'
+                };
+            if is_eol || is_comment || ch == b',' || ch == b'}' || ch == b']' {
+                if self.track_ends {
+                    let len =
+                        str::from_utf8(&self.buf).map_or(self.buf.len(), |s| s.trim_end().len());
+                    self.token_end = Some(start + len);
+                }
+                if let Some(scalar) = self.keyword_or_number()? {
+                    self.rdr.uneat_char_or_null(ch);
+                    return Ok(scalar);
+                }
+                if self.strict {
+                    return Err(Error::Syntax(ErrorCode::ExpectedSomeValue, 0, 0, 0));
+                }
+                if is_eol {
+                    // remove any whitespace at the end (ignored in quoteless strings)
+                    let s = as_str(&self.buf)?;
+                    let skip = s.len() - s.trim_start().len();
+                    let end = skip + s.trim().len();
+                    if end - skip > self.max_string_length {
+                        return Err(self.rdr.error(ErrorCode::StringTooLong));
+                    }
+                    return Ok(Scalar::Quoteless(skip..end));
+                }
+            }
+            self.buf.push(ch);
+
+            if self.buf == b"'''" {
+                self.parse_ml_string()?;
+                return Ok(Scalar::Multiline);
+            }
+        }
+    }
+
+    // Reads the text of a scalar in `buf` as a keyword or number. Returns `None` when it is neither,
+    // so it is a quoteless string.
+    fn keyword_or_number(&mut self) -> Result<Option<Scalar>> {
+        let text = as_str(&self.buf)?.trim();
+        if self.allow_non_finite {
+            let value = match text {
+                "NaN" => Some(f64::NAN),
+                "Infinity" => Some(f64::INFINITY),
+                "-Infinity" => Some(f64::NEG_INFINITY),
+                _ => None,
+            };
+            if let Some(value) = value {
+                return Ok(Some(Scalar::Number(Number::F64(value))));
+            }
+        }
+        match self.buf[0] {
+            b'f' if text == "false" => return Ok(Some(Scalar::Bool(false))),
+            b'n' if text == "null" => return Ok(Some(Scalar::Null)),
+            b't' if text == "true" => return Ok(Some(Scalar::Bool(true))),
+            ch if b"-_+.".contains(&ch) || ch.is_ascii_digit() => {}
+            _ => return Ok(None),
+        }
+
+        if self.radix_literals {
+            if let Some(number) = parse_radix_literal(text)? {
+                return Ok(Some(Scalar::Number(number)));
+            }
+        }
+        let separated = if self.number_separators {
+            strip_number_separators(text)?
+        } else {
+            None
+        };
+        let digits = separated.as_ref().map_or(&self.buf[..], |d| d.as_bytes());
+        let lenient = lenient_number(as_str(digits)?.trim());
+        if lenient.is_some() && !self.lenient_numbers && self.strict {
+            return Err(Error::Syntax(ErrorCode::InvalidNumber, 0, 0, 0));
+        }
+        let digits = match lenient {
+            Some(ref number) if self.lenient_numbers => number.as_bytes(),
+            _ => digits,
+        };
+        let unpadded = strip_leading_zeros(as_str(digits)?.trim());
+        if unpadded.is_some() && !self.leading_zeros && self.strict {
+            return Err(Error::Syntax(ErrorCode::InvalidNumber, 0, 0, 0));
+        }
+        let digits = match unpadded {
+            Some(ref unpadded) if self.leading_zeros => unpadded.as_bytes(),
+            _ => digits,
+        };
+        let mut pn = ParseNumber::new(digits.iter().cloned());
+        match pn.parse(false) {
+            Ok(number) => {
+                self.number.clear();
+                self.number.push_str(as_str(digits)?.trim());
+                Ok(Some(Scalar::Decimal(number)))
+            }
+            Err(_) if separated.is_some() => Err(Error::Syntax(ErrorCode::InvalidNumber, 0, 0, 0)),
+            Err(_) => Ok(None), // not a number, continue
+        }
+    }
+
+    fn decode_hex_escape(&mut self) -> Result<u16> {
+        Ok(self.decode_hex_digits(4)? as u16)
+    }
+
+    // Reads exactly `count` hex digits.
+    fn decode_hex_digits(&mut self, count: usize) -> Result<u32> {
+        let mut i = 0;
+        let mut n = 0u32;
+        while i < count && !self.rdr.eof()? {
+            n = match (self.rdr.next_char_or_null()? as char).to_digit(16) {
+                Some(digit) => n * 16 + digit,
+                None => {
+                    return Err(self.rdr.error(ErrorCode::InvalidEscape));
+                }
+            };
+
+            i += 1;
+        }
+
+        // Error out if we didn't parse all digits.
+        if i != count {
+            return Err(self.rdr.error(ErrorCode::InvalidEscape));
+        }
+
+        Ok(n)
+    }
+
+    // Reads the `{...}` of a `\u{...}` escape, one to six hex digits naming a Unicode scalar
+    // value.
+    fn decode_braced_escape(&mut self) -> Result<char> {
+        self.rdr.eat_char();
+        let mut digits = 0;
+        let mut n = 0u32;
+        loop {
+            match self.rdr.next_char_or_null()? {
+                b'}' if digits > 0 => break,
+                ch => match (ch as char).to_digit(16) {
+                    Some(digit) if digits < 6 => n = n * 16 + digit,
+                    _ => return Err(self.rdr.error(ErrorCode::InvalidEscape)),
+                },
+            }
+            digits += 1;
+        }
+        char::from_u32(n).ok_or_else(|| self.rdr.error(ErrorCode::InvalidUnicodeCodePoint))
+    }
+
+    fn ml_skip_white(&mut self) -> Result<bool> {
+        match self.rdr.peek_or_null()? {
+            b' ' | b'\t' | b'\r' => {
+                self.rdr.eat_char();
+                return Ok(true);
+            }
+            _ => return Ok(false),
+        }
+    }
+
+    // Skips up to `indent` spaces and tabs at the start of a line.
+    fn ml_skip_indent(&mut self, indent: usize) -> Result<()> {
+        for _ in 0..indent {
+            match self.rdr.peek_or_null()? {
+                b' ' | b'\t' => {
+                    self.rdr.eat_char();
+                }
+                _ => break,
+            }
+        }
+        Ok(())
+    }
+
+    // Parses a multiline string after its opening `'''`. The indentation removed from its lines
+    // is the column of the opening quotes, that is the number of bytes before them on their line
+    // (the key, its colon and any whitespace). Every line after the first one loses up to that
+    // many leading spaces and tabs, a tab counting as one column like a space, and keeps the
+    // rest; a line that is indented less loses only its whitespace. Whitespace after the
+    // opening quotes up to the end of their line is dropped, and so are a `\r` before each
+    // newline and the newline right before the closing quotes.
+    pub(crate) fn parse_ml_string(&mut self) -> Result<()> {
+        self.buf.clear();
+
+        let mut triple = 0;
+
+        // the last of the opening quotes was just consumed
+        let indent = self.rdr.consumed_col() - 3;
+
+        // skip white/to (newline)
+        while self.ml_skip_white()? {}
+        if self.rdr.peek_or_null()? == b'\n' {
+            self.rdr.eat_char();
+            self.ml_skip_indent(indent)?;
+        }
+
+        // When parsing multiline string values, we must look for ' characters.
+        loop {
+            self.check_string_length()?;
+            if self.rdr.eof()? {
+                return Err(self.rdr.error(ErrorCode::EOFWhileParsingString));
+            } // todo error("Bad multiline string");
+            let ch = self.rdr.next_char_or_null()?;
+
+            if ch == b'\'' {
+                triple += 1;
+                if triple == 3 {
+                    if self.buf.last() == Some(&b'\n') {
+                        self.buf.pop();
+                    }
+                    //todo if (self.buf.slice(-1) === '\n') self.buf=self.buf.slice(0, -1); // remove last EOL
+                    return Ok(());
+                } else {
+                    continue;
+                }
+            }
+
+            while triple > 0 {
+                self.buf.push(b'\'');
+                triple -= 1;
+            }
+
+            if ch != b'\r' {
+                self.buf.push(ch);
+            }
+            if ch == b'\n' {
+                self.ml_skip_indent(indent)?;
+            }
+        }
+    }
+
+    // Whether the input continues with the `'''` that starts a multiline string.
+    pub(crate) fn ml_string_ahead(&mut self) -> Result<bool> {
+        Ok(self.rdr.peek()? == Some(b'\'')
+            && self.rdr.peek_next(1)? == Some(b'\'')
+            && self.rdr.peek_next(2)? == Some(b'\''))
+    }
+
+    // Parses a string up to the closing `quote`, after the opening one.
+    pub(crate) fn parse_string(&mut self, quote: u8) -> Result<()> {
+        self.buf.clear();
+
+        loop {
+            self.check_string_length()?;
+            let ch = match self.rdr.next_char()? {
+                Some(ch) => ch,
+                None => {
+                    return Err(self.rdr.error(ErrorCode::EOFWhileParsingString));
+                }
+            };
+
+            match ch {
+                ch if ch == quote => {
+                    return Ok(());
+                }
+                b'\\' => {
+                    let ch = match self.rdr.next_char()? {
+                        Some(ch) => ch,
+                        None => {
+                            return Err(self.rdr.error(ErrorCode::EOFWhileParsingString));
+                        }
+                    };
+
+                    match ch {
+                        b'"' => self.buf.push(b'"'),
+                        b'\'' if !self.strict => self.buf.push(b'\''),
+                        b'\\' => self.buf.push(b'\\'),
+                        b'/' => self.buf.push(b'/'),
+                        b'b' => self.buf.push(b'\x08'),
+                        b'f' => self.buf.push(b'\x0c'),
+                        b'n' => self.buf.push(b'\n'),
+                        b'r' => self.buf.push(b'\r'),
+                        b't' => self.buf.push(b'\t'),
+                        b'x' if self.relaxed_escapes => {
+                            let c = char::from_u32(self.decode_hex_digits(2)?).unwrap();
+                            self.buf.extend(c.encode_utf8(&mut [0; 4]).as_bytes());
+                        }
+                        b'u' if self.relaxed_escapes && self.rdr.peek()? == Some(b'{') => {
+                            let c = self.decode_braced_escape()?;
+                            self.buf.extend(c.encode_utf8(&mut [0; 4]).as_bytes());
+                        }
+                        b'u' => {
+                            let c = match self.decode_hex_escape()? {
+                                0xDC00..=0xDFFF => {
+                                    return Err(self
+                                        .rdr
+                                        .error(ErrorCode::LoneLeadingSurrogateInHexEscape));
+                                }
+
+                                // Non-BMP characters are encoded as a sequence of
+                                // two hex escapes, representing UTF-16 surrogates.
+                                n1 @ 0xD800..=0xDBFF => {
+                                    match (self.rdr.next_char()?, self.rdr.next_char()?) {
+                                        (Some(b'\\'), Some(b'u')) => (),
+                                        _ => {
+                                            return Err(self
+                                                .rdr
+                                                .error(ErrorCode::UnexpectedEndOfHexEscape));
+                                        }
+                                    }
+
+                                    let n2 = self.decode_hex_escape()?;
+
+                                    if n2 < 0xDC00 || n2 > 0xDFFF {
+                                        return Err(self
+                                            .rdr
+                                            .error(ErrorCode::LoneLeadingSurrogateInHexEscape));
+                                    }
+
+                                    let n = (((n1 - 0xD800) as u32) << 10 | (n2 - 0xDC00) as u32)
+                                        + 0x1_0000;
+
+                                    match char::from_u32(n as u32) {
+                                        Some(c) => c,
+                                        None => {
+                                            return Err(self
+                                                .rdr
+                                                .error(ErrorCode::InvalidUnicodeCodePoint));
+                                        }
+                                    }
+                                }
+
+                                n => match char::from_u32(n as u32) {
+                                    Some(c) => c,
+                                    None => {
+                                        return Err(self
+                                            .rdr
+                                            .error(ErrorCode::InvalidUnicodeCodePoint));
+                                    }
+                                },
+                            };
+
+                            self.buf.extend(c.encode_utf8(&mut [0; 4]).as_bytes());
+                        }
+                        _ => match self.escape_policy {
+                            EscapePolicy::Strict => {
+                                return Err(self.rdr.error(ErrorCode::InvalidEscape));
+                            }
+                            EscapePolicy::PassThrough => self.buf.extend_from_slice(&[b'\\', ch]),
+                            EscapePolicy::DropBackslash => self.buf.push(ch),
+                        },
+                    }
+                }
+                ch => {
+                    self.buf.push(ch);
+                }
+            }
+        }
+    }
+}
+
+// Strings are collected byte by byte. The reader already rejects input that is not UTF-8, so this
+// should not fail, but it avoids handing out an invalid `&str` if it ever does.
+pub(crate) fn as_str(buf: &[u8]) -> Result<&str> {
+    str::from_utf8(buf)
+        .map_err(|_| Error::FromUtf8(String::from_utf8(buf.to_vec()).unwrap_err(), 0, 0, 0))
+}
+
+fn is_punctuator_char(ch: u8) -> bool {
+    match ch {
+        b'{' | b'}' | b'[' | b']' | b',' | b':' => true,
+        _ => false,
+    }
+}
+
+// Parses an integer with a `0x`, `0o` or `0b` prefix, see `allow_radix_literals`. Returns `None`
+// for text without such a prefix, which is then parsed as usual.
+fn parse_radix_literal(text: &str) -> Result<Option<Number>> {
+    let (negative, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let radix = match unsigned.get(..2) {
+        Some("0x") => 16,
+        Some("0o") => 8,
+        Some("0b") => 2,
+        _ => return Ok(None),
+    };
+    let digits = &unsigned[2..];
+    // from_str_radix would also take a leading `+`
+    if digits.is_empty() || !digits.chars().all(|ch| ch.is_digit(radix)) {
+        return Err(Error::Syntax(ErrorCode::InvalidNumber, 0, 0, 0));
+    }
+    let magnitude = u64::from_str_radix(digits, radix)
+        .map_err(|_| Error::Syntax(ErrorCode::InvalidNumber, 0, 0, 0))?;
+    if !negative {
+        Ok(Some(Number::U64(magnitude)))
+    } else if magnitude <= i64::MAX as u64 + 1 {
+        Ok(Some(Number::I64((magnitude as i64).wrapping_neg())))
+    } else {
+        Err(Error::Syntax(ErrorCode::InvalidNumber, 0, 0, 0))
+    }
+}
+
+// Removes the leading zeros of a number but the one before a decimal point, see
+// `allow_leading_zeros`. Returns `None` when there are none to remove.
+fn strip_leading_zeros(text: &str) -> Option<String> {
+    let (sign, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", text),
+    };
+    let zeros = unsigned.len() - unsigned.trim_start_matches('0').len();
+    let rest = &unsigned[zeros..];
+    if zeros == 0 || !unsigned[1..].starts_with(|ch: char| ch.is_ascii_digit()) {
+        return None;
+    }
+    if rest.starts_with(|ch: char| ch.is_ascii_digit()) {
+        Some(format!("{}{}", sign, rest))
+    } else {
+        Some(format!("{}0{}", sign, rest))
+    }
+}
+
+// Writes a number in one of the forms of `lenient_numbers` the way JSON does, like `0.5e3` for
+// `+.5e3`. Returns `None` for text in none of those forms, which is then parsed as usual.
+fn lenient_number(text: &str) -> Option<String> {
+    let (sign, plus, unsigned) = match text.as_bytes().first() {
+        Some(b'+') => ("", true, &text[1..]),
+        Some(b'-') => ("-", false, &text[1..]),
+        _ => ("", false, text),
+    };
+    let (mantissa, exponent) =
+        unsigned.split_at(unsigned.find(['e', 'E']).unwrap_or(unsigned.len()));
+    let (int, frac) = match mantissa.find('.') {
+        Some(i) => (&mantissa[..i], Some(&mantissa[i + 1..])),
+        None => (mantissa, None),
+    };
+    let digits = |part: &str| part.bytes().all(|ch| ch.is_ascii_digit());
+    let frac_digits = frac.is_none_or(digits);
+    if !digits(int) || !frac_digits || int.is_empty() && frac.is_none_or(str::is_empty) {
+        return None;
+    }
+    let bare_point = int.is_empty() || frac == Some("");
+    if !plus && !bare_point {
+        return None;
+    }
+    let int = if int.is_empty() { "0" } else { int };
+    let frac = match frac {
+        Some("") => ".0".to_string(),
+        Some(frac) => format!(".{}", frac),
+        None => String::new(),
+    };
+    Some(format!("{}{}{}{}", sign, int, frac, exponent))
+}
+
+// Removes the `_` between the digits of a number, see `allow_number_separators`. Returns `None`
+// for text without separators or that is not made of number characters, which is then parsed as
+// usual.
+fn strip_number_separators(text: &str) -> Result<Option<String>> {
+    let is_number_char = |ch: u8| ch.is_ascii_digit() || b"_.eE+-".contains(&ch);
+    let bytes = text.as_bytes();
+    if !text.contains('_')
+        || !bytes.iter().any(u8::is_ascii_digit)
+        || !bytes.iter().cloned().all(is_number_char)
+    {
+        return Ok(None);
+    }
+    for (i, _) in text.match_indices('_') {
+        let digit_before = i > 0 && bytes[i - 1].is_ascii_digit();
+        let digit_after = bytes.get(i + 1).is_some_and(u8::is_ascii_digit);
+        if !digit_before || !digit_after {
+            return Err(Error::Syntax(ErrorCode::InvalidNumber, 0, 0, 0));
+        }
+    }
+    Ok(Some(text.replace('_', "")))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use error::{Error, ErrorCode};
+
+    fn tokens(text: &str) -> Vec<(TokenKind, &str)> {
+        Tokenizer::new(text)
+            .map(|token| {
+                let token = token.unwrap();
+                (token.kind, token.text(text))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn document_with_comment() {
+        use self::TokenKind::*;
+        let text = "{\n  // server\n  host: local host\n  \"port\": 80, list: [1, true, x]\n}";
+        assert_eq!(
+            tokens(text),
+            vec![
+                (BeginObject, "{"),
+                (Whitespace, "\n  "),
+                (Comment, "// server"),
+                (Whitespace, "\n  "),
+                (QuotelessString, "host"),
+                (Colon, ":"),
+                (Whitespace, " "),
+                (QuotelessString, "local host"),
+                (Whitespace, "\n  "),
+                (String, "\"port\""),
+                (Colon, ":"),
+                (Whitespace, " "),
+                (Number, "80"),
+                (Comma, ","),
+                (Whitespace, " "),
+                (QuotelessString, "list"),
+                (Colon, ":"),
+                (Whitespace, " "),
+                (BeginArray, "["),
+                (Number, "1"),
+                (Comma, ","),
+                (Whitespace, " "),
+                (Literal, "true"),
+                (Comma, ","),
+                (Whitespace, " "),
+                (QuotelessString, "x]"),
+                (Whitespace, "\n"),
+                (EndObject, "}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn roots_strings_and_comments() {
+        use self::TokenKind::*;
+        assert_eq!(
+            tokens("# top\n1: '''\n  a\n  ''' /* c */\nb:2"),
+            vec![
+                (Comment, "# top"),
+                (Whitespace, "\n"),
+                (QuotelessString, "1"),
+                (Colon, ":"),
+                (Whitespace, " "),
+                (String, "'''\n  a\n  '''"),
+                (Whitespace, " "),
+                (Comment, "/* c */"),
+                (Whitespace, "\n"),
+                (QuotelessString, "b"),
+                (Colon, ":"),
+                (Number, "2"),
+            ]
+        );
+        assert_eq!(
+            tokens("\u{feff}a: b"),
+            vec![
+                (Whitespace, "\u{feff}"),
+                (QuotelessString, "a"),
+                (Colon, ":"),
+                (Whitespace, " "),
+                (QuotelessString, "b"),
+            ]
+        );
+        // a root value, not an object
+        assert_eq!(tokens("a b"), vec![(QuotelessString, "a b")]);
+        // brackets are not matched
+        assert_eq!(tokens("{]"), vec![(BeginObject, "{"), (EndArray, "]")]);
+        assert_eq!(
+            tokens("[-1.5e3,null]"),
+            vec![
+                (BeginArray, "["),
+                (Number, "-1.5e3"),
+                (Comma, ","),
+                (Literal, "null"),
+                (EndArray, "]"),
+            ]
+        );
+
+        assert_eq!(
+            tokens("['a\\'b',''''''\n]"),
+            vec![
+                (BeginArray, "["),
+                (String, "'a\\'b'"),
+                (Comma, ","),
+                (String, "''''''"),
+                (Whitespace, "\n"),
+                (EndArray, "]"),
+            ]
+        );
+
+        // the tokens cover the input
+        let text = "{a: [{b: \"\\\"\"}, 2 // x\n ]\n}\n";
+        let texts: Vec<&str> = tokens(text).iter().map(|token| token.1).collect();
+        assert_eq!(texts.concat(), text);
+    }
+
+    #[test]
+    fn errors() {
+        // reported like the deserializer does
+        let res: Result<Vec<Token>> = Tokenizer::new("{\n  a: \"text").collect();
+        match res {
+            Err(Error::Syntax(ErrorCode::EOFWhileParsingString, 2, 10, 12)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        match ::de::from_str::<::value::Value>("{\n  a: \"text") {
+            Err(Error::Syntax(ErrorCode::EOFWhileParsingString, 2, 10, 12)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        let mut tokenizer = Tokenizer::new("1 /* open");
+        assert!(tokenizer.next().unwrap().is_ok());
+        assert!(tokenizer.next().unwrap().is_ok());
+        match tokenizer.next() {
+            Some(Err(Error::Syntax(ErrorCode::EOFWhileParsingComment, 1, 9, 9))) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert!(tokenizer.next().is_none());
+    }
+
+    #[test]
+    fn byte_order_marks() {
+        let res: Result<Vec<Token>> = Tokenizer::new("a: x\u{feff}y").collect();
+        match res {
+            Err(Error::Syntax(ErrorCode::UnexpectedByteOrderMark, 1, 5, 4)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        let res: Result<Vec<Token>> = Tokenizer::new("a: 1\n\u{feff}b: 2").collect();
+        match res {
+            Err(Error::Syntax(ErrorCode::UnexpectedByteOrderMark, 2, 1, 5)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(
+            tokens("[\"\u{feff}\"]"),
+            vec![
+                (TokenKind::BeginArray, "["),
+                (TokenKind::String, "\"\u{feff}\""),
+                (TokenKind::EndArray, "]"),
+            ]
+        );
+    }
+}
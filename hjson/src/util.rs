@@ -1,5 +1,6 @@
-use std::io;
-use std::str;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::str;
 
 use super::error::{Error, ErrorCode, Result};
 
@@ -7,7 +8,29 @@ pub struct StringReader<Iter: Iterator<Item = u8>> {
     iter: Iter,
     line: usize,
     col: usize,
+    read: usize,
     ch: Vec<u8>,
+    // line and column of each character in `ch` and of the last one consumed, so positions do
+    // not move when the parser looks ahead
+    ch_pos: Vec<(usize, usize)>,
+    eaten_pos: (usize, usize),
+    // the input is checked to be UTF-8 as it is read: continuation bytes still expected, the
+    // allowed range of the next one, the position where the character started and its bytes
+    utf8_pending: u8,
+    utf8_next: (u8, u8),
+    utf8_start: (usize, usize, usize),
+    utf8_seq: Vec<u8>,
+    // no byte above 0x7f has been read yet; while this holds the bytes skip the UTF-8 checks,
+    // as most documents are ASCII
+    ascii: bool,
+    // comments skipped by parse_whitespace, with a flag telling whether they started on their
+    // own line; only collected when enabled
+    comments: Option<Vec<(bool, String)>>,
+    // when set, parse_whitespace stops at comments instead of skipping them
+    deny_comments: bool,
+    // when set, parse_whitespace skips a byte order mark at the start of the input
+    strip_bom: bool,
+    max_input_size: usize,
 }
 
 impl<Iter> StringReader<Iter>
@@ -20,27 +43,136 @@ where
             iter: iter,
             line: 1,
             col: 0,
+            read: 0,
             ch: Vec::new(),
+            ch_pos: Vec::new(),
+            eaten_pos: (1, 0),
+            utf8_pending: 0,
+            utf8_next: (0x80, 0xbf),
+            utf8_start: (0, 0, 0),
+            utf8_seq: Vec::new(),
+            ascii: true,
+            comments: None,
+            deny_comments: false,
+            strip_bom: true,
+            max_input_size: usize::MAX,
         }
     }
 
-    fn next(&mut self) -> Option<io::Result<u8>> {
-        match self.iter.next() {
+    fn next(&mut self) -> Option<Result<u8>> {
+        if self.read >= self.max_input_size {
+            return match self.iter.next() {
+                Some(_) => Some(Err(Error::Syntax(
+                    ErrorCode::InputTooLarge,
+                    self.line,
+                    self.col,
+                    self.read,
+                ))),
+                None => None,
+            };
+        }
+        let next = self.iter.next();
+        if self.ascii {
+            match next {
+                Some(c) if c < 0x80 => {
+                    self.read += 1;
+                    if c == b'\n' {
+                        self.line += 1;
+                        self.col = 0;
+                    } else {
+                        self.col += 1;
+                    }
+                    return Some(Ok(c));
+                }
+                // the rest of the input takes the checked path
+                Some(_) => self.ascii = false,
+                None => return None,
+            }
+        }
+        match next {
+            None if self.utf8_pending > 0 => Some(Err(self.utf8_error())),
             None => None,
-            Some(b'\n') => {
+            Some(b'\n') if self.utf8_pending == 0 => {
+                self.read += 1;
                 self.line += 1;
                 self.col = 0;
                 Some(Ok(b'\n'))
             }
             Some(c) => {
+                self.read += 1;
                 self.col += 1;
-                Some(Ok(c))
+                match self.check_utf8(c) {
+                    Ok(()) => Some(Ok(c)),
+                    Err(err) => Some(Err(err)),
+                }
             }
         }
     }
 
+    fn check_utf8(&mut self, c: u8) -> Result<()> {
+        if self.utf8_pending > 0 {
+            self.utf8_seq.push(c);
+            if c < self.utf8_next.0 || c > self.utf8_next.1 {
+                return Err(self.utf8_error());
+            }
+            self.utf8_pending -= 1;
+            self.utf8_next = (0x80, 0xbf);
+            return Ok(());
+        }
+        if c < 0x80 {
+            return Ok(());
+        }
+        self.utf8_start = (self.line, self.col, self.read - 1);
+        self.utf8_seq.clear();
+        self.utf8_seq.push(c);
+        // the ranges exclude overlong encodings, surrogates and code points above U+10FFFF
+        let (pending, next) = match c {
+            0xc2..=0xdf => (1, (0x80, 0xbf)),
+            0xe0 => (2, (0xa0, 0xbf)),
+            0xe1..=0xec | 0xee..=0xef => (2, (0x80, 0xbf)),
+            0xed => (2, (0x80, 0x9f)),
+            0xf0 => (3, (0x90, 0xbf)),
+            0xf1..=0xf3 => (3, (0x80, 0xbf)),
+            0xf4 => (3, (0x80, 0x8f)),
+            _ => return Err(self.utf8_error()),
+        };
+        self.utf8_pending = pending;
+        self.utf8_next = next;
+        Ok(())
+    }
+
+    // Reported at the start of the invalid sequence, with its bytes up to the one that made it
+    // invalid.
+    fn utf8_error(&mut self) -> Error {
+        let (line, col, offset) = self.utf8_start;
+        // the ranges above match what the standard library accepts, so this is always an error
+        let error = String::from_utf8(self.utf8_seq.clone()).unwrap_err();
+        Error::FromUtf8(error, line, col, offset)
+    }
+
     pub fn pos(&mut self) -> (usize, usize) {
-        (self.line, self.col)
+        self.ch_pos
+            .first()
+            .cloned()
+            .unwrap_or((self.line, self.col))
+    }
+
+    /// Line of the last character that was consumed, a newline counts for the line it ends.
+    pub fn consumed_line(&self) -> usize {
+        match self.eaten_pos {
+            (line, 0) if line > 1 => line - 1,
+            (line, _) => line,
+        }
+    }
+
+    /// Column of the last character that was consumed, counted in bytes from 1.
+    pub fn consumed_col(&self) -> usize {
+        self.eaten_pos.1
+    }
+
+    /// Byte offset of the next character that has not been consumed yet.
+    pub fn offset(&self) -> usize {
+        self.read - self.ch.len()
     }
 
     pub fn eof(&mut self) -> Result<bool> {
@@ -50,8 +182,11 @@ where
     pub fn peek_next(&mut self, idx: usize) -> Result<Option<u8>> {
         while self.ch.len() <= idx {
             match self.next() {
-                Some(Err(err)) => return Err(Error::Io(err)),
-                Some(Ok(ch)) => self.ch.push(ch),
+                Some(Err(err)) => return Err(err),
+                Some(Ok(ch)) => {
+                    self.ch.push(ch);
+                    self.ch_pos.push((self.line, self.col));
+                }
                 None => return Ok(None),
             }
         }
@@ -71,11 +206,20 @@ where
     }
 
     pub fn eat_char(&mut self) -> u8 {
+        self.eaten_pos = self.ch_pos.remove(0);
         self.ch.remove(0)
     }
 
     pub fn uneat_char(&mut self, ch: u8) {
         self.ch.insert(0, ch);
+        self.ch_pos.insert(0, self.eaten_pos);
+    }
+
+    /// Undoes `next_char_or_null`: the null it returns at the end of the input is not put back.
+    pub fn uneat_char_or_null(&mut self, ch: u8) {
+        if ch != b'\x00' {
+            self.uneat_char(ch);
+        }
     }
 
     pub fn next_char(&mut self) -> Result<Option<u8>> {
@@ -85,8 +229,11 @@ where
                 Ok(Some(ch))
             }
             None => match self.next() {
-                Some(Err(err)) => Err(Error::Io(err)),
-                Some(Ok(ch)) => Ok(Some(ch)),
+                Some(Err(err)) => Err(err),
+                Some(Ok(ch)) => {
+                    self.eaten_pos = (self.line, self.col);
+                    Ok(Some(ch))
+                }
                 None => Ok(None),
             },
         }
@@ -96,50 +243,133 @@ where
         Ok(self.next_char()?.unwrap_or(b'\x00'))
     }
 
-    fn eat_line(&mut self) -> Result<()> {
+    fn eat_line(&mut self, text: &mut Vec<u8>) -> Result<()> {
         loop {
             match self.peek()? {
                 Some(b'\n') | None => return Ok(()),
                 _ => {}
             }
+            text.push(self.eat_char());
+        }
+    }
+
+    /// Make `parse_whitespace` stop at comments, so they are reported as unexpected characters.
+    pub fn deny_comments(&mut self, deny: bool) {
+        self.deny_comments = deny;
+    }
+
+    /// Fail with `ErrorCode::InputTooLarge` when more than `size` bytes are read.
+    pub fn max_input_size(&mut self, size: usize) {
+        self.max_input_size = size;
+    }
+
+    /// Make `parse_whitespace` skip a UTF-8 byte order mark at the start of the input (the
+    /// default). The mark does not count as a column, but byte offsets still include it.
+    pub fn strip_bom(&mut self, strip: bool) {
+        self.strip_bom = strip;
+    }
+
+    fn skip_bom(&mut self) -> Result<()> {
+        for (idx, &byte) in b"\xef\xbb\xbf".iter().enumerate() {
+            if self.peek_next(idx)? != Some(byte) {
+                return Ok(());
+            }
+        }
+        for _ in 0..3 {
             self.eat_char();
         }
+        self.col = 0;
+        self.eaten_pos = (1, 0);
+        Ok(())
+    }
+
+    /// Start keeping the comments skipped by `parse_whitespace`.
+    pub fn collect_comments(&mut self) {
+        self.comments = Some(Vec::new());
+    }
+
+    /// Returns the comments collected since the last call, each with a flag telling whether it
+    /// started on its own line.
+    pub fn take_comments(&mut self) -> Vec<(bool, String)> {
+        match self.comments {
+            Some(ref mut comments) => ::core::mem::take(comments),
+            None => Vec::new(),
+        }
     }
 
     pub fn parse_whitespace(&mut self) -> Result<()> {
+        if self.strip_bom && self.offset() == 0 {
+            self.skip_bom()?;
+        }
+        // a quoteless value before the comment has already consumed the newline that ended it
+        let mut own_line = self.consumed_line() < self.eaten_pos.0;
+        let mut text = Vec::new();
         loop {
             match self.peek_or_null()? {
-                b' ' | b'\n' | b'\t' | b'\r' => {
+                b'\n' => {
                     self.eat_char();
+                    own_line = true;
                 }
-                b'#' => self.eat_line()?,
-                b'/' => {
-                    match self.peek_next(1)? {
-                        Some(b'/') => self.eat_line()?,
-                        Some(b'*') => {
-                            self.eat_char();
-                            self.eat_char();
-                            while !(self.peek()?.unwrap_or(b'*') == b'*'
-                                && self.peek_next(1)?.unwrap_or(b'/') == b'/')
-                            {
-                                self.eat_char();
-                            }
-                            self.eat_char();
-                            self.eat_char();
-                        },
-                        Some(_) => { return Ok(()); },
-                        None => return Err(self.error(ErrorCode::TrailingCharacters)), //todo
+                b' ' | b'\t' | b'\r' => {
+                    self.eat_char();
+                }
+                b'#' | b'/' if self.deny_comments => return Ok(()),
+                b'#' | b'/' => {
+                    if !self.parse_comment(&mut text)? {
+                        return Ok(());
                     }
                 }
                 _ => {
                     return Ok(());
                 }
             }
+            if !text.is_empty() {
+                if let Some(ref mut comments) = self.comments {
+                    let comment = String::from_utf8_lossy(&text);
+                    comments.push((own_line, comment.trim_end().to_string()));
+                }
+                text.clear();
+            }
         }
     }
 
+    /// Consumes the comment at the current position into `text`. Returns false, consuming
+    /// nothing, when there is none.
+    pub fn parse_comment(&mut self, text: &mut Vec<u8>) -> Result<bool> {
+        match self.peek_or_null()? {
+            b'#' => self.eat_line(text)?,
+            b'/' => {
+                match self.peek_next(1)? {
+                    Some(b'/') => self.eat_line(text)?,
+                    Some(b'*') => {
+                        text.push(self.eat_char());
+                        text.push(self.eat_char());
+                        // like in the other Hjson implementations block comments do not nest,
+                        // the first */ ends the comment
+                        loop {
+                            match self.peek()? {
+                                Some(b'*') if self.peek_next(1)? == Some(b'/') => break,
+                                Some(_) => {
+                                    text.push(self.eat_char());
+                                }
+                                None => return Err(self.error(ErrorCode::EOFWhileParsingComment)),
+                            }
+                        }
+                        text.push(self.eat_char());
+                        text.push(self.eat_char());
+                    }
+                    Some(_) => return Ok(false),
+                    None => return Err(self.error(ErrorCode::TrailingCharacters)), //todo
+                }
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
     pub fn error(&mut self, reason: ErrorCode) -> Error {
-        Error::Syntax(reason, self.line, self.col)
+        let (line, col) = self.pos();
+        Error::Syntax(reason, line, col, self.offset())
     }
 }
 
@@ -158,7 +388,7 @@ pub struct ParseNumber<Iter: Iterator<Item = u8>> {
 //     ($e:expr) => {
 //         match $e {
 //             Some(v) => v,
-//             None => { return Err(Error::Syntax(ErrorCode::InvalidNumber, 0, 0)); }
+//             None => { return Err(Error::Syntax(ErrorCode::InvalidNumber, 0, 0, 0)); }
 //         }
 //     }
 // }
@@ -175,7 +405,10 @@ impl<Iter: Iterator<Item = u8>> ParseNumber<Iter> {
     pub fn parse(&mut self, stop_at_next: bool) -> Result<Number> {
         match self.try_parse() {
             Ok(()) => {
-                self.rdr.parse_whitespace()?;
+                // skip whitespace but not comments, they are checked for below
+                while let b' ' | b'\t' | b'\r' | b'\n' = self.rdr.peek_or_null()? {
+                    self.rdr.eat_char();
+                }
 
                 let mut ch = self.rdr.next_char_or_null()?;
 
@@ -204,17 +437,20 @@ impl<Iter: Iterator<Item = u8>> ParseNumber<Iter> {
                             }
                         }
 
+                        // like JSON, integers that do not fit into 64 bits become floats
                         if is_float {
                             Ok(Number::F64(res.parse::<f64>().unwrap()))
+                        } else if res.starts_with("-") {
+                            Ok(res
+                                .parse::<i64>()
+                                .map_or_else(|_| Number::F64(res.parse().unwrap()), Number::I64))
                         } else {
-                            if res.starts_with("-") {
-                                Ok(Number::I64(res.parse::<i64>()?))
-                            } else {
-                                Ok(Number::U64(res.parse::<u64>()?))
-                            }
+                            Ok(res
+                                .parse::<u64>()
+                                .map_or_else(|_| Number::F64(res.parse().unwrap()), Number::U64))
                         }
                     }
-                    _ => Err(Error::Syntax(ErrorCode::InvalidNumber, 0, 0)),
+                    _ => Err(Error::Syntax(ErrorCode::InvalidNumber, 0, 0, 0)),
                 }
             }
             Err(e) => Err(e),
@@ -234,7 +470,7 @@ impl<Iter: Iterator<Item = u8>> ParseNumber<Iter> {
             // There can be only one leading '0'.
             match self.rdr.peek_or_null()? {
                 b'0'..=b'9' => {
-                    return Err(Error::Syntax(ErrorCode::InvalidNumber, 0, 0));
+                    return Err(Error::Syntax(ErrorCode::InvalidNumber, 0, 0, 0));
                 }
                 _ => {}
             }
@@ -248,21 +484,21 @@ impl<Iter: Iterator<Item = u8>> ParseNumber<Iter> {
                 }
                 b'.' => {
                     if !has_value {
-                        return Err(Error::Syntax(ErrorCode::InvalidNumber, 0, 0));
+                        return Err(Error::Syntax(ErrorCode::InvalidNumber, 0, 0, 0));
                     }
                     self.rdr.eat_char();
                     return self.try_decimal();
                 }
                 b'e' | b'E' => {
                     if !has_value {
-                        return Err(Error::Syntax(ErrorCode::InvalidNumber, 0, 0));
+                        return Err(Error::Syntax(ErrorCode::InvalidNumber, 0, 0, 0));
                     }
                     self.rdr.eat_char();
                     return self.try_exponent();
                 }
                 _ => {
                     if !has_value {
-                        return Err(Error::Syntax(ErrorCode::InvalidNumber, 0, 0));
+                        return Err(Error::Syntax(ErrorCode::InvalidNumber, 0, 0, 0));
                     }
                     return Ok(());
                 }
@@ -279,7 +515,7 @@ impl<Iter: Iterator<Item = u8>> ParseNumber<Iter> {
                 self.result.push(c);
             }
             _ => {
-                return Err(Error::Syntax(ErrorCode::InvalidNumber, 0, 0));
+                return Err(Error::Syntax(ErrorCode::InvalidNumber, 0, 0, 0));
             }
         };
 
@@ -322,7 +558,7 @@ impl<Iter: Iterator<Item = u8>> ParseNumber<Iter> {
                 self.result.push(c);
             }
             _ => {
-                return Err(Error::Syntax(ErrorCode::InvalidNumber, 0, 0));
+                return Err(Error::Syntax(ErrorCode::InvalidNumber, 0, 0, 0));
             }
         };
 
@@ -340,3 +576,57 @@ impl<Iter: Iterator<Item = u8>> ParseNumber<Iter> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // a byte the reader returned with its line, column and offset
+    type Read = (u8, usize, usize, usize);
+
+    // every byte the reader returns, and the error that ends the input
+    fn read_all(text: &[u8], ascii: bool) -> (Vec<Read>, Option<Error>) {
+        let mut rdr = StringReader::new(text.iter().cloned());
+        rdr.ascii = ascii;
+        let mut bytes = Vec::new();
+        loop {
+            match rdr.next() {
+                Some(Ok(c)) => bytes.push((c, rdr.line, rdr.col, rdr.read)),
+                Some(Err(err)) => return (bytes, Some(err)),
+                None => return (bytes, None),
+            }
+        }
+    }
+
+    #[test]
+    fn ascii_fast_path() {
+        let inputs: [&[u8]; 6] = [
+            b"{\n  a: 1\n}\n",
+            "key: caf\u{e9}\nnext: \u{1F600}\n".as_bytes(),
+            "\u{e9}first".as_bytes(),
+            b"ascii then \xff invalid",
+            b"cut \xe2\x82",
+            b"",
+        ];
+        for text in inputs.iter() {
+            assert_eq!(read_all(text, true), read_all(text, false), "{:?}", text);
+        }
+
+        // ASCII input never reaches check_utf8, the first other byte switches to it for good
+        let mut rdr = StringReader::new("{\n  a: 1\n}\n".bytes());
+        while let Some(c) = rdr.next() {
+            c.unwrap();
+        }
+        assert!(rdr.ascii);
+        assert_eq!(rdr.utf8_seq, b"");
+        let mut rdr = StringReader::new("ab\u{e9}cd".bytes());
+        let mut fast = Vec::new();
+        while let Some(c) = rdr.next() {
+            fast.push((c.unwrap(), rdr.ascii));
+        }
+        let bytes = [(b'a', true), (b'b', true), (0xc3, false), (0xa9, false)];
+        assert_eq!(fast[..4], bytes);
+        assert_eq!(rdr.utf8_seq, b"\xc3\xa9");
+        assert!(!rdr.ascii);
+    }
+}
@@ -33,23 +33,31 @@
 //! ```
 
 #[cfg(not(feature = "preserve_order"))]
-use std::collections::{btree_map, BTreeMap};
+use alloc::collections::btree_map;
+use alloc::collections::BTreeMap;
 
 #[cfg(feature = "preserve_order")]
 use linked_hash_map::{self, LinkedHashMap};
 
-use std::fmt;
-use std::io;
-use std::str;
-use std::vec;
+use alloc::borrow::ToOwned;
+use alloc::string::{String, ToString};
+use alloc::vec::{self, Vec};
+use core::convert::TryFrom;
+use core::fmt;
+use core::iter;
+use core::ops;
+use core::str;
 
 use num_traits::NumCast;
 
 use serde::{de, ser};
 
+use diff::{self, ValueDiff};
 use error::{Error, ErrorCode};
+use io;
+use raw;
 
-type Result<T, E = Error> = std::result::Result<T, E>;
+type Result<T, E = Error> = core::result::Result<T, E>;
 
 /// Represents a key/value type.
 #[cfg(not(feature = "preserve_order"))]
@@ -65,7 +73,7 @@ pub type MapIntoIter<K, V> = btree_map::IntoIter<K, V>;
 #[cfg(feature = "preserve_order")]
 pub type MapIntoIter<K, V> = linked_hash_map::IntoIter<K, V>;
 
-fn map_with_capacity<K: std::hash::Hash + Eq, V>(size: Option<usize>) -> Map<K, V> {
+fn map_with_capacity<K: core::hash::Hash + Eq, V>(size: Option<usize>) -> Map<K, V> {
     #[cfg(not(feature = "preserve_order"))]
     {
         let _ = size;
@@ -78,7 +86,109 @@ fn map_with_capacity<K: std::hash::Hash + Eq, V>(size: Option<usize>) -> Map<K,
     }
 }
 
+// Newtype name used to pass number literals through serde. With `arbitrary_precision` the Hjson
+// deserializer hands literals only to the types that ask for them with a newtype of this name, see
+// `deserialize_any_with_literals`.
+pub(crate) const NUMBER_TOKEN: &str = "$serde_hjson::private::Number";
+
+// Deserializes like `deserialize_any`, except that the Hjson deserializer visits the literals of
+// the numbers of the value with `visit_newtype_struct` where they would be written differently
+// after parsing.
+pub(crate) fn deserialize_any_with_literals<'de, D, V>(
+    deserializer: D,
+    visitor: V,
+) -> Result<V::Value, D::Error>
+where
+    D: de::Deserializer<'de>,
+    V: de::Visitor<'de>,
+{
+    #[cfg(feature = "arbitrary_precision")]
+    return deserializer.deserialize_newtype_struct(NUMBER_TOKEN, LiteralsRequest(visitor));
+    #[cfg(not(feature = "arbitrary_precision"))]
+    deserializer.deserialize_any(visitor)
+}
+
+// Other deserializers answer the request with the value itself, which is handed on as it is.
+#[cfg(feature = "arbitrary_precision")]
+struct LiteralsRequest<V>(V);
+
+#[cfg(feature = "arbitrary_precision")]
+macro_rules! forward_visits {
+    ($($method:ident($ty:ty))*) => {
+        $(
+            #[inline]
+            fn $method<E: de::Error>(self, value: $ty) -> Result<V::Value, E> {
+                self.0.$method(value)
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "arbitrary_precision")]
+impl<'de, V: de::Visitor<'de>> de::Visitor<'de> for LiteralsRequest<V> {
+    type Value = V::Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.expecting(f)
+    }
+
+    #[inline]
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<V::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self.0)
+    }
+
+    forward_visits! {
+        visit_bool(bool) visit_i8(i8) visit_i16(i16) visit_i32(i32) visit_i64(i64)
+        visit_i128(i128) visit_u8(u8) visit_u16(u16) visit_u32(u32) visit_u64(u64)
+        visit_u128(u128) visit_f32(f32) visit_f64(f64) visit_char(char) visit_str(&str)
+        visit_borrowed_str(&'de str) visit_string(String) visit_bytes(&[u8])
+        visit_borrowed_bytes(&'de [u8]) visit_byte_buf(Vec<u8>)
+    }
+
+    #[inline]
+    fn visit_none<E: de::Error>(self) -> Result<V::Value, E> {
+        self.0.visit_none()
+    }
+
+    #[inline]
+    fn visit_unit<E: de::Error>(self) -> Result<V::Value, E> {
+        self.0.visit_unit()
+    }
+
+    #[inline]
+    fn visit_some<D>(self, deserializer: D) -> Result<V::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        self.0.visit_some(deserializer)
+    }
+
+    #[inline]
+    fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<V::Value, A::Error> {
+        self.0.visit_seq(seq)
+    }
+
+    #[inline]
+    fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<V::Value, A::Error> {
+        self.0.visit_map(map)
+    }
+
+    #[inline]
+    fn visit_enum<A: de::EnumAccess<'de>>(self, data: A) -> Result<V::Value, A::Error> {
+        self.0.visit_enum(data)
+    }
+}
+
 /// Represents a Hjson/JSON value
+///
+/// Integers are held as long as they fit into 64 bits; `i128` and `u128` values beyond that can be
+/// serialized and deserialized directly to and from Hjson, but only converted to a `Value` with
+/// the `arbitrary_precision` feature, which makes them a `Value::Number`. Without the feature
+/// such an integer in Hjson input becomes a `Value::F64` like in JSON, which only holds it
+/// approximately.
 #[derive(Clone, PartialEq)]
 pub enum Value {
     /// Represents a JSON null value
@@ -96,6 +206,11 @@ pub enum Value {
     /// Represents a JSON floating point number
     F64(f64),
 
+    /// Represents a JSON number by its literal, for numbers that the other variants cannot hold
+    /// without changing how they are written. Parsing and `to_value` only create it with the
+    /// `arbitrary_precision` feature.
+    Number(String),
+
     /// Represents a JSON string
     String(String),
 
@@ -107,6 +222,43 @@ pub enum Value {
 }
 
 impl Value {
+    /// Returns an object without members.
+    pub fn empty_object() -> Value {
+        Value::Object(Map::new())
+    }
+
+    /// Returns an array without elements.
+    pub fn empty_array() -> Value {
+        Value::Array(Vec::new())
+    }
+
+    /// Returns an object with the members of `members`, where a later member replaces an
+    /// earlier one with the same key.
+    ///
+    /// ```rust
+    /// use serde_hjson::Value;
+    ///
+    /// let server = Value::object_from(vec![
+    ///     ("host".to_string(), Value::String("localhost".to_string())),
+    ///     ("ports".to_string(), Value::array_from(vec![Value::U64(80), Value::U64(443)])),
+    /// ]);
+    /// assert_eq!(server["ports"][1].as_u64(), Some(443));
+    /// ```
+    pub fn object_from<I>(members: I) -> Value
+    where
+        I: IntoIterator<Item = (String, Value)>,
+    {
+        Value::Object(members.into_iter().collect())
+    }
+
+    /// Returns an array with the elements of `items`.
+    pub fn array_from<I>(items: I) -> Value
+    where
+        I: IntoIterator<Item = Value>,
+    {
+        Value::Array(items.into_iter().collect())
+    }
+
     /// If the `Value` is an Object, returns the value associated with the provided key.
     /// Otherwise, returns None.
     pub fn find<'a>(&'a self, key: &str) -> Option<&'a Value> {
@@ -116,6 +268,32 @@ impl Value {
         }
     }
 
+    /// If the `Value` is an Object, returns the value of the first key that matches `key` when
+    /// ASCII case is ignored. Otherwise, returns None.
+    ///
+    /// When several keys only differ by case the first one in iteration order wins, which is the
+    /// insertion order with the `preserve_order` feature and the sorted order without it.
+    pub fn get_ci<'a>(&'a self, key: &str) -> Option<&'a Value> {
+        match *self {
+            Value::Object(ref map) => map
+                .iter()
+                .find(|&(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Like `get_ci`, but returns a mutable reference.
+    pub fn get_ci_mut<'a>(&'a mut self, key: &str) -> Option<&'a mut Value> {
+        match *self {
+            Value::Object(ref mut map) => map
+                .iter_mut()
+                .find(|&(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
     /// Attempts to get a nested Value Object for each key in `keys`.
     /// If any key is found not to exist, find_path will return None.
     /// Otherwise, it will return the `Value` associated with the final key.
@@ -173,6 +351,67 @@ impl Value {
         Some(target)
     }
 
+    /// Looks up a value by a path of keys and array indexes separated by `.`, like
+    /// `servers.0.host`. A numeric segment indexes into an array and is a key for an object; an
+    /// empty path returns `self`. Keys that contain a `.` cannot be reached this way, use
+    /// `pointer` for them.
+    pub fn get_path<'a>(&'a self, path: &str) -> Option<&'a Value> {
+        if path.is_empty() {
+            return Some(self);
+        }
+        let mut target = self;
+        for segment in path.split('.') {
+            target = match *target {
+                Value::Object(ref map) => map.get(segment)?,
+                Value::Array(ref list) => {
+                    if segment.starts_with('+') {
+                        return None;
+                    }
+                    list.get(segment.parse::<usize>().ok()?)?
+                }
+                _ => return None,
+            };
+        }
+        Some(target)
+    }
+
+    /// Looks up a value by a JSON Pointer and returns a mutable reference to
+    /// that value.
+    ///
+    /// See `pointer` for the pointer syntax. If there is no such value `None`
+    /// is returned.
+    pub fn pointer_mut<'a>(&'a mut self, pointer: &str) -> Option<&'a mut Value> {
+        fn parse_index(s: &str) -> Option<usize> {
+            if s.starts_with('+') || (s.starts_with('0') && s.len() != 1) {
+                return None;
+            }
+            s.parse().ok()
+        }
+        if pointer.is_empty() {
+            return Some(self);
+        }
+        if !pointer.starts_with('/') {
+            return None;
+        }
+        let mut target = self;
+        for escaped_token in pointer.split('/').skip(1) {
+            let token = escaped_token.replace("~1", "/").replace("~0", "~");
+            let target_opt = match *target {
+                Value::Object(ref mut map) => map.get_mut(&token[..]),
+                Value::Array(ref mut list) => {
+                    parse_index(&token[..]).and_then(move |x| list.get_mut(x))
+                }
+                _ => return None,
+            };
+            if let Some(t) = target_opt {
+                target = t;
+            } else {
+                return None;
+            }
+        }
+        Some(target)
+    }
+
     /// If the `Value` is an Object, performs a depth-first search until
     /// a value associated with the provided key is found. If no value is found
     /// or the `Value` is not an Object, returns None.
@@ -194,6 +433,415 @@ impl Value {
         }
     }
 
+    /// Deep merges `other` into this `Value`.
+    ///
+    /// If both values are objects they are merged key by key, recursing into nested objects
+    /// present on both sides. In every other case (including arrays, null and differing types)
+    /// `self` is replaced by `other`.
+    pub fn merge(&mut self, other: Value) {
+        self.merge_with(other, false)
+    }
+
+    /// Deep merges `other` into this `Value` like `merge`, but keeps the existing value
+    /// wherever `other` is a `Value::Null`.
+    pub fn merge_preserve(&mut self, other: Value) {
+        self.merge_with(other, true)
+    }
+
+    /// Sorts the keys of this object and of all nested objects (byte-wise), also those inside
+    /// arrays; the order of array elements is kept. Without the `preserve_order` feature objects
+    /// are always sorted and this does nothing.
+    pub fn sort_keys(&mut self) {
+        match *self {
+            Value::Array(ref mut list) => {
+                for value in list {
+                    value.sort_keys();
+                }
+            }
+            Value::Object(ref mut map) => {
+                #[cfg(feature = "preserve_order")]
+                {
+                    let mut entries: Vec<_> =
+                        ::core::mem::replace(map, Map::new()).into_iter().collect();
+                    entries.sort_by(|a, b| a.0.cmp(&b.0));
+                    map.extend(entries);
+                }
+                for (_, value) in map.iter_mut() {
+                    value.sort_keys();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// If the `Value` is an object, keeps only the members for which `f` returns true, in their
+    /// order. `f` gets each value mutably, so it can also change the members it keeps. Only the
+    /// members of this object are visited, not those of nested objects. Does nothing for other
+    /// values.
+    ///
+    /// ```rust
+    /// use serde_hjson::Value;
+    ///
+    /// let mut config: Value = serde_hjson::from_str("user: admin\n_password: secret").unwrap();
+    /// config.retain(|key, _| !key.starts_with('_'));
+    /// assert_eq!(config, serde_hjson::from_str::<Value>("user: admin").unwrap());
+    /// ```
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&str, &mut Value) -> bool,
+    {
+        if let Value::Object(ref mut map) = *self {
+            let members = ::core::mem::replace(map, Map::new());
+            for (key, mut value) in members {
+                if f(&key, &mut value) {
+                    map.insert(key, value);
+                }
+            }
+        }
+    }
+
+    /// If the `Value` is an array, keeps only the elements for which `f` returns true, like
+    /// `retain` does for objects. Does nothing for other values.
+    pub fn retain_elements<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut Value) -> bool,
+    {
+        if let Value::Array(ref mut list) = *self {
+            let items = ::core::mem::take(list);
+            list.extend(items.into_iter().filter_map(
+                |mut item| {
+                    if f(&mut item) {
+                        Some(item)
+                    } else {
+                        None
+                    }
+                },
+            ));
+        }
+    }
+
+    /// Calls `f` on every value in the tree, this one included, in post-order: the elements of an
+    /// array and the values of an object are visited first, in their order, and then the array
+    /// or object itself, so `f` sees containers whose contents are already changed. Values that
+    /// `f` puts into the tree are not visited again.
+    ///
+    /// ```rust
+    /// use serde_hjson::Value;
+    ///
+    /// let mut v: Value = serde_hjson::from_str("home: $HOME\npaths: [\"$HOME/bin\"]").unwrap();
+    /// v.map_values(|value| {
+    ///     if let Value::String(ref mut s) = *value {
+    ///         *s = s.replace("$HOME", "/home/me");
+    ///     }
+    /// });
+    /// assert_eq!(v["paths"][0].as_str(), Some("/home/me/bin"));
+    /// ```
+    pub fn map_values<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut Value),
+    {
+        self.map_values_with(&mut f)
+    }
+
+    fn map_values_with<F>(&mut self, f: &mut F)
+    where
+        F: FnMut(&mut Value),
+    {
+        match *self {
+            Value::Array(ref mut list) => {
+                for value in list {
+                    value.map_values_with(f);
+                }
+            }
+            Value::Object(ref mut map) => {
+                for (_, value) in map.iter_mut() {
+                    value.map_values_with(f);
+                }
+            }
+            _ => {}
+        }
+        f(self)
+    }
+
+    fn merge_with(&mut self, other: Value, preserve: bool) {
+        match (self, other) {
+            (&mut Value::Object(ref mut map), Value::Object(other)) => {
+                for (key, value) in other {
+                    if let Some(target) = map.get_mut(&key) {
+                        target.merge_with(value, preserve);
+                        continue;
+                    }
+                    map.insert(key, value);
+                }
+            }
+            (_, Value::Null) if preserve => {}
+            (target, other) => *target = other,
+        }
+    }
+
+    /// Compares this `Value` (the old one) with `other` (the new one) and describes what changed,
+    /// recursing into objects and arrays.
+    ///
+    /// ```rust
+    /// use serde_hjson::Value;
+    /// use serde_hjson::diff::ValueDiff;
+    ///
+    /// let old: Value = serde_hjson::from_str("{a: 1, b: 2}").unwrap();
+    /// let new: Value = serde_hjson::from_str("{a: 1, c: 3}").unwrap();
+    /// match old.diff(&new) {
+    ///     ValueDiff::Object(members) => {
+    ///         assert_eq!(members["b"], ValueDiff::Removed(Value::U64(2)));
+    ///         assert_eq!(members["c"], ValueDiff::Added(Value::U64(3)));
+    ///     }
+    ///     _ => unreachable!(),
+    /// }
+    /// ```
+    pub fn diff(&self, other: &Value) -> ValueDiff {
+        diff::diff(self, other)
+    }
+
+    /// Describes the structure of this `Value`, for example to document a configuration format
+    /// from a sample: scalars become the name of their type (`"null"`, `"bool"`, `"number"` or
+    /// `"string"`), objects keep their keys with the shape of each value, and arrays hold a single
+    /// element with the shape shared by all their elements (an empty array stays empty).
+    ///
+    /// Elements of different types are reported as a union like `"number | string"`. Objects
+    /// are merged member by member and arrays element by element, but when an object or array
+    /// is mixed with another type it is only named `"object"` or `"array"` in the union.
+    ///
+    /// ```rust
+    /// use serde_hjson::Value;
+    ///
+    /// let sample: Value = serde_hjson::from_str(r#"{port: 80, hosts: ["a", "b"]}"#).unwrap();
+    /// let shape: Value = serde_hjson::from_str(r#"{port: "number", hosts: ["string"]}"#).unwrap();
+    /// assert_eq!(sample.shape(), shape);
+    /// ```
+    pub fn shape(&self) -> Value {
+        let name = match *self {
+            Value::Null => "null",
+            Value::Bool(_) => "bool",
+            Value::I64(_) | Value::U64(_) | Value::F64(_) => "number",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Array(ref list) => {
+                let element = list.iter().map(Value::shape).fold(None, |acc, shape| {
+                    Some(match acc {
+                        Some(acc) => unify_shapes(acc, shape),
+                        None => shape,
+                    })
+                });
+                return Value::Array(element.into_iter().collect());
+            }
+            Value::Object(ref map) => {
+                return Value::Object(
+                    map.iter()
+                        .map(|(key, value)| (key.clone(), value.shape()))
+                        .collect(),
+                );
+            }
+        };
+        Value::String(name.to_string())
+    }
+
+    /// Flattens nested objects and arrays into one object that maps the path of every scalar to
+    /// it, joining the keys and array indices on the way with `sep`, like `server.ports.0` for
+    /// `"."`. Empty objects and arrays are kept as values, as they have no scalars, and a scalar
+    /// at the root gets the empty path. Keys are used as they are, so when paths collide, like
+    /// for `{a: {b: 1}, "a.b": 2}`, the later scalar is kept; see `unflatten` for when the paths
+    /// can be split again.
+    ///
+    /// ```rust
+    /// use serde_hjson::Value;
+    ///
+    /// let config: Value = serde_hjson::from_str(r#"{server: {host: "x", ports: [80, 443]}}"#).unwrap();
+    /// let flat = config.flatten("_");
+    /// assert_eq!(flat["server_host"], Value::String("x".to_string()));
+    /// assert_eq!(flat["server_ports_1"], Value::U64(443));
+    /// ```
+    pub fn flatten(&self, sep: &str) -> Map<String, Value> {
+        let mut flat = Map::new();
+        self.flatten_into(&mut String::new(), sep, &mut flat);
+        flat
+    }
+
+    /// Rebuilds the nested `Value` that `flatten` was called on from the paths in `flat`, which
+    /// are split at `sep`. A path segment that is a number (without leading zeros) is an index
+    /// into an array, any other segment a key of an object, and an empty path is the root
+    /// itself. An empty `flat` gives an empty object.
+    ///
+    /// The indices of every array have to go from 0 without gaps, in any order, like `flatten`
+    /// writes them; a missing index is an error rather than filled with `Null`. Paths that need
+    /// the same place to be different things, like `a` and `a.b` or `a.0` and `a.b`, are errors
+    /// too.
+    ///
+    /// `flatten` does not escape keys, so this only inverts it while the keys of the objects
+    /// neither contain `sep` nor look like array indices and are not empty: `{"a.b": 1}` comes
+    /// back as `{a: {b: 1}}`, `{"0": 1}` as `[1]`, and `{"": 1}` as `1` for `"."`.
+    ///
+    /// ```rust
+    /// use serde_hjson::Value;
+    ///
+    /// let config: Value = serde_hjson::from_str("{list: [{a: 1}, 2]}").unwrap();
+    /// assert_eq!(Value::unflatten(config.flatten("."), ".").unwrap(), config);
+    /// ```
+    pub fn unflatten(flat: Map<String, Value>, sep: &str) -> Result<Value> {
+        let mut root = Node::Vacant;
+        for (path, value) in flat {
+            let segments: Vec<&str> = if path.is_empty() {
+                Vec::new()
+            } else if sep.is_empty() {
+                vec![&path[..]]
+            } else {
+                path.split(sep).collect()
+            };
+            let mut node = &mut root;
+            for segment in segments {
+                let index = array_index(segment);
+                if let Node::Vacant = *node {
+                    *node = match index {
+                        Some(_) => Node::Array(BTreeMap::new()),
+                        None => Node::Object(Map::new()),
+                    };
+                }
+                node = match (node, index) {
+                    (&mut Node::Array(ref mut items), Some(index)) => {
+                        items.entry(index).or_insert(Node::Vacant)
+                    }
+                    (&mut Node::Object(ref mut members), None) => {
+                        members.entry(segment.to_string()).or_insert(Node::Vacant)
+                    }
+                    _ => return Err(conflicting_path(&path)),
+                };
+            }
+            match *node {
+                Node::Vacant => *node = Node::Leaf(value),
+                _ => return Err(conflicting_path(&path)),
+            }
+        }
+        match root {
+            Node::Vacant => Ok(Value::Object(Map::new())),
+            root => root.into_value(&mut String::new(), sep),
+        }
+    }
+
+    fn flatten_into(&self, path: &mut String, sep: &str, flat: &mut Map<String, Value>) {
+        let len = path.len();
+        let mut child = |path: &mut String, segment: &str, value: &Value| {
+            if len > 0 {
+                path.push_str(sep);
+            }
+            path.push_str(segment);
+            value.flatten_into(path, sep, flat);
+            path.truncate(len);
+        };
+        match *self {
+            Value::Array(ref list) if !list.is_empty() => {
+                for (index, value) in list.iter().enumerate() {
+                    child(path, &index.to_string(), value);
+                }
+            }
+            Value::Object(ref map) if !map.is_empty() => {
+                for (key, value) in map.iter() {
+                    child(path, key, value);
+                }
+            }
+            _ => {
+                flat.insert(path.clone(), self.clone());
+            }
+        }
+    }
+
+    /// Takes the value out of the `Value`, leaving a `Null` in its place.
+    ///
+    /// ```rust
+    /// use serde_hjson::Value;
+    ///
+    /// let mut v: Value = serde_hjson::from_str("{list: [1, 2]}").unwrap();
+    /// let list = v["list"].take();
+    /// assert_eq!(list.as_array().map(|a| a.len()), Some(2));
+    /// assert!(v["list"].is_null());
+    /// ```
+    pub fn take(&mut self) -> Value {
+        ::core::mem::replace(self, Value::Null)
+    }
+
+    /// Gets the member `key` of an object for in-place manipulation, see `Entry`. Like indexing
+    /// with `IndexMut`, a `Value::Null` is turned into an empty object first.
+    ///
+    /// ```rust
+    /// use serde_hjson::Value;
+    ///
+    /// let mut config = Value::Null;
+    /// config.entry("retries").or_insert(Value::U64(3));
+    /// config
+    ///     .entry("retries")
+    ///     .and_modify(|v| *v = Value::U64(v.as_u64().unwrap() + 1))
+    ///     .or_insert(Value::U64(1));
+    /// assert_eq!(config["retries"].as_u64(), Some(4));
+    /// ```
+    ///
+    /// Panics if the `Value` is neither an object nor null.
+    pub fn entry<S: Into<String>>(&mut self, key: S) -> Entry<'_> {
+        let key = key.into();
+        if let Value::Null = *self {
+            *self = Value::Object(Map::new());
+        }
+        match *self {
+            Value::Object(ref mut map) => Entry { map, key },
+            _ => panic!("cannot get the entry {:?} of a non-object value", key),
+        }
+    }
+
+    /// Iterates over the members of an object, in their order. Other values have none.
+    ///
+    /// ```rust
+    /// use serde_hjson::Value;
+    ///
+    /// let v: Value = serde_hjson::from_str("{a: 1, b: 2}").unwrap();
+    /// let keys: Vec<&str> = v.entries().map(|(key, _)| &key[..]).collect();
+    /// assert_eq!(keys, ["a", "b"]);
+    /// ```
+    pub fn entries(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.as_object().into_iter().flat_map(|map| map.iter())
+    }
+
+    /// Iterates over the members of an object like `entries`, with mutable values.
+    pub fn entries_mut(&mut self) -> impl Iterator<Item = (&String, &mut Value)> {
+        self.as_object_mut()
+            .into_iter()
+            .flat_map(|map| map.iter_mut())
+    }
+
+    /// Iterates over the elements of an array or the values of an object, in their order.
+    /// Other values have none.
+    ///
+    /// ```rust
+    /// use serde_hjson::Value;
+    ///
+    /// let v: Value = serde_hjson::from_str("[1, 2, 3]").unwrap();
+    /// let sum: u64 = v.values().filter_map(Value::as_u64).sum();
+    /// assert_eq!(sum, 6);
+    /// ```
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        let list = self.as_array().into_iter().flatten();
+        list.chain(self.as_object().into_iter().flat_map(|map| map.values()))
+    }
+
+    /// Iterates over the elements of an array or the values of an object like `values`, but
+    /// mutably.
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut Value> {
+        let (list, map) = match *self {
+            Value::Array(ref mut list) => (Some(list), None),
+            Value::Object(ref mut map) => (None, Some(map)),
+            _ => (None, None),
+        };
+        let list = list.into_iter().flat_map(|list| list.iter_mut());
+        list.chain(
+            map.into_iter()
+                .flat_map(|map| map.iter_mut().map(|(_, value)| value)),
+        )
+    }
+
     /// Returns true if the `Value` is an Object. Returns false otherwise.
     pub fn is_object(&self) -> bool {
         self.as_object().is_some()
@@ -256,10 +904,10 @@ impl Value {
 
     /// Returns true if the `Value` is a Number. Returns false otherwise.
     pub fn is_number(&self) -> bool {
-        match *self {
-            Value::I64(_) | Value::U64(_) | Value::F64(_) => true,
-            _ => false,
-        }
+        matches!(
+            *self,
+            Value::I64(_) | Value::U64(_) | Value::F64(_) | Value::Number(_)
+        )
     }
 
     /// Returns true if the `Value` is a i64. Returns false otherwise.
@@ -292,6 +940,7 @@ impl Value {
         match *self {
             Value::I64(n) => Some(n),
             Value::U64(n) => NumCast::from(n),
+            Value::Number(ref n) => n.parse().ok(),
             _ => None,
         }
     }
@@ -302,6 +951,7 @@ impl Value {
         match *self {
             Value::I64(n) => NumCast::from(n),
             Value::U64(n) => Some(n),
+            Value::Number(ref n) => n.parse().ok(),
             _ => None,
         }
     }
@@ -313,6 +963,7 @@ impl Value {
             Value::I64(n) => NumCast::from(n),
             Value::U64(n) => NumCast::from(n),
             Value::F64(n) => Some(n),
+            Value::Number(ref n) => n.parse().ok(),
             _ => None,
         }
     }
@@ -352,6 +1003,7 @@ impl Value {
             Value::I64(v) => de::Unexpected::Signed(v),
             Value::U64(v) => de::Unexpected::Unsigned(v),
             Value::F64(v) => de::Unexpected::Float(v),
+            Value::Number(_) => de::Unexpected::Other("number"),
             Value::String(ref v) => de::Unexpected::Str(v),
             Value::Array(_) => de::Unexpected::Seq,
             Value::Object(_) => de::Unexpected::Map,
@@ -359,6 +1011,99 @@ impl Value {
     }
 }
 
+/// The default value is `Value::Null`.
+impl Default for Value {
+    fn default() -> Value {
+        Value::Null
+    }
+}
+
+// Integers convert like they are deserialized: non-negative ones into `Value::U64`.
+macro_rules! from_integers {
+    ($($signed:ty)*; $($unsigned:ty)*) => {
+        $(
+            impl From<$signed> for Value {
+                fn from(value: $signed) -> Value {
+                    if value < 0 {
+                        Value::I64(value as i64)
+                    } else {
+                        Value::U64(value as u64)
+                    }
+                }
+            }
+        )*
+        $(
+            impl From<$unsigned> for Value {
+                fn from(value: $unsigned) -> Value {
+                    Value::U64(value as u64)
+                }
+            }
+        )*
+    };
+}
+
+from_integers! { i8 i16 i32 i64 isize; u8 u16 u32 u64 usize }
+
+impl From<f32> for Value {
+    fn from(value: f32) -> Value {
+        Value::F64(value as f64)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Value {
+        Value::F64(value)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Value {
+        Value::Bool(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Value {
+        Value::String(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Value {
+        Value::String(value)
+    }
+}
+
+impl From<Map<String, Value>> for Value {
+    fn from(map: Map<String, Value>) -> Value {
+        Value::Object(map)
+    }
+}
+
+/// Converts every element, so a `Vec<Value>` is taken as it is.
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(items: Vec<T>) -> Value {
+        Value::Array(items.into_iter().map(Into::into).collect())
+    }
+}
+
+/// `None` becomes `Value::Null`.
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Value {
+        value.map_or(Value::Null, Into::into)
+    }
+}
+
+/// Collects an object, see `Value::object_from`.
+impl iter::FromIterator<(String, Value)> for Value {
+    fn from_iter<I>(members: I) -> Value
+    where
+        I: IntoIterator<Item = (String, Value)>,
+    {
+        Value::object_from(members)
+    }
+}
+
 impl ser::Serialize for Value {
     #[inline]
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -371,6 +1116,7 @@ impl ser::Serialize for Value {
             Value::I64(v) => serializer.serialize_i64(v),
             Value::U64(v) => serializer.serialize_u64(v),
             Value::F64(v) => serializer.serialize_f64(v),
+            Value::Number(ref v) => serializer.serialize_newtype_struct(NUMBER_TOKEN, v),
             Value::String(ref v) => serializer.serialize_str(v),
             Value::Array(ref v) => v.serialize(serializer),
             Value::Object(ref v) => v.serialize(serializer),
@@ -417,6 +1163,17 @@ impl<'de> de::Deserialize<'de> for Value {
                 Ok(Value::F64(value))
             }
 
+            // the Hjson deserializer hands over number literals as a newtype
+            #[cfg(feature = "arbitrary_precision")]
+            #[inline]
+            fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Value, D::Error>
+            where
+                D: de::Deserializer<'de>,
+            {
+                let literal: String = de::Deserialize::deserialize(deserializer)?;
+                Ok(Value::Number(literal))
+            }
+
             #[inline]
             fn visit_str<E>(self, value: &str) -> Result<Value, E>
             where
@@ -478,7 +1235,7 @@ impl<'de> de::Deserialize<'de> for Value {
             }
         }
 
-        deserializer.deserialize_any(ValueVisitor)
+        deserialize_any_with_literals(deserializer, ValueVisitor)
     }
 }
 
@@ -507,15 +1264,24 @@ impl fmt::Debug for Value {
     /// Serializes a Hjson value into a string
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut wr = WriterFormatter { inner: f };
-        super::ser::to_writer(&mut wr, self).map_err(|_| fmt::Error)
+        ser::Serialize::serialize(self, &mut super::ser::Serializer::new(&mut wr))
+            .map_err(|_| fmt::Error)
     }
 }
 
 impl fmt::Display for Value {
-    /// Serializes a Hjson value into a string
+    /// Serializes a Hjson value into a string: on a single line with every key and string
+    /// quoted, or indented over multiple lines like `to_string` with the alternate flag (`{:#}`).
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let alternate = f.alternate();
         let mut wr = WriterFormatter { inner: f };
-        super::ser::to_writer(&mut wr, self).map_err(|_| fmt::Error)
+        if alternate {
+            ser::Serialize::serialize(self, &mut super::ser::Serializer::new(&mut wr))
+                .map_err(|_| fmt::Error)
+        } else {
+            ser::Serialize::serialize(self, &mut super::ser::Serializer::compact(&mut wr))
+                .map_err(|_| fmt::Error)
+        }
     }
 }
 
@@ -526,245 +1292,514 @@ impl str::FromStr for Value {
     }
 }
 
+static NULL: Value = Value::Null;
+
+/// Index into an object by key. Returns `Value::Null` if the key is missing or the `Value` is
+/// not an object.
+impl ops::Index<&str> for Value {
+    type Output = Value;
+
+    fn index(&self, key: &str) -> &Value {
+        self.find(key).unwrap_or(&NULL)
+    }
+}
+
+/// Index into an array. Returns `Value::Null` if the index is out of range or the `Value` is
+/// not an array.
+impl ops::Index<usize> for Value {
+    type Output = Value;
+
+    fn index(&self, idx: usize) -> &Value {
+        match *self {
+            Value::Array(ref list) => list.get(idx).unwrap_or(&NULL),
+            _ => &NULL,
+        }
+    }
+}
+
+/// Mutably index into an object by key.
+///
+/// A `Value::Null` is turned into an empty object first and a missing key is inserted with a
+/// `Value::Null` value, so `value["a"]["b"] = ...` creates the intermediate objects.
+///
+/// Panics if the `Value` is neither an object nor null.
+impl ops::IndexMut<&str> for Value {
+    fn index_mut(&mut self, key: &str) -> &mut Value {
+        if let Value::Null = *self {
+            *self = Value::Object(Map::new());
+        }
+        match *self {
+            Value::Object(ref mut map) => map.entry(key.to_owned()).or_insert(Value::Null),
+            _ => panic!("cannot access key {:?} in a non-object value", key),
+        }
+    }
+}
+
+/// Mutably index into an array.
+///
+/// Panics if the `Value` is not an array or the index is out of range.
+impl ops::IndexMut<usize> for Value {
+    fn index_mut(&mut self, idx: usize) -> &mut Value {
+        match *self {
+            Value::Array(ref mut list) => {
+                let len = list.len();
+                list.get_mut(idx).unwrap_or_else(|| {
+                    panic!("cannot access index {} of an array of length {}", idx, len)
+                })
+            }
+            _ => panic!("cannot access index {} in a non-array value", idx),
+        }
+    }
+}
+
+// Combines the shapes of two elements of the same array, see `Value::shape`.
+fn unify_shapes(a: Value, b: Value) -> Value {
+    match (a, b) {
+        (a, b) if a == b => a,
+        (Value::Object(mut a), Value::Object(b)) => {
+            for (key, shape) in b {
+                if let Some(target) = a.get_mut(&key) {
+                    *target = unify_shapes(target.take(), shape);
+                    continue;
+                }
+                a.insert(key, shape);
+            }
+            Value::Object(a)
+        }
+        (Value::Array(mut a), Value::Array(b)) => match (a.pop(), b.into_iter().next()) {
+            (Some(a), Some(b)) => Value::Array(vec![unify_shapes(a, b)]),
+            (a, b) => Value::Array(a.or(b).into_iter().collect()),
+        },
+        (a, b) => {
+            let mut names: Vec<String> = Vec::new();
+            for shape in [a, b].iter() {
+                let parts = match *shape {
+                    Value::String(ref union) => union.split(" | ").collect(),
+                    Value::Array(_) => vec!["array"],
+                    _ => vec!["object"],
+                };
+                for part in parts {
+                    if !names.iter().any(|name| name == part) {
+                        names.push(part.to_string());
+                    }
+                }
+            }
+            Value::String(names.join(" | "))
+        }
+    }
+}
+
+// A place in the tree that `Value::unflatten` builds.
+enum Node {
+    Vacant,
+    Leaf(Value),
+    Array(BTreeMap<usize, Node>),
+    Object(Map<String, Node>),
+}
+
+impl Node {
+    // `path` is where the node is, for errors.
+    fn into_value(self, path: &mut String, sep: &str) -> Result<Value> {
+        let len = path.len();
+        let child = |path: &mut String, segment: &str, node: Node| {
+            if len > 0 {
+                path.push_str(sep);
+            }
+            path.push_str(segment);
+            let value = node.into_value(path, sep);
+            path.truncate(len);
+            value
+        };
+        match self {
+            // every node that was created was either filled or descended into
+            Node::Vacant => Ok(Value::Null),
+            Node::Leaf(value) => Ok(value),
+            Node::Array(items) => {
+                let mut list = Vec::with_capacity(items.len());
+                for (index, node) in items {
+                    if index != list.len() {
+                        let missing = match len {
+                            0 => list.len().to_string(),
+                            _ => format!("{}{}{}", path, sep, list.len()),
+                        };
+                        return Err(Error::Syntax(
+                            ErrorCode::Custom(format!("missing array element `{}`", missing)),
+                            0,
+                            0,
+                            0,
+                        ));
+                    }
+                    list.push(child(path, &index.to_string(), node)?);
+                }
+                Ok(Value::Array(list))
+            }
+            Node::Object(members) => {
+                let mut map = Map::new();
+                for (key, node) in members {
+                    let value = child(path, &key, node)?;
+                    map.insert(key, value);
+                }
+                Ok(Value::Object(map))
+            }
+        }
+    }
+}
+
+// The array index that a path segment of `Value::unflatten` stands for, if it is one.
+fn array_index(segment: &str) -> Option<usize> {
+    let canonical = segment == "0" || !segment.starts_with('0');
+    if canonical && !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit()) {
+        segment.parse().ok()
+    } else {
+        None
+    }
+}
+
+fn conflicting_path(path: &str) -> Error {
+    Error::Syntax(
+        ErrorCode::Custom(format!("conflicting path `{}`", path)),
+        0,
+        0,
+        0,
+    )
+}
+
+/// A member of an object that may or may not exist yet, returned by `Value::entry`.
+pub struct Entry<'a> {
+    map: &'a mut Map<String, Value>,
+    key: String,
+}
+
+impl<'a> Entry<'a> {
+    /// Returns the key of the entry.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Inserts `default` if the member does not exist yet and returns the value of the member.
+    pub fn or_insert(self, default: Value) -> &'a mut Value {
+        self.or_insert_with(|| default)
+    }
+
+    /// Inserts the result of `default` if the member does not exist yet and returns the value of
+    /// the member.
+    pub fn or_insert_with<F>(self, default: F) -> &'a mut Value
+    where
+        F: FnOnce() -> Value,
+    {
+        self.map.entry(self.key).or_insert_with(default)
+    }
+
+    /// Calls `f` with the value of the member if it exists.
+    pub fn and_modify<F>(self, f: F) -> Self
+    where
+        F: FnOnce(&mut Value),
+    {
+        if let Some(value) = self.map.get_mut(&self.key) {
+            f(value);
+        }
+        self
+    }
+}
+
 /// Create a `serde::Serializer` that serializes a `Serialize`e into a `Value`.
 #[derive(Default)]
 pub struct Serializer;
 
-impl ser::Serializer for Serializer {
-    type Ok = Value;
-    type Error = Error;
+// Like `Serializer`, but leaves out the members of maps and structs whose value is `None`, see
+// `ser::Serializer::skip_none`.
+struct SkipNoneSerializer;
+
+// Both serializers into `Value` share this implementation, `$skip_none` tells whether members
+// whose value is `None` are left out.
+macro_rules! value_serializer {
+    ($name:ident, $skip_none:expr) => {
+        impl ser::Serializer for $name {
+            type Ok = Value;
+            type Error = Error;
+
+            type SerializeSeq = SerializeVec;
+            type SerializeTuple = SerializeVec;
+            type SerializeTupleStruct = SerializeVec;
+            type SerializeTupleVariant = SerializeTupleVariant;
+            type SerializeMap = SerializeMap;
+            type SerializeStruct = SerializeMap;
+            type SerializeStructVariant = SerializeStructVariant;
 
-    type SerializeSeq = SerializeVec;
-    type SerializeTuple = SerializeVec;
-    type SerializeTupleStruct = SerializeVec;
-    type SerializeTupleVariant = SerializeTupleVariant;
-    type SerializeMap = SerializeMap;
-    type SerializeStruct = SerializeMap;
-    type SerializeStructVariant = SerializeStructVariant;
+            #[inline]
+            fn serialize_bool(self, value: bool) -> Result<Value> {
+                Ok(Value::Bool(value))
+            }
 
-    #[inline]
-    fn serialize_bool(self, value: bool) -> Result<Value> {
-        Ok(Value::Bool(value))
-    }
+            #[inline]
+            fn serialize_i8(self, value: i8) -> Result<Value> {
+                self.serialize_i64(value as i64)
+            }
 
-    #[inline]
-    fn serialize_i8(self, value: i8) -> Result<Value> {
-        self.serialize_i64(value as i64)
-    }
+            #[inline]
+            fn serialize_i16(self, value: i16) -> Result<Value> {
+                self.serialize_i64(value as i64)
+            }
 
-    #[inline]
-    fn serialize_i16(self, value: i16) -> Result<Value> {
-        self.serialize_i64(value as i64)
-    }
+            #[inline]
+            fn serialize_i32(self, value: i32) -> Result<Value> {
+                self.serialize_i64(value as i64)
+            }
 
-    #[inline]
-    fn serialize_i32(self, value: i32) -> Result<Value> {
-        self.serialize_i64(value as i64)
-    }
+            fn serialize_i64(self, value: i64) -> Result<Value> {
+                let v = if value < 0 {
+                    Value::I64(value)
+                } else {
+                    Value::U64(value as u64)
+                };
+                Ok(v)
+            }
 
-    fn serialize_i64(self, value: i64) -> Result<Value> {
-        let v = if value < 0 {
-            Value::I64(value)
-        } else {
-            Value::U64(value as u64)
-        };
-        Ok(v)
-    }
+            #[inline]
+            fn serialize_u8(self, value: u8) -> Result<Value> {
+                self.serialize_u64(value as u64)
+            }
 
-    #[inline]
-    fn serialize_u8(self, value: u8) -> Result<Value> {
-        self.serialize_u64(value as u64)
-    }
+            #[inline]
+            fn serialize_u16(self, value: u16) -> Result<Value> {
+                self.serialize_u64(value as u64)
+            }
 
-    #[inline]
-    fn serialize_u16(self, value: u16) -> Result<Value> {
-        self.serialize_u64(value as u64)
-    }
+            #[inline]
+            fn serialize_u32(self, value: u32) -> Result<Value> {
+                self.serialize_u64(value as u64)
+            }
 
-    #[inline]
-    fn serialize_u32(self, value: u32) -> Result<Value> {
-        self.serialize_u64(value as u64)
-    }
+            #[inline]
+            fn serialize_u64(self, value: u64) -> Result<Value> {
+                Ok(Value::U64(value))
+            }
 
-    #[inline]
-    fn serialize_u64(self, value: u64) -> Result<Value> {
-        Ok(Value::U64(value))
-    }
+            // a `Value` only holds 64 bit integers, larger ones are kept by their literal with
+            // the `arbitrary_precision` feature
+            fn serialize_i128(self, value: i128) -> Result<Value> {
+                if let Ok(value) = u64::try_from(value) {
+                    return self.serialize_u64(value);
+                }
+                if let Ok(value) = i64::try_from(value) {
+                    return self.serialize_i64(value);
+                }
+                #[cfg(feature = "arbitrary_precision")]
+                return Ok(Value::Number(value.to_string()));
+                #[cfg(not(feature = "arbitrary_precision"))]
+                Err(ser::Error::custom("number out of range"))
+            }
 
-    #[inline]
-    fn serialize_f32(self, value: f32) -> Result<Value> {
-        self.serialize_f64(value as f64)
-    }
+            fn serialize_u128(self, value: u128) -> Result<Value> {
+                match u64::try_from(value) {
+                    Ok(value) => self.serialize_u64(value),
+                    #[cfg(feature = "arbitrary_precision")]
+                    Err(_) => Ok(Value::Number(value.to_string())),
+                    #[cfg(not(feature = "arbitrary_precision"))]
+                    Err(_) => Err(ser::Error::custom("number out of range")),
+                }
+            }
 
-    #[inline]
-    fn serialize_f64(self, value: f64) -> Result<Value> {
-        Ok(Value::F64(value))
-    }
+            #[inline]
+            fn serialize_f32(self, value: f32) -> Result<Value> {
+                self.serialize_f64(value as f64)
+            }
 
-    #[inline]
-    fn serialize_char(self, value: char) -> Result<Value> {
-        let mut s = String::new();
-        s.push(value);
-        self.serialize_str(&s)
-    }
+            #[inline]
+            fn serialize_f64(self, value: f64) -> Result<Value> {
+                Ok(Value::F64(value))
+            }
 
-    #[inline]
-    fn serialize_str(self, value: &str) -> Result<Value> {
-        Ok(Value::String(String::from(value)))
-    }
+            #[inline]
+            fn serialize_char(self, value: char) -> Result<Value> {
+                let mut s = String::new();
+                s.push(value);
+                self.serialize_str(&s)
+            }
 
-    fn serialize_bytes(self, value: &[u8]) -> Result<Value> {
-        let mut state = self.serialize_seq(Some(value.len()))?;
-        for byte in value {
-            ser::SerializeSeq::serialize_element(&mut state, byte)?;
-        }
-        ser::SerializeSeq::end(state)
-    }
+            #[inline]
+            fn serialize_str(self, value: &str) -> Result<Value> {
+                Ok(Value::String(String::from(value)))
+            }
 
-    #[inline]
-    fn serialize_unit(self) -> Result<Value> {
-        Ok(Value::Null)
-    }
+            fn serialize_bytes(self, value: &[u8]) -> Result<Value> {
+                let mut state = self.serialize_seq(Some(value.len()))?;
+                for byte in value {
+                    ser::SerializeSeq::serialize_element(&mut state, byte)?;
+                }
+                ser::SerializeSeq::end(state)
+            }
 
-    #[inline]
-    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value> {
-        self.serialize_unit()
-    }
+            #[inline]
+            fn serialize_unit(self) -> Result<Value> {
+                Ok(Value::Null)
+            }
 
-    #[inline]
-    fn serialize_unit_variant(
-        self,
-        _name: &'static str,
-        _variant_index: u32,
-        variant: &'static str,
-    ) -> Result<Value> {
-        self.serialize_str(variant)
-    }
+            #[inline]
+            fn serialize_unit_struct(self, _name: &'static str) -> Result<Value> {
+                self.serialize_unit()
+            }
 
-    #[inline]
-    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Value>
-    where
-        T: ?Sized + ser::Serialize,
-    {
-        value.serialize(self)
-    }
+            #[inline]
+            fn serialize_unit_variant(
+                self,
+                _name: &'static str,
+                _variant_index: u32,
+                variant: &'static str,
+            ) -> Result<Value> {
+                self.serialize_str(variant)
+            }
 
-    fn serialize_newtype_variant<T>(
-        self,
-        _name: &'static str,
-        _variant_index: u32,
-        variant: &'static str,
-        value: &T,
-    ) -> Result<Value>
-    where
-        T: ?Sized + ser::Serialize,
-    {
-        let mut values = Map::new();
-        values.insert(String::from(variant), to_value(&value)?);
-        Ok(Value::Object(values))
-    }
+            #[inline]
+            fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Value>
+            where
+                T: ?Sized + ser::Serialize,
+            {
+                // the text of a `RawValue` is parsed
+                if name == raw::RAW_TOKEN {
+                    return match value.serialize(self)? {
+                        Value::String(text) => super::de::from_str(&text),
+                        other => Ok(other),
+                    };
+                }
+                if name == NUMBER_TOKEN {
+                    return match value.serialize(self)? {
+                        Value::String(literal) => Ok(Value::Number(literal)),
+                        other => Ok(other),
+                    };
+                }
+                value.serialize(self)
+            }
 
-    #[inline]
-    fn serialize_none(self) -> Result<Value> {
-        self.serialize_unit()
-    }
+            fn serialize_newtype_variant<T>(
+                self,
+                _name: &'static str,
+                _variant_index: u32,
+                variant: &'static str,
+                value: &T,
+            ) -> Result<Value>
+            where
+                T: ?Sized + ser::Serialize,
+            {
+                let mut values = Map::new();
+                values.insert(String::from(variant), to_value_with(&value, $skip_none)?);
+                Ok(Value::Object(values))
+            }
 
-    #[inline]
-    fn serialize_some<V>(self, value: &V) -> Result<Value>
-    where
-        V: ?Sized + ser::Serialize,
-    {
-        value.serialize(self)
-    }
+            #[inline]
+            fn serialize_none(self) -> Result<Value> {
+                self.serialize_unit()
+            }
 
-    #[inline]
-    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
-        Ok(SerializeVec {
-            vec: Vec::with_capacity(len.unwrap_or(0)),
-        })
-    }
+            #[inline]
+            fn serialize_some<V>(self, value: &V) -> Result<Value>
+            where
+                V: ?Sized + ser::Serialize,
+            {
+                value.serialize(self)
+            }
 
-    #[inline]
-    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
-        self.serialize_seq(Some(len))
-    }
+            #[inline]
+            fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+                Ok(SerializeVec {
+                    vec: Vec::with_capacity(len.unwrap_or(0)),
+                    skip_none: $skip_none,
+                })
+            }
 
-    #[inline]
-    fn serialize_tuple_struct(
-        self,
-        _name: &'static str,
-        len: usize,
-    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
-        self.serialize_seq(Some(len))
-    }
+            #[inline]
+            fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+                self.serialize_seq(Some(len))
+            }
 
-    #[inline]
-    fn serialize_tuple_variant(
-        self,
-        _name: &'static str,
-        _variant_index: u32,
-        variant: &'static str,
-        len: usize,
-    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
-        Ok(SerializeTupleVariant {
-            name: variant,
-            vec: Vec::with_capacity(len),
-        })
-    }
+            #[inline]
+            fn serialize_tuple_struct(
+                self,
+                _name: &'static str,
+                len: usize,
+            ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+                self.serialize_seq(Some(len))
+            }
 
-    #[inline]
-    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
-        Ok(SerializeMap {
-            map: map_with_capacity(len),
-            next_key: None,
-        })
-    }
+            #[inline]
+            fn serialize_tuple_variant(
+                self,
+                _name: &'static str,
+                _variant_index: u32,
+                variant: &'static str,
+                len: usize,
+            ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+                Ok(SerializeTupleVariant {
+                    name: variant,
+                    vec: Vec::with_capacity(len),
+                    skip_none: $skip_none,
+                })
+            }
 
-    #[inline]
-    fn serialize_struct(
-        self,
-        _name: &'static str,
-        len: usize,
-    ) -> Result<Self::SerializeStruct, Self::Error> {
-        self.serialize_map(Some(len))
-    }
+            #[inline]
+            fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+                Ok(SerializeMap {
+                    map: map_with_capacity(len),
+                    next_key: None,
+                    skip_none: $skip_none,
+                })
+            }
 
-    #[inline]
-    fn serialize_struct_variant(
-        self,
-        _name: &'static str,
-        _variant_index: u32,
-        variant: &'static str,
-        len: usize,
-    ) -> Result<Self::SerializeStructVariant, Self::Error> {
-        Ok(SerializeStructVariant {
-            name: variant,
-            map: map_with_capacity(Some(len)),
-        })
-    }
+            #[inline]
+            fn serialize_struct(
+                self,
+                _name: &'static str,
+                len: usize,
+            ) -> Result<Self::SerializeStruct, Self::Error> {
+                self.serialize_map(Some(len))
+            }
+
+            #[inline]
+            fn serialize_struct_variant(
+                self,
+                _name: &'static str,
+                _variant_index: u32,
+                variant: &'static str,
+                len: usize,
+            ) -> Result<Self::SerializeStructVariant, Self::Error> {
+                Ok(SerializeStructVariant {
+                    name: variant,
+                    map: map_with_capacity(Some(len)),
+                    skip_none: $skip_none,
+                })
+            }
+        }
+    };
 }
 
+value_serializer!(Serializer, false);
+value_serializer!(SkipNoneSerializer, true);
+
 #[doc(hidden)]
 pub struct SerializeVec {
     vec: Vec<Value>,
+    skip_none: bool,
 }
 
 #[doc(hidden)]
 pub struct SerializeTupleVariant {
     name: &'static str,
     vec: Vec<Value>,
+    skip_none: bool,
 }
 
 #[doc(hidden)]
 pub struct SerializeMap {
     map: Map<String, Value>,
     next_key: Option<String>,
+    skip_none: bool,
 }
 
 #[doc(hidden)]
 pub struct SerializeStructVariant {
     name: &'static str,
     map: Map<String, Value>,
+    skip_none: bool,
 }
 
 impl ser::SerializeSeq for SerializeVec {
@@ -775,7 +1810,7 @@ impl ser::SerializeSeq for SerializeVec {
     where
         T: ?Sized + ser::Serialize,
     {
-        self.vec.push(to_value(&value)?);
+        self.vec.push(to_value_with(value, self.skip_none)?);
         Ok(())
     }
 
@@ -824,7 +1859,7 @@ impl ser::SerializeTupleVariant for SerializeTupleVariant {
     where
         T: ?Sized + ser::Serialize,
     {
-        self.vec.push(to_value(&value)?);
+        self.vec.push(to_value_with(value, self.skip_none)?);
         Ok(())
     }
 
@@ -845,10 +1880,7 @@ impl ser::SerializeMap for SerializeMap {
     where
         T: ?Sized + ser::Serialize,
     {
-        match to_value(key)? {
-            Value::String(s) => self.next_key = Some(s),
-            _ => return Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0)),
-        };
+        self.next_key = Some(to_key(key)?);
         Ok(())
     }
 
@@ -860,7 +1892,10 @@ impl ser::SerializeMap for SerializeMap {
         // Panic because this indicates a bug in the program rather than an
         // expected failure.
         let key = key.expect("serialize_value called before serialize_key");
-        self.map.insert(key, to_value(value)?);
+        if self.skip_none && super::ser::is_none(value) {
+            return Ok(());
+        }
+        self.map.insert(key, to_value_with(value, self.skip_none)?);
         Ok(())
     }
 
@@ -893,7 +1928,11 @@ impl ser::SerializeStructVariant for SerializeStructVariant {
     where
         T: ?Sized + ser::Serialize,
     {
-        self.map.insert(key.to_owned(), to_value(&value)?);
+        if self.skip_none && super::ser::is_none(value) {
+            return Ok(());
+        }
+        self.map
+            .insert(key.to_owned(), to_value_with(value, self.skip_none)?);
         Ok(())
     }
 
@@ -920,6 +1959,18 @@ impl<'de> de::Deserializer<'de> for Value {
             Value::I64(v) => visitor.visit_i64(v),
             Value::U64(v) => visitor.visit_u64(v),
             Value::F64(v) => visitor.visit_f64(v),
+            Value::Number(v) => {
+                if let Ok(n) = v.parse() {
+                    visitor.visit_u64(n)
+                } else if let Ok(n) = v.parse() {
+                    visitor.visit_i64(n)
+                } else {
+                    visitor.visit_f64(
+                        v.parse()
+                            .map_err(|_| Error::Syntax(ErrorCode::InvalidNumber, 0, 0, 0))?,
+                    )
+                }
+            }
             Value::String(v) => visitor.visit_string(v),
             Value::Array(v) => visitor.visit_seq(SeqDeserializer {
                 iter: v.into_iter(),
@@ -988,12 +2039,17 @@ impl<'de> de::Deserializer<'de> for Value {
     #[inline]
     fn deserialize_newtype_struct<V>(
         self,
-        _name: &'static str,
+        name: &'static str,
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: de::Visitor<'de>,
     {
+        // a `RawValue` gets the text of the value
+        if name == raw::RAW_TOKEN {
+            let text = super::ser::to_string(&self)?;
+            return visitor.visit_newtype_struct(de::IntoDeserializer::into_deserializer(text));
+        }
         visitor.visit_newtype_struct(self)
     }
 
@@ -1152,7 +2208,7 @@ impl<'de, 'a> de::MapAccess<'de> for MapDeserializer {
 ///
 /// ```rust
 /// use serde_hjson::to_value;
-/// let val = to_value("foo");
+/// let val = to_value("foo").unwrap();
 /// assert_eq!(val.as_str(), Some("foo"))
 /// ```
 pub fn to_value<T: ?Sized>(value: &T) -> Result<Value>
@@ -1162,7 +2218,37 @@ where
     value.serialize(Serializer)
 }
 
-/// Shortcut function to decode a Hjson `Value` into a `T`
+// Like `to_value`, but leaves out members whose value is `None` when `skip_none` is set.
+pub(crate) fn to_value_with<T>(value: &T, skip_none: bool) -> Result<Value>
+where
+    T: ?Sized + ser::Serialize,
+{
+    if skip_none {
+        value.serialize(SkipNoneSerializer)
+    } else {
+        value.serialize(Serializer)
+    }
+}
+
+// Converts a map key into the text of an object key: strings are taken as they are, booleans and
+// numbers are written out.
+pub(crate) fn to_key<T>(key: &T) -> Result<String>
+where
+    T: ?Sized + ser::Serialize,
+{
+    match to_value(key)? {
+        Value::String(s) => Ok(s),
+        Value::Bool(v) => Ok(v.to_string()),
+        Value::I64(v) => Ok(v.to_string()),
+        Value::U64(v) => Ok(v.to_string()),
+        Value::F64(v) if v.is_finite() => Ok(v.to_string()),
+        Value::Number(literal) => Ok(literal),
+        _ => Err(Error::Syntax(ErrorCode::KeyMustBeAString, 0, 0, 0)),
+    }
+}
+
+/// Shortcut function to decode a Hjson `Value` into a `T`. The value is consumed, so its strings,
+/// arrays and objects are moved into `T` instead of being copied.
 pub fn from_value<T>(value: Value) -> Result<T>
 where
     T: de::DeserializeOwned,
@@ -1187,8 +2273,151 @@ where
 
 #[cfg(test)]
 mod test {
-    use super::Value;
+    use super::{Map, Value};
     use de::from_str;
+    use error::{Error, ErrorCode};
+    use ser::to_string;
+
+    #[test]
+    fn retain() {
+        let text = "{\n  _id: 1\n  name: \"hjson\"\n  db: {_password: \"x\", user: \"admin\"}\n  _internal: true\n}";
+        let mut config: Value = from_str(text).unwrap();
+        config.retain(|key, _| !key.starts_with('_'));
+        assert_eq!(
+            config,
+            from_str::<Value>("{name: \"hjson\", db: {_password: \"x\", user: \"admin\"}}")
+                .unwrap()
+        );
+        // nested objects are retained one level at a time
+        config["db"].retain(|key, _| !key.starts_with('_'));
+        assert_eq!(
+            config["db"],
+            from_str::<Value>("{user: \"admin\"}").unwrap()
+        );
+
+        // the kept values can be changed
+        config.retain(|_, value| {
+            if let Value::String(ref mut s) = *value {
+                *s = s.to_uppercase();
+            }
+            true
+        });
+        assert_eq!(config["name"].as_str(), Some("HJSON"));
+
+        let mut list: Value = from_str("[1, null, 2, null]").unwrap();
+        list.retain_elements(|item| !item.is_null());
+        assert_eq!(list, from_str::<Value>("[1, 2]").unwrap());
+
+        // other values are left alone
+        let mut scalar = Value::Bool(true);
+        scalar.retain(|_, _| false);
+        scalar.retain_elements(|_| false);
+        assert_eq!(scalar, Value::Bool(true));
+        list.retain(|_, _| false);
+        assert_eq!(list.as_array().map(|a| a.len()), Some(2));
+    }
+
+    #[test]
+    fn map_values() {
+        let text = "{\n  name: \"app\"\n  servers: [\n    {host: \"alpha\", port: 80}\n    [\"beta\", \"gamma\"]\n  ]\n  empty: {}\n}";
+        let mut v: Value = from_str(text).unwrap();
+        v.map_values(|value| {
+            if let Value::String(ref mut s) = *value {
+                *s = s.to_uppercase();
+            }
+        });
+        let expected = "{\n  name: \"APP\"\n  servers: [\n    {host: \"ALPHA\", port: 80}\n    [\"BETA\", \"GAMMA\"]\n  ]\n  empty: {}\n}";
+        assert_eq!(v, from_str::<Value>(expected).unwrap());
+
+        // children come before their parents
+        let mut v: Value = from_str("[[1], 2]").unwrap();
+        let mut order = Vec::new();
+        v.map_values(|value| order.push(to_string(value).unwrap().replace('\n', "")));
+        assert_eq!(order, vec!["1", "[  1]", "2", "[  [    1  ]  2]"]);
+    }
+
+    #[test]
+    fn iterators() {
+        let mut object: Value = from_str("{b: 1, a: [2], c: \"x\"}").unwrap();
+        let keys: Vec<&String> = object.entries().map(|(key, _)| key).collect();
+        #[cfg(feature = "preserve_order")]
+        assert_eq!(keys, ["b", "a", "c"]);
+        #[cfg(not(feature = "preserve_order"))]
+        assert_eq!(keys, ["a", "b", "c"]);
+        assert_eq!(object.values().count(), 3);
+        for (key, value) in object.entries_mut() {
+            *value = Value::String(key.clone());
+        }
+        assert_eq!(object["a"].as_str(), Some("a"));
+
+        let mut array: Value = from_str("[1, 2, 3]").unwrap();
+        let items: Vec<u64> = array.values().filter_map(Value::as_u64).collect();
+        assert_eq!(items, vec![1, 2, 3]);
+        for value in array.values_mut() {
+            *value = Value::Null;
+        }
+        assert!(array.values().all(Value::is_null));
+        assert_eq!(array.entries().count(), 0);
+
+        let mut scalar = Value::U64(1);
+        assert_eq!(scalar.entries().count(), 0);
+        assert_eq!(scalar.values().count(), 0);
+        assert_eq!(scalar.values_mut().count(), 0);
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(
+            Value::from(vec![1, -2, 3]),
+            from_str::<Value>("[1, -2, 3]").unwrap()
+        );
+        assert_eq!(
+            Value::from(Some("x".to_string())),
+            Value::String("x".to_string())
+        );
+        assert_eq!(Value::from(None::<String>), Value::Null);
+        assert_eq!(
+            Value::from(vec![Some(1.5), None]),
+            from_str::<Value>("[1.5, null]").unwrap()
+        );
+        assert_eq!(
+            Value::from(vec![Value::Null]),
+            Value::Array(vec![Value::Null])
+        );
+        assert_eq!(Value::from("a"), Value::String("a".to_string()));
+        assert_eq!(Value::from(true), Value::Bool(true));
+        assert_eq!(Value::from(255u8), Value::U64(255));
+        assert_eq!(Value::from(-1i64), Value::I64(-1));
+
+        let object: Value = vec![("a", 1), ("b", 2)]
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), Value::from(value)))
+            .collect();
+        assert_eq!(object, from_str::<Value>("{a: 1, b: 2}").unwrap());
+        assert_eq!(Value::from(Map::new()), Value::empty_object());
+    }
+
+    #[test]
+    fn constructors() {
+        assert_eq!(Value::default(), Value::Null);
+        assert_eq!(Value::empty_object(), Value::Object(Map::new()));
+        assert_eq!(Value::empty_array(), Value::Array(vec![]));
+        assert_eq!(to_string(&Value::empty_object()).unwrap(), "{}");
+
+        let object = Value::object_from(vec![
+            ("b".to_string(), Value::U64(1)),
+            ("a".to_string(), Value::Bool(true)),
+            ("b".to_string(), Value::U64(2)),
+        ]);
+        assert_eq!(object["b"], Value::U64(2));
+        assert_eq!(object.as_object().map(|o| o.len()), Some(2));
+        assert_eq!(Value::object_from(None), Value::empty_object());
+
+        let array = Value::array_from((1..4).map(Value::I64));
+        assert_eq!(array[2], Value::I64(3));
+        assert_eq!(array.as_array().map(|a| a.len()), Some(3));
+        assert_eq!(Value::array_from(vec![]), Value::empty_array());
+    }
 
     #[test]
     fn number_deserialize() {
@@ -1216,4 +2445,446 @@ mod test {
         let vo = v.as_object().unwrap();
         assert_eq!(vo["a"].as_f64().unwrap(), -1e6);
     }
+
+    #[test]
+    fn pointer() {
+        let mut v: Value =
+            from_str("{servers: [{host: \"a\"}, {host: \"b\"}], \"a/b\": 1, \"m~n\": 2, \"\": 3}")
+                .unwrap();
+        assert_eq!(v.pointer(""), Some(&v.clone()));
+        assert_eq!(v.pointer("/servers/1/host").unwrap().as_str(), Some("b"));
+        assert_eq!(v.pointer("/a~1b").unwrap().as_u64(), Some(1));
+        assert_eq!(v.pointer("/m~0n").unwrap().as_u64(), Some(2));
+        assert_eq!(v.pointer("/").unwrap().as_u64(), Some(3));
+        assert_eq!(v.pointer("/servers/2"), None);
+        assert_eq!(v.pointer("/servers/01"), None);
+        assert_eq!(v.pointer("/missing/0"), None);
+        assert_eq!(v.pointer("servers"), None);
+
+        *v.pointer_mut("/servers/0/host").unwrap() = Value::String("c".to_string());
+        assert_eq!(v.pointer("/servers/0/host").unwrap().as_str(), Some("c"));
+        assert!(v.pointer_mut("/servers/x").is_none());
+    }
+
+    #[test]
+    fn get_path() {
+        let v: Value = from_str(
+            "{servers: [{host: \"a\", ports: [80, 443]}, {host: \"b\"}], \"0\": {x: 1}, \"a.b\": 2}",
+        )
+        .unwrap();
+        assert_eq!(v.get_path("servers.0.host").unwrap().as_str(), Some("a"));
+        assert_eq!(v.get_path("servers.0.ports.1").unwrap().as_u64(), Some(443));
+        assert_eq!(v.get_path("servers.1"), v.pointer("/servers/1"));
+        assert_eq!(v.get_path("0.x").unwrap().as_u64(), Some(1));
+        assert_eq!(v.get_path(""), Some(&v));
+
+        assert_eq!(v.get_path("servers.2.host"), None);
+        assert_eq!(v.get_path("servers.x"), None);
+        assert_eq!(v.get_path("servers.+0"), None);
+        assert_eq!(v.get_path("servers.0.host.len"), None);
+        assert_eq!(v.get_path("missing.host"), None);
+        assert_eq!(v.get_path("servers."), None);
+        // keys with a dot need a pointer
+        assert_eq!(v.get_path("a.b"), None);
+        assert_eq!(v.pointer("/a.b").unwrap().as_u64(), Some(2));
+    }
+
+    #[test]
+    fn index() {
+        let mut v: Value = from_str("{a: {b: [1, 2, 3]}\nc: x\n}").unwrap();
+        assert_eq!(v["a"]["b"][2].as_u64(), Some(3));
+        assert!(v["a"]["b"][3].is_null());
+        assert!(v["missing"]["b"][0].is_null());
+        assert!(v["c"]["b"].is_null());
+
+        v["a"]["b"][0] = Value::U64(7);
+        assert_eq!(v["a"]["b"][0].as_u64(), Some(7));
+
+        v["d"]["e"] = Value::Bool(true);
+        assert_eq!(v.pointer("/d/e"), Some(&Value::Bool(true)));
+    }
+
+    #[test]
+    fn merge() {
+        let base: Value =
+            from_str("{a: {b: {c: 1, d: 2}, e: [1, 2]}, f: 1, g: {h: 1}, n: 1}").unwrap();
+        let update: Value =
+            from_str("{a: {b: {c: 3, x: 4}, e: [3]}, f: {y: 1}, g: 2, n: null}").unwrap();
+
+        let mut merged = base.clone();
+        merged.merge(update.clone());
+        let expected: Value =
+            from_str("{a: {b: {c: 3, d: 2, x: 4}, e: [3]}, f: {y: 1}, g: 2, n: null}").unwrap();
+        assert_eq!(merged, expected);
+
+        let mut merged = base.clone();
+        merged.merge_preserve(update);
+        assert_eq!(merged["n"].as_u64(), Some(1));
+        assert_eq!(merged["a"]["b"]["d"].as_u64(), Some(2));
+    }
+
+    #[cfg(feature = "preserve_order")]
+    #[test]
+    fn preserve_order() {
+        let v: Value = from_str("{b: 1, a: 2, c: 3}").unwrap();
+        let keys: Vec<&str> = v.as_object().unwrap().keys().map(|k| &k[..]).collect();
+        assert_eq!(keys, ["b", "a", "c"]);
+        assert_eq!(to_string(&v).unwrap(), "{\n  b: 1\n  a: 2\n  c: 3\n}");
+    }
+
+    #[cfg(feature = "preserve_order")]
+    #[test]
+    fn sort_keys() {
+        use ser::to_string_sorted;
+
+        let mut v: Value =
+            from_str("{b: {z: 1, y: [{d: 1, c: 2}, 3, [{f: 1, e: 2}]]}, a: 1, B: {}}").unwrap();
+        v.sort_keys();
+        assert_eq!(
+            v,
+            hjson!({"B": {}, "a": 1, "b": {"y": [{"c": 2, "d": 1}, 3, [{"e": 2, "f": 1}]], "z": 1}})
+        );
+        let keys: Vec<&str> = v.as_object().unwrap().keys().map(|k| &k[..]).collect();
+        assert_eq!(keys, ["B", "a", "b"]);
+        let keys: Vec<&str> = v["b"]["y"][0]
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(|k| &k[..])
+            .collect();
+        assert_eq!(keys, ["c", "d"]);
+        assert_eq!(
+            to_string(&v["b"]).unwrap(),
+            to_string_sorted(&v["b"]).unwrap()
+        );
+    }
+
+    #[cfg(not(feature = "preserve_order"))]
+    #[test]
+    fn sorted_order() {
+        let v: Value = from_str("{b: 1, a: 2, c: 3}").unwrap();
+        assert_eq!(to_string(&v).unwrap(), "{\n  a: 2\n  b: 1\n  c: 3\n}");
+    }
+
+    #[cfg(feature = "arbitrary_precision")]
+    #[test]
+    fn arbitrary_precision() {
+        use super::{from_value, to_value};
+        use serde::de;
+        use std::collections::BTreeMap;
+        use std::fmt;
+
+        // sorted, so that the text is written back the same without `preserve_order`
+        let text = "{\n  big: 1234567890123456789012345678901234567890\n  exp: 1e3\n  half: 0.5\n  neg: -1234567890123456789012345678901234567890\n  pi: 3.14159265358979323846264338327950288\n  small: 42\n}";
+        let v: Value = from_str(text).unwrap();
+        assert_eq!(
+            v["big"],
+            Value::Number("1234567890123456789012345678901234567890".to_string())
+        );
+        assert_eq!(v["exp"].as_f64(), Some(1000.0));
+        assert_eq!(v["exp"].as_u64(), None);
+        // numbers that are written back unchanged keep their usual variants
+        assert_eq!(v["small"], Value::U64(42));
+        assert_eq!(v["half"], Value::F64(0.5));
+        assert_eq!(to_string(&v).unwrap(), text);
+        assert_eq!(to_string(&to_value(&v).unwrap()).unwrap(), text);
+
+        // primitives still get converted numbers
+        let plain: BTreeMap<String, f64> =
+            from_str("{exp: 1e3, pi: 3.14159265358979323846}").unwrap();
+        assert_eq!(plain["exp"], 1000.0);
+        assert_eq!(plain["pi"], ::std::f64::consts::PI);
+        let plain: BTreeMap<String, Value> = from_value(v).unwrap();
+        assert_eq!(from_value::<f64>(plain["exp"].clone()).unwrap(), 1000.0);
+
+        // as do types that deserialize any value but do not ask for literals
+        struct Float(f64);
+        impl<'de> de::Deserialize<'de> for Float {
+            fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Float, D::Error> {
+                struct FloatVisitor;
+                impl<'de> de::Visitor<'de> for FloatVisitor {
+                    type Value = Float;
+                    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                        f.write_str("a float")
+                    }
+                    fn visit_f64<E>(self, value: f64) -> Result<Float, E> {
+                        Ok(Float(value))
+                    }
+                }
+                deserializer.deserialize_any(FloatVisitor)
+            }
+        }
+        let plain: Vec<Float> = from_str("[1e3, 3.14159265358979323846]").unwrap();
+        assert_eq!(plain[0].0, 1000.0);
+        assert_eq!(plain[1].0, ::std::f64::consts::PI);
+        let nested: Vec<Value> = from_str("[[1e3]]").unwrap();
+        assert_eq!(nested[0][0], Value::Number("1e3".to_string()));
+    }
+
+    #[test]
+    fn accessors() {
+        let mut v: Value =
+            from_str("{s: \"text\", i: -3, u: 7, f: 1.5, b: true, n: null, a: [1], o: {k: 1}}")
+                .unwrap();
+
+        assert_eq!(v["s"].as_str(), Some("text"));
+        assert!(v["s"].is_string());
+        assert_eq!(v["i"].as_str(), None);
+
+        assert_eq!(v["i"].as_i64(), Some(-3));
+        assert_eq!(v["i"].as_u64(), None);
+        assert_eq!(v["i"].as_f64(), Some(-3.0));
+        assert!(v["i"].is_i64() && v["i"].is_number() && !v["i"].is_u64());
+        assert_eq!(v["u"].as_i64(), Some(7));
+        assert_eq!(v["u"].as_u64(), Some(7));
+        assert_eq!(v["u"].as_f64(), Some(7.0));
+        assert!(v["u"].is_u64() && !v["u"].is_f64());
+        assert_eq!(v["f"].as_f64(), Some(1.5));
+        assert_eq!(v["f"].as_i64(), None);
+        assert_eq!(v["f"].as_u64(), None);
+        assert!(v["f"].is_f64());
+        assert_eq!(Value::U64(u64::MAX).as_i64(), None);
+        assert_eq!(v["s"].as_f64(), None);
+        assert!(!v["s"].is_number());
+
+        assert_eq!(v["b"].as_bool(), Some(true));
+        assert!(v["b"].is_boolean());
+        assert_eq!(v["n"].as_bool(), None);
+        assert_eq!(v["n"].as_null(), Some(()));
+        assert!(v["n"].is_null() && !v["b"].is_null());
+
+        assert_eq!(v["a"].as_array().map(|a| a.len()), Some(1));
+        assert!(v["a"].is_array() && !v["o"].is_array());
+        assert_eq!(v["o"].as_array(), None);
+        v["a"].as_array_mut().unwrap().push(Value::U64(2));
+        assert_eq!(v["a"][1], Value::U64(2));
+        assert!(v["s"].as_array_mut().is_none());
+
+        assert_eq!(v["o"].as_object().map(|o| o.len()), Some(1));
+        assert!(v["o"].is_object() && !v["a"].is_object());
+        assert_eq!(v["a"].as_object(), None);
+        v["o"]
+            .as_object_mut()
+            .unwrap()
+            .insert("x".to_string(), Value::Null);
+        assert_eq!(v["o"].as_object().map(|o| o.len()), Some(2));
+        assert!(v["u"].as_object_mut().is_none());
+    }
+
+    #[test]
+    fn take() {
+        let mut v: Value = from_str("{a: {b: [1, 2, 3]}, c: true}").unwrap();
+        let b = v.pointer_mut("/a/b").unwrap().take();
+        assert_eq!(b, from_str::<Value>("[1, 2, 3]").unwrap());
+        assert_eq!(v["a"]["b"], Value::Null);
+        assert_eq!(v["c"], Value::Bool(true));
+
+        let mut s = Value::String("x".to_string());
+        assert_eq!(s.take(), Value::String("x".to_string()));
+        assert!(s.is_null());
+    }
+
+    #[test]
+    fn get_ci() {
+        let mut v: Value = from_str("{Name: \"a\", PORT: 80, port: 81}").unwrap();
+        assert_eq!(v.get_ci("Name").and_then(Value::as_str), Some("a"));
+        assert_eq!(v.get_ci("nAME").and_then(Value::as_str), Some("a"));
+        assert_eq!(v.get_ci("missing"), None);
+        assert_eq!(v.get_ci("Port").and_then(Value::as_u64), Some(80));
+
+        *v.get_ci_mut("name").unwrap() = Value::Bool(true);
+        assert_eq!(v["Name"], Value::Bool(true));
+        assert!(v.get_ci_mut("missing").is_none());
+
+        let mut list: Value = from_str("[{name: 1}]").unwrap();
+        assert_eq!(list.get_ci("name"), None);
+        assert!(list.get_ci_mut("name").is_none());
+        assert_eq!(Value::String("name".to_string()).get_ci("name"), None);
+    }
+
+    #[test]
+    fn display() {
+        let v: Value = from_str("{a: {b: [1, \"x y\"]}, c: null}").unwrap();
+        assert_eq!(format!("{}", v), r#"{"a":{"b":[1,"x y"]},"c":null}"#);
+        assert_eq!(format!("{:#}", v), to_string(&v).unwrap());
+        assert_eq!(
+            format!("{:#}", v),
+            "{\n  a:\n  {\n    b:\n    [\n      1\n      x y\n    ]\n  }\n  c: null\n}"
+        );
+        assert_eq!(from_str::<Value>(&v.to_string()).unwrap(), v);
+    }
+
+    #[test]
+    #[should_panic]
+    fn index_mut_out_of_range() {
+        let mut v: Value = from_str("[1, 2, 3]").unwrap();
+        v[3] = Value::Null;
+    }
+
+    #[test]
+    fn entry() {
+        let mut v: Value = from_str("{a: 1}").unwrap();
+        assert_eq!(v.entry("b").key(), "b");
+        *v.entry("b").or_insert(Value::U64(2)) = Value::U64(3);
+        v.entry("a").or_insert(Value::Null);
+        v.entry("a")
+            .and_modify(|a| *a = Value::String("one".to_string()))
+            .or_insert_with(|| unreachable!());
+        v.entry("c").and_modify(|_| unreachable!());
+        assert_eq!(v, from_str::<Value>("{a: \"one\", b: 3}").unwrap());
+
+        let mut v = Value::Null;
+        v.entry(String::from("list"))
+            .or_insert_with(|| Value::Array(vec![]));
+        assert_eq!(v["list"], Value::Array(vec![]));
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot get the entry \"a\" of a non-object value")]
+    fn entry_of_array() {
+        Value::Array(vec![]).entry("a");
+    }
+
+    #[test]
+    fn shape() {
+        let v: Value =
+            from_str("{\n  name: x\n  port: 80\n  tls: {enabled: true, cert: null}\n  list: []\n}")
+                .unwrap();
+        let expected = hjson!({
+            "name": "string",
+            "port": "number",
+            "tls": {"enabled": "bool", "cert": "null"},
+            "list": [],
+        });
+        assert_eq!(v.shape(), expected);
+
+        let v: Value = from_str("[1, \"a\", 2.5, null, [true], {a: 1}]").unwrap();
+        assert_eq!(
+            v.shape(),
+            hjson!(["number | string | null | array | object"])
+        );
+        let v: Value = from_str("[[true], [], [1], [[]]]").unwrap();
+        assert_eq!(v.shape(), hjson!([["bool | number | array"]]));
+        let v: Value = from_str("[{a: 1}, {a: \"x\", b: [1]}, {b: [null]}]").unwrap();
+        assert_eq!(
+            v.shape(),
+            hjson!([{"a": "number | string", "b": ["number | null"]}])
+        );
+        assert_eq!(hjson!([]).shape(), hjson!([]));
+    }
+
+    #[test]
+    fn flatten() {
+        let config: Value = from_str(
+            "{\n  server: {\n    host: localhost\n    ports: [80, 443]\n    tls: {}\n  }\n  debug: true\n  tags: [[\"a\"], {b: null}]\n}",
+        )
+        .unwrap();
+        let flat = config.flatten(".");
+        let mut keys: Vec<&str> = flat.keys().map(|key| &key[..]).collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec![
+                "debug",
+                "server.host",
+                "server.ports.0",
+                "server.ports.1",
+                "server.tls",
+                "tags.0.0",
+                "tags.1.b",
+            ]
+        );
+        assert_eq!(flat["server.host"], Value::String("localhost".to_string()));
+        assert_eq!(flat["server.ports.1"], Value::U64(443));
+        assert_eq!(flat["server.tls"], Value::Object(Map::new()));
+        assert_eq!(flat["tags.1.b"], Value::Null);
+
+        let flat = config["server"].flatten("__");
+        assert_eq!(flat["ports__0"], Value::U64(80));
+        assert_eq!(Value::Bool(true).flatten(".")[""], Value::Bool(true));
+    }
+
+    #[test]
+    fn unflatten() {
+        let config: Value = from_str(
+            "{\n  server: {\n    host: localhost\n    ports: [80, 443]\n    tls: {}\n  }\n  debug: true\n  tags: [[\"a\"], {b: null}, []]\n}",
+        )
+        .unwrap();
+        for sep in [".", "__"].iter() {
+            let flat = config.flatten(sep);
+            assert_eq!(Value::unflatten(flat, sep).unwrap(), config);
+        }
+        assert_eq!(
+            Value::unflatten(Value::U64(1).flatten("."), ".").unwrap(),
+            Value::U64(1)
+        );
+        assert_eq!(
+            Value::unflatten(Map::new(), ".").unwrap(),
+            Value::Object(Map::new())
+        );
+
+        // indices may come in any order, and keys that look like numbers with leading zeros
+        // stay keys
+        let mut flat = Map::new();
+        flat.insert("a.1".to_string(), Value::U64(1));
+        flat.insert("a.0".to_string(), Value::U64(0));
+        flat.insert("b.01".to_string(), Value::Null);
+        let value = Value::unflatten(flat, ".").unwrap();
+        assert_eq!(
+            to_string(&value).unwrap(),
+            "{\n  a:\n  [\n    0\n    1\n  ]\n  b:\n  {\n    01: null\n  }\n}"
+        );
+
+        let errors = [
+            (&["a", "a.b"][..], "conflicting path `a.b`"),
+            (&["a.0", "a.b"][..], "conflicting path `a.b`"),
+            (&["", "a"][..], "conflicting path `a`"),
+            (&["a.1", "a.2.x"][..], "missing array element `a.0`"),
+            (
+                &["a.0.b.0", "a.0.b.2"][..],
+                "missing array element `a.0.b.1`",
+            ),
+        ];
+        for &(paths, message) in errors.iter() {
+            let flat = paths
+                .iter()
+                .map(|path| (path.to_string(), Value::Bool(true)))
+                .collect();
+            match Value::unflatten(flat, ".") {
+                Err(Error::Syntax(ErrorCode::Custom(ref msg), ..)) if msg == message => {}
+                other => panic!("{:?}: unexpected result {:?}", paths, other),
+            }
+        }
+
+        // keys are not escaped, so some do not come back as they were
+        let round_trip = |text: &str| {
+            let value: Value = from_str(text).unwrap();
+            Value::unflatten(value.flatten("."), ".").unwrap()
+        };
+        assert_eq!(
+            round_trip("{\"a.b\": 1}"),
+            from_str::<Value>("{a: {b: 1}}").unwrap()
+        );
+        assert_eq!(round_trip("{\"0\": 1}"), Value::Array(vec![Value::U64(1)]));
+        assert_eq!(round_trip("{\"\": 1}"), Value::U64(1));
+        let value: Value = from_str("{a: {b: 1}, \"a.b\": 2}").unwrap();
+        let flat = value.flatten(".");
+        assert_eq!(flat.len(), 1);
+        assert_eq!(flat["a.b"], Value::U64(2));
+    }
+
+    #[test]
+    fn parse() {
+        fn port(text: &str) -> ::Result<u64> {
+            let config: Value = text.parse()?;
+            Ok(config["server"]["port"].as_u64().unwrap_or(0))
+        }
+
+        let text = "# config\nserver: {\n  host: quoteless host name\n  port: 8080\n}\n";
+        let v: Value = text.parse().unwrap();
+        assert_eq!(v, from_str::<Value>(text).unwrap());
+        assert_eq!(v["server"]["host"].as_str(), Some("quoteless host name"));
+        assert_eq!(port(text).unwrap(), 8080);
+        assert!(port("{server: [}").is_err());
+    }
 }
@@ -0,0 +1,42 @@
+//! Uses serde-hjson from a `#![no_std]` crate, so building it checks that the library compiles
+//! with only `core` and `alloc`.
+
+#![no_std]
+
+extern crate alloc;
+extern crate serde;
+extern crate serde_hjson;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde_hjson::builder::ObjectBuilder;
+use serde_hjson::{hjson, Value};
+
+/// Parses Hjson and writes it back.
+pub fn reformat(text: &str) -> serde_hjson::Result<String> {
+    let value: Value = serde_hjson::from_str(text)?;
+    serde_hjson::to_string(&value)
+}
+
+/// Parses Hjson bytes into a type.
+pub fn parse_list(bytes: &[u8]) -> serde_hjson::Result<Vec<u64>> {
+    serde_hjson::from_slice(bytes)
+}
+
+/// Builds values with the macro and the builders and serializes them.
+pub fn build() -> serde_hjson::Result<Vec<u8>> {
+    let value = hjson!({
+        "name": "x",
+        "ports": [80, 443],
+        "nested": {"enabled": true, "parent": null},
+    });
+    let built = ObjectBuilder::new()
+        .insert("value", value)
+        .insert_array("list", |b| b.push(1).push("two"))
+        .unwrap();
+
+    let mut writer = Vec::new();
+    serde::Serialize::serialize(&built, &mut serde_hjson::Serializer::new(&mut writer))?;
+    Ok(writer)
+}
@@ -0,0 +1,66 @@
+// Measures the ASCII fast path of the reader, run with `cargo bench --bench ascii`. The same
+// document is parsed as it is and with a non-ASCII comment in front, which makes the reader check
+// every byte after it as UTF-8; the last column is how much longer that takes.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use serde_hjson::Value;
+
+fn document() -> String {
+    let members: Vec<String> = (0..20_000)
+        .map(|i| {
+            format!(
+                "  item{}: {{\n    name: some quoteless text {}\n    \"tags\": [\"a\", 'b', \"c\\n\"]\n    size: {}\n  }}",
+                i,
+                i,
+                i * 7
+            )
+        })
+        .collect();
+    format!("{{\n{}\n}}\n", members.join("\n"))
+}
+
+// The fastest of a few runs of `f`.
+fn measure<T>(f: impl Fn() -> T) -> Duration {
+    (0..20)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(f());
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let ascii = document();
+    let mixed = format!("# déjà vu\n{}", ascii);
+    let mut fast = Vec::new();
+    for (name, text) in [("ascii", &ascii), ("non-ascii", &mixed)] {
+        let parse = measure(|| serde_hjson::from_str::<Value>(black_box(text)).unwrap());
+        let validate = measure(|| serde_hjson::validate(black_box(text)).unwrap());
+        let mb = text.len() as f64 / 1e6;
+        for (i, &(api, time)) in [("from_str", parse), ("validate", validate)]
+            .iter()
+            .enumerate()
+        {
+            // the time of the checked path relative to the fast one
+            let ratio = match fast.get(i) {
+                Some(fast) => format!("{:.2}x", time.as_secs_f64() / fast),
+                None => {
+                    fast.push(time.as_secs_f64());
+                    String::new()
+                }
+            };
+            println!(
+                "{:<10} {:<9} {:>8.2?} {:>8.1} MB/s {:>6}",
+                name,
+                api,
+                time,
+                mb / time.as_secs_f64(),
+                ratio
+            );
+        }
+    }
+}
@@ -0,0 +1,80 @@
+// Measures `Deserializer::capacity_hint` on a large array, run with `cargo bench --bench
+// capacity`. A counting allocator reports the allocations of each parse and the bytes they
+// asked for next to its time; without the hint the vector is copied each time it grows.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+// serde caps what a `Vec` reserves up front at 1 MiB, so this many `u64` get all their room
+const LEN: usize = 100_000;
+
+fn parse(text: &str, hint: Option<usize>) -> Vec<u64> {
+    let mut de = serde_hjson::Deserializer::from_str(text);
+    if let Some(len) = hint {
+        de = de.capacity_hint(len);
+    }
+    serde::Deserialize::deserialize(&mut de).unwrap()
+}
+
+// The fastest of a few runs of `f`, and the allocations and allocated bytes of one run.
+fn measure<T>(f: impl Fn() -> T) -> (Duration, usize, usize) {
+    let before = (
+        ALLOCATIONS.load(Ordering::Relaxed),
+        BYTES.load(Ordering::Relaxed),
+    );
+    black_box(f());
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before.0;
+    let bytes = BYTES.load(Ordering::Relaxed) - before.1;
+    let time = (0..10)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(f());
+            start.elapsed()
+        })
+        .min()
+        .unwrap();
+    (time, allocations, bytes)
+}
+
+fn main() {
+    let items: Vec<String> = (0..LEN).map(|i| (i * 7).to_string()).collect();
+    let text = format!("[\n{}\n]\n", items.join("\n"));
+    for (name, hint) in [("no hint", None), ("hint", Some(LEN))] {
+        let (time, allocations, bytes) = measure(|| parse(black_box(&text), hint));
+        println!(
+            "{:<8} {:>8.2?} {:>8} allocations {:>6.1} MB",
+            name,
+            time,
+            allocations,
+            bytes as f64 / 1e6
+        );
+    }
+}
@@ -0,0 +1,48 @@
+// Compares `validate` with parsing into a `Value`, run with `cargo bench --bench validate`. It
+// only uses `std::time`, so it works on stable without a benchmark framework.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use serde_hjson::Value;
+
+fn document() -> String {
+    let members: Vec<String> = (0..20_000)
+        .map(|i| {
+            format!(
+                "  item{}: {{\n    name: some quoteless text {}\n    \"tags\": [\"a\", 'b', \"c\\n\"]\n    size: {}\n  }}",
+                i,
+                i,
+                i * 7
+            )
+        })
+        .collect();
+    format!("{{\n{}\n}}\n", members.join("\n"))
+}
+
+// The fastest of a few runs of `f`.
+fn measure<T>(f: impl Fn() -> T) -> Duration {
+    (0..10)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(f());
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let text = document();
+    let parse = measure(|| serde_hjson::from_str::<Value>(black_box(&text)).unwrap());
+    let validate = measure(|| serde_hjson::validate(black_box(&text)).unwrap());
+    let mb = text.len() as f64 / 1e6;
+    for (name, time) in [("from_str::<Value>", parse), ("validate", validate)] {
+        println!(
+            "{:<18} {:>8.2?} {:>8.1} MB/s",
+            name,
+            time,
+            mb / time.as_secs_f64()
+        );
+    }
+}
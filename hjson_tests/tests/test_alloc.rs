@@ -0,0 +1,99 @@
+// Counts the allocations of the parser, kept apart from the other tests so that nothing else
+// allocates while it runs. The tests here take `SERIAL` so they do not count each other's.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use serde_hjson::Value;
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+static SERIAL: Mutex<()> = Mutex::new(());
+
+fn allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let result = f();
+    (result, ALLOCATIONS.load(Ordering::Relaxed) - before)
+}
+
+fn parse(text: &str, hint: Option<usize>) -> (Vec<u64>, usize) {
+    let mut de = serde_hjson::Deserializer::from_str(text);
+    if let Some(len) = hint {
+        de = de.capacity_hint(len);
+    }
+    allocations(|| serde::Deserialize::deserialize(&mut de).unwrap())
+}
+
+#[test]
+fn test_capacity_hint_allocations() {
+    let _serial = SERIAL.lock().unwrap();
+    let len = 100_000;
+    let items: Vec<String> = (0..len).map(|i| i.to_string()).collect();
+    let text = format!("[\n{}\n]", items.join("\n"));
+
+    let (grown, grown_allocations) = parse(&text, None);
+    let (reserved, reserved_allocations) = parse(&text, Some(len));
+    assert_eq!(grown, reserved);
+    assert_eq!(reserved.len(), len);
+    // the vector grows about 17 times without the hint
+    assert!(
+        reserved_allocations + 10 < grown_allocations,
+        "{} allocations with the hint, {} without",
+        reserved_allocations,
+        grown_allocations
+    );
+}
+
+#[test]
+fn test_validate_allocations() {
+    let _serial = SERIAL.lock().unwrap();
+    let items: Vec<String> = (0..10_000)
+        .map(|i| {
+            format!(
+                "  item{}: {{ name: \"n{}\", list: [{}, true, 'text'] }}",
+                i, i, i
+            )
+        })
+        .collect();
+    let text = items.join("\n");
+
+    let (parsed, parsed_allocations) = allocations(|| serde_hjson::from_str::<Value>(&text));
+    let (validated, validated_allocations) = allocations(|| serde_hjson::validate(&text));
+    assert!(parsed.is_ok());
+    assert!(validated.is_ok());
+    // only the numbers allocate while validating, the value needs a map, keys and strings too
+    assert!(
+        validated_allocations * 4 < parsed_allocations,
+        "{} allocations to validate, {} to parse",
+        validated_allocations,
+        parsed_allocations
+    );
+
+    // the same document with an error at the end
+    let broken = format!("{}\n  last: [1, 2", text);
+    let parsed = serde_hjson::from_str::<Value>(&broken).unwrap_err();
+    let validated = serde_hjson::validate(&broken).unwrap_err();
+    assert_eq!(validated.to_string(), parsed.to_string());
+}
@@ -3,6 +3,7 @@ extern crate serde_hjson;
 extern crate serde_json;
 
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use serde_hjson::Value;
 use std::borrow::Cow;
 use std::fs;
@@ -102,7 +103,6 @@ fn test_hjson() {
     run_test!(failJSON21, done, std_fix);
     run_test!(failJSON22, done, std_fix);
     run_test!(failJSON23, done, std_fix);
-    run_test!(failJSON24, done, std_fix);
     run_test!(failJSON26, done, std_fix);
     run_test!(failJSON28, done, std_fix);
     run_test!(failJSON29, done, std_fix);
@@ -152,6 +152,7 @@ fn test_hjson() {
     run_test!(pass4, done, std_fix);
     run_test!(passSingle, done, std_fix);
     run_test!(root, done, std_fix);
+    run_test!(singleQuote, done, std_fix);
     run_test!(stringify1, done, std_fix);
     run_test!(strings, done, std_fix);
     run_test!(trail, done, std_fix);
@@ -176,3 +177,555 @@ fn test_hjson() {
         assert!(false);
     }
 }
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Borrowed<'a> {
+    quoted: &'a str,
+    quoteless: &'a str,
+    #[serde(borrow)]
+    list: Vec<&'a str>,
+}
+
+#[test]
+fn test_borrowed_str() {
+    let text = "{\n  quoted: \"no escapes\"\n  \"quoteless\": a b c  \n  list: [\n    x\n    \"y\"\n  ]\n}";
+    let value: Borrowed = serde_hjson::from_str(text).unwrap();
+    assert_eq!(
+        value,
+        Borrowed {
+            quoted: "no escapes",
+            quoteless: "a b c",
+            list: vec!["x", "y"],
+        }
+    );
+    let range = text.as_ptr() as usize..text.as_ptr() as usize + text.len();
+    assert!(range.contains(&(value.quoted.as_ptr() as usize)));
+    assert!(range.contains(&(value.quoteless.as_ptr() as usize)));
+
+    let mut de = serde_hjson::Deserializer::from_str(text);
+    let value = Borrowed::deserialize(&mut de).unwrap();
+    assert_eq!(value.list, vec!["x", "y"]);
+
+    // a string with escapes can't be borrowed
+    let text = "{quoted: \"a\\nb\", quoteless: x, list: []}";
+    assert!(serde_hjson::from_str::<Borrowed>(text).is_err());
+}
+
+#[derive(Serialize, PartialEq, Eq, PartialOrd, Ord)]
+enum Level {
+    Low,
+    High,
+}
+
+#[test]
+fn test_enum_keys() {
+    let mut map = std::collections::BTreeMap::new();
+    map.insert(Level::High, 2);
+    map.insert(Level::Low, 1);
+    assert_eq!(
+        serde_hjson::to_string(&map).unwrap(),
+        "{\n  Low: 1\n  High: 2\n}"
+    );
+    let value = serde_hjson::to_value(&map).unwrap();
+    assert_eq!(value["High"].as_u64(), Some(2));
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum Shape {
+    Circle(f64),
+    Rect { w: u32, h: u32 },
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Drawing {
+    name: String,
+    layers: Vec<Shape>,
+    hidden: Option<bool>,
+}
+
+#[test]
+fn test_value_conversion() {
+    let drawing = Drawing {
+        name: "plan".to_string(),
+        layers: vec![Shape::Circle(1.5), Shape::Rect { w: 2, h: 3 }],
+        hidden: None,
+    };
+    let value = serde_hjson::to_value(&drawing).unwrap();
+    assert_eq!(value["name"].as_str(), Some("plan"));
+    assert_eq!(value["layers"][0]["Circle"].as_f64(), Some(1.5));
+    assert_eq!(value["layers"][1]["Rect"]["h"].as_u64(), Some(3));
+    assert!(value["hidden"].is_null());
+    assert_eq!(
+        value,
+        serde_hjson::from_str::<serde_hjson::Value>(&serde_hjson::to_string(&drawing).unwrap())
+            .unwrap()
+    );
+
+    let back: Drawing = serde_hjson::from_value(value).unwrap();
+    assert_eq!(back, drawing);
+
+    let value = serde_hjson::to_value(&[1, 2]).unwrap();
+    assert!(serde_hjson::from_value::<Drawing>(value).is_err());
+}
+
+#[test]
+fn test_force_quotes_json() {
+    let drawing = Drawing {
+        name: "true".to_string(),
+        layers: vec![Shape::Circle(-0.5), Shape::Rect { w: 1, h: 2 }],
+        hidden: Some(false),
+    };
+    let mut text = Vec::new();
+    let mut ser = serde_hjson::Serializer::new(&mut text)
+        .force_quote_keys(true)
+        .force_quote_strings(true);
+    drawing.serialize(&mut ser).unwrap();
+    let text = String::from_utf8(text).unwrap();
+
+    let json: serde_json::Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(json, serde_json::to_value(&drawing).unwrap());
+    assert_eq!(serde_hjson::from_str::<Drawing>(&text).unwrap(), drawing);
+
+    // multiline strings and a narrow width that puts the arrays on their own lines
+    let value: serde_hjson::Value =
+        serde_hjson::from_str("{key: \"plain\", list: [1, \"x\\ny\", [], {a: 'q\"'}]}").unwrap();
+    let mut text = Vec::new();
+    let ser = serde_hjson::Serializer::new(&mut text)
+        .force_quote_keys(true)
+        .force_quote_strings(true);
+    value.serialize(&mut ser.compact_width(0)).unwrap();
+    let text = String::from_utf8(text).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({"key": "plain", "list": [1, "x\ny", [], {"a": "q\""}]})
+    );
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+enum Mode {
+    Off,
+    Fixed(u32),
+    Range(u32, u32),
+    Auto { min: u32, max: u32 },
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Settings {
+    mode: Mode,
+    fallback: Vec<Mode>,
+}
+
+#[test]
+fn test_enums() {
+    use serde_hjson::from_str;
+
+    assert_eq!(from_str::<Mode>("Off").unwrap(), Mode::Off);
+    assert_eq!(from_str::<Mode>("\"Off\"").unwrap(), Mode::Off);
+    assert_eq!(from_str::<Mode>("{Fixed: 3}").unwrap(), Mode::Fixed(3));
+    assert_eq!(from_str::<Mode>("Fixed: 3").unwrap(), Mode::Fixed(3));
+    assert_eq!(
+        from_str::<Mode>("{\"Range\": [1, 2],}").unwrap(),
+        Mode::Range(1, 2)
+    );
+    assert_eq!(
+        from_str::<Mode>("{\n  Auto:\n  {\n    min: 1\n    max: 5\n  }\n}").unwrap(),
+        Mode::Auto { min: 1, max: 5 }
+    );
+
+    let text =
+        "# settings\nmode: Off\nfallback: [\n  {Fixed: 2}\n  Off\n  {Auto: {min: 0, max: 9}}\n]\n";
+    assert_eq!(
+        from_str::<Settings>(text).unwrap(),
+        Settings {
+            mode: Mode::Off,
+            fallback: vec![Mode::Fixed(2), Mode::Off, Mode::Auto { min: 0, max: 9 }],
+        }
+    );
+
+    let drawing = Drawing {
+        name: "plan".to_string(),
+        layers: vec![Shape::Circle(1.5), Shape::Rect { w: 2, h: 3 }],
+        hidden: Some(true),
+    };
+    let text = serde_hjson::to_string(&drawing).unwrap();
+    assert_eq!(from_str::<Drawing>(&text).unwrap(), drawing);
+
+    for text in [
+        "Unknown",
+        "{Fixed: 1, Off: null}",
+        "Fixed",
+        "{Off: null",
+        "{}",
+    ]
+    .iter()
+    {
+        assert!(from_str::<Mode>(text).is_err(), "{}", text);
+    }
+    match from_str::<Settings>("mode: Unknown\nfallback: []") {
+        Err(serde_hjson::Error::Syntax(_, 1, 7, 6)) => {}
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct Server {
+    host: String,
+    port: Option<u16>,
+    backup: Option<Box<Server>>,
+    tags: Vec<Option<&'static str>>,
+}
+
+#[test]
+fn test_skip_none() {
+    let server = Server {
+        host: "localhost".to_string(),
+        port: None,
+        backup: Some(Box::new(Server {
+            host: "spare".to_string(),
+            port: None,
+            backup: None,
+            tags: vec![],
+        })),
+        tags: vec![None, Some("main")],
+    };
+    let write = |skip_none: bool, sort_keys: bool| {
+        let mut text = Vec::new();
+        let mut ser = serde_hjson::Serializer::new(&mut text)
+            .skip_none(skip_none)
+            .sort_keys(sort_keys);
+        server.serialize(&mut ser).unwrap();
+        String::from_utf8(text).unwrap()
+    };
+
+    assert_eq!(
+        write(false, false),
+        "{\n  host: localhost\n  port: null\n  backup:\n  {\n    host: spare\n    port: null\n    backup: null\n    tags: []\n  }\n  tags:\n  [\n    null\n    main\n  ]\n}"
+    );
+    // elements of arrays are kept
+    let skipped =
+        "{\n  host: localhost\n  backup:\n  {\n    host: spare\n    tags: []\n  }\n  tags:\n  [\n    null\n    main\n  ]\n}";
+    assert_eq!(write(true, false), skipped);
+    // members held back to be sorted are left out as well
+    assert_eq!(
+        write(true, true),
+        skipped
+            .replace("  host: localhost\n  backup:", "  backup:")
+            .replace("  }\n  tags", "  }\n  host: localhost\n  tags")
+    );
+
+    let mut text = Vec::new();
+    let mut ser = serde_hjson::Serializer::new(&mut text)
+        .skip_none(true)
+        .compact_width(60);
+    server.serialize(&mut ser).unwrap();
+    assert_eq!(
+        String::from_utf8(text).unwrap(),
+        "{\n  host: localhost\n  backup: {host: \"spare\", tags: []}\n  tags: [null, \"main\"]\n}"
+    );
+}
+
+#[derive(Serialize, Debug)]
+struct Bounds {
+    min: Option<u32>,
+    max: Option<u32>,
+}
+
+#[derive(Serialize, Debug)]
+struct Tuning {
+    attempts: u32,
+    bounds: Bounds,
+}
+
+#[test]
+fn test_skip_none_all_members() {
+    fn write<T: Serialize>(value: &T, sort_keys: bool) -> String {
+        let mut text = Vec::new();
+        let mut ser = serde_hjson::Serializer::new(&mut text)
+            .skip_none(true)
+            .sort_keys(sort_keys);
+        value.serialize(&mut ser).unwrap();
+        String::from_utf8(text).unwrap()
+    }
+
+    // written like any other empty object
+    for &sort_keys in [false, true].iter() {
+        let bounds = Bounds {
+            min: None,
+            max: None,
+        };
+        assert_eq!(write(&bounds, sort_keys), "{}");
+        let tuning = Tuning {
+            attempts: 1,
+            bounds,
+        };
+        assert_eq!(
+            write(&tuning, sort_keys),
+            "{\n  attempts: 1\n  bounds: {}\n}"
+        );
+    }
+    let bounds = Bounds {
+        min: None,
+        max: Some(3),
+    };
+    assert_eq!(write(&bounds, false), "{\n  max: 3\n}");
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Deployment {
+    name: String,
+    plugin: serde_hjson::RawValue,
+}
+
+#[test]
+fn test_raw_value() {
+    let plugin = "{\n  # generated\n  enabled: true\n}";
+    let deployment = Deployment {
+        name: "web".to_string(),
+        plugin: serde_hjson::RawValue::from_string(plugin.to_string()).unwrap(),
+    };
+    let text = serde_hjson::to_string(&deployment).unwrap();
+    assert_eq!(
+        text,
+        "{\n  name: web\n  plugin: {\n  # generated\n  enabled: true\n}\n}"
+    );
+
+    let back: Deployment = serde_hjson::from_str(&text).unwrap();
+    assert_eq!(back, deployment);
+    assert_eq!(back.plugin.get(), plugin);
+
+    let value: Value = serde_hjson::to_value(&deployment).unwrap();
+    assert_eq!(value["plugin"]["enabled"], Value::Bool(true));
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Limits {
+    soft: u32,
+    hard: u32,
+}
+
+#[test]
+fn test_hash_map() {
+    use std::collections::HashMap;
+
+    let text =
+        "# ports by service\nweb: [80, 443]\nmail: [\n  25\n  587\n]\nnone: []\nweb: [8080]\n";
+    let ports: HashMap<String, Vec<i32>> = serde_hjson::from_str(text).unwrap();
+    let mut expected = HashMap::new();
+    expected.insert("web".to_string(), vec![8080]);
+    expected.insert("mail".to_string(), vec![25, 587]);
+    expected.insert("none".to_string(), vec![]);
+    assert_eq!(ports, expected);
+
+    let text = "files: {\n  soft: 1024\n  hard: 4096\n}\n'procs': {soft: 64\nhard: 128}\n";
+    let limits: HashMap<String, Limits> = serde_hjson::from_str(text).unwrap();
+    assert_eq!(
+        limits["files"],
+        Limits {
+            soft: 1024,
+            hard: 4096
+        }
+    );
+    assert_eq!(
+        limits["procs"],
+        Limits {
+            soft: 64,
+            hard: 128
+        }
+    );
+
+    // keys of other types are parsed from the key text
+    let names: HashMap<u16, String> = serde_hjson::from_str("80: http\n\"443\": https\n").unwrap();
+    assert_eq!(names[&80], "http");
+    assert_eq!(names[&443], "https");
+    let flags: HashMap<bool, i64> = serde_hjson::from_str("{true: 1, false: -1}").unwrap();
+    assert_eq!(flags[&false], -1);
+}
+
+#[test]
+fn test_hjson_to_json() {
+    let text = "// deployment\n{\n  # quoteless values run to the end of the line\n  name: my app  \n  /* a path */ path: C:\\dir\\x\n  escapes: \"a\\tb\\u0001\"\n  notes:\n    '''\n    one\n    two \"q\"\n    '''\n  list: [1, 2.5, true, null, [], {}]\n  'single': 'it\\'s'\n  nested: {\n    a: [\n      x\n      y\n    ]\n  }\n}\n";
+    let expected = serde_json::json!({
+        "name": "my app",
+        "path": "C:\\dir\\x",
+        "escapes": "a\tb\u{1}",
+        "notes": "one\ntwo \"q\"",
+        "list": [1, 2.5, true, null, [], {}],
+        "single": "it's",
+        "nested": {"a": ["x", "y"]},
+    });
+
+    let json = serde_hjson::hjson_to_json(text).unwrap();
+    assert_eq!(
+        json,
+        r#"{"name":"my app","path":"C:\\dir\\x","escapes":"a\tb\u0001","notes":"one\ntwo \"q\"","list":[1,2.5,true,null,[],{}],"single":"it's","nested":{"a":["x","y"]}}"#
+    );
+    let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, expected);
+
+    let json = serde_hjson::hjson_to_json_pretty(text).unwrap();
+    assert!(json.starts_with("{\n  \"name\": \"my app\",\n"));
+    let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, expected);
+
+    assert!(serde_hjson::hjson_to_json("{a: 1").is_err());
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(dead_code)]
+struct Listener {
+    host: String,
+    port: u16,
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(dead_code)]
+struct Cluster {
+    name: String,
+    servers: Vec<Listener>,
+    limits: std::collections::BTreeMap<String, Limits>,
+}
+
+#[test]
+fn test_error_path() {
+    let text = "name: main\nservers: [\n  {host: \"a\", port: 80}\n  {\n    host: b\n    port: eighty\n  }\n]\nlimits: {}\n";
+    let err = serde_hjson::from_str::<Cluster>(text).unwrap_err();
+    match err {
+        serde_hjson::Error::Syntax(serde_hjson::ErrorCode::Custom(ref msg), line, col, _) => {
+            assert_eq!(
+                msg,
+                "servers[1].port: invalid type: string \"eighty\", expected u16"
+            );
+            assert_eq!((line, col), (6, 11));
+        }
+        ref other => panic!("unexpected error {:?}", other),
+    }
+    assert_eq!(
+        err.to_string(),
+        "servers[1].port: invalid type: string \"eighty\", expected u16 at line 6 column 11"
+    );
+
+    // missing fields are reported at the object that lacks them
+    let text = "{\n  name: main\n  servers: []\n  limits: {\n    files: {soft: 1}\n  }\n}";
+    let err = serde_hjson::from_str::<Cluster>(text).unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with("limits.files: missing field `hard`"),
+        "{}",
+        err
+    );
+
+    // the path starts with an index in a root array, and is empty for a root value
+    let err = serde_hjson::from_str::<Vec<u16>>("[1, -1]").unwrap_err();
+    assert!(err.to_string().starts_with("[1]: invalid value"), "{}", err);
+    let err = serde_hjson::from_str::<u16>("x").unwrap_err();
+    assert!(err.to_string().starts_with("invalid type"), "{}", err);
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Identifiers {
+    id: u128,
+    offset: i128,
+    small: u128,
+}
+
+#[test]
+fn test_128_bit_integers() {
+    let ids = Identifiers {
+        id: u128::MAX,
+        offset: i128::MIN,
+        small: 7,
+    };
+    let text = serde_hjson::to_string(&ids).unwrap();
+    assert_eq!(
+        text,
+        "{\n  id: 340282366920938463463374607431768211455\n  offset: -170141183460469231731687303715884105728\n  small: 7\n}"
+    );
+    assert_eq!(serde_hjson::from_str::<Identifiers>(&text).unwrap(), ids);
+
+    // the modes that hold members back write them the same way
+    let id = "340282366920938463463374607431768211455";
+    let offset = "-170141183460469231731687303715884105728";
+    assert_eq!(serde_hjson::to_string_sorted(&ids).unwrap(), text);
+    assert_eq!(
+        serde_hjson::to_string_canonical(&ids).unwrap(),
+        format!("{{\"id\":{},\"offset\":{},\"small\":7}}", id, offset)
+    );
+    let write = |inline: bool| {
+        let mut out = Vec::new();
+        let ser = serde_hjson::Serializer::new(&mut out);
+        let mut ser = if inline {
+            ser.compact_width(120)
+        } else {
+            ser.align_colons(true)
+        };
+        ids.serialize(&mut ser).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    assert_eq!(
+        write(true),
+        format!("{{id: {}, offset: {}, small: 7}}", id, offset)
+    );
+    assert_eq!(
+        write(false),
+        format!(
+            "{{\n  id:     {}\n  offset: {}\n  small:  7\n}}",
+            id, offset
+        )
+    );
+
+    // out of range and non-integer values are still errors
+    assert!(serde_hjson::from_str::<u128>("340282366920938463463374607431768211456").is_err());
+    assert!(serde_hjson::from_str::<u128>("-1").is_err());
+    assert!(serde_hjson::from_str::<i128>("1.5").is_err());
+
+    // a `Value` only holds them with the arbitrary_precision feature of serde-hjson
+    match serde_hjson::to_value(&u128::MAX) {
+        Ok(value) => assert_eq!(value.to_string(), u128::MAX.to_string()),
+        Err(serde_hjson::Error::Syntax(serde_hjson::ErrorCode::Custom(msg), ..)) => {
+            assert_eq!(msg, "number out of range")
+        }
+        Err(other) => panic!("unexpected error {:?}", other),
+    }
+    assert_eq!(serde_hjson::to_value(&5u128).unwrap(), Value::U64(5));
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Marker;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Tagged {
+    name: String,
+    marker: Marker,
+    nothing: (),
+}
+
+#[test]
+fn test_unit() {
+    assert_eq!(serde_hjson::from_str::<()>("null").unwrap(), ());
+    assert_eq!(
+        serde_hjson::from_str::<Marker>(" null # none\n").unwrap(),
+        Marker
+    );
+    assert_eq!(serde_hjson::to_string(&()).unwrap(), "null");
+    assert_eq!(serde_hjson::to_string(&Marker).unwrap(), "null");
+
+    let tagged = Tagged {
+        name: "x".to_string(),
+        marker: Marker,
+        nothing: (),
+    };
+    let text = serde_hjson::to_string(&tagged).unwrap();
+    assert_eq!(text, "{\n  name: x\n  marker: null\n  nothing: null\n}");
+    assert_eq!(serde_hjson::from_str::<Tagged>(&text).unwrap(), tagged);
+    assert_eq!(
+        serde_hjson::from_value::<Marker>(serde_hjson::to_value(&Marker).unwrap()).unwrap(),
+        Marker
+    );
+
+    // only null is a unit, not an empty object or a string
+    for text in ["{}", "[]", "\"null\"", "0"].iter() {
+        assert!(serde_hjson::from_str::<Marker>(text).is_err(), "{}", text);
+        assert!(serde_hjson::from_str::<()>(text).is_err(), "{}", text);
+    }
+}